@@ -0,0 +1,392 @@
+use crate::models::AppError;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// BLAKE3 of the whole file, hex-encoded.
+pub fn file_hash(path: &Path) -> Result<String, AppError> {
+    let mut file = File::open(path).map_err(|e| AppError::Io(format!("Failed to open {:?}: {}", path, e)))?;
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| AppError::Io(format!("Failed to read {:?}: {}", path, e)))?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// BLAKE3 of the audio payload only, hex-encoded: tag blocks (ID3v1/v2, APE,
+/// FLAC metadata, Ogg comment headers, MP4 `moov`, RIFF/AIFF side chunks)
+/// are left out, so retagging a file or changing its cover keeps the hash.
+pub fn audio_hash(path: &Path) -> Result<String, AppError> {
+    let file = File::open(path).map_err(|e| AppError::Io(format!("Failed to open {:?}: {}", path, e)))?;
+    let mut hasher = blake3::Hasher::new();
+    hash_audio_payload(file, &mut hasher)
+        .map_err(|e| AppError::Io(format!("Failed to hash audio of {:?}: {}", path, e)))?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn hash_audio_payload(mut file: File, hasher: &mut blake3::Hasher) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    let mut magic = [0u8; 12];
+    let n = read_up_to(&mut file, &mut magic)?;
+    let magic = &magic[..n];
+
+    if magic.starts_with(b"fLaC") {
+        hash_flac(file, hasher)
+    } else if magic.starts_with(b"OggS") {
+        hash_ogg(file, hasher)
+    } else if magic.starts_with(b"RIFF") && magic.get(8..12) == Some(b"WAVE") {
+        hash_chunks(file, len, hasher, Endian::Little, b"data")
+    } else if magic.starts_with(b"FORM") && matches!(magic.get(8..12), Some(b"AIFF" | b"AIFC")) {
+        hash_chunks(file, len, hasher, Endian::Big, b"SSND")
+    } else if magic.get(4..8) == Some(b"ftyp") {
+        hash_mp4(file, len, hasher)
+    } else {
+        hash_untagged_range(file, len, hasher)
+    }
+}
+
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn hash_range(file: &mut File, start: u64, len: u64, hasher: &mut blake3::Hasher) -> io::Result<()> {
+    file.seek(SeekFrom::Start(start))?;
+    let copied = io::copy(&mut (&mut *file).take(len), hasher)?;
+    if copied < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated audio data"));
+    }
+    Ok(())
+}
+
+/// Everything after the metadata blocks (STREAMINFO, VORBIS_COMMENT, PICTURE, …).
+fn hash_flac(mut file: File, hasher: &mut blake3::Hasher) -> io::Result<()> {
+    let mut pos = 4;
+    loop {
+        let mut header = [0u8; 4];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header)?;
+        let size = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        pos += 4 + size;
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    file.seek(SeekFrom::Start(pos))?;
+    io::copy(&mut file, hasher)?;
+    Ok(())
+}
+
+/// Page bodies after the header packets (comments included). Page headers
+/// are skipped too, since their sequence numbers shift when the comments grow.
+fn hash_ogg(mut file: File, hasher: &mut blake3::Hasher) -> io::Result<()> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    // Header packets still to come; None once the codec isn't recognized
+    let mut headers_left: Option<usize> = None;
+    let mut first_page = true;
+    loop {
+        let mut header = [0u8; 27];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        if &header[..4] != b"OggS" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "lost Ogg page sync"));
+        }
+        let granule = u64::from_le_bytes(header[6..14].try_into().unwrap());
+        let mut segments = vec![0u8; header[26] as usize];
+        reader.read_exact(&mut segments)?;
+        let body_len: u64 = segments.iter().map(|&s| s as u64).sum();
+        // A segment shorter than 255 bytes ends a packet
+        let packets_ended = segments.iter().filter(|&&s| s < 255).count();
+
+        if first_page {
+            first_page = false;
+            let mut body = vec![0u8; body_len as usize];
+            reader.read_exact(&mut body)?;
+            headers_left = ogg_header_packets(&body).map(|n| n.saturating_sub(packets_ended));
+            continue;
+        }
+        let in_headers = match headers_left {
+            Some(0) => false,
+            Some(n) => {
+                headers_left = Some(n.saturating_sub(packets_ended));
+                true
+            }
+            // Unknown codecs: headers are on pages with granule position 0
+            None => granule == 0,
+        };
+        if in_headers {
+            reader.seek_relative(body_len as i64)?;
+        } else {
+            let copied = io::copy(&mut (&mut reader).take(body_len), hasher)?;
+            if copied < body_len {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated Ogg page"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How many header packets precede the audio, by the first packet's codec
+/// signature: Vorbis has three (identification, comments, setup), Opus two
+/// (head, tags), and Ogg FLAC counts its metadata blocks in the first one.
+fn ogg_header_packets(first: &[u8]) -> Option<usize> {
+    if first.starts_with(b"\x01vorbis") {
+        Some(3)
+    } else if first.starts_with(b"OpusHead") {
+        Some(2)
+    } else if first.starts_with(b"\x7FFLAC") && first.len() >= 9 {
+        match u16::from_be_bytes([first[7], first[8]]) {
+            0 => None,
+            blocks => Some(1 + blocks as usize),
+        }
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Endian {
+    Little,
+    Big,
+}
+
+/// The sample chunk of a RIFF (WAV) or IFF (AIFF) file; LIST/id3/COMT chunks are ignored.
+fn hash_chunks(
+    mut file: File,
+    len: u64,
+    hasher: &mut blake3::Hasher,
+    endian: Endian,
+    audio_id: &[u8; 4],
+) -> io::Result<()> {
+    let mut pos = 12;
+    while pos + 8 <= len {
+        let mut header = [0u8; 8];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header)?;
+        let size_bytes: [u8; 4] = header[4..].try_into().unwrap();
+        let size = match endian {
+            Endian::Little => u32::from_le_bytes(size_bytes),
+            Endian::Big => u32::from_be_bytes(size_bytes),
+        } as u64;
+        if &header[..4] == audio_id {
+            return hash_range(&mut file, pos + 8, size.min(len - pos - 8), hasher);
+        }
+        // Chunks are padded to an even length
+        pos += 8 + size + (size & 1);
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "no audio chunk found"))
+}
+
+/// The `mdat` boxes; tags live in `moov`/`udta`.
+fn hash_mp4(mut file: File, len: u64, hasher: &mut blake3::Hasher) -> io::Result<()> {
+    let mut pos = 0;
+    let mut found = false;
+    while pos + 8 <= len {
+        let mut header = [0u8; 8];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut header)?;
+        let (size, header_len) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            0 => (len - pos, 8),
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)?;
+                (u64::from_be_bytes(large), 16)
+            }
+            n => (n as u64, 8),
+        };
+        if size < header_len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid MP4 box size"));
+        }
+        if &header[4..] == b"mdat" {
+            hash_range(&mut file, pos + header_len, (size - header_len).min(len - pos - header_len), hasher)?;
+            found = true;
+        }
+        pos = pos
+            .checked_add(size)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid MP4 box size"))?;
+    }
+    if !found {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no mdat box found"));
+    }
+    Ok(())
+}
+
+/// MP3, AAC (ADTS), APE, WavPack and similar: the bytes between a leading
+/// ID3v2 tag and trailing APEv2/ID3v1 tags.
+fn hash_untagged_range(mut file: File, len: u64, hasher: &mut blake3::Hasher) -> io::Result<()> {
+    let mut start = 0;
+    let mut header = [0u8; 10];
+    file.seek(SeekFrom::Start(0))?;
+    if read_up_to(&mut file, &mut header)? == 10 && &header[..3] == b"ID3" {
+        let size = header[6..10].iter().fold(0u64, |acc, &b| (acc << 7) | (b & 0x7F) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        start = (10 + size + footer).min(len);
+    }
+
+    let mut end = len;
+    if end >= start + 128 {
+        let mut tag = [0u8; 3];
+        file.seek(SeekFrom::Start(end - 128))?;
+        file.read_exact(&mut tag)?;
+        if &tag == b"TAG" {
+            end -= 128;
+        }
+    }
+    if end >= start + 32 {
+        let mut footer = [0u8; 32];
+        file.seek(SeekFrom::Start(end - 32))?;
+        file.read_exact(&mut footer)?;
+        if &footer[..8] == b"APETAGEX" {
+            let size = u32::from_le_bytes(footer[12..16].try_into().unwrap()) as u64;
+            let flags = u32::from_le_bytes(footer[20..24].try_into().unwrap());
+            let has_header = flags & 0x8000_0000 != 0;
+            let tag_len = size + if has_header { 32 } else { 0 };
+            end = end.saturating_sub(tag_len).max(start);
+        }
+    }
+    hash_range(&mut file, start, end - start, hasher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_audio_hash_ignores_id3_and_ape_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio: Vec<u8> = (0..2000u32).map(|i| (i % 251) as u8).collect();
+
+        let bare = write(dir.path(), "bare.mp3", &audio);
+
+        let mut tagged = b"ID3\x04\x00\x00\x00\x00\x00\x05hello".to_vec();
+        tagged.extend_from_slice(&audio);
+        let mut ape = b"APETAGEX".to_vec();
+        ape.extend_from_slice(&2000u32.to_le_bytes());
+        ape.extend_from_slice(&32u32.to_le_bytes());
+        ape.extend_from_slice(&0u32.to_le_bytes());
+        ape.extend_from_slice(&0u32.to_le_bytes());
+        ape.extend_from_slice(&[0u8; 8]);
+        tagged.extend_from_slice(&ape);
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, b' ');
+        tagged.extend_from_slice(&id3v1);
+        let tagged = write(dir.path(), "tagged.mp3", &tagged);
+
+        assert_eq!(audio_hash(&bare).unwrap(), audio_hash(&tagged).unwrap());
+        assert_ne!(file_hash(&bare).unwrap(), file_hash(&tagged).unwrap());
+
+        let mut changed = audio.clone();
+        changed[1000] ^= 1;
+        let changed = write(dir.path(), "changed.mp3", &changed);
+        assert_ne!(audio_hash(&bare).unwrap(), audio_hash(&changed).unwrap());
+    }
+
+    #[test]
+    fn test_audio_hash_rejects_oversized_mp4_box() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut mp4 = 16u32.to_be_bytes().to_vec();
+        mp4.extend_from_slice(b"ftypM4A \0\0\0\0");
+        // A 64-bit box size that runs past the end of any file
+        mp4.extend_from_slice(&1u32.to_be_bytes());
+        mp4.extend_from_slice(b"free");
+        mp4.extend_from_slice(&(u64::MAX - 8).to_be_bytes());
+        let path = write(dir.path(), "broken.m4a", &mp4);
+        assert!(matches!(audio_hash(&path), Err(AppError::Io(_))));
+    }
+
+    #[test]
+    fn test_audio_hash_skips_flac_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let flac = |comment: &[u8]| {
+            let mut bytes = b"fLaC".to_vec();
+            bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x22]);
+            bytes.extend_from_slice(&[7u8; 0x22]);
+            bytes.extend_from_slice(&[0x84, 0x00, 0x00, comment.len() as u8]);
+            bytes.extend_from_slice(comment);
+            bytes.extend_from_slice(b"\xFF\xF8frames");
+            bytes
+        };
+        let a = write(dir.path(), "a.flac", &flac(b"TITLE=One"));
+        let b = write(dir.path(), "b.flac", &flac(b"TITLE=Another one"));
+        assert_eq!(audio_hash(&a).unwrap(), audio_hash(&b).unwrap());
+    }
+
+    /// A Vorbis stream: the identification header alone on the first page,
+    /// then the comment and setup headers and an `audio` packet, laced across
+    /// pages of at most four segments. Header pages where no packet ends get
+    /// granule position -1.
+    fn ogg_vorbis(comment: &[u8], audio: &[u8]) -> Vec<u8> {
+        let page = |granule: u64, seq: u32, lacing: &[u8], body: &[u8]| {
+            let mut bytes = b"OggS\0\0".to_vec();
+            bytes.extend_from_slice(&granule.to_le_bytes());
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            bytes.extend_from_slice(&seq.to_le_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+            bytes.push(lacing.len() as u8);
+            bytes.extend_from_slice(lacing);
+            bytes.extend_from_slice(body);
+            bytes
+        };
+        let lace = |packet: &[u8]| {
+            let mut lacing = vec![255u8; packet.len() / 255];
+            lacing.push((packet.len() % 255) as u8);
+            lacing
+        };
+        let mut identification = b"\x01vorbis".to_vec();
+        identification.resize(30, 0);
+        let mut bytes = page(0, 0, &lace(&identification), &identification);
+
+        let mut headers = b"\x03vorbis".to_vec();
+        headers.extend_from_slice(comment);
+        let mut lacing = lace(&headers);
+        lacing.extend(lace(b"\x05vorbis setup"));
+        headers.extend_from_slice(b"\x05vorbis setup");
+        // Audio starts on a fresh page, with a real granule position
+        let mut seq = 1;
+        for (body, lacing, audio) in [(&headers[..], lacing, false), (audio, lace(audio), true)] {
+            let mut offset = 0;
+            for segments in lacing.chunks(4) {
+                let len: usize = segments.iter().map(|&s| s as usize).sum();
+                let granule = match segments.iter().any(|&s| s < 255) {
+                    _ if audio => 1000,
+                    true => 0,
+                    false => u64::MAX,
+                };
+                bytes.extend(page(granule, seq, segments, &body[offset..offset + len]));
+                offset += len;
+                seq += 1;
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_audio_hash_skips_ogg_comments_across_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        let short = write(dir.path(), "short.ogg", &ogg_vorbis(b"TITLE=One", &audio));
+        // A cover sized comment spanning several pages that end no packet
+        let cover = vec![b'x'; 4000];
+        let long = write(dir.path(), "long.ogg", &ogg_vorbis(&cover, &audio));
+        assert_eq!(audio_hash(&short).unwrap(), audio_hash(&long).unwrap());
+
+        let mut changed = audio.clone();
+        changed[2000] ^= 1;
+        let changed = write(dir.path(), "changed.ogg", &ogg_vorbis(b"TITLE=One", &changed));
+        assert_ne!(audio_hash(&short).unwrap(), audio_hash(&changed).unwrap());
+    }
+}
@@ -0,0 +1,215 @@
+use lofty::aac::AacFile;
+use lofty::config::ParseOptions;
+use lofty::file::{FileType, TaggedFile};
+use lofty::mp4::{AudioObjectType, Mp4Codec, Mp4File};
+use lofty::mpeg::{Layer, MpegFile};
+use lofty::prelude::*;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Technical stream properties of an audio file, as stored on its track row.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AudioProperties {
+    /// Bitrate of the audio stream alone
+    pub bitrate_kbps: Option<i32>,
+    /// File size over duration, tags and container overhead included
+    pub overall_bitrate_kbps: Option<i32>,
+    pub sample_rate_hz: Option<i32>,
+    pub channels: Option<i32>,
+    /// Only reported by PCM-based formats (FLAC, ALAC, WAV, AIFF, APE, WavPack)
+    pub bit_depth: Option<i32>,
+    /// e.g. "MP3", "AAC", "ALAC", "FLAC", "Opus"
+    pub codec: Option<String>,
+    /// e.g. "LC" or "HE-AAC" for AAC, "CBR", "VBR" or "ABR" for MP3
+    pub codec_profile: Option<String>,
+}
+
+/// Properties of an already read `file`. MPEG, MP4 and ADTS files are parsed
+/// once more (without tags) for the codec details `TaggedFile` doesn't keep.
+pub fn read(path: &Path, file: &TaggedFile) -> AudioProperties {
+    let generic = file.properties();
+    let positive = |v: Option<u32>| v.filter(|&v| v > 0).map(|v| v as i32);
+    let mut props = AudioProperties {
+        bitrate_kbps: positive(generic.audio_bitrate()),
+        overall_bitrate_kbps: positive(generic.overall_bitrate()),
+        sample_rate_hz: positive(generic.sample_rate()),
+        channels: positive(generic.channels().map(u32::from)),
+        bit_depth: positive(generic.bit_depth().map(u32::from)),
+        codec: None,
+        codec_profile: None,
+    };
+
+    let (codec, profile) = match file.file_type() {
+        FileType::Mpeg => mpeg_codec(path),
+        FileType::Mp4 => mp4_codec(path),
+        FileType::Aac => {
+            let profile = read_concrete::<AacFile>(path).and_then(|f| aac_profile(f.properties().audio_object_type()));
+            (Some("AAC"), profile)
+        }
+        FileType::Flac => (Some("FLAC"), None),
+        FileType::Ape => (Some("APE"), None),
+        FileType::WavPack => (Some("WavPack"), None),
+        FileType::Mpc => (Some("Musepack"), None),
+        FileType::Opus => (Some("Opus"), None),
+        FileType::Vorbis => (Some("Vorbis"), None),
+        FileType::Speex => (Some("Speex"), None),
+        FileType::Wav | FileType::Aiff => (Some("PCM"), None),
+        _ => (None, None),
+    };
+    props.codec = codec.map(str::to_string);
+    props.codec_profile = profile.map(str::to_string);
+    props
+}
+
+fn read_concrete<F: AudioFile>(path: &Path) -> Option<F> {
+    let mut file = File::open(path).ok()?;
+    F::read_from(&mut file, ParseOptions::new().read_tags(false).read_cover_art(false)).ok()
+}
+
+fn mpeg_codec(path: &Path) -> (Option<&'static str>, Option<&'static str>) {
+    let codec = match read_concrete::<MpegFile>(path).map(|f| *f.properties().layer()) {
+        Some(Layer::Layer1) => "MP1",
+        Some(Layer::Layer2) => "MP2",
+        _ => "MP3",
+    };
+    let mode = File::open(path).and_then(mpeg_bitrate_mode).ok();
+    (Some(codec), mode)
+}
+
+fn mp4_codec(path: &Path) -> (Option<&'static str>, Option<&'static str>) {
+    let Some(file) = read_concrete::<Mp4File>(path) else {
+        return (None, None);
+    };
+    let props = file.properties();
+    match props.codec() {
+        Mp4Codec::AAC => (Some("AAC"), props.audio_object_type().and_then(aac_profile)),
+        Mp4Codec::ALAC => (Some("ALAC"), None),
+        Mp4Codec::MP3 => (Some("MP3"), None),
+        Mp4Codec::FLAC => (Some("FLAC"), None),
+        _ => (None, None),
+    }
+}
+
+fn aac_profile(object_type: AudioObjectType) -> Option<&'static str> {
+    match object_type {
+        AudioObjectType::AacMain => Some("Main"),
+        AudioObjectType::AacLowComplexity => Some("LC"),
+        AudioObjectType::AacScalableSampleRate => Some("SSR"),
+        AudioObjectType::AacLongTermPrediction => Some("LTP"),
+        AudioObjectType::SpectralBandReplication => Some("HE-AAC"),
+        AudioObjectType::ParametricStereo => Some("HE-AACv2"),
+        AudioObjectType::ErrorResilientAacLowDelay => Some("LD"),
+        AudioObjectType::ErrorResilientAacEnhancedLowDelay => Some("ELD"),
+        AudioObjectType::UnifiedSpeechAudioCoding => Some("xHE-AAC"),
+        _ => None,
+    }
+}
+
+// ── MP3 bitrate mode ──
+
+/// How far past the ID3v2 tag to look for the first frame.
+const FRAME_SEARCH_BYTES: usize = 16 * 1024;
+
+/// "VBR", "ABR" or "CBR", from the Xing/Info or VBRI header in the first frame
+/// (and the LAME tag after it, when present). Files without such a header are
+/// taken as CBR; every VBR encoder in common use writes one.
+fn mpeg_bitrate_mode(mut file: File) -> io::Result<&'static str> {
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header)?;
+    let start = if &header[..3] == b"ID3" {
+        let size = header[6..10].iter().fold(0u64, |acc, &b| (acc << 7) | (b & 0x7F) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        10 + size + footer
+    } else {
+        0
+    };
+    file.seek(SeekFrom::Start(start))?;
+    let mut buf = Vec::with_capacity(FRAME_SEARCH_BYTES);
+    file.take(FRAME_SEARCH_BYTES as u64).read_to_end(&mut buf)?;
+    Ok(bitrate_mode(&buf))
+}
+
+fn bitrate_mode(buf: &[u8]) -> &'static str {
+    let Some(frame) = (0..buf.len().saturating_sub(4)).find(|&i| is_frame_header(&buf[i..i + 4])) else {
+        return "CBR";
+    };
+    let frame = &buf[frame..];
+    let mpeg1 = frame[1] & 0x18 == 0x18;
+    let mono = frame[3] & 0xC0 == 0xC0;
+    let side_info = match (mpeg1, mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+    let xing = 4 + side_info;
+    match frame.get(xing..xing + 4) {
+        Some(b"Xing") => lame_mode(frame, xing).unwrap_or("VBR"),
+        // LAME writes "Info" instead of "Xing" for CBR files
+        Some(b"Info") => lame_mode(frame, xing).unwrap_or("CBR"),
+        _ if frame.get(36..40) == Some(b"VBRI") => "VBR",
+        _ => "CBR",
+    }
+}
+
+fn is_frame_header(h: &[u8]) -> bool {
+    h[0] == 0xFF
+        && h[1] & 0xE0 == 0xE0
+        // version 01 and layer 00 are reserved
+        && h[1] & 0x18 != 0x08
+        && h[1] & 0x06 != 0x00
+        // bitrate index 1111 and sample rate index 11 are invalid
+        && h[2] & 0xF0 != 0xF0
+        && h[2] & 0x0C != 0x0C
+}
+
+/// The VBR method recorded in the LAME tag that follows a Xing/Info header.
+fn lame_mode(frame: &[u8], xing: usize) -> Option<&'static str> {
+    let flags = u32::from_be_bytes(frame.get(xing + 4..xing + 8)?.try_into().ok()?);
+    // Optional fields: frame count, byte count, seek table, quality
+    let lame = xing + 8
+        + if flags & 0x1 != 0 { 4 } else { 0 }
+        + if flags & 0x2 != 0 { 4 } else { 0 }
+        + if flags & 0x4 != 0 { 100 } else { 0 }
+        + if flags & 0x8 != 0 { 4 } else { 0 };
+    if frame.get(lame..lame + 4)? != b"LAME" {
+        return None;
+    }
+    match frame.get(lame + 9)? & 0x0F {
+        1 | 8 => Some("CBR"),
+        2 | 9 => Some("ABR"),
+        3..=6 => Some("VBR"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An MPEG-1 Layer III stereo frame header followed by a Xing/Info header.
+    fn first_frame(magic: &[u8; 4], lame_method: Option<u8>) -> Vec<u8> {
+        let mut frame = vec![0u8; 3];
+        frame.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        frame.extend_from_slice(&[0u8; 32]);
+        frame.extend_from_slice(magic);
+        frame.extend_from_slice(&0x1u32.to_be_bytes());
+        frame.extend_from_slice(&1000u32.to_be_bytes());
+        if let Some(method) = lame_method {
+            frame.extend_from_slice(b"LAME3.100");
+            frame.push(method);
+        }
+        frame.resize(417, 0);
+        frame
+    }
+
+    #[test]
+    fn test_bitrate_mode_from_headers() {
+        assert_eq!(bitrate_mode(&first_frame(b"Xing", None)), "VBR");
+        assert_eq!(bitrate_mode(&first_frame(b"Info", None)), "CBR");
+        assert_eq!(bitrate_mode(&first_frame(b"Xing", Some(0x22))), "ABR");
+        assert_eq!(bitrate_mode(&first_frame(b"Info", Some(0x01))), "CBR");
+        assert_eq!(bitrate_mode(&first_frame(b"none", None)), "CBR");
+    }
+}
@@ -0,0 +1,343 @@
+use crate::commands::get_setting_inner;
+use crate::db::DbPool;
+use crate::models::AppError;
+use crate::safe_write::write_atomically;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use lofty::config::WriteOptions;
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Setting: "off" (default), "tags" or "full".
+pub const BACKUP_MODE_SETTING: &str = "backup_mode";
+/// Setting: days to keep backups; "0" keeps them forever.
+pub const BACKUP_RETENTION_SETTING: &str = "backup_retention_days";
+/// Setting: directory of the backup store, seeded at startup under app data.
+pub const BACKUP_DIR_SETTING: &str = "backup_dir";
+
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// What is saved before Chant first writes to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupMode {
+    Off,
+    /// Every tag in the file as Lofty reads it (small, but frames Lofty cannot
+    /// represent are not kept)
+    Tags,
+    /// A byte-for-byte copy of the whole file
+    Full,
+}
+
+impl BackupMode {
+    fn parse(value: Option<&str>) -> Self {
+        match value {
+            Some("tags") => Self::Tags,
+            Some("full") => Self::Full,
+            _ => Self::Off,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Tags => "tags",
+            Self::Full => "full",
+        }
+    }
+}
+
+// ── Tag Snapshots ──
+
+#[derive(Serialize, Deserialize)]
+struct TagSnapshot {
+    tags: Vec<SavedTag>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedTag {
+    tag_type: String,
+    items: Vec<SavedItem>,
+    pictures: Vec<SavedPicture>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedItem {
+    /// Key in the tag's own format (e.g. `TKEY` for ID3v2, `INITIALKEY` for Vorbis)
+    key: String,
+    value: SavedValue,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data")]
+enum SavedValue {
+    Text(String),
+    Locator(String),
+    /// Base64-encoded
+    Binary(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedPicture {
+    pic_type: u8,
+    mime_type: Option<String>,
+    description: Option<String>,
+    /// Base64-encoded
+    data: String,
+}
+
+const TAG_TYPES: &[(TagType, &str)] = &[
+    (TagType::Ape, "ape"),
+    (TagType::Id3v1, "id3v1"),
+    (TagType::Id3v2, "id3v2"),
+    (TagType::Mp4Ilst, "mp4ilst"),
+    (TagType::VorbisComments, "vorbisComments"),
+    (TagType::RiffInfo, "riffInfo"),
+    (TagType::AiffText, "aiffText"),
+];
+
+fn tag_type_name(tag_type: TagType) -> Option<&'static str> {
+    TAG_TYPES.iter().find(|(t, _)| *t == tag_type).map(|(_, n)| *n)
+}
+
+fn tag_type_from_name(name: &str) -> Option<TagType> {
+    TAG_TYPES.iter().find(|(_, n)| *n == name).map(|(t, _)| *t)
+}
+
+fn read_tagged_file(path: &Path) -> Result<lofty::file::TaggedFile, AppError> {
+    Probe::open(path)
+        .map_err(|e| AppError::Io(format!("Cannot open audio file: {e}")))?
+        .read()
+        .map_err(|e| AppError::Io(format!("Cannot read audio file tags: {e}")))
+}
+
+fn snapshot_tags(path: &Path) -> Result<TagSnapshot, AppError> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let tagged_file = read_tagged_file(path)?;
+    let mut tags = Vec::new();
+    for tag in tagged_file.tags() {
+        let Some(tag_type) = tag_type_name(tag.tag_type()) else {
+            continue;
+        };
+        let items = tag
+            .items()
+            .filter_map(|item| {
+                let key = item.key().map_key(tag.tag_type(), true)?.to_string();
+                let value = match item.value() {
+                    ItemValue::Text(v) => SavedValue::Text(v.clone()),
+                    ItemValue::Locator(v) => SavedValue::Locator(v.clone()),
+                    ItemValue::Binary(v) => SavedValue::Binary(b64.encode(v)),
+                };
+                Some(SavedItem { key, value })
+            })
+            .collect();
+        let pictures = tag
+            .pictures()
+            .iter()
+            .map(|pic| SavedPicture {
+                pic_type: pic.pic_type().as_u8(),
+                mime_type: pic.mime_type().map(|m| m.as_str().to_string()),
+                description: pic.description().map(|d| d.to_string()),
+                data: b64.encode(pic.data()),
+            })
+            .collect();
+        tags.push(SavedTag {
+            tag_type: tag_type.to_string(),
+            items,
+            pictures,
+        });
+    }
+    Ok(TagSnapshot { tags })
+}
+
+/// Replace every tag in the file with the ones in `snapshot`.
+fn restore_tags(path: &Path, snapshot: &TagSnapshot) -> Result<(), AppError> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let current: Vec<TagType> = read_tagged_file(path)?.tags().iter().map(|t| t.tag_type()).collect();
+    for tag_type in current {
+        tag_type
+            .remove_from_path(path)
+            .map_err(|e| AppError::Io(format!("Failed to strip tags: {e}")))?;
+    }
+
+    for saved in &snapshot.tags {
+        let Some(tag_type) = tag_type_from_name(&saved.tag_type) else {
+            warn!("Unknown tag type {:?} in backup of {:?}", saved.tag_type, path);
+            continue;
+        };
+        let mut tag = Tag::new(tag_type);
+        for item in &saved.items {
+            let value = match &item.value {
+                SavedValue::Text(v) => ItemValue::Text(v.clone()),
+                SavedValue::Locator(v) => ItemValue::Locator(v.clone()),
+                SavedValue::Binary(v) => ItemValue::Binary(
+                    b64.decode(v).map_err(|e| AppError::Serialization(e.to_string()))?,
+                ),
+            };
+            tag.push_unchecked(TagItem::new(ItemKey::from_key(tag_type, &item.key), value));
+        }
+        for pic in &saved.pictures {
+            let data = b64.decode(&pic.data).map_err(|e| AppError::Serialization(e.to_string()))?;
+            tag.push_picture(Picture::new_unchecked(
+                PictureType::from_u8(pic.pic_type),
+                pic.mime_type.as_deref().map(MimeType::from_str),
+                pic.description.clone(),
+                data,
+            ));
+        }
+        tag.save_to_path(path, WriteOptions::default())
+            .map_err(|e| AppError::TagWrite(format!("Failed to restore tags: {e}")))?;
+    }
+    Ok(())
+}
+
+// ── Backup Store ──
+
+/// Back up the files of `track_ids` that have no backup yet. See `backup_file`.
+pub async fn backup_tracks(db: &DbPool, track_ids: &[i64]) -> Result<(), AppError> {
+    if current_mode(db).await? == BackupMode::Off {
+        return Ok(());
+    }
+    for &id in track_ids {
+        let row: Option<(String,)> = sqlx::query_as("SELECT file_path FROM tracks WHERE id = ?")
+            .bind(id)
+            .fetch_optional(db)
+            .await?;
+        if let Some((file_path,)) = row {
+            backup_file(db, &file_path).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Save the file's original state into the backup store before Chant first writes
+/// to it, according to the `backup_mode` setting. Files that already have a backup
+/// are left alone, so the store always holds the state from before any edit.
+pub async fn backup_file(db: &DbPool, file_path: &str) -> Result<(), AppError> {
+    let mode = current_mode(db).await?;
+    if mode == BackupMode::Off {
+        return Ok(());
+    }
+    let existing: Option<(i64,)> = sqlx::query_as("SELECT id FROM file_backups WHERE file_path = ?")
+        .bind(file_path)
+        .fetch_optional(db)
+        .await?;
+    if existing.is_some() {
+        return Ok(());
+    }
+    let Some(dir) = get_setting_inner(db, BACKUP_DIR_SETTING).await?.map(PathBuf::from) else {
+        warn!("Backups are enabled but no backup directory is set; writing without backup");
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir)?;
+
+    let mut tx = db.begin().await?;
+    let id = sqlx::query(
+        "INSERT INTO file_backups (file_path, mode, backup_path, created_at) VALUES (?, ?, '', ?)",
+    )
+    .bind(file_path)
+    .bind(mode.as_str())
+    .bind(Utc::now().to_rfc3339())
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    let source = Path::new(file_path);
+    let backup_path = match mode {
+        BackupMode::Full => {
+            let ext = source.extension().and_then(|e| e.to_str()).unwrap_or("bak");
+            let target = dir.join(format!("{}.{}", id, ext));
+            std::fs::copy(source, &target)?;
+            target
+        }
+        BackupMode::Tags => {
+            let target = dir.join(format!("{}.json", id));
+            std::fs::write(&target, serde_json::to_vec(&snapshot_tags(source)?)?)?;
+            target
+        }
+        BackupMode::Off => unreachable!(),
+    };
+
+    sqlx::query("UPDATE file_backups SET backup_path = ? WHERE id = ?")
+        .bind(backup_path.to_string_lossy().replace('\\', "/"))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    prune_expired_backups(db).await?;
+    Ok(())
+}
+
+/// Put the backed-up original back in place of `file_path` and drop the backup.
+pub async fn restore_file(db: &DbPool, file_path: &str) -> Result<(), AppError> {
+    let row: Option<(i64, String, String)> =
+        sqlx::query_as("SELECT id, mode, backup_path FROM file_backups WHERE file_path = ?")
+            .bind(file_path)
+            .fetch_optional(db)
+            .await?;
+    let (id, mode, backup_path) =
+        row.ok_or_else(|| AppError::NotFound(format!("No backup of {}", file_path)))?;
+
+    let path = Path::new(file_path);
+    match BackupMode::parse(Some(&mode)) {
+        BackupMode::Full => {
+            write_atomically(path, |copy| {
+                std::fs::copy(&backup_path, copy)
+                    .map(|_| ())
+                    .map_err(|e| AppError::Io(format!("Cannot copy backup {:?}: {}", backup_path, e)))
+            })?;
+        }
+        BackupMode::Tags => {
+            let snapshot: TagSnapshot = serde_json::from_slice(&std::fs::read(&backup_path)?)?;
+            write_atomically(path, |copy| restore_tags(copy, &snapshot))?;
+        }
+        BackupMode::Off => {
+            return Err(AppError::InvalidInput(format!("Backup {} has unknown mode {:?}", id, mode)));
+        }
+    }
+
+    remove_backup(db, id, &backup_path).await?;
+    info!("Restored {:?} from backup", file_path);
+    Ok(())
+}
+
+/// Delete backups older than the retention setting. Returns how many were removed.
+pub async fn prune_expired_backups(db: &DbPool) -> Result<u32, AppError> {
+    let days = get_setting_inner(db, BACKUP_RETENTION_SETTING)
+        .await?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    if days <= 0 {
+        return Ok(0);
+    }
+    let cutoff = (Utc::now() - Duration::days(days)).to_rfc3339();
+    let expired: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, backup_path FROM file_backups WHERE created_at < ?")
+            .bind(&cutoff)
+            .fetch_all(db)
+            .await?;
+    for (id, backup_path) in &expired {
+        remove_backup(db, *id, backup_path).await?;
+    }
+    Ok(expired.len() as u32)
+}
+
+async fn remove_backup(db: &DbPool, id: i64, backup_path: &str) -> Result<(), AppError> {
+    if let Err(e) = std::fs::remove_file(backup_path) {
+        warn!("Failed to delete backup {:?}: {}", backup_path, e);
+    }
+    sqlx::query("DELETE FROM file_backups WHERE id = ?")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+async fn current_mode(db: &DbPool) -> Result<BackupMode, AppError> {
+    Ok(BackupMode::parse(get_setting_inner(db, BACKUP_MODE_SETTING).await?.as_deref()))
+}
@@ -1,16 +1,39 @@
+use crate::audio_hash;
+use crate::audio_properties::{self, AudioProperties};
+use crate::backups;
+use crate::db::albums::{self, find_or_create_artist, AlbumKey};
+use crate::db::credits::{self, CreditField};
+use crate::db::library_query;
+use crate::db::name_keys::{self, name_key};
+use crate::db::orphans;
 use crate::db::DbPool;
+use crate::duplicates;
+use crate::fingerprint::{self, FingerprintIndex};
+use crate::jobs::{AnalysisJobs, JobControl, ScanJobs};
+use crate::loudness::{self, Measurement};
+use crate::organize;
+use crate::playlist_formats;
+use crate::safe_write::{write_atomically, write_file_atomically, TEMP_FILE_PREFIX};
+use crate::watcher::CollectionWatchers;
 use crate::models::{
-    Album, AlbumRow, AppError, Artist, ArtistRow, Collection, CollectionInput, CoverArt,
-    ExtraTag, LibraryStats, Setting, TrackRow, TrackUpdateInput,
+    AcoustIdFingerprint, Album, AlbumRow, AlbumSearchHit, AnalysisFailure, AnalysisProgress, AppError, Artist,
+    ArtistRow, ArtistSearchHit, Collection, CollectionInput, CoverArt, DuplicateAction, DuplicateCriteria,
+    DuplicateGroup, EditOperation, EntityEditSummary, ExtraTag, FingerprintOptions, FingerprintSummary,
+    LibraryChange, LibraryChangeKind, LibraryFilter, LibraryStats, LoudnessOptions, LoudnessSummary, NameDuplicates,
+    OrganizeMove, Page, PageRequest, Playlist, PlaylistEntry, PlaylistFormat, PlaylistImport, RemovedDuplicate,
+    ScanJob, ScanSummary, SearchResults, Setting, SimilarAudioGroup, SmartPlaylist, SmartPlaylistInput, SnippetPart,
+    SortSpec, TrackCredit, TrackRow, TrackSearchHit, TrackTags, TrackUpdateInput, VacuumSummary,
 };
 use chrono::Utc;
 use lofty::config::WriteOptions;
 use lofty::prelude::*;
 use lofty::probe::Probe;
-use lofty::tag::{Tag, TagItem, ItemValue};
+use lofty::tag::{Tag, TagItem, TagType, ItemValue};
 use log::{error, info, warn};
-use sqlx::{Column, Row};
+use sqlx::{Column, FromRow, Row, SqliteConnection};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tauri::{Emitter, Manager, State};
 use walkdir::WalkDir;
 
@@ -19,7 +42,7 @@ use walkdir::WalkDir;
 pub async fn list_collections_inner(db: &DbPool) -> Result<Vec<Collection>, AppError> {
     Ok(
         sqlx::query_as::<_, Collection>(
-            "SELECT id, path, label, created_at FROM collections ORDER BY created_at DESC",
+            "SELECT id, path, label, created_at, watch FROM collections ORDER BY created_at DESC",
         )
         .fetch_all(db)
         .await?,
@@ -66,7 +89,7 @@ pub async fn add_collection_inner(
 
     Ok(
         sqlx::query_as::<_, Collection>(
-            "SELECT id, path, label, created_at FROM collections WHERE path = ?",
+            "SELECT id, path, label, created_at, watch FROM collections WHERE path = ?",
         )
         .bind(&normalized)
         .fetch_one(db)
@@ -91,6 +114,7 @@ pub async fn delete_collection_inner(
         .bind(collection_id)
         .execute(db)
         .await?;
+    prune_orphans(db).await?;
     Ok(())
 }
 
@@ -98,11 +122,52 @@ pub async fn delete_collection_inner(
 #[specta::specta]
 pub async fn delete_collection(
     db: State<'_, DbPool>,
+    watchers: State<'_, CollectionWatchers>,
     collection_id: i64,
 ) -> Result<(), AppError> {
+    watchers.unwatch(collection_id);
     delete_collection_inner(db.inner(), collection_id).await
 }
 
+pub async fn set_collection_watch_inner(
+    db: &DbPool,
+    collection_id: i64,
+    enabled: bool,
+) -> Result<Collection, AppError> {
+    sqlx::query("UPDATE collections SET watch = ? WHERE id = ?")
+        .bind(enabled)
+        .bind(collection_id)
+        .execute(db)
+        .await?;
+    sqlx::query_as::<_, Collection>(
+        "SELECT id, path, label, created_at, watch FROM collections WHERE id = ?",
+    )
+    .bind(collection_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Collection {} not found", collection_id)))
+}
+
+/// Turn live filesystem sync for a collection on or off. The setting persists
+/// and watchers are restored on the next launch.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_collection_watch(
+    app_handle: tauri::AppHandle,
+    db: State<'_, DbPool>,
+    watchers: State<'_, CollectionWatchers>,
+    collection_id: i64,
+    enabled: bool,
+) -> Result<Collection, AppError> {
+    let collection = set_collection_watch_inner(db.inner(), collection_id, enabled).await?;
+    if enabled {
+        watchers.watch(&app_handle, &collection)?;
+    } else {
+        watchers.unwatch(collection_id);
+    }
+    Ok(collection)
+}
+
 pub async fn clear_all_data_inner(
     db: &DbPool,
     covers_dir: Option<std::path::PathBuf>,
@@ -110,6 +175,7 @@ pub async fn clear_all_data_inner(
     // Delete in FK-safe order: tracks first (they ref albums/artists/collections),
     // then albums (refs artists), then artists, then collections.
     // Settings are preserved so the user doesn't have to re-pick their folder.
+    sqlx::query("DELETE FROM edit_operations").execute(db).await?;
     sqlx::query("DELETE FROM tracks").execute(db).await?;
     sqlx::query("DELETE FROM albums").execute(db).await?;
     sqlx::query("DELETE FROM artists").execute(db).await?;
@@ -128,8 +194,10 @@ pub async fn clear_all_data_inner(
 #[specta::specta]
 pub async fn clear_all_data(
     db: State<'_, DbPool>,
+    watchers: State<'_, CollectionWatchers>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), AppError> {
+    watchers.unwatch_all();
     let covers_dir = app_handle
         .path()
         .app_data_dir()
@@ -138,6 +206,76 @@ pub async fn clear_all_data(
     clear_all_data_inner(db.inner(), covers_dir).await
 }
 
+/// Delete the artists and albums left without tracks by an edit, delete or
+/// scan, and the cover files only those albums used.
+async fn prune_orphans(db: &DbPool) -> Result<orphans::Pruned, AppError> {
+    let mut tx = db.begin().await?;
+    let pruned = orphans::prune_orphans(&mut tx).await?;
+    tx.commit().await?;
+    orphans::remove_cover_files(&pruned.cover_paths);
+    if pruned.artists > 0 || pruned.albums > 0 {
+        info!("Removed {} orphaned artists and {} orphaned albums", pruned.artists, pruned.albums);
+    }
+    Ok(pruned)
+}
+
+/// Size of the database file, once every page in the write-ahead log has been
+/// copied back into it.
+async fn database_size(db: &DbPool) -> Result<i64, AppError> {
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(db).await?;
+    let (size,): (i64,) = sqlx::query_as("SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()")
+        .fetch_one(db)
+        .await?;
+    Ok(size)
+}
+
+pub async fn vacuum_library_inner(db: &DbPool, covers_dir: Option<&Path>) -> Result<VacuumSummary, AppError> {
+    let size_before = database_size(db).await?;
+    let mut tx = db.begin().await?;
+    let pruned = orphans::prune_orphans(&mut tx).await?;
+    tx.commit().await?;
+    let mut removed_covers = orphans::remove_cover_files(&pruned.cover_paths);
+    if let Some(dir) = covers_dir {
+        let mut conn = db.acquire().await?;
+        let unused = orphans::unused_cover_files(&mut conn, dir).await?;
+        removed_covers += orphans::remove_cover_files(&unused);
+    }
+
+    sqlx::query("VACUUM").execute(db).await?;
+    sqlx::query("ANALYZE").execute(db).await?;
+    let summary = VacuumSummary {
+        removed_artists: pruned.artists,
+        removed_albums: pruned.albums,
+        removed_covers,
+        reclaimed_bytes: (size_before - database_size(db).await?).max(0),
+    };
+    info!("Vacuumed library: {:?}", summary);
+    Ok(summary)
+}
+
+/// Remove unreferenced artists, albums and cover files, then compact the
+/// database and refresh its query statistics. Refused while a scan runs, and
+/// waits for watcher syncs, as both write cover files before the albums that
+/// use them are committed; neither starts again until the vacuum is done.
+#[tauri::command]
+#[specta::specta]
+pub async fn vacuum_library(
+    app_handle: tauri::AppHandle,
+    db: State<'_, DbPool>,
+    jobs: State<'_, ScanJobs>,
+) -> Result<VacuumSummary, AppError> {
+    if jobs.is_busy() {
+        return Err(AppError::InvalidInput("Wait for running scans to finish".into()));
+    }
+    let _library = jobs.lock_library().await;
+    let covers_dir = app_handle
+        .path()
+        .app_data_dir()
+        .ok()
+        .map(|d| d.join("covers"));
+    vacuum_library_inner(db.inner(), covers_dir.as_deref()).await
+}
+
 // ── Library Stats ──
 
 pub async fn get_library_stats_inner(db: &DbPool) -> Result<LibraryStats, AppError> {
@@ -220,6 +358,11 @@ pub async fn set_setting_inner(
     .bind(value)
     .execute(db)
     .await?;
+    if key == name_keys::NAME_FOLDING_SETTING {
+        let mut tx = db.begin().await?;
+        name_keys::rekey_names(&mut tx).await?;
+        tx.commit().await?;
+    }
     Ok(())
 }
 
@@ -299,7 +442,8 @@ pub async fn debug_query_table(
 
 pub async fn list_tracks_inner(db: &DbPool) -> Result<Vec<TrackRow>, AppError> {
     Ok(sqlx::query_as::<_, TrackRow>(
-        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path
+        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path,
+                al.loudness_lufs as album_loudness_lufs, al.true_peak as album_true_peak
          FROM tracks t
          LEFT JOIN artists a ON t.artist_id = a.id
          LEFT JOIN albums al ON t.album_id = al.id
@@ -315,16 +459,43 @@ pub async fn list_tracks(db: State<'_, DbPool>) -> Result<Vec<TrackRow>, AppErro
     list_tracks_inner(db.inner()).await
 }
 
+/// One page of tracks matching `filter`, sorted by `sort` (title by default).
+pub async fn query_tracks_inner(
+    db: &DbPool,
+    filter: &LibraryFilter,
+    sort: Option<&SortSpec>,
+    page: &PageRequest,
+) -> Result<Page<TrackRow>, AppError> {
+    library_query::fetch_page(db, &library_query::TRACKS, filter, sort, page).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn query_tracks(
+    db: State<'_, DbPool>,
+    filter: LibraryFilter,
+    sort: Option<SortSpec>,
+    page: PageRequest,
+) -> Result<Page<TrackRow>, AppError> {
+    query_tracks_inner(db.inner(), &filter, sort.as_ref(), &page).await
+}
+
 pub async fn get_track_inner(db: &DbPool, track_id: i64) -> Result<TrackRow, AppError> {
+    fetch_track_row(&mut *db.acquire().await?, track_id).await
+}
+
+/// Load one track with its joined artist/album names.
+async fn fetch_track_row(conn: &mut SqliteConnection, track_id: i64) -> Result<TrackRow, AppError> {
     sqlx::query_as::<_, TrackRow>(
-        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path
+        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path,
+                al.loudness_lufs as album_loudness_lufs, al.true_peak as album_true_peak
          FROM tracks t
          LEFT JOIN artists a ON t.artist_id = a.id
          LEFT JOIN albums al ON t.album_id = al.id
          WHERE t.id = ?",
     )
     .bind(track_id)
-    .fetch_optional(db)
+    .fetch_optional(&mut *conn)
     .await?
     .ok_or_else(|| AppError::NotFound(format!("Track {} not found", track_id)))
 }
@@ -335,76 +506,75 @@ pub async fn get_track(db: State<'_, DbPool>, track_id: i64) -> Result<TrackRow,
     get_track_inner(db.inner(), track_id).await
 }
 
-/// Find an artist by exact name, or insert a new one and return its id.
-async fn find_or_create_artist(db: &DbPool, name: &str) -> Result<i64, AppError> {
-    let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM artists WHERE name = ?")
-        .bind(name)
-        .fetch_optional(db)
-        .await?;
-    if let Some((id,)) = row {
-        return Ok(id);
-    }
-    let now = Utc::now().to_rfc3339();
-    let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-        .bind(name)
-        .bind(&now)
-        .execute(db)
-        .await?;
-    Ok(res.last_insert_rowid())
-}
+/// Write tag fields to the audio file via Lofty.
+/// Called before any DB update so the file is always the source of truth.
+/// Fields that are DB-only (comment_lang, lyrics_lang) are intentionally not
+/// written to the file.
+fn write_tags_to_file(file_path: &str, tags: &TrackTags) -> Result<(), AppError> {
+    edit_file_tag(file_path, |tag| {
+        // Standard fields via Accessor trait
+        tag.set_title(tags.title.clone());
+
+        match tags.artist_name.as_deref().filter(|s| !s.is_empty()) {
+            Some(v) => tag.set_artist(v.to_string()),
+            None => tag.remove_artist(),
+        }
+        match tags.album_title.as_deref().filter(|s| !s.is_empty()) {
+            Some(v) => tag.set_album(v.to_string()),
+            None => tag.remove_album(),
+        }
+        match tags.year.filter(|&y| y > 0) {
+            Some(y) => tag.set_year(y as u32),
+            None => tag.remove_year(),
+        }
+        match tags.track_number.filter(|&n| n > 0) {
+            Some(n) => tag.set_track(n as u32),
+            None => tag.remove_track(),
+        }
+        match tags.disc_number.filter(|&n| n > 0) {
+            Some(n) => tag.set_disk(n as u32),
+            None => tag.remove_disk(),
+        }
+        match tags.genre.as_deref().filter(|s| !s.is_empty()) {
+            Some(v) => tag.set_genre(v.to_string()),
+            None => tag.remove_genre(),
+        }
 
-/// Find an album by title (optionally scoped to an artist), or insert and return its id.
-async fn find_or_create_album(
-    db: &DbPool,
-    title: &str,
-    artist_id: Option<i64>,
-) -> Result<i64, AppError> {
-    let row: Option<(i64,)> = if let Some(aid) = artist_id {
-        sqlx::query_as("SELECT id FROM albums WHERE title = ? AND artist_id = ?")
-            .bind(title)
-            .bind(aid)
-            .fetch_optional(db)
-            .await?
-    } else {
-        sqlx::query_as("SELECT id FROM albums WHERE title = ? AND artist_id IS NULL")
-            .bind(title)
-            .fetch_optional(db)
-            .await?
-    };
-    if let Some((id,)) = row {
-        return Ok(id);
-    }
-    let now = Utc::now().to_rfc3339();
-    let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
-        .bind(title)
-        .bind(artist_id)
-        .bind(&now)
-        .execute(db)
-        .await?;
-    Ok(res.last_insert_rowid())
+        // Extended fields via ItemKey (no Accessor convenience method)
+        tag.remove_key(&ItemKey::AlbumArtist);
+        if let Some(v) = tags.album_artist.as_deref().filter(|s| !s.is_empty()) {
+            tag.insert(TagItem::new(ItemKey::AlbumArtist, ItemValue::Text(v.to_string())));
+        }
+        tag.remove_key(&ItemKey::Composer);
+        if let Some(v) = tags.composer.as_deref().filter(|s| !s.is_empty()) {
+            tag.insert(TagItem::new(ItemKey::Composer, ItemValue::Text(v.to_string())));
+        }
+        tag.remove_key(&ItemKey::Comment);
+        if let Some(v) = tags.comment.as_deref().filter(|s| !s.is_empty()) {
+            tag.insert(TagItem::new(ItemKey::Comment, ItemValue::Text(v.to_string())));
+        }
+        tag.remove_key(&ItemKey::Lyrics);
+        if let Some(v) = tags.lyrics.as_deref().filter(|s| !s.is_empty()) {
+            tag.insert(TagItem::new(ItemKey::Lyrics, ItemValue::Text(v.to_string())));
+        }
+        tag.remove_key(&ItemKey::Bpm);
+        if let Some(b) = tags.bpm {
+            tag.insert(TagItem::new(ItemKey::Bpm, ItemValue::Text(b.to_string())));
+        }
+        tag.remove_key(&ItemKey::TrackTotal);
+        if let Some(t) = tags.track_total.filter(|&t| t > 0) {
+            tag.insert(TagItem::new(ItemKey::TrackTotal, ItemValue::Text(t.to_string())));
+        }
+        tag.remove_key(&ItemKey::DiscTotal);
+        if let Some(d) = tags.disc_total.filter(|&d| d > 0) {
+            tag.insert(TagItem::new(ItemKey::DiscTotal, ItemValue::Text(d.to_string())));
+        }
+    })
 }
 
-/// Write tag fields to the audio file via Lofty.
-/// Called before any DB update so the file is always the source of truth.
-/// Fields that are DB-only (collection_id, timestamps, comment_lang, lyrics_lang)
-/// are intentionally not written to the file.
-fn write_tags_to_file(
-    file_path: &str,
-    title: &str,
-    artist_name: Option<&str>,
-    album_title: Option<&str>,
-    year: Option<i32>,
-    track_number: Option<i32>,
-    disc_number: Option<i32>,
-    track_total: Option<i32>,
-    disc_total: Option<i32>,
-    genre: Option<&str>,
-    album_artist: Option<&str>,
-    composer: Option<&str>,
-    bpm: Option<i32>,
-    comment: Option<&str>,
-    lyrics: Option<&str>,
-) -> Result<(), AppError> {
+/// Open the file's primary tag (creating one if the file has none), let `edit`
+/// change it, and save the file atomically. Shared by every path that writes tags.
+fn edit_file_tag(file_path: &str, edit: impl FnOnce(&mut Tag)) -> Result<(), AppError> {
     let path = Path::new(file_path);
     let mut tagged_file = Probe::open(path)
         .map_err(|e| AppError::Io(format!("Cannot open audio file: {e}")))?
@@ -424,69 +594,97 @@ fn write_tags_to_file(
             .ok_or_else(|| AppError::Io("No writable tag found in audio file".into()))?
     };
 
-    // Standard fields via Accessor trait
-    tag.set_title(title.to_string());
+    edit(tag);
 
-    match artist_name.filter(|s| !s.is_empty()) {
-        Some(v) => tag.set_artist(v.to_string()),
-        None => tag.remove_artist(),
-    }
-    match album_title.filter(|s| !s.is_empty()) {
-        Some(v) => tag.set_album(v.to_string()),
-        None => tag.remove_album(),
-    }
-    match year.filter(|&y| y > 0) {
-        Some(y) => tag.set_year(y as u32),
-        None => tag.remove_year(),
-    }
-    match track_number.filter(|&n| n > 0) {
-        Some(n) => tag.set_track(n as u32),
-        None => tag.remove_track(),
-    }
-    match disc_number.filter(|&n| n > 0) {
-        Some(n) => tag.set_disk(n as u32),
-        None => tag.remove_disk(),
-    }
-    match genre.filter(|s| !s.is_empty()) {
-        Some(v) => tag.set_genre(v.to_string()),
-        None => tag.remove_genre(),
-    }
+    write_atomically(path, |copy| {
+        tagged_file
+            .save_to_path(copy, WriteOptions::default())
+            .map_err(|e| AppError::TagWrite(format!("Failed to save audio file: {e}")))
+    })
+}
 
-    // Extended fields via ItemKey (no Accessor convenience method)
-    tag.remove_key(&ItemKey::AlbumArtist);
-    if let Some(v) = album_artist.filter(|s| !s.is_empty()) {
-        tag.insert(TagItem::new(ItemKey::AlbumArtist, ItemValue::Text(v.to_string())));
-    }
-    tag.remove_key(&ItemKey::Composer);
-    if let Some(v) = composer.filter(|s| !s.is_empty()) {
-        tag.insert(TagItem::new(ItemKey::Composer, ItemValue::Text(v.to_string())));
-    }
-    tag.remove_key(&ItemKey::Comment);
-    if let Some(v) = comment.filter(|s| !s.is_empty()) {
-        tag.insert(TagItem::new(ItemKey::Comment, ItemValue::Text(v.to_string())));
-    }
-    tag.remove_key(&ItemKey::Lyrics);
-    if let Some(v) = lyrics.filter(|s| !s.is_empty()) {
-        tag.insert(TagItem::new(ItemKey::Lyrics, ItemValue::Text(v.to_string())));
-    }
-    tag.remove_key(&ItemKey::Bpm);
-    if let Some(b) = bpm {
-        tag.insert(TagItem::new(ItemKey::Bpm, ItemValue::Text(b.to_string())));
-    }
-    tag.remove_key(&ItemKey::TrackTotal);
-    if let Some(t) = track_total.filter(|&t| t > 0) {
-        tag.insert(TagItem::new(ItemKey::TrackTotal, ItemValue::Text(t.to_string())));
-    }
-    tag.remove_key(&ItemKey::DiscTotal);
-    if let Some(d) = disc_total.filter(|&d| d > 0) {
-        tag.insert(TagItem::new(ItemKey::DiscTotal, ItemValue::Text(d.to_string())));
+/// Item keys stored in dedicated `tracks` columns; everything else is an extra tag.
+const STANDARD_ITEM_KEYS: &[ItemKey] = &[
+    ItemKey::TrackTitle,
+    ItemKey::TrackArtist,
+    ItemKey::TrackArtists,
+    ItemKey::AlbumTitle,
+    ItemKey::AlbumArtist,
+    ItemKey::Composer,
+    ItemKey::Genre,
+    ItemKey::Comment,
+    ItemKey::Lyrics,
+    ItemKey::Bpm,
+    ItemKey::IntegerBpm,
+    ItemKey::TrackNumber,
+    ItemKey::TrackTotal,
+    ItemKey::DiscNumber,
+    ItemKey::DiscTotal,
+    ItemKey::Year,
+    ItemKey::RecordingDate,
+];
+
+/// Name under which an extra tag item is stored in `track_extra_tags`.
+/// Uses the ID3v2 frame id when one exists (e.g. `TKEY` for a Vorbis `INITIALKEY`)
+/// so the same field has one name across formats, else the format's own key.
+fn extra_tag_frame_id(key: &ItemKey, tag_type: TagType) -> Option<String> {
+    if STANDARD_ITEM_KEYS.contains(key) {
+        return None;
     }
+    key.map_key(TagType::Id3v2, false)
+        .or_else(|| key.map_key(tag_type, true))
+        .filter(|k| !k.is_empty())
+        .map(|k| k.to_string())
+}
 
-    tagged_file
-        .save_to_path(path, WriteOptions::default())
-        .map_err(|e| AppError::Io(format!("Failed to save audio file: {e}")))?;
+/// Text items of `tag` that have no dedicated column, first value per key.
+fn read_extra_tags(tag: &Tag) -> Vec<(String, String)> {
+    let mut extra: Vec<(String, String)> = Vec::new();
+    for item in tag.items() {
+        let value = match item.value() {
+            ItemValue::Text(v) | ItemValue::Locator(v) if !v.is_empty() => v,
+            _ => continue,
+        };
+        if let Some(frame_id) = extra_tag_frame_id(item.key(), tag.tag_type()) {
+            if !extra.iter().any(|(k, _)| *k == frame_id) {
+                extra.push((frame_id, value.clone()));
+            }
+        }
+    }
+    extra
+}
 
-    Ok(())
+/// Write extra tags to the audio file, dropping the `removed` frame ids first.
+/// Keys the file's tag format cannot hold are skipped with a warning.
+fn write_extra_tags_to_file(
+    file_path: &str,
+    removed: &[String],
+    tags: &[ExtraTag],
+) -> Result<(), AppError> {
+    edit_file_tag(file_path, |tag| {
+        for frame_id in removed {
+            tag.remove_key(&ItemKey::from_key(TagType::Id3v2, frame_id));
+        }
+        for extra in tags {
+            let key = ItemKey::from_key(TagType::Id3v2, &extra.frame_id);
+            tag.remove_key(&key);
+            if extra.value.is_empty() {
+                continue;
+            }
+            let item = TagItem::new(key, ItemValue::Text(extra.value.clone()));
+            if matches!(item.key(), ItemKey::Unknown(_)) {
+                // Custom keys (TXXX descriptions, Vorbis fields) have no mapping to verify
+                tag.insert_unchecked(item);
+            } else if !tag.insert(item) {
+                warn!(
+                    "{} cannot be stored in a {:?} tag of {:?}",
+                    extra.frame_id,
+                    tag.tag_type(),
+                    file_path
+                );
+            }
+        }
+    })
 }
 
 /// Read the file's modification time as Unix seconds (None if unavailable).
@@ -498,91 +696,103 @@ fn read_file_mtime(path: &str) -> Option<i64> {
         .map(|d| d.as_secs() as i64)
 }
 
+/// Read the file's size in bytes and modification time (Unix seconds) in one stat call.
+fn read_file_stat(path: &Path) -> (i64, Option<i64>) {
+    match std::fs::metadata(path) {
+        Ok(meta) => {
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64);
+            (meta.len() as i64, mtime)
+        }
+        Err(_) => (0, None),
+    }
+}
+
 pub async fn update_track_inner(
     db: &DbPool,
     track_id: i64,
     input: TrackUpdateInput,
     skip_file_write: bool,
 ) -> Result<TrackRow, AppError> {
-    let now = Utc::now().to_rfc3339();
-
     // Use joined query so we have artist_name and album_title strings for the file write
     let existing = get_track_inner(db, track_id).await?;
-
-    let title = input.title.unwrap_or(existing.title);
-    let track_number = input.track_number.or(existing.track_number);
-    let disc_number = input.disc_number.or(existing.disc_number);
-    let lyrics = input.lyrics.or(existing.lyrics);
-
-    // Extended fields — None = keep existing, Some("") = set NULL
-    fn opt_str(input: Option<String>, existing: Option<String>) -> Option<String> {
-        match input {
-            None => existing,
-            Some(s) if s.is_empty() => None,
-            Some(s) => Some(s),
-        }
-    }
-    let genre = opt_str(input.genre, existing.genre);
-    let album_artist = opt_str(input.album_artist, existing.album_artist);
-    let composer = opt_str(input.composer, existing.composer);
-    let comment = opt_str(input.comment, existing.comment);
-    let comment_lang = opt_str(input.comment_lang, existing.comment_lang);
-    let lyrics_lang = opt_str(input.lyrics_lang, existing.lyrics_lang);
-    let bpm = input.bpm.or(existing.bpm);
-    let year = input.year.or(existing.year);
-    let track_total = input.track_total.or(existing.track_total);
-    let disc_total = input.disc_total.or(existing.disc_total);
-
-    // Resolve new artist_id: None = keep existing, Some("") = clear, Some(name) = find-or-create
-    let new_artist_id: Option<i64> = match input.artist_name.as_deref() {
-        None => existing.artist_id,
-        Some("") => None,
-        Some(name) => Some(find_or_create_artist(db, name).await?),
-    };
-
-    // Resolve new album_id: None = keep existing, Some("") = clear, Some(title) = find-or-create
-    let new_album_id: Option<i64> = match input.album_title.as_deref() {
-        None => existing.album_id,
-        Some("") => None,
-        Some(title) => Some(find_or_create_album(db, title, new_artist_id).await?),
-    };
-
-    // Derive name strings for the file write from the merged input + existing joined values
-    let artist_name_str = match input.artist_name.as_deref() {
-        None => existing.artist_name.as_deref(),
-        Some("") => None,
-        Some(name) => Some(name),
-    };
-    let album_title_str = match input.album_title.as_deref() {
-        None => existing.album_title.as_deref(),
-        Some("") => None,
-        Some(t) => Some(t),
-    };
+    let before = TrackTags::from_row(&existing);
+    let after = before.merged(&input);
 
     // Write tags to file BEFORE touching the DB (file is source of truth)
     let file_mtime = if !skip_file_write {
-        write_tags_to_file(
-            &existing.file_path,
-            &title,
-            artist_name_str,
-            album_title_str,
-            year,
-            track_number,
-            disc_number,
-            track_total,
-            disc_total,
-            genre.as_deref(),
-            album_artist.as_deref(),
-            composer.as_deref(),
-            bpm,
-            comment.as_deref(),
-            lyrics.as_deref(),
-        )?;
+        backups::backup_file(db, &existing.file_path).await?;
+        write_tags_to_file(&existing.file_path, &after)?;
         read_file_mtime(&existing.file_path)
     } else {
         existing.file_mtime
     };
 
+    let mut tx = db.begin().await?;
+    store_track_tags(&mut tx, &existing, &after, file_mtime).await?;
+    let label = format!("Edit \"{}\"", after.title);
+    record_edit(&mut tx, &label, vec![(track_id, before, after)]).await?;
+    tx.commit().await?;
+    prune_orphans(db).await?;
+
+    get_track_inner(db, track_id).await
+}
+
+/// Write a tag snapshot to a track's DB row, re-linking artist and album when
+/// their names changed. `existing` is the row as it was before the edit.
+async fn store_track_tags(
+    conn: &mut SqliteConnection,
+    existing: &TrackRow,
+    tags: &TrackTags,
+    file_mtime: Option<i64>,
+) -> Result<(), AppError> {
+    let artist_id = if tags.artist_name == existing.artist_name {
+        existing.artist_id
+    } else {
+        match tags.artist_name.as_deref() {
+            None => None,
+            Some(name) => Some(find_or_create_artist(conn, name).await?),
+        }
+    };
+    let album_id = if tags.album_title == existing.album_title
+        && tags.album_artist == existing.album_artist
+        && tags.artist_name == existing.artist_name
+    {
+        existing.album_id
+    } else {
+        match tags.album_title.as_deref() {
+            None => None,
+            Some(title) => {
+                let (compilation, release) = albums::stored_album_tags(conn, existing.id).await?;
+                let key = AlbumKey {
+                    title,
+                    album_artist: tags.album_artist.as_deref(),
+                    track_artist: tags.artist_name.as_deref(),
+                    compilation,
+                    musicbrainz_release_id: release.as_deref(),
+                    file_path: &existing.file_path,
+                };
+                Some(albums::find_or_create_album(conn, &key, tags.year).await?)
+            }
+        }
+    };
+    store_track_columns(conn, existing, tags, artist_id, album_id, file_mtime).await
+}
+
+/// Write a tag snapshot and the given artist and album links to a track's row,
+/// re-crediting its artists and composers when their names changed. Extra
+/// tags the snapshot sets are stored and re-credited too.
+async fn store_track_columns(
+    conn: &mut SqliteConnection,
+    existing: &TrackRow,
+    tags: &TrackTags,
+    artist_id: Option<i64>,
+    album_id: Option<i64>,
+    file_mtime: Option<i64>,
+) -> Result<(), AppError> {
     sqlx::query(
         "UPDATE tracks SET title = ?, track_number = ?, disc_number = ?, lyrics = ?, \
          artist_id = ?, album_id = ?, \
@@ -591,29 +801,58 @@ pub async fn update_track_inner(
          track_total = ?, disc_total = ?, file_mtime = ?, \
          updated_at = ? WHERE id = ?",
     )
-    .bind(&title)
-    .bind(track_number)
-    .bind(disc_number)
-    .bind(&lyrics)
-    .bind(new_artist_id)
-    .bind(new_album_id)
-    .bind(&genre)
-    .bind(&album_artist)
-    .bind(&composer)
-    .bind(bpm)
-    .bind(&comment)
-    .bind(&comment_lang)
-    .bind(year)
-    .bind(&lyrics_lang)
-    .bind(track_total)
-    .bind(disc_total)
+    .bind(&tags.title)
+    .bind(tags.track_number)
+    .bind(tags.disc_number)
+    .bind(&tags.lyrics)
+    .bind(artist_id)
+    .bind(album_id)
+    .bind(&tags.genre)
+    .bind(&tags.album_artist)
+    .bind(&tags.composer)
+    .bind(tags.bpm)
+    .bind(&tags.comment)
+    .bind(&tags.comment_lang)
+    .bind(tags.year)
+    .bind(&tags.lyrics_lang)
+    .bind(tags.track_total)
+    .bind(tags.disc_total)
     .bind(file_mtime)
-    .bind(&now)
-    .bind(track_id)
-    .execute(db)
+    .bind(Utc::now().to_rfc3339())
+    .bind(existing.id)
+    .execute(&mut *conn)
     .await?;
 
-    get_track_inner(db, track_id).await
+    if tags.artist_name != existing.artist_name {
+        credits::refresh_credits(conn, existing.id, &[CreditField::Artist]).await?;
+    }
+    if tags.composer != existing.composer {
+        credits::refresh_credits(conn, existing.id, &[CreditField::Composer]).await?;
+    }
+    for extra in &tags.extra_tags {
+        sqlx::query("DELETE FROM track_extra_tags WHERE track_id = ? AND frame_id = ?")
+            .bind(existing.id)
+            .bind(&extra.frame_id)
+            .execute(&mut *conn)
+            .await?;
+        if !extra.value.is_empty() {
+            sqlx::query("INSERT INTO track_extra_tags (track_id, frame_id, value) VALUES (?, ?, ?)")
+                .bind(existing.id)
+                .bind(&extra.frame_id)
+                .bind(&extra.value)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+    let extra_credits: Vec<CreditField> = CreditField::EXTRA
+        .iter()
+        .copied()
+        .filter(|f| f.extra_frame().is_some_and(|frame| tags.extra_tags.iter().any(|t| t.frame_id == frame)))
+        .collect();
+    if !extra_credits.is_empty() {
+        credits::refresh_credits(conn, existing.id, &extra_credits).await?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -634,160 +873,66 @@ pub async fn batch_update_tracks_inner(
     input: TrackUpdateInput,
     skip_file_write: bool,
 ) -> Result<(), AppError> {
+    if !skip_file_write {
+        backups::backup_tracks(db, &track_ids).await?;
+    }
     let mut tx = db.begin().await?;
+    let mut edits = Vec::with_capacity(track_ids.len());
+    let mut aborted = None;
     for &id in &track_ids {
         // Fetch existing track with joined names (needed for file write)
-        let existing = sqlx::query_as::<_, TrackRow>(
-            "SELECT t.*, a.name as artist_name, al.title as album_title, \
-             al.cover_path as album_cover_path \
-             FROM tracks t \
-             LEFT JOIN artists a ON t.artist_id = a.id \
-             LEFT JOIN albums al ON t.album_id = al.id \
-             WHERE t.id = ?",
-        )
-        .bind(id)
-        .fetch_optional(&mut *tx)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Track {} not found", id)))?;
-
-        let now = Utc::now().to_rfc3339();
-        let title = input.title.clone().unwrap_or(existing.title);
-        let track_number = input.track_number.or(existing.track_number);
-        let disc_number = input.disc_number.or(existing.disc_number);
-        let lyrics = input.lyrics.clone().or(existing.lyrics);
-
-        fn opt_str_batch(input: Option<String>, existing: Option<String>) -> Option<String> {
-            match input {
-                None => existing,
-                Some(s) if s.is_empty() => None,
-                Some(s) => Some(s),
-            }
-        }
-        let genre = opt_str_batch(input.genre.clone(), existing.genre);
-        let album_artist = opt_str_batch(input.album_artist.clone(), existing.album_artist);
-        let composer = opt_str_batch(input.composer.clone(), existing.composer);
-        let comment = opt_str_batch(input.comment.clone(), existing.comment);
-        let comment_lang = opt_str_batch(input.comment_lang.clone(), existing.comment_lang);
-        let lyrics_lang = opt_str_batch(input.lyrics_lang.clone(), existing.lyrics_lang);
-        let bpm = input.bpm.or(existing.bpm);
-        let year = input.year.or(existing.year);
-        let track_total = input.track_total.or(existing.track_total);
-        let disc_total = input.disc_total.or(existing.disc_total);
-
-        // Resolve artist
-        let new_artist_id: Option<i64> = match input.artist_name.as_deref() {
-            None => existing.artist_id,
-            Some("") => None,
-            Some(name) => {
-                let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM artists WHERE name = ?")
-                    .bind(name)
-                    .fetch_optional(&mut *tx)
-                    .await?;
-                if let Some((aid,)) = row {
-                    Some(aid)
-                } else {
-                    let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-                        .bind(name)
-                        .bind(&now)
-                        .execute(&mut *tx)
-                        .await?;
-                    Some(res.last_insert_rowid())
-                }
-            }
-        };
-
-        // Resolve album
-        let new_album_id: Option<i64> = match input.album_title.as_deref() {
-            None => existing.album_id,
-            Some("") => None,
-            Some(atitle) => {
-                let row: Option<(i64,)> = if let Some(aid) = new_artist_id {
-                    sqlx::query_as("SELECT id FROM albums WHERE title = ? AND artist_id = ?")
-                        .bind(atitle).bind(aid).fetch_optional(&mut *tx).await?
-                } else {
-                    sqlx::query_as("SELECT id FROM albums WHERE title = ? AND artist_id IS NULL")
-                        .bind(atitle).fetch_optional(&mut *tx).await?
-                };
-                if let Some((alid,)) = row {
-                    Some(alid)
-                } else {
-                    let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
-                        .bind(atitle).bind(new_artist_id).bind(&now).execute(&mut *tx).await?;
-                    Some(res.last_insert_rowid())
-                }
-            }
-        };
+        let existing = fetch_track_row(&mut tx, id).await?;
+        let before = TrackTags::from_row(&existing);
+        let after = before.merged(&input);
 
         // Write tags to file — skip DB update for this track if the write fails
         // (file is source of truth; DB must not diverge from the file)
         let file_mtime = if !skip_file_write {
-            let artist_name_str = match input.artist_name.as_deref() {
-                None => existing.artist_name.as_deref(),
-                Some("") => None,
-                Some(name) => Some(name),
-            };
-            let album_title_str = match input.album_title.as_deref() {
-                None => existing.album_title.as_deref(),
-                Some("") => None,
-                Some(t) => Some(t),
-            };
-            if let Err(e) = write_tags_to_file(
-                &existing.file_path,
-                &title,
-                artist_name_str,
-                album_title_str,
-                year,
-                track_number,
-                disc_number,
-                track_total,
-                disc_total,
-                genre.as_deref(),
-                album_artist.as_deref(),
-                composer.as_deref(),
-                bpm,
-                comment.as_deref(),
-                lyrics.as_deref(),
-            ) {
-                warn!("Skipping DB update for {:?}: file write failed: {}", existing.file_path, e);
-                continue;
+            match write_file_or_skip(&existing.file_path, &after) {
+                Ok(true) => read_file_mtime(&existing.file_path),
+                Ok(false) => continue,
+                Err(e) => {
+                    aborted = Some(e);
+                    break;
+                }
             }
-            read_file_mtime(&existing.file_path)
         } else {
             existing.file_mtime
         };
 
-        sqlx::query(
-            "UPDATE tracks SET title = ?, track_number = ?, disc_number = ?, lyrics = ?, \
-             artist_id = ?, album_id = ?, \
-             genre = ?, album_artist = ?, composer = ?, bpm = ?, \
-             comment = ?, comment_lang = ?, year = ?, lyrics_lang = ?, \
-             track_total = ?, disc_total = ?, file_mtime = ?, \
-             updated_at = ? WHERE id = ?",
-        )
-        .bind(&title)
-        .bind(track_number)
-        .bind(disc_number)
-        .bind(&lyrics)
-        .bind(new_artist_id)
-        .bind(new_album_id)
-        .bind(&genre)
-        .bind(&album_artist)
-        .bind(&composer)
-        .bind(bpm)
-        .bind(&comment)
-        .bind(&comment_lang)
-        .bind(year)
-        .bind(&lyrics_lang)
-        .bind(track_total)
-        .bind(disc_total)
-        .bind(file_mtime)
-        .bind(&now)
-        .bind(id)
-        .execute(&mut *tx)
-        .await?;
+        store_track_tags(&mut tx, &existing, &after, file_mtime).await?;
+        edits.push((id, before, after));
     }
+    // Keep whatever was written before an abort so the DB matches the files
+    let label = format!("Edit {} tracks", edits.len());
+    record_edit(&mut tx, &label, edits).await?;
     tx.commit().await?;
-    Ok(())
+    prune_orphans(db).await?;
+    aborted.map_or(Ok(()), Err)
+}
+
+/// File write step of a multi-track edit. Returns `Ok(false)` when this track should
+/// be skipped (ordinary I/O trouble such as a missing file), and `Err` only for a
+/// `TagWrite` failure, which should stop the whole operation before more files
+/// of the same kind are touched.
+fn write_file_or_skip(file_path: &str, tags: &TrackTags) -> Result<bool, AppError> {
+    let written = write_tags_to_file(file_path, tags).and_then(|()| {
+        if tags.extra_tags.is_empty() {
+            return Ok(());
+        }
+        write_extra_tags_to_file(file_path, &[], &tags.extra_tags)
+    });
+    match written {
+        Ok(()) => Ok(true),
+        Err(e @ AppError::TagWrite(_)) => {
+            error!("Stopping edit: writing {:?} failed verification: {}", file_path, e);
+            Err(e)
+        }
+        Err(e) => {
+            warn!("Skipping DB update for {:?}: file write failed: {}", file_path, e);
+            Ok(false)
+        }
+    }
 }
 
 #[tauri::command]
@@ -800,15 +945,188 @@ pub async fn batch_update_tracks(
     batch_update_tracks_inner(db.inner(), track_ids, input, false).await
 }
 
-// ── Stale Track Detection ──
+// ── Edit History ──
 
-/// Returns the IDs of tracks whose file has been modified externally since the last scan/update.
-/// A track is stale when its actual on-disk mtime is newer than the stored `file_mtime`.
-pub async fn stale_track_ids_inner(db: &DbPool) -> Result<Vec<i64>, AppError> {
-    let rows: Vec<(i64, String, i64)> = sqlx::query_as(
-        "SELECT id, file_path, file_mtime FROM tracks WHERE file_mtime IS NOT NULL",
+/// How many edit operations are kept for undo.
+const EDIT_HISTORY_LIMIT: i64 = 100;
+
+/// Journal one edit operation as (track id, tags before, tags after) per track.
+/// Tracks whose tags did not change are left out; nothing is recorded if none changed.
+/// A new edit discards everything that was undone, so it can no longer be redone.
+async fn record_edit(
+    conn: &mut SqliteConnection,
+    label: &str,
+    edits: Vec<(i64, TrackTags, TrackTags)>,
+) -> Result<(), AppError> {
+    let edits: Vec<_> = edits.into_iter().filter(|(_, before, after)| before != after).collect();
+    if edits.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query("DELETE FROM edit_operations WHERE undone = 1")
+        .execute(&mut *conn)
+        .await?;
+    let op_id = sqlx::query("INSERT INTO edit_operations (label, created_at) VALUES (?, ?)")
+        .bind(label)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+    for (track_id, before, after) in &edits {
+        sqlx::query(
+            "INSERT INTO edit_entries (operation_id, track_id, before_tags, after_tags) VALUES (?, ?, ?, ?)",
+        )
+        .bind(op_id)
+        .bind(track_id)
+        .bind(serde_json::to_string(before)?)
+        .bind(serde_json::to_string(after)?)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    sqlx::query(
+        "DELETE FROM edit_operations WHERE id NOT IN \
+         (SELECT id FROM edit_operations ORDER BY id DESC LIMIT ?)",
     )
-    .fetch_all(db)
+    .bind(EDIT_HISTORY_LIMIT)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+const SELECT_EDIT_OPERATIONS: &str = "SELECT o.id, o.label, o.created_at, o.undone, \
+     (SELECT COUNT(*) FROM edit_entries e WHERE e.operation_id = o.id) AS track_count \
+     FROM edit_operations o";
+
+/// Put every track of an operation back to its `before` (undo) or `after` (redo) tags,
+/// file first, and flip the operation's undone flag. Like a batch edit, a track whose
+/// file cannot be written is skipped, and a `TagWrite` failure stops the replay.
+/// The flag only flips once every track was replayed; after a partial replay the
+/// operation can be undone or redone again to finish it.
+async fn replay_edit(
+    db: &DbPool,
+    op: EditOperation,
+    undo: bool,
+    skip_file_write: bool,
+) -> Result<EditOperation, AppError> {
+    let entries: Vec<(i64, String, String)> = sqlx::query_as(
+        "SELECT track_id, before_tags, after_tags FROM edit_entries WHERE operation_id = ? ORDER BY id",
+    )
+    .bind(op.id)
+    .fetch_all(db)
+    .await?;
+    if !skip_file_write {
+        let track_ids: Vec<i64> = entries.iter().map(|(id, _, _)| *id).collect();
+        backups::backup_tracks(db, &track_ids).await?;
+    }
+
+    let total = entries.len();
+    let mut skipped = 0;
+    let mut tx = db.begin().await?;
+    for (track_id, before, after) in entries {
+        let tags: TrackTags = serde_json::from_str(if undo { &before } else { &after })?;
+        let existing = fetch_track_row(&mut tx, track_id).await?;
+        let file_mtime = if !skip_file_write {
+            match write_file_or_skip(&existing.file_path, &tags) {
+                Ok(true) => read_file_mtime(&existing.file_path),
+                Ok(false) => {
+                    skipped += 1;
+                    continue;
+                }
+                Err(e) => {
+                    // Leave the operation's state as is; tracks already replayed
+                    // are committed so the DB matches their files
+                    tx.commit().await?;
+                    return Err(e);
+                }
+            }
+        } else {
+            existing.file_mtime
+        };
+        store_track_tags(&mut tx, &existing, &tags, file_mtime).await?;
+    }
+    if skipped > 0 {
+        tx.commit().await?;
+        prune_orphans(db).await?;
+        return Err(AppError::Io(format!(
+            "{} of {} tracks could not be written; \"{}\" was not {}",
+            skipped,
+            total,
+            op.label,
+            if undo { "undone" } else { "redone" }
+        )));
+    }
+    sqlx::query("UPDATE edit_operations SET undone = ? WHERE id = ?")
+        .bind(undo)
+        .bind(op.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    prune_orphans(db).await?;
+
+    Ok(EditOperation { undone: undo, ..op })
+}
+
+/// Revert the most recent edit that has not been undone yet.
+pub async fn undo_last_edit_inner(db: &DbPool, skip_file_write: bool) -> Result<EditOperation, AppError> {
+    let op = sqlx::query_as::<_, EditOperation>(&format!(
+        "{} WHERE o.undone = 0 ORDER BY o.id DESC LIMIT 1",
+        SELECT_EDIT_OPERATIONS
+    ))
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Nothing to undo".into()))?;
+    replay_edit(db, op, true, skip_file_write).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn undo_last_edit(db: State<'_, DbPool>) -> Result<EditOperation, AppError> {
+    undo_last_edit_inner(db.inner(), false).await
+}
+
+/// Re-apply the oldest undone edit, i.e. the one undone last.
+pub async fn redo_edit_inner(db: &DbPool, skip_file_write: bool) -> Result<EditOperation, AppError> {
+    let op = sqlx::query_as::<_, EditOperation>(&format!(
+        "{} WHERE o.undone = 1 ORDER BY o.id ASC LIMIT 1",
+        SELECT_EDIT_OPERATIONS
+    ))
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Nothing to redo".into()))?;
+    replay_edit(db, op, false, skip_file_write).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn redo_edit(db: State<'_, DbPool>) -> Result<EditOperation, AppError> {
+    redo_edit_inner(db.inner(), false).await
+}
+
+/// Journaled edits, newest first.
+pub async fn list_edit_history_inner(db: &DbPool) -> Result<Vec<EditOperation>, AppError> {
+    Ok(
+        sqlx::query_as::<_, EditOperation>(&format!("{} ORDER BY o.id DESC", SELECT_EDIT_OPERATIONS))
+            .fetch_all(db)
+            .await?,
+    )
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_edit_history(db: State<'_, DbPool>) -> Result<Vec<EditOperation>, AppError> {
+    list_edit_history_inner(db.inner()).await
+}
+
+// ── Stale Track Detection ──
+
+/// Returns the IDs of tracks whose file has been modified externally since the last scan/update.
+/// A track is stale when its actual on-disk mtime is newer than the stored `file_mtime`.
+pub async fn stale_track_ids_inner(db: &DbPool) -> Result<Vec<i64>, AppError> {
+    let rows: Vec<(i64, String, i64)> = sqlx::query_as(
+        "SELECT id, file_path, file_mtime FROM tracks WHERE file_mtime IS NOT NULL",
+    )
+    .fetch_all(db)
     .await?;
 
     let stale = rows
@@ -828,6 +1146,179 @@ pub async fn stale_track_ids(db: State<'_, DbPool>) -> Result<Vec<i64>, AppError
     stale_track_ids_inner(db.inner()).await
 }
 
+/// Returns the IDs of tracks whose audio no longer matches the hash stored at
+/// the last scan: damaged or re-encoded files, or ones that can't be read.
+/// Tag edits don't count. Reads every hashed file in the collection (or library).
+pub async fn verify_audio_integrity_inner(db: &DbPool, collection_id: Option<i64>) -> Result<Vec<i64>, AppError> {
+    let rows: Vec<(i64, String, String)> = sqlx::query_as(
+        "SELECT id, file_path, audio_hash FROM tracks
+         WHERE audio_hash IS NOT NULL AND (?1 IS NULL OR collection_id = ?1)",
+    )
+    .bind(collection_id)
+    .fetch_all(db)
+    .await?;
+
+    tokio::task::spawn_blocking(move || {
+        rows.into_iter()
+            .filter(|(_, file_path, stored)| {
+                audio_hash::audio_hash(Path::new(file_path)).map_or(true, |actual| &actual != stored)
+            })
+            .map(|(id, _, _)| id)
+            .collect()
+    })
+    .await
+    .map_err(|e| AppError::Io(format!("Integrity check failed: {}", e)))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn verify_audio_integrity(db: State<'_, DbPool>, collection_id: Option<i64>) -> Result<Vec<i64>, AppError> {
+    verify_audio_integrity_inner(db.inner(), collection_id).await
+}
+
+// ── File Backups ──
+
+/// Put a track's file back to its backed-up original, then re-read it so the DB
+/// matches the restored tags.
+pub async fn restore_track_file_inner(
+    db: &DbPool,
+    track_id: i64,
+    covers_dir: Option<&Path>,
+) -> Result<TrackRow, AppError> {
+    let existing = get_track_inner(db, track_id).await?;
+    backups::restore_file(db, &existing.file_path).await?;
+    process_track(db, existing.collection_id, Path::new(&existing.file_path), covers_dir).await?;
+    prune_orphans(db).await?;
+    get_track_inner(db, track_id).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn restore_track_file(
+    app_handle: tauri::AppHandle,
+    db: State<'_, DbPool>,
+    track_id: i64,
+) -> Result<TrackRow, AppError> {
+    let covers_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .join("covers");
+    restore_track_file_inner(db.inner(), track_id, Some(&covers_dir)).await
+}
+
+// ── File Organizer ──
+
+/// Compute where `track_ids` would go under `template` (relative to each track's
+/// collection folder) and, unless `dry_run`, move them. Returns every planned
+/// move; collisions and failed moves carry a `problem` and are left in place.
+pub async fn organize_files_inner(
+    db: &DbPool,
+    track_ids: Vec<i64>,
+    template: &str,
+    dry_run: bool,
+) -> Result<Vec<OrganizeMove>, AppError> {
+    let template = organize::Template::parse(template)?;
+
+    let mut tracks = Vec::with_capacity(track_ids.len());
+    let mut conn = db.acquire().await?;
+    for id in track_ids {
+        let track = fetch_track_row(&mut conn, id).await?;
+        let (root,): (String,) = sqlx::query_as("SELECT path FROM collections WHERE id = ?")
+            .bind(track.collection_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        tracks.push((track, root));
+    }
+    drop(conn);
+
+    let mut moves = organize::plan_moves(&template, &tracks);
+    if !dry_run {
+        organize::apply_moves(db, &mut moves).await?;
+    }
+    Ok(moves)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn organize_files(
+    db: State<'_, DbPool>,
+    track_ids: Vec<i64>,
+    template: String,
+    dry_run: bool,
+) -> Result<Vec<OrganizeMove>, AppError> {
+    organize_files_inner(db.inner(), track_ids, &template, dry_run).await
+}
+
+// ── Duplicates ──
+
+/// Duplicate sets among the library's tracks (or one collection's), best copy first.
+pub async fn find_duplicates_inner(
+    db: &DbPool,
+    criteria: DuplicateCriteria,
+    collection_id: Option<i64>,
+) -> Result<Vec<DuplicateGroup>, AppError> {
+    let tracks = sqlx::query_as::<_, TrackRow>(
+        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path,
+                al.loudness_lufs as album_loudness_lufs, al.true_peak as album_true_peak
+         FROM tracks t
+         LEFT JOIN artists a ON t.artist_id = a.id
+         LEFT JOIN albums al ON t.album_id = al.id
+         WHERE ?1 IS NULL OR t.collection_id = ?1",
+    )
+    .bind(collection_id)
+    .fetch_all(db)
+    .await?;
+    tokio::task::spawn_blocking(move || duplicates::find_duplicates(tracks, &criteria))
+        .await
+        .map_err(|e| AppError::Io(format!("Duplicate search failed: {}", e)))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn find_duplicates(
+    db: State<'_, DbPool>,
+    criteria: DuplicateCriteria,
+    collection_id: Option<i64>,
+) -> Result<Vec<DuplicateGroup>, AppError> {
+    find_duplicates_inner(db.inner(), criteria, collection_id).await
+}
+
+/// Delete or move the files of the given tracks and remove them from the library.
+pub async fn remove_duplicates_inner(
+    db: &DbPool,
+    track_ids: Vec<i64>,
+    action: DuplicateAction,
+) -> Result<Vec<RemovedDuplicate>, AppError> {
+    if let DuplicateAction::MoveTo { directory } = &action {
+        if !Path::new(directory).is_absolute() {
+            return Err(AppError::InvalidInput(format!("Path must be absolute: {}", directory)));
+        }
+    }
+    let mut tracks = Vec::with_capacity(track_ids.len());
+    for id in track_ids {
+        let row: Option<(String,)> = sqlx::query_as("SELECT file_path FROM tracks WHERE id = ?")
+            .bind(id)
+            .fetch_optional(db)
+            .await?;
+        let (file_path,) = row.ok_or_else(|| AppError::NotFound(format!("Track {} not found", id)))?;
+        tracks.push((id, file_path));
+    }
+    let removed = duplicates::remove_tracks(db, tracks, &action).await?;
+    prune_orphans(db).await?;
+    Ok(removed)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_duplicates(
+    db: State<'_, DbPool>,
+    track_ids: Vec<i64>,
+    action: DuplicateAction,
+) -> Result<Vec<RemovedDuplicate>, AppError> {
+    remove_duplicates_inner(db.inner(), track_ids, action).await
+}
+
 // ── Extra Tag Commands ──
 
 pub async fn get_track_extra_tags_inner(
@@ -857,13 +1348,37 @@ pub async fn set_track_extra_tags_inner(
     db: &DbPool,
     track_id: i64,
     tags: Vec<ExtraTag>,
+    skip_file_write: bool,
 ) -> Result<(), AppError> {
+    let row: Option<(String, Option<i64>)> =
+        sqlx::query_as("SELECT file_path, file_mtime FROM tracks WHERE id = ?")
+            .bind(track_id)
+            .fetch_optional(db)
+            .await?;
+    let (file_path, existing_mtime) =
+        row.ok_or_else(|| AppError::NotFound(format!("Track {} not found", track_id)))?;
+    let existing = get_track_extra_tags_inner(db, track_id).await?;
+
+    // Write tags to file BEFORE touching the DB (file is source of truth)
+    let file_mtime = if !skip_file_write {
+        let removed: Vec<String> = existing
+            .iter()
+            .map(|t| t.frame_id.clone())
+            .filter(|id| !tags.iter().any(|t| t.frame_id == *id))
+            .collect();
+        backups::backup_file(db, &file_path).await?;
+        write_extra_tags_to_file(&file_path, &removed, &tags)?;
+        read_file_mtime(&file_path)
+    } else {
+        existing_mtime
+    };
+
     let mut tx = db.begin().await?;
     sqlx::query("DELETE FROM track_extra_tags WHERE track_id = ?")
         .bind(track_id)
         .execute(&mut *tx)
         .await?;
-    for tag in &tags {
+    for tag in tags.iter().filter(|t| !t.value.is_empty()) {
         sqlx::query(
             "INSERT INTO track_extra_tags (track_id, frame_id, value) VALUES (?, ?, ?)",
         )
@@ -873,7 +1388,28 @@ pub async fn set_track_extra_tags_inner(
         .execute(&mut *tx)
         .await?;
     }
+    sqlx::query("UPDATE tracks SET file_mtime = ?, updated_at = ? WHERE id = ?")
+        .bind(file_mtime)
+        .bind(Utc::now().to_rfc3339())
+        .bind(track_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Re-credit only the fields whose value changed, so multi-valued frames
+    // read by the scan keep all their names
+    let value = |list: &[ExtraTag], frame_id: &str| {
+        list.iter().find(|t| t.frame_id == frame_id && !t.value.is_empty()).map(|t| t.value.clone())
+    };
+    let changed: Vec<CreditField> = CreditField::EXTRA
+        .iter()
+        .copied()
+        .filter(|f| f.extra_frame().is_some_and(|frame| value(&existing, frame) != value(&tags, frame)))
+        .collect();
+    if !changed.is_empty() {
+        credits::refresh_credits(&mut tx, track_id, &changed).await?;
+    }
     tx.commit().await?;
+    prune_orphans(db).await?;
     Ok(())
 }
 
@@ -884,1759 +1420,5903 @@ pub async fn set_track_extra_tags(
     track_id: i64,
     tags: Vec<ExtraTag>,
 ) -> Result<(), AppError> {
-    set_track_extra_tags_inner(db.inner(), track_id, tags).await
+    set_track_extra_tags_inner(db.inner(), track_id, tags, false).await
 }
 
-// ── Artist Commands ──
+// ── Smart Playlists ──
+
+#[derive(FromRow)]
+struct SmartPlaylistRecord {
+    id: i64,
+    name: String,
+    rules: String,
+    sort_field: Option<String>,
+    sort_descending: bool,
+    track_limit: Option<i64>,
+    created_at: String,
+    updated_at: String,
+}
 
-pub async fn list_artists_inner(db: &DbPool) -> Result<Vec<Artist>, AppError> {
-    Ok(
-        sqlx::query_as::<_, Artist>(
-            "SELECT id, name, sort_name, musicbrainz_id, created_at FROM artists ORDER BY name ASC",
-        )
+impl TryFrom<SmartPlaylistRecord> for SmartPlaylist {
+    type Error = AppError;
+
+    fn try_from(r: SmartPlaylistRecord) -> Result<Self, AppError> {
+        Ok(SmartPlaylist {
+            id: r.id,
+            name: r.name,
+            rules: serde_json::from_str(&r.rules)?,
+            sort: r.sort_field.map(|field| SortSpec { field, descending: r.sort_descending }),
+            limit: r.track_limit,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        })
+    }
+}
+
+/// Check a playlist definition before it is stored, so a saved playlist always evaluates.
+fn validate_smart_playlist(input: &SmartPlaylistInput) -> Result<String, AppError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Playlist name cannot be empty".to_string()));
+    }
+    if input.limit.is_some_and(|l| l < 1) {
+        return Err(AppError::InvalidInput("Playlist limit must be at least 1".to_string()));
+    }
+    library_query::TRACKS.validate_rules(&input.rules, input.sort.as_ref())?;
+    Ok(name.to_string())
+}
+
+pub async fn list_smart_playlists_inner(db: &DbPool) -> Result<Vec<SmartPlaylist>, AppError> {
+    sqlx::query_as::<_, SmartPlaylistRecord>("SELECT * FROM smart_playlists ORDER BY name COLLATE NOCASE")
         .fetch_all(db)
-        .await?,
-    )
+        .await?
+        .into_iter()
+        .map(SmartPlaylist::try_from)
+        .collect()
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn list_artists(db: State<'_, DbPool>) -> Result<Vec<Artist>, AppError> {
-    list_artists_inner(db.inner()).await
+pub async fn list_smart_playlists(db: State<'_, DbPool>) -> Result<Vec<SmartPlaylist>, AppError> {
+    list_smart_playlists_inner(db.inner()).await
 }
 
-pub async fn list_artist_rows_inner(db: &DbPool) -> Result<Vec<ArtistRow>, AppError> {
-    Ok(sqlx::query_as::<_, ArtistRow>(
-        "SELECT a.id, a.name, a.sort_name,
-                (SELECT COUNT(*) FROM albums al WHERE al.artist_id = a.id) as album_count,
-                (SELECT COUNT(*) FROM tracks t WHERE t.artist_id = a.id) as track_count,
-                (SELECT COALESCE(SUM(t.duration_secs), 0.0) FROM tracks t WHERE t.artist_id = a.id) as total_duration_secs
-         FROM artists a
-         ORDER BY a.name ASC",
+pub async fn get_smart_playlist_inner(db: &DbPool, playlist_id: i64) -> Result<SmartPlaylist, AppError> {
+    sqlx::query_as::<_, SmartPlaylistRecord>("SELECT * FROM smart_playlists WHERE id = ?")
+        .bind(playlist_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Smart playlist {} not found", playlist_id)))?
+        .try_into()
+}
+
+pub async fn create_smart_playlist_inner(
+    db: &DbPool,
+    input: SmartPlaylistInput,
+) -> Result<SmartPlaylist, AppError> {
+    let name = validate_smart_playlist(&input)?;
+    let now = Utc::now().to_rfc3339();
+    let id = sqlx::query(
+        "INSERT INTO smart_playlists (name, rules, sort_field, sort_descending, track_limit, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
     )
-    .fetch_all(db)
-    .await?)
+    .bind(&name)
+    .bind(serde_json::to_string(&input.rules)?)
+    .bind(input.sort.as_ref().map(|s| &s.field))
+    .bind(input.sort.as_ref().is_some_and(|s| s.descending))
+    .bind(input.limit)
+    .bind(&now)
+    .bind(&now)
+    .execute(db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref d) if d.is_unique_violation() => {
+            AppError::InvalidInput(format!("A smart playlist named {:?} already exists", name))
+        }
+        e => e.into(),
+    })?
+    .last_insert_rowid();
+    get_smart_playlist_inner(db, id).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn list_artist_rows(db: State<'_, DbPool>) -> Result<Vec<ArtistRow>, AppError> {
-    list_artist_rows_inner(db.inner()).await
+pub async fn create_smart_playlist(
+    db: State<'_, DbPool>,
+    input: SmartPlaylistInput,
+) -> Result<SmartPlaylist, AppError> {
+    create_smart_playlist_inner(db.inner(), input).await
 }
 
-// ── Album Commands ──
-
-pub async fn list_albums_inner(
+pub async fn update_smart_playlist_inner(
     db: &DbPool,
-    artist_id: Option<i64>,
-) -> Result<Vec<Album>, AppError> {
-    if let Some(aid) = artist_id {
-        Ok(sqlx::query_as::<_, Album>(
-            "SELECT id, title, artist_id, year, genre, cover_path, musicbrainz_id, created_at
-             FROM albums WHERE artist_id = ? ORDER BY year ASC, title ASC",
-        )
-        .bind(aid)
-        .fetch_all(db)
-        .await?)
-    } else {
-        Ok(sqlx::query_as::<_, Album>(
-            "SELECT id, title, artist_id, year, genre, cover_path, musicbrainz_id, created_at
-             FROM albums ORDER BY title ASC",
-        )
-        .fetch_all(db)
-        .await?)
+    playlist_id: i64,
+    input: SmartPlaylistInput,
+) -> Result<SmartPlaylist, AppError> {
+    let name = validate_smart_playlist(&input)?;
+    let result = sqlx::query(
+        "UPDATE smart_playlists
+         SET name = ?, rules = ?, sort_field = ?, sort_descending = ?, track_limit = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(&name)
+    .bind(serde_json::to_string(&input.rules)?)
+    .bind(input.sort.as_ref().map(|s| &s.field))
+    .bind(input.sort.as_ref().is_some_and(|s| s.descending))
+    .bind(input.limit)
+    .bind(Utc::now().to_rfc3339())
+    .bind(playlist_id)
+    .execute(db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref d) if d.is_unique_violation() => {
+            AppError::InvalidInput(format!("A smart playlist named {:?} already exists", name))
+        }
+        e => e.into(),
+    })?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Smart playlist {} not found", playlist_id)));
     }
+    get_smart_playlist_inner(db, playlist_id).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn list_albums(
+pub async fn update_smart_playlist(
     db: State<'_, DbPool>,
-    artist_id: Option<i64>,
-) -> Result<Vec<Album>, AppError> {
-    list_albums_inner(db.inner(), artist_id).await
+    playlist_id: i64,
+    input: SmartPlaylistInput,
+) -> Result<SmartPlaylist, AppError> {
+    update_smart_playlist_inner(db.inner(), playlist_id, input).await
 }
 
-pub async fn list_album_rows_inner(db: &DbPool) -> Result<Vec<AlbumRow>, AppError> {
-    Ok(sqlx::query_as::<_, AlbumRow>(
-        "SELECT al.id, al.title, ar.name as artist_name, al.year, al.genre,
-                COUNT(t.id) as track_count,
-                COALESCE(SUM(t.duration_secs), 0.0) as total_duration_secs,
-                COALESCE(SUM(t.file_size_bytes), 0) as total_size_bytes
-         FROM albums al
-         LEFT JOIN artists ar ON al.artist_id = ar.id
-         LEFT JOIN tracks t ON t.album_id = al.id
-         GROUP BY al.id
-         ORDER BY al.title ASC",
-    )
-    .fetch_all(db)
-    .await?)
+pub async fn delete_smart_playlist_inner(db: &DbPool, playlist_id: i64) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM smart_playlists WHERE id = ?")
+        .bind(playlist_id)
+        .execute(db)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Smart playlist {} not found", playlist_id)));
+    }
+    Ok(())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn list_album_rows(db: State<'_, DbPool>) -> Result<Vec<AlbumRow>, AppError> {
-    list_album_rows_inner(db.inner()).await
+pub async fn delete_smart_playlist(db: State<'_, DbPool>, playlist_id: i64) -> Result<(), AppError> {
+    delete_smart_playlist_inner(db.inner(), playlist_id).await
 }
 
-pub async fn list_tracks_by_album_inner(
+/// Evaluate a smart playlist's rules against the current library.
+pub async fn list_smart_playlist_tracks_inner(
     db: &DbPool,
-    album_id: i64,
+    playlist_id: i64,
 ) -> Result<Vec<TrackRow>, AppError> {
-    Ok(sqlx::query_as::<_, TrackRow>(
-        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path
-         FROM tracks t
-         LEFT JOIN artists a ON t.artist_id = a.id
-         LEFT JOIN albums al ON t.album_id = al.id
-         WHERE t.album_id = ?
-         ORDER BY t.disc_number ASC, t.track_number ASC",
+    let playlist = get_smart_playlist_inner(db, playlist_id).await?;
+    library_query::fetch_matching(
+        db,
+        &library_query::TRACKS,
+        &playlist.rules,
+        playlist.sort.as_ref(),
+        playlist.limit,
     )
-    .bind(album_id)
-    .fetch_all(db)
-    .await?)
+    .await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn list_tracks_by_album(
+pub async fn list_smart_playlist_tracks(
     db: State<'_, DbPool>,
-    album_id: i64,
+    playlist_id: i64,
 ) -> Result<Vec<TrackRow>, AppError> {
-    list_tracks_by_album_inner(db.inner(), album_id).await
+    list_smart_playlist_tracks_inner(db.inner(), playlist_id).await
 }
 
-// ── Scan ──
+// ── Playlists ──
 
-pub async fn scan_collection_inner(
-    db: &DbPool,
-    collection_id: i64,
-    covers_dir: Option<&Path>,
-    on_progress: impl Fn(u32),
-) -> Result<u32, AppError> {
-    let collection = sqlx::query_as::<_, Collection>(
-        "SELECT * FROM collections WHERE id = ?",
-    )
-    .bind(collection_id)
-    .fetch_optional(db)
-    .await?
-    .ok_or_else(|| AppError::NotFound(format!("Collection {} not found", collection_id)))?;
+const PLAYLIST_SELECT: &str = "SELECT p.id, p.name, COUNT(e.id) AS track_count, p.created_at, p.updated_at
+     FROM playlists p
+     LEFT JOIN playlist_entries e ON e.playlist_id = p.id";
 
-    let root_path = PathBuf::from(&collection.path);
-    if !root_path.exists() {
-        return Err(AppError::Io(format!("Directory not found: {:?}", root_path)));
+fn playlist_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Playlist name cannot be empty".to_string()));
     }
+    Ok(name.to_string())
+}
 
-    // Ensure covers directory exists if provided
-    if let Some(dir) = covers_dir {
-        std::fs::create_dir_all(dir)
-            .map_err(|e| AppError::Io(format!("Failed to create covers dir: {}", e)))?;
+fn map_playlist_name_taken(name: &str) -> impl FnOnce(sqlx::Error) -> AppError + '_ {
+    move |e| match e {
+        sqlx::Error::Database(ref d) if d.is_unique_violation() => {
+            AppError::InvalidInput(format!("A playlist named {:?} already exists", name))
+        }
+        e => e.into(),
     }
+}
 
-    info!("Starting scan of collection: {:?}", root_path);
+async fn fetch_playlist(conn: &mut SqliteConnection, playlist_id: i64) -> Result<Playlist, AppError> {
+    sqlx::query_as::<_, Playlist>(&format!("{} WHERE p.id = ? GROUP BY p.id", PLAYLIST_SELECT))
+        .bind(playlist_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Playlist {} not found", playlist_id)))
+}
 
-    let audio_extensions = ["mp3", "m4a", "flac", "wav", "ogg", "opus", "wma"];
-    let mut scanned: u32 = 0;
+async fn insert_playlist(conn: &mut SqliteConnection, name: &str) -> Result<i64, AppError> {
+    let now = Utc::now().to_rfc3339();
+    Ok(sqlx::query("INSERT INTO playlists (name, created_at, updated_at) VALUES (?, ?, ?)")
+        .bind(name)
+        .bind(&now)
+        .bind(&now)
+        .execute(&mut *conn)
+        .await
+        .map_err(map_playlist_name_taken(name))?
+        .last_insert_rowid())
+}
 
-    for entry in WalkDir::new(&root_path).follow_links(true) {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(e) => {
-                warn!("Scan: skipping inaccessible path: {}", e);
-                continue;
-            }
-        };
+/// Entry ids of a playlist in play order.
+async fn playlist_entry_ids(conn: &mut SqliteConnection, playlist_id: i64) -> Result<Vec<i64>, AppError> {
+    let rows: Vec<(i64,)> =
+        sqlx::query_as("SELECT id FROM playlist_entries WHERE playlist_id = ? ORDER BY position, id")
+            .bind(playlist_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
 
-        if !entry.file_type().is_file() {
-            continue;
-        }
+/// Store `entry_ids` as the playlist's order, numbering positions from 0.
+async fn write_playlist_order(
+    conn: &mut SqliteConnection,
+    playlist_id: i64,
+    entry_ids: &[i64],
+) -> Result<(), AppError> {
+    for (position, entry_id) in entry_ids.iter().enumerate() {
+        sqlx::query("UPDATE playlist_entries SET position = ? WHERE id = ? AND playlist_id = ?")
+            .bind(position as i64)
+            .bind(entry_id)
+            .bind(playlist_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query("UPDATE playlists SET updated_at = ? WHERE id = ?")
+        .bind(Utc::now().to_rfc3339())
+        .bind(playlist_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
 
-        let path = entry.into_path();
-        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+/// Insert entries for `track_ids` before `position` (appending when None or past the end).
+async fn insert_playlist_tracks(
+    conn: &mut SqliteConnection,
+    playlist_id: i64,
+    track_ids: &[i64],
+    position: Option<i64>,
+) -> Result<(), AppError> {
+    let mut order = playlist_entry_ids(conn, playlist_id).await?;
+    let at = position.map_or(order.len(), |p| (p.max(0) as usize).min(order.len()));
+    let mut added = Vec::with_capacity(track_ids.len());
+    for &track_id in track_ids {
+        let entry_id = sqlx::query("INSERT INTO playlist_entries (playlist_id, track_id, position) VALUES (?, ?, -1)")
+            .bind(playlist_id)
+            .bind(track_id)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+        added.push(entry_id);
+    }
+    order.splice(at..at, added);
+    write_playlist_order(conn, playlist_id, &order).await
+}
 
-        if !audio_extensions.contains(&ext.as_str()) {
-            continue;
-        }
+async fn fetch_playlist_entries(
+    conn: &mut SqliteConnection,
+    playlist_id: i64,
+) -> Result<Vec<PlaylistEntry>, AppError> {
+    let rows = sqlx::query(
+        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path,
+                al.loudness_lufs as album_loudness_lufs, al.true_peak as album_true_peak,
+                e.id AS entry_id, e.position AS entry_position
+         FROM playlist_entries e
+         JOIN tracks t ON e.track_id = t.id
+         LEFT JOIN artists a ON t.artist_id = a.id
+         LEFT JOIN albums al ON t.album_id = al.id
+         WHERE e.playlist_id = ?
+         ORDER BY e.position, e.id",
+    )
+    .bind(playlist_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            Ok(PlaylistEntry {
+                id: row.try_get("entry_id")?,
+                position: row.try_get("entry_position")?,
+                track: TrackRow::from_row(row)?,
+            })
+        })
+        .collect()
+}
 
-        match process_track(db, collection_id, &path, covers_dir).await {
-            Ok(_) => {
-                scanned += 1;
-                on_progress(scanned);
-            }
-            Err(e) => error!("Error processing track {:?}: {:?}", path, e),
-        }
-    }
+pub async fn list_playlists_inner(db: &DbPool) -> Result<Vec<Playlist>, AppError> {
+    Ok(sqlx::query_as::<_, Playlist>(&format!(
+        "{} GROUP BY p.id ORDER BY p.name COLLATE NOCASE",
+        PLAYLIST_SELECT
+    ))
+    .fetch_all(db)
+    .await?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_playlists(db: State<'_, DbPool>) -> Result<Vec<Playlist>, AppError> {
+    list_playlists_inner(db.inner()).await
+}
 
-    info!("Scan of collection {:?} complete: {} tracks", root_path, scanned);
-    Ok(scanned)
+pub async fn create_playlist_inner(db: &DbPool, name: &str) -> Result<Playlist, AppError> {
+    let name = playlist_name(name)?;
+    let mut conn = db.acquire().await?;
+    let id = insert_playlist(&mut conn, &name).await?;
+    fetch_playlist(&mut conn, id).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn scan_collection(
-    app_handle: tauri::AppHandle,
-    db: State<'_, DbPool>,
-    collection_id: i64,
-) -> Result<u32, AppError> {
-    let covers_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| AppError::Io(format!("Failed to get app data dir: {}", e)))?
-        .join("covers");
-    let handle = app_handle.clone();
-    let total = scan_collection_inner(db.inner(), collection_id, Some(&covers_dir), move |n| {
-        let _ = handle.emit("scan:progress", n);
-    })
-    .await?;
-    let _ = app_handle.emit("scan:complete", total);
-    Ok(total)
+pub async fn create_playlist(db: State<'_, DbPool>, name: String) -> Result<Playlist, AppError> {
+    create_playlist_inner(db.inner(), &name).await
 }
 
-async fn process_track(
-    db: &DbPool,
-    collection_id: i64,
-    path: &Path,
-    covers_dir: Option<&Path>,
-) -> Result<(), AppError> {
-    let path_str = path.to_string_lossy().replace('\\', "/");
-    let meta = std::fs::metadata(path);
-    let file_size = meta.as_ref().map(|m| m.len() as i64).unwrap_or(0);
-    let file_mtime: Option<i64> = meta
-        .ok()
-        .and_then(|m| m.modified().ok())
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-        .map(|d| d.as_secs() as i64);
-    let now = Utc::now().to_rfc3339();
+pub async fn rename_playlist_inner(db: &DbPool, playlist_id: i64, name: &str) -> Result<Playlist, AppError> {
+    let name = playlist_name(name)?;
+    let mut conn = db.acquire().await?;
+    let result = sqlx::query("UPDATE playlists SET name = ?, updated_at = ? WHERE id = ?")
+        .bind(&name)
+        .bind(Utc::now().to_rfc3339())
+        .bind(playlist_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(map_playlist_name_taken(&name))?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Playlist {} not found", playlist_id)));
+    }
+    fetch_playlist(&mut conn, playlist_id).await
+}
 
-    // Read tags
-    #[allow(clippy::type_complexity)]
-    let (tag_title, artist_name, album_title, year, track_num, disc_num, duration, cover_data,
-         genre, album_artist, composer, bpm, comment, lyrics_text) = match Probe::open(path) {
-        Ok(probe) => {
-            match probe.read() {
-                Ok(tagged_file) => {
-                    let properties = tagged_file.properties();
-                    let duration = properties.duration().as_secs_f64();
+#[tauri::command]
+#[specta::specta]
+pub async fn rename_playlist(
+    db: State<'_, DbPool>,
+    playlist_id: i64,
+    name: String,
+) -> Result<Playlist, AppError> {
+    rename_playlist_inner(db.inner(), playlist_id, &name).await
+}
 
-                    let tag = tagged_file.primary_tag()
-                        .or_else(|| tagged_file.first_tag());
+pub async fn delete_playlist_inner(db: &DbPool, playlist_id: i64) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM playlist_entries WHERE playlist_id = ?")
+        .bind(playlist_id)
+        .execute(&mut *tx)
+        .await?;
+    let result = sqlx::query("DELETE FROM playlists WHERE id = ?")
+        .bind(playlist_id)
+        .execute(&mut *tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Playlist {} not found", playlist_id)));
+    }
+    tx.commit().await?;
+    Ok(())
+}
 
-                    if let Some(t) = tag {
-                        // Extract first picture if present
-                        let picture_data = t.pictures().first().map(|pic| {
-                            let ext = match pic.mime_type() {
-                                Some(lofty::picture::MimeType::Png) => "png",
-                                _ => "jpg",
-                            };
-                            (pic.data().to_vec(), ext.to_string())
-                        });
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_playlist(db: State<'_, DbPool>, playlist_id: i64) -> Result<(), AppError> {
+    delete_playlist_inner(db.inner(), playlist_id).await
+}
 
-                        let genre = t.genre().map(|s| s.to_string());
-                        let album_artist = t.get_string(&ItemKey::AlbumArtist).map(|s| s.to_string());
-                        let composer = t.get_string(&ItemKey::Composer).map(|s| s.to_string());
-                        let bpm = t.get_string(&ItemKey::Bpm)
-                            .and_then(|s| s.parse::<i32>().ok());
-                        let comment = t.get_string(&ItemKey::Comment).map(|s| s.to_string());
-                        let lyrics_text = t.get_string(&ItemKey::Lyrics).map(|s| s.to_string());
-
-                        (
-                            t.title().map(|s| s.to_string()),
-                            t.artist().map(|s| s.to_string()),
-                            t.album().map(|s| s.to_string()),
-                            t.year().map(|y| y as i32),
-                            t.track().map(|tn| tn as i32),
-                            t.disk().map(|dn| dn as i32),
-                            Some(duration),
-                            picture_data,
-                            genre,
-                            album_artist,
-                            composer,
-                            bpm,
-                            comment,
-                            lyrics_text,
-                        )
-                    } else {
-                        (None, None, None, None, None, None, Some(duration), None,
-                         None, None, None, None, None, None)
-                    }
-                }
-                Err(e) => {
-                    warn!("Failed to read tags for {:?}: {:?}", path, e);
-                    (None, None, None, None, None, None, None, None,
-                     None, None, None, None, None, None)
-                }
-            }
-        }
-        Err(e) => {
-            warn!("Failed to probe file {:?}: {:?}", path, e);
-            (None, None, None, None, None, None, None, None,
-             None, None, None, None, None, None)
-        }
-    };
+pub async fn list_playlist_entries_inner(db: &DbPool, playlist_id: i64) -> Result<Vec<PlaylistEntry>, AppError> {
+    let mut conn = db.acquire().await?;
+    fetch_playlist(&mut conn, playlist_id).await?;
+    fetch_playlist_entries(&mut conn, playlist_id).await
+}
 
-    let title = tag_title.unwrap_or_else(|| {
-        path.file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("Unknown Track")
-            .to_string()
-    });
+#[tauri::command]
+#[specta::specta]
+pub async fn list_playlist_entries(
+    db: State<'_, DbPool>,
+    playlist_id: i64,
+) -> Result<Vec<PlaylistEntry>, AppError> {
+    list_playlist_entries_inner(db.inner(), playlist_id).await
+}
 
+/// Add tracks before `position`, or at the end when it is None. Returns the new entry list.
+pub async fn add_tracks_to_playlist_inner(
+    db: &DbPool,
+    playlist_id: i64,
+    track_ids: &[i64],
+    position: Option<i64>,
+) -> Result<Vec<PlaylistEntry>, AppError> {
     let mut tx = db.begin().await?;
-
-    // 1. Ensure Artist exists
-    let artist_id = if let Some(name) = artist_name {
-        let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM artists WHERE name = ?")
-            .bind(&name)
+    fetch_playlist(&mut tx, playlist_id).await?;
+    for &track_id in track_ids {
+        let exists: Option<(i64,)> = sqlx::query_as("SELECT id FROM tracks WHERE id = ?")
+            .bind(track_id)
             .fetch_optional(&mut *tx)
             .await?;
-
-        if let Some(r) = row {
-            Some(r.0)
-        } else {
-            let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-                .bind(&name)
-                .bind(&now)
-                .execute(&mut *tx)
-                .await?;
-            Some(res.last_insert_rowid())
+        if exists.is_none() {
+            return Err(AppError::NotFound(format!("Track {} not found", track_id)));
         }
-    } else {
-        None
-    };
+    }
+    insert_playlist_tracks(&mut tx, playlist_id, track_ids, position).await?;
+    let entries = fetch_playlist_entries(&mut tx, playlist_id).await?;
+    tx.commit().await?;
+    Ok(entries)
+}
 
-    // 2. Ensure Album exists
-    let album_id = if let Some(title) = album_title {
-        let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM albums WHERE title = ? AND (artist_id = ? OR (artist_id IS NULL AND ? IS NULL))")
-            .bind(&title)
-            .bind(artist_id)
-            .bind(artist_id)
-            .fetch_optional(&mut *tx)
+#[tauri::command]
+#[specta::specta]
+pub async fn add_tracks_to_playlist(
+    db: State<'_, DbPool>,
+    playlist_id: i64,
+    track_ids: Vec<i64>,
+    position: Option<i64>,
+) -> Result<Vec<PlaylistEntry>, AppError> {
+    add_tracks_to_playlist_inner(db.inner(), playlist_id, &track_ids, position).await
+}
+
+pub async fn remove_playlist_entries_inner(
+    db: &DbPool,
+    playlist_id: i64,
+    entry_ids: &[i64],
+) -> Result<Vec<PlaylistEntry>, AppError> {
+    let mut tx = db.begin().await?;
+    fetch_playlist(&mut tx, playlist_id).await?;
+    for &entry_id in entry_ids {
+        sqlx::query("DELETE FROM playlist_entries WHERE id = ? AND playlist_id = ?")
+            .bind(entry_id)
+            .bind(playlist_id)
+            .execute(&mut *tx)
             .await?;
+    }
+    let order = playlist_entry_ids(&mut tx, playlist_id).await?;
+    write_playlist_order(&mut tx, playlist_id, &order).await?;
+    let entries = fetch_playlist_entries(&mut tx, playlist_id).await?;
+    tx.commit().await?;
+    Ok(entries)
+}
 
-        if let Some(r) = row {
-            Some(r.0)
-        } else {
-            let res = sqlx::query("INSERT INTO albums (title, artist_id, year, created_at) VALUES (?, ?, ?, ?)")
-                .bind(&title)
-                .bind(artist_id)
-                .bind(year)
-                .bind(&now)
-                .execute(&mut *tx)
-                .await?;
-            Some(res.last_insert_rowid())
-        }
-    } else {
-        None
-    };
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_playlist_entries(
+    db: State<'_, DbPool>,
+    playlist_id: i64,
+    entry_ids: Vec<i64>,
+) -> Result<Vec<PlaylistEntry>, AppError> {
+    remove_playlist_entries_inner(db.inner(), playlist_id, &entry_ids).await
+}
 
-    // 2b. Save cover art if we have picture data, a covers dir, and album has no cover yet
-    if let (Some(album_id), Some((ref data, ref ext)), Some(dir)) =
-        (album_id, &cover_data, covers_dir)
-    {
-        let existing_cover: Option<(Option<String>,)> =
-            sqlx::query_as("SELECT cover_path FROM albums WHERE id = ?")
-                .bind(album_id)
-                .fetch_optional(&mut *tx)
-                .await?;
+/// Replace the playlist's order. `entry_ids` must list every entry exactly once.
+pub async fn reorder_playlist_inner(
+    db: &DbPool,
+    playlist_id: i64,
+    entry_ids: &[i64],
+) -> Result<Vec<PlaylistEntry>, AppError> {
+    let mut tx = db.begin().await?;
+    fetch_playlist(&mut tx, playlist_id).await?;
+    let mut current = playlist_entry_ids(&mut tx, playlist_id).await?;
+    let mut requested = entry_ids.to_vec();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Err(AppError::InvalidInput(
+            "New order must list every entry of the playlist exactly once".to_string(),
+        ));
+    }
+    write_playlist_order(&mut tx, playlist_id, entry_ids).await?;
+    let entries = fetch_playlist_entries(&mut tx, playlist_id).await?;
+    tx.commit().await?;
+    Ok(entries)
+}
 
-        let needs_cover = existing_cover
-            .map(|(cp,)| cp.is_none())
-            .unwrap_or(false);
+#[tauri::command]
+#[specta::specta]
+pub async fn reorder_playlist(
+    db: State<'_, DbPool>,
+    playlist_id: i64,
+    entry_ids: Vec<i64>,
+) -> Result<Vec<PlaylistEntry>, AppError> {
+    reorder_playlist_inner(db.inner(), playlist_id, &entry_ids).await
+}
 
-        if needs_cover {
-            let cover_filename = format!("{}.{}", album_id, ext);
-            let cover_path = dir.join(&cover_filename);
-            if let Err(e) = std::fs::write(&cover_path, data) {
-                warn!("Failed to write cover art for album {}: {}", album_id, e);
-            } else {
-                let cover_path_str = cover_path.to_string_lossy().replace('\\', "/");
-                sqlx::query("UPDATE albums SET cover_path = ? WHERE id = ?")
-                    .bind(&cover_path_str)
-                    .bind(album_id)
-                    .execute(&mut *tx)
-                    .await?;
-            }
+/// Create a playlist from an M3U/M3U8, PLS or XSPF file. Entries are matched
+/// to library tracks by path (case-insensitively as a fallback); the rest are
+/// reported back. `name` defaults to the file name.
+pub async fn import_playlist_inner(
+    db: &DbPool,
+    path: &str,
+    name: Option<&str>,
+) -> Result<PlaylistImport, AppError> {
+    let file = Path::new(path);
+    let format = PlaylistFormat::from_path(file)
+        .ok_or_else(|| AppError::InvalidInput(format!("Not a playlist file: {}", path)))?;
+    let content = playlist_formats::decode_text(&std::fs::read(file)?);
+    let locations = playlist_formats::parse(format, &content)?;
+    let base_dir = file.parent().unwrap_or(Path::new(""));
+    let name = playlist_name(match name {
+        Some(n) => n,
+        None => file.file_stem().and_then(|s| s.to_str()).unwrap_or_default(),
+    })?;
+
+    let mut tx = db.begin().await?;
+    let mut track_ids = Vec::new();
+    let mut unresolved = Vec::new();
+    for location in locations {
+        let mut track_id = None;
+        if let Some(resolved) = playlist_formats::resolve_location(&location, base_dir) {
+            let exact: Option<(i64,)> = sqlx::query_as("SELECT id FROM tracks WHERE file_path = ?")
+                .bind(&resolved)
+                .fetch_optional(&mut *tx)
+                .await?;
+            track_id = match exact {
+                Some((id,)) => Some(id),
+                None => sqlx::query_as::<_, (i64,)>(
+                    "SELECT id FROM tracks WHERE file_path = ? COLLATE NOCASE ORDER BY id LIMIT 1",
+                )
+                .bind(&resolved)
+                .fetch_optional(&mut *tx)
+                .await?
+                .map(|(id,)| id),
+            };
+        }
+        match track_id {
+            Some(id) => track_ids.push(id),
+            None => unresolved.push(location),
         }
     }
 
-    // 3. Upsert Track
-    sqlx::query(
-        r#"
-        INSERT INTO tracks (
-            collection_id, album_id, artist_id, title,
-            track_number, disc_number, duration_secs,
-            file_path, file_size_bytes, file_format,
-            genre, album_artist, composer, bpm, comment, lyrics,
-            file_mtime, created_at, updated_at
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-        ON CONFLICT(file_path) DO UPDATE SET
-            album_id = excluded.album_id,
-            artist_id = excluded.artist_id,
-            title = excluded.title,
-            track_number = excluded.track_number,
-            disc_number = excluded.disc_number,
-            duration_secs = excluded.duration_secs,
-            file_size_bytes = excluded.file_size_bytes,
-            genre = excluded.genre,
-            album_artist = excluded.album_artist,
-            composer = excluded.composer,
-            bpm = excluded.bpm,
-            comment = excluded.comment,
-            lyrics = excluded.lyrics,
-            file_mtime = excluded.file_mtime,
-            updated_at = excluded.updated_at
-        "#
-    )
-    .bind(collection_id)
-    .bind(album_id)
-    .bind(artist_id)
-    .bind(&title)
-    .bind(track_num)
-    .bind(disc_num)
-    .bind(duration)
-    .bind(&path_str)
-    .bind(file_size)
-    .bind(path.extension().and_then(|s| s.to_str()))
-    .bind(&genre)
-    .bind(&album_artist)
-    .bind(&composer)
-    .bind(bpm)
-    .bind(&comment)
-    .bind(&lyrics_text)
-    .bind(file_mtime)
-    .bind(&now)
-    .bind(&now)
-    .execute(&mut *tx)
-    .await?;
-
+    let playlist_id = insert_playlist(&mut tx, &name).await?;
+    insert_playlist_tracks(&mut tx, playlist_id, &track_ids, None).await?;
+    let playlist = fetch_playlist(&mut tx, playlist_id).await?;
     tx.commit().await?;
-    Ok(())
+    info!(
+        "Imported playlist {:?}: {} tracks, {} unresolved",
+        playlist.name,
+        playlist.track_count,
+        unresolved.len()
+    );
+    Ok(PlaylistImport { playlist, unresolved })
 }
 
-// ── Cover Art ──
+#[tauri::command]
+#[specta::specta]
+pub async fn import_playlist(
+    db: State<'_, DbPool>,
+    path: String,
+    name: Option<String>,
+) -> Result<PlaylistImport, AppError> {
+    import_playlist_inner(db.inner(), &path, name.as_deref()).await
+}
 
-pub async fn get_cover_art_inner(
+/// Write a playlist to `path`. Track paths are written relative to
+/// `relative_to` (the playlist file's folder by default). The format follows
+/// the file extension unless given.
+pub async fn export_playlist_inner(
     db: &DbPool,
-    track_id: i64,
-) -> Result<Option<CoverArt>, AppError> {
-    let row: Option<(String,)> =
-        sqlx::query_as("SELECT file_path FROM tracks WHERE id = ?")
-            .bind(track_id)
-            .fetch_optional(db)
-            .await?;
-
-    let file_path = row
-        .ok_or_else(|| AppError::NotFound(format!("Track {} not found", track_id)))?
-        .0;
+    playlist_id: i64,
+    path: &str,
+    format: Option<PlaylistFormat>,
+    relative_to: Option<&str>,
+) -> Result<(), AppError> {
+    let file = Path::new(path);
+    let format = format
+        .or_else(|| PlaylistFormat::from_path(file))
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown playlist format for {}", path)))?;
+    let base_dir = match relative_to {
+        Some(dir) => PathBuf::from(dir),
+        None => file.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
 
-    // Normalize forward slashes back to native separators
-    let path = PathBuf::from(file_path.replace('/', std::path::MAIN_SEPARATOR_STR));
+    let mut conn = db.acquire().await?;
+    let playlist = fetch_playlist(&mut conn, playlist_id).await?;
+    let entries: Vec<playlist_formats::ExportEntry> = fetch_playlist_entries(&mut conn, playlist_id)
+        .await?
+        .into_iter()
+        .map(|e| playlist_formats::ExportEntry {
+            location: playlist_formats::relative_location(Path::new(&e.track.file_path), &base_dir),
+            title: e.track.title,
+            artist: e.track.artist_name,
+            duration_secs: e.track.duration_secs,
+        })
+        .collect();
+    drop(conn);
 
-    let tagged_file = Probe::open(&path)
-        .map_err(|e| AppError::Io(format!("Failed to open {:?}: {}", path, e)))?
-        .read()
-        .map_err(|e| AppError::Io(format!("Failed to read tags from {:?}: {}", path, e)))?;
+    write_file_atomically(file, playlist_formats::write(format, &playlist.name, &entries).as_bytes())?;
+    Ok(())
+}
 
-    let tag = tagged_file
-        .primary_tag()
-        .or_else(|| tagged_file.first_tag());
+#[tauri::command]
+#[specta::specta]
+pub async fn export_playlist(
+    db: State<'_, DbPool>,
+    playlist_id: i64,
+    path: String,
+    format: Option<PlaylistFormat>,
+    relative_to: Option<String>,
+) -> Result<(), AppError> {
+    export_playlist_inner(db.inner(), playlist_id, &path, format, relative_to.as_deref()).await
+}
 
-    let picture = tag.and_then(|t| t.pictures().first());
+// ── Search ──
+
+/// Hits returned per group when the caller doesn't say.
+const SEARCH_DEFAULT_LIMIT: i64 = 10;
+/// Match markers handed to FTS5 `snippet()`/`highlight()`; never present in tag text.
+const HIGHLIGHT_START: char = '\u{1}';
+const HIGHLIGHT_END: char = '\u{2}';
+
+/// Turn what the user typed into an FTS5 query: every word must match, as a
+/// prefix, so results narrow while typing. Words are quoted so FTS5 operators
+/// and punctuation in the input are taken literally.
+fn fts_match_expr(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|w| w.chars().any(char::is_alphanumeric))
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
 
-    match picture {
-        Some(pic) => {
-            use base64::Engine;
-            let mime = match pic.mime_type() {
-                Some(lofty::picture::MimeType::Png) => "image/png",
-                Some(lofty::picture::MimeType::Bmp) => "image/bmp",
-                Some(lofty::picture::MimeType::Gif) => "image/gif",
-                Some(lofty::picture::MimeType::Tiff) => "image/tiff",
-                _ => "image/jpeg",
-            };
-            let b64 = base64::engine::general_purpose::STANDARD.encode(pic.data());
-            Ok(Some(CoverArt {
-                data: b64,
-                mime_type: mime.to_string(),
-            }))
+/// Split FTS5 output marked with `HIGHLIGHT_START`/`HIGHLIGHT_END` into parts.
+fn split_highlights(marked: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut highlight = false;
+    for piece in marked.split([HIGHLIGHT_START, HIGHLIGHT_END]) {
+        if !piece.is_empty() {
+            parts.push(SnippetPart { text: piece.to_string(), highlight });
         }
-        None => Ok(None),
+        highlight = !highlight;
     }
+    parts
 }
 
-pub async fn get_album_cover_art_inner(
+/// Ranked full-text search over track titles, artists, albums, album artists,
+/// composers, genres, comments and lyrics. Albums and artists are found through
+/// their names on the tracks that reference them.
+pub async fn search_library_inner(
     db: &DbPool,
-    album_id: i64,
-) -> Result<Option<CoverArt>, AppError> {
-    // Try up to 5 tracks — skip ones with unreadable files or no embedded art
-    let rows: Vec<(i64,)> =
-        sqlx::query_as("SELECT id FROM tracks WHERE album_id = ? LIMIT 5")
-            .bind(album_id)
-            .fetch_all(db)
-            .await?;
-
-    for (track_id,) in rows {
-        if let Ok(Some(art)) = get_cover_art_inner(db, track_id).await {
-            return Ok(Some(art));
-        }
+    query: &str,
+    limit: Option<i64>,
+) -> Result<SearchResults, AppError> {
+    let Some(expr) = fts_match_expr(query) else {
+        return Ok(SearchResults::default());
+    };
+    let limit = limit.unwrap_or(SEARCH_DEFAULT_LIMIT).clamp(1, 100);
+
+    // Column weights: title, artist, album, album_artist, composer, genre, comment, lyrics
+    let track_rows = sqlx::query(
+        "SELECT t.id as track_id, t.title, t.artist_id, a.name as artist_name, t.album_id,
+                al.title as album_title,
+                snippet(tracks_fts, -1, char(1), char(2), '…', 12) as marked
+         FROM tracks_fts
+         JOIN tracks t ON t.id = tracks_fts.rowid
+         LEFT JOIN artists a ON t.artist_id = a.id
+         LEFT JOIN albums al ON t.album_id = al.id
+         WHERE tracks_fts MATCH ?
+         ORDER BY bm25(tracks_fts, 10.0, 5.0, 5.0, 3.0, 2.0, 2.0, 1.0, 1.0)
+         LIMIT ?",
+    )
+    .bind(&expr)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    let mut tracks = Vec::with_capacity(track_rows.len());
+    for row in &track_rows {
+        let mut hit = TrackSearchHit::from_row(row)?;
+        hit.snippet = split_highlights(row.try_get("marked")?);
+        tracks.push(hit);
     }
-    Ok(None)
-}
 
-pub async fn get_artist_cover_art_inner(
-    db: &DbPool,
-    artist_id: i64,
-) -> Result<Option<CoverArt>, AppError> {
-    // Try up to 5 tracks — skip ones with unreadable files or no embedded art
-    let rows: Vec<(i64,)> =
-        sqlx::query_as("SELECT id FROM tracks WHERE artist_id = ? LIMIT 5")
-            .bind(artist_id)
-            .fetch_all(db)
-            .await?;
+    // FTS5 auxiliary functions only work in the statement doing the MATCH, so the
+    // per-track hits are materialized before grouping instead of being flattened.
+    let album_rows = sqlx::query(
+        "WITH h AS MATERIALIZED (
+             SELECT t.album_id, highlight(tracks_fts, 2, char(1), char(2)) as marked,
+                    bm25(tracks_fts) as rank
+             FROM tracks_fts JOIN tracks t ON t.id = tracks_fts.rowid
+             WHERE tracks_fts MATCH ?
+         )
+         SELECT al.id as album_id, al.title, ar.name as artist_name, h.marked, MIN(h.rank) as rank
+         FROM h
+         JOIN albums al ON al.id = h.album_id
+         LEFT JOIN artists ar ON al.artist_id = ar.id
+         GROUP BY al.id
+         ORDER BY rank
+         LIMIT ?",
+    )
+    .bind(format!("{{album}} : ({})", expr))
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    let mut albums = Vec::with_capacity(album_rows.len());
+    for row in &album_rows {
+        let mut hit = AlbumSearchHit::from_row(row)?;
+        hit.highlighted = split_highlights(row.try_get("marked")?);
+        albums.push(hit);
+    }
 
-    for (track_id,) in rows {
-        if let Ok(Some(art)) = get_cover_art_inner(db, track_id).await {
-            return Ok(Some(art));
-        }
+    let artist_rows = sqlx::query(
+        "WITH h AS MATERIALIZED (
+             SELECT t.artist_id, highlight(tracks_fts, 1, char(1), char(2)) as marked,
+                    bm25(tracks_fts) as rank
+             FROM tracks_fts JOIN tracks t ON t.id = tracks_fts.rowid
+             WHERE tracks_fts MATCH ?
+         )
+         SELECT a.id as artist_id, a.name, h.marked, MIN(h.rank) as rank
+         FROM h
+         JOIN artists a ON a.id = h.artist_id
+         GROUP BY a.id
+         ORDER BY rank
+         LIMIT ?",
+    )
+    .bind(format!("{{artist}} : ({})", expr))
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    let mut artists = Vec::with_capacity(artist_rows.len());
+    for row in &artist_rows {
+        let mut hit = ArtistSearchHit::from_row(row)?;
+        hit.highlighted = split_highlights(row.try_get("marked")?);
+        artists.push(hit);
     }
-    Ok(None)
+
+    Ok(SearchResults { tracks, albums, artists })
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_cover_art(
+pub async fn search_library(
     db: State<'_, DbPool>,
-    track_id: i64,
-) -> Result<Option<CoverArt>, AppError> {
-    get_cover_art_inner(db.inner(), track_id).await
+    query: String,
+    limit: Option<i64>,
+) -> Result<SearchResults, AppError> {
+    search_library_inner(db.inner(), &query, limit).await
+}
+
+// ── Artist Commands ──
+
+pub async fn list_artists_inner(db: &DbPool) -> Result<Vec<Artist>, AppError> {
+    Ok(
+        sqlx::query_as::<_, Artist>(
+            "SELECT id, name, sort_name, musicbrainz_id, created_at FROM artists ORDER BY name ASC",
+        )
+        .fetch_all(db)
+        .await?,
+    )
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_album_cover_art(
-    db: State<'_, DbPool>,
-    album_id: i64,
-) -> Result<Option<CoverArt>, AppError> {
-    get_album_cover_art_inner(db.inner(), album_id).await
+pub async fn list_artists(db: State<'_, DbPool>) -> Result<Vec<Artist>, AppError> {
+    list_artists_inner(db.inner()).await
+}
+
+/// Artists with at least one album or track. Artists kept only for a sort
+/// name or MusicBrainz id the user set are left out until they're used again.
+pub async fn list_artist_rows_inner(db: &DbPool) -> Result<Vec<ArtistRow>, AppError> {
+    Ok(sqlx::query_as::<_, ArtistRow>(
+        "SELECT a.id, a.name, a.sort_name,
+                (SELECT COUNT(*) FROM albums al WHERE al.artist_id = a.id) as album_count,
+                (SELECT COUNT(*) FROM artist_tracks at WHERE at.artist_id = a.id) as track_count,
+                (SELECT COALESCE(SUM(t.duration_secs), 0.0) FROM artist_tracks at
+                 JOIN tracks t ON t.id = at.track_id WHERE at.artist_id = a.id) as total_duration_secs
+         FROM artists a
+         WHERE EXISTS (SELECT 1 FROM albums al WHERE al.artist_id = a.id)
+            OR EXISTS (SELECT 1 FROM artist_tracks at WHERE at.artist_id = a.id)
+         ORDER BY a.name ASC",
+    )
+    .fetch_all(db)
+    .await?)
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_artist_cover_art(
-    db: State<'_, DbPool>,
-    artist_id: i64,
-) -> Result<Option<CoverArt>, AppError> {
-    get_artist_cover_art_inner(db.inner(), artist_id).await
+pub async fn list_artist_rows(db: State<'_, DbPool>) -> Result<Vec<ArtistRow>, AppError> {
+    list_artist_rows_inner(db.inner()).await
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::db::test_helpers::setup_test_db;
-    use crate::models::TrackUpdateInput;
+/// Every artist credited on a track, main artists first, then by role.
+pub async fn get_track_credits_inner(db: &DbPool, track_id: i64) -> Result<Vec<TrackCredit>, AppError> {
+    Ok(sqlx::query_as::<_, TrackCredit>(
+        "SELECT ta.artist_id, a.name as artist_name, ta.role, ta.position
+         FROM track_artists ta
+         JOIN artists a ON a.id = ta.artist_id
+         WHERE ta.track_id = ?
+         ORDER BY CASE ta.role WHEN 'artist' THEN 0 WHEN 'featured' THEN 1 ELSE 2 END, ta.role, ta.position",
+    )
+    .bind(track_id)
+    .fetch_all(db)
+    .await?)
+}
 
-    // ── Collection Tests ──
+#[tauri::command]
+#[specta::specta]
+pub async fn get_track_credits(db: State<'_, DbPool>, track_id: i64) -> Result<Vec<TrackCredit>, AppError> {
+    get_track_credits_inner(db.inner(), track_id).await
+}
 
-    #[tokio::test]
-    async fn test_list_collections_empty() {
-        let db = setup_test_db().await;
-        let result = list_collections_inner(&db).await.unwrap();
-        assert!(result.is_empty());
-    }
+/// One page of artist rows matching `filter`, sorted by `sort` (name by default).
+pub async fn query_artist_rows_inner(
+    db: &DbPool,
+    filter: &LibraryFilter,
+    sort: Option<&SortSpec>,
+    page: &PageRequest,
+) -> Result<Page<ArtistRow>, AppError> {
+    library_query::fetch_page(db, &library_query::ARTIST_ROWS, filter, sort, page).await
+}
 
-    /// Helper: returns a platform-appropriate absolute path for tests
-    fn abs_test_path(suffix: &str) -> String {
-        if cfg!(windows) {
-            format!("C:/music{}", suffix)
-        } else {
-            format!("/music{}", suffix)
-        }
-    }
+#[tauri::command]
+#[specta::specta]
+pub async fn query_artist_rows(
+    db: State<'_, DbPool>,
+    filter: LibraryFilter,
+    sort: Option<SortSpec>,
+    page: PageRequest,
+) -> Result<Page<ArtistRow>, AppError> {
+    query_artist_rows_inner(db.inner(), &filter, sort.as_ref(), &page).await
+}
 
-    #[tokio::test]
-    async fn test_add_and_list_collection() {
-        let db = setup_test_db().await;
-        let path = abs_test_path("/library");
-        let input = CollectionInput {
-            path: path.clone(),
-            label: Some("My Music".to_string()),
-        };
-        let col = add_collection_inner(&db, input, true).await.unwrap();
-        assert_eq!(col.path, path);
-        assert_eq!(col.label, Some("My Music".to_string()));
+// ── Album Commands ──
 
-        let all = list_collections_inner(&db).await.unwrap();
-        assert_eq!(all.len(), 1);
-        assert_eq!(all[0].id, col.id);
+pub async fn list_albums_inner(
+    db: &DbPool,
+    artist_id: Option<i64>,
+) -> Result<Vec<Album>, AppError> {
+    if let Some(aid) = artist_id {
+        Ok(sqlx::query_as::<_, Album>(
+            "SELECT id, title, artist_id, year, genre, cover_path, musicbrainz_id, created_at,
+                    loudness_lufs, true_peak, loudness_range_lu
+             FROM albums WHERE artist_id = ? ORDER BY year ASC, title ASC",
+        )
+        .bind(aid)
+        .fetch_all(db)
+        .await?)
+    } else {
+        Ok(sqlx::query_as::<_, Album>(
+            "SELECT id, title, artist_id, year, genre, cover_path, musicbrainz_id, created_at,
+                    loudness_lufs, true_peak, loudness_range_lu
+             FROM albums ORDER BY title ASC",
+        )
+        .fetch_all(db)
+        .await?)
     }
+}
 
-    #[tokio::test]
-    async fn test_add_collection_rejects_relative_path() {
-        let db = setup_test_db().await;
-        let input = CollectionInput {
-            path: "relative/path".to_string(),
-            label: None,
-        };
-        let result = add_collection_inner(&db, input, true).await;
-        assert!(result.is_err());
-        match result.unwrap_err() {
-            AppError::InvalidInput(msg) => assert!(msg.contains("absolute")),
-            other => panic!("Expected InvalidInput, got {:?}", other),
-        }
-    }
+#[tauri::command]
+#[specta::specta]
+pub async fn list_albums(
+    db: State<'_, DbPool>,
+    artist_id: Option<i64>,
+) -> Result<Vec<Album>, AppError> {
+    list_albums_inner(db.inner(), artist_id).await
+}
 
-    #[tokio::test]
-    async fn test_add_collection_duplicate_path_upserts() {
-        let db = setup_test_db().await;
-        let path = abs_test_path("/library");
-        let input1 = CollectionInput {
-            path: path.clone(),
-            label: Some("Label 1".to_string()),
-        };
-        let col1 = add_collection_inner(&db, input1, true).await.unwrap();
+pub async fn list_album_rows_inner(db: &DbPool) -> Result<Vec<AlbumRow>, AppError> {
+    Ok(sqlx::query_as::<_, AlbumRow>(
+        "SELECT al.id, al.title, ar.name as artist_name, al.year, al.genre,
+                COUNT(t.id) as track_count,
+                COALESCE(SUM(t.duration_secs), 0.0) as total_duration_secs,
+                COALESCE(SUM(t.file_size_bytes), 0) as total_size_bytes,
+                GROUP_CONCAT(DISTINCT t.codec) as codecs,
+                CAST(ROUND(AVG(t.bitrate_kbps)) AS INTEGER) as avg_bitrate_kbps,
+                MAX(t.sample_rate_hz) as max_sample_rate_hz,
+                MAX(t.bit_depth) as max_bit_depth
+         FROM albums al
+         LEFT JOIN artists ar ON al.artist_id = ar.id
+         LEFT JOIN tracks t ON t.album_id = al.id
+         GROUP BY al.id
+         ORDER BY al.title ASC",
+    )
+    .fetch_all(db)
+    .await?)
+}
 
-        let input2 = CollectionInput {
-            path: path.clone(),
-            label: Some("Label 2".to_string()),
+#[tauri::command]
+#[specta::specta]
+pub async fn list_album_rows(db: State<'_, DbPool>) -> Result<Vec<AlbumRow>, AppError> {
+    list_album_rows_inner(db.inner()).await
+}
+
+/// One page of album rows matching `filter`, sorted by `sort` (title by default).
+pub async fn query_album_rows_inner(
+    db: &DbPool,
+    filter: &LibraryFilter,
+    sort: Option<&SortSpec>,
+    page: &PageRequest,
+) -> Result<Page<AlbumRow>, AppError> {
+    library_query::fetch_page(db, &library_query::ALBUM_ROWS, filter, sort, page).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn query_album_rows(
+    db: State<'_, DbPool>,
+    filter: LibraryFilter,
+    sort: Option<SortSpec>,
+    page: PageRequest,
+) -> Result<Page<AlbumRow>, AppError> {
+    query_album_rows_inner(db.inner(), &filter, sort.as_ref(), &page).await
+}
+
+pub async fn list_tracks_by_album_inner(
+    db: &DbPool,
+    album_id: i64,
+) -> Result<Vec<TrackRow>, AppError> {
+    Ok(sqlx::query_as::<_, TrackRow>(
+        "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path,
+                al.loudness_lufs as album_loudness_lufs, al.true_peak as album_true_peak
+         FROM tracks t
+         LEFT JOIN artists a ON t.artist_id = a.id
+         LEFT JOIN albums al ON t.album_id = al.id
+         WHERE t.album_id = ?
+         ORDER BY t.disc_number ASC, t.track_number ASC",
+    )
+    .bind(album_id)
+    .fetch_all(db)
+    .await?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_tracks_by_album(
+    db: State<'_, DbPool>,
+    album_id: i64,
+) -> Result<Vec<TrackRow>, AppError> {
+    list_tracks_by_album_inner(db.inner(), album_id).await
+}
+
+// ── Artist and Album Edits ──
+//
+// Each edit retags the affected files first, all or none, then re-points
+// `tracks`/`albums`/`track_artists` in one transaction. With `dry_run` it only
+// counts what it would touch.
+
+/// A track touched by an artist or album edit: its row, and its tags before and
+/// after. `before.extra_tags` holds the old values of the frames the edit sets.
+struct Retag {
+    track: TrackRow,
+    before: TrackTags,
+    after: TrackTags,
+}
+
+impl Retag {
+    fn new(track: TrackRow) -> Self {
+        let before = TrackTags::from_row(&track);
+        Self { track, after: before.clone(), before }
+    }
+
+    fn is_changed(&self) -> bool {
+        self.before != self.after
+    }
+
+    /// Set the extra tag frame `frame_id` to `value` ("" removes it), over its
+    /// stored value `old`.
+    fn set_extra_tag(&mut self, frame_id: &str, old: Option<String>, value: &str) {
+        let tag = |value: String| ExtraTag { frame_id: frame_id.to_string(), value };
+        self.before.extra_tags.push(tag(old.unwrap_or_default()));
+        self.after.extra_tags.push(tag(value.to_string()));
+    }
+
+    /// Write `tags`, the before or after side of this retag, to the file.
+    /// The standard fields are only written if the edit changes them.
+    fn write_file(&self, tags: &TrackTags) -> Result<(), AppError> {
+        let fields = |t: &TrackTags| TrackTags { extra_tags: Vec::new(), ..t.clone() };
+        if fields(&self.before) != fields(&self.after) {
+            write_tags_to_file(&self.track.file_path, tags)?;
+        }
+        if !tags.extra_tags.is_empty() {
+            write_extra_tags_to_file(&self.track.file_path, &[], &tags.extra_tags)?;
+        }
+        Ok(())
+    }
+}
+
+async fn stored_extra_tag(
+    conn: &mut SqliteConnection,
+    track_id: i64,
+    frame_id: &str,
+) -> Result<Option<String>, AppError> {
+    let value: Option<(String,)> =
+        sqlx::query_as("SELECT value FROM track_extra_tags WHERE track_id = ? AND frame_id = ?")
+            .bind(track_id)
+            .bind(frame_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    Ok(value.map(|(v,)| v))
+}
+
+async fn fetch_artist(conn: &mut SqliteConnection, artist_id: i64) -> Result<Artist, AppError> {
+    sqlx::query_as::<_, Artist>("SELECT id, name, sort_name, musicbrainz_id, created_at FROM artists WHERE id = ?")
+        .bind(artist_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Artist {} not found", artist_id)))
+}
+
+async fn fetch_album(conn: &mut SqliteConnection, album_id: i64) -> Result<Album, AppError> {
+    sqlx::query_as::<_, Album>(
+        "SELECT id, title, artist_id, year, genre, cover_path, musicbrainz_id, created_at,
+                loudness_lufs, true_peak, loudness_range_lu
+         FROM albums WHERE id = ?",
+    )
+    .bind(album_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Album {} not found", album_id)))
+}
+
+async fn fetch_track_rows(conn: &mut SqliteConnection, track_ids: &[i64]) -> Result<Vec<TrackRow>, AppError> {
+    let mut tracks = Vec::with_capacity(track_ids.len());
+    for &id in track_ids {
+        tracks.push(fetch_track_row(conn, id).await?);
+    }
+    Ok(tracks)
+}
+
+/// `ids` without duplicates or `except`, in their original order.
+fn other_ids(ids: &[i64], except: i64) -> Vec<i64> {
+    let mut seen = HashSet::new();
+    ids.iter().copied().filter(|&id| id != except && seen.insert(id)).collect()
+}
+
+/// Tracks whose tags change, and so whose files are rewritten.
+fn changed_retags(retags: &[Retag]) -> impl Iterator<Item = &Retag> {
+    retags.iter().filter(|r| r.is_changed())
+}
+
+fn edit_summary(retags: &[Retag], dry_run: bool) -> EntityEditSummary {
+    EntityEditSummary {
+        track_count: retags.len() as i64,
+        file_count: changed_retags(retags).count() as i64,
+        dry_run,
+    }
+}
+
+/// Every track linked to one of the artists, by a credit in any role or by
+/// an album filed under them, with each artist's name replaced by `new_name`
+/// in its artist, album artist and composer tags and its extra credit frames
+/// (lyricist, remixer, conductor, performer).
+async fn retag_artist_names(
+    conn: &mut SqliteConnection,
+    artists: &[Artist],
+    new_name: &str,
+) -> Result<Vec<Retag>, AppError> {
+    let mut track_ids = Vec::new();
+    for artist in artists {
+        let ids: Vec<(i64,)> = sqlx::query_as(
+            "SELECT track_id FROM artist_tracks WHERE artist_id = ?
+             UNION
+             SELECT t.id FROM tracks t JOIN albums al ON al.id = t.album_id WHERE al.artist_id = ?",
+        )
+        .bind(artist.id)
+        .bind(artist.id)
+        .fetch_all(&mut *conn)
+        .await?;
+        track_ids.extend(ids.into_iter().map(|(id,)| id));
+    }
+    track_ids.sort_unstable();
+    track_ids.dedup();
+
+    let separators = credits::separators(&mut *conn).await?;
+    let old: Vec<&str> = artists.iter().map(|a| a.name.as_str()).collect();
+    let replace = |value: &mut Option<String>| {
+        if let Some(new) = value.as_deref().and_then(|v| credits::replace_names(v, &old, new_name, &separators)) {
+            *value = Some(new);
+        }
+    };
+    let mut retags = Vec::with_capacity(track_ids.len());
+    for track in fetch_track_rows(conn, &track_ids).await? {
+        let mut retag = Retag::new(track);
+        replace(&mut retag.after.artist_name);
+        replace(&mut retag.after.album_artist);
+        replace(&mut retag.after.composer);
+        for &field in CreditField::EXTRA {
+            let Some(frame_id) = field.extra_frame() else { continue };
+            let Some(value) = stored_extra_tag(conn, retag.track.id, frame_id).await? else { continue };
+            let replaced = match field {
+                CreditField::Performer => credits::replace_performer_names(&value, &old, new_name, &separators),
+                _ => credits::replace_names(&value, &old, new_name, &separators),
+            };
+            if let Some(new) = replaced {
+                retag.set_extra_tag(frame_id, Some(value), &new);
+            }
+        }
+        retags.push(retag);
+    }
+    Ok(retags)
+}
+
+/// Write the new tags of every changed file, before any DB change. If one
+/// write fails, the files already written get their old tags back and the
+/// error is returned, so an edit reaches all of its files or none.
+/// Returns each track's file mtime afterwards.
+async fn write_retags(db: &DbPool, retags: &[Retag]) -> Result<Vec<Option<i64>>, AppError> {
+    let changed: Vec<i64> = changed_retags(retags).map(|r| r.track.id).collect();
+    backups::backup_tracks(db, &changed).await?;
+
+    let mut mtimes = Vec::with_capacity(retags.len());
+    for (i, retag) in retags.iter().enumerate() {
+        if !retag.is_changed() {
+            mtimes.push(retag.track.file_mtime);
+            continue;
+        }
+        if let Err(e) = retag.write_file(&retag.after) {
+            restore_retags(&retags[..i]);
+            return Err(e);
+        }
+        mtimes.push(read_file_mtime(&retag.track.file_path));
+    }
+    Ok(mtimes)
+}
+
+/// Put the old tags back into the files `write_retags` already wrote.
+fn restore_retags(written: &[Retag]) {
+    for retag in changed_retags(written) {
+        if let Err(e) = retag.write_file(&retag.before) {
+            error!("Could not restore the tags of {:?}: {}", retag.track.file_path, e);
+        }
+    }
+}
+
+/// Store the new tags of an edit's tracks and journal them for undo. Artists
+/// are re-linked by name; `album_id`, when given, replaces each track's album.
+async fn store_retags(
+    conn: &mut SqliteConnection,
+    label: &str,
+    retags: Vec<Retag>,
+    mtimes: Vec<Option<i64>>,
+    album_id: Option<i64>,
+) -> Result<(), AppError> {
+    let mut edits = Vec::with_capacity(retags.len());
+    for (Retag { track, before, after }, file_mtime) in retags.into_iter().zip(mtimes) {
+        let artist_id = match after.artist_name.as_deref() {
+            _ if after.artist_name == track.artist_name => track.artist_id,
+            None => None,
+            Some(name) => Some(find_or_create_artist(conn, name).await?),
         };
-        let col2 = add_collection_inner(&db, input2, true).await.unwrap();
+        let album_id = album_id.or(track.album_id);
+        store_track_columns(conn, &track, &after, artist_id, album_id, file_mtime).await?;
+        edits.push((track.id, before, after));
+    }
+    record_edit(conn, label, edits).await
+}
 
-        assert_eq!(col1.id, col2.id);
-        assert_eq!(col2.label, Some("Label 2".to_string()));
+/// Rename an artist everywhere it is credited, keeping its id. Fails if
+/// another artist already has the name; merge the two instead.
+pub async fn rename_artist_inner(
+    db: &DbPool,
+    artist_id: i64,
+    name: &str,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Artist name cannot be empty".into()));
+    }
+    let mut conn = db.acquire().await?;
+    let artist = fetch_artist(&mut conn, artist_id).await?;
+    // A rename may change only the spelling of the same key, e.g. its case
+    let key = name_key(name, name_keys::folding(&mut conn).await?);
+    let taken: Option<(String,)> = sqlx::query_as("SELECT name FROM artists WHERE name_key = ? AND id != ?")
+        .bind(&key)
+        .bind(artist_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    if let Some((existing,)) = taken {
+        return Err(AppError::InvalidInput(format!(
+            "An artist named \"{}\" already exists; merge the two instead",
+            existing
+        )));
+    }
+    let retags = retag_artist_names(&mut conn, std::slice::from_ref(&artist), name).await?;
+    drop(conn);
+    let summary = edit_summary(&retags, dry_run);
+    if dry_run {
+        return Ok(summary);
+    }
 
-        let all = list_collections_inner(&db).await.unwrap();
-        assert_eq!(all.len(), 1);
+    let mtimes = write_retags(db, &retags).await?;
+    let mut tx = db.begin().await?;
+    sqlx::query("UPDATE artists SET name = ?, name_key = ? WHERE id = ?")
+        .bind(name)
+        .bind(&key)
+        .bind(artist_id)
+        .execute(&mut *tx)
+        .await?;
+    let label = format!("Rename artist \"{}\" to \"{}\"", artist.name, name);
+    store_retags(&mut tx, &label, retags, mtimes, None).await?;
+    tx.commit().await?;
+    prune_orphans(db).await?;
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn rename_artist(
+    db: State<'_, DbPool>,
+    artist_id: i64,
+    name: String,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    rename_artist_inner(db.inner(), artist_id, &name, dry_run).await
+}
+
+/// Fold `artist_ids` into `target_id`: their credits, albums and tags name the
+/// target, and the merged artists are deleted. The target keeps its own sort
+/// name and MusicBrainz id, taking a merged artist's only where it has none.
+pub async fn merge_artists_inner(
+    db: &DbPool,
+    artist_ids: &[i64],
+    target_id: i64,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    let mut conn = db.acquire().await?;
+    let target = fetch_artist(&mut conn, target_id).await?;
+    let mut sources = Vec::new();
+    for id in other_ids(artist_ids, target_id) {
+        sources.push(fetch_artist(&mut conn, id).await?);
+    }
+    if sources.is_empty() {
+        return Err(AppError::InvalidInput("Choose at least one other artist to merge".into()));
+    }
+    let retags = retag_artist_names(&mut conn, &sources, &target.name).await?;
+    drop(conn);
+    let summary = edit_summary(&retags, dry_run);
+    if dry_run {
+        return Ok(summary);
+    }
+
+    let mtimes = write_retags(db, &retags).await?;
+    let mut tx = db.begin().await?;
+    for source in &sources {
+        // Credits whose tags don't spell the name as the artist has it move
+        // over as they are, unless the target already holds the same role
+        sqlx::query(
+            "DELETE FROM track_artists WHERE artist_id = ? AND EXISTS (
+                 SELECT 1 FROM track_artists o
+                 WHERE o.track_id = track_artists.track_id AND o.role = track_artists.role AND o.artist_id = ?)",
+        )
+        .bind(source.id)
+        .bind(target_id)
+        .execute(&mut *tx)
+        .await?;
+        for table in ["track_artists", "albums", "tracks"] {
+            sqlx::query(&format!("UPDATE {} SET artist_id = ? WHERE artist_id = ?", table))
+                .bind(target_id)
+                .bind(source.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(
+            "UPDATE artists SET sort_name = COALESCE(sort_name, ?), musicbrainz_id = COALESCE(musicbrainz_id, ?)
+             WHERE id = ?",
+        )
+        .bind(&source.sort_name)
+        .bind(&source.musicbrainz_id)
+        .bind(target_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    let names: Vec<String> = sources.iter().map(|a| format!("\"{}\"", a.name)).collect();
+    let label = format!("Merge {} into \"{}\"", names.join(", "), target.name);
+    store_retags(&mut tx, &label, retags, mtimes, None).await?;
+    for source in &sources {
+        sqlx::query("DELETE FROM artists WHERE id = ?").bind(source.id).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    prune_orphans(db).await?;
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn merge_artists(
+    db: State<'_, DbPool>,
+    artist_ids: Vec<i64>,
+    target_id: i64,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    merge_artists_inner(db.inner(), &artist_ids, target_id, dry_run).await
+}
+
+/// Track and album artist sort order frames, written where the artist is the
+/// track's whole artist credit or its album artist.
+const ARTIST_SORT_FRAME: &str = "TSOP";
+const ALBUM_ARTIST_SORT_FRAME: &str = "TSO2";
+
+/// Set (or, with None, clear) an artist's sort name, e.g. "Beatles, The", and
+/// the sort order tags of the files naming them.
+pub async fn set_artist_sort_name_inner(
+    db: &DbPool,
+    artist_id: i64,
+    sort_name: Option<&str>,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    let sort_name = sort_name.map(str::trim).filter(|s| !s.is_empty());
+    let mut conn = db.acquire().await?;
+    let artist = fetch_artist(&mut conn, artist_id).await?;
+    let ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM tracks WHERE artist_id = ? OR album_artist = ? ORDER BY id")
+        .bind(artist_id)
+        .bind(&artist.name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let ids: Vec<i64> = ids.into_iter().map(|(id,)| id).collect();
+    let mut retags = Vec::with_capacity(ids.len());
+    for track in fetch_track_rows(&mut conn, &ids).await? {
+        let frames = [
+            (track.artist_id == Some(artist_id), ARTIST_SORT_FRAME),
+            (track.album_artist.as_deref() == Some(artist.name.as_str()), ALBUM_ARTIST_SORT_FRAME),
+        ];
+        let mut retag = Retag::new(track);
+        for frame_id in frames.iter().filter(|(applies, _)| *applies).map(|(_, frame)| *frame) {
+            let old = stored_extra_tag(&mut conn, retag.track.id, frame_id).await?;
+            retag.set_extra_tag(frame_id, old, sort_name.unwrap_or_default());
+        }
+        retags.push(retag);
+    }
+    drop(conn);
+    let summary = edit_summary(&retags, dry_run);
+    if dry_run {
+        return Ok(summary);
+    }
+
+    let mtimes = write_retags(db, &retags).await?;
+    let mut tx = db.begin().await?;
+    sqlx::query("UPDATE artists SET sort_name = ? WHERE id = ?")
+        .bind(sort_name)
+        .bind(artist_id)
+        .execute(&mut *tx)
+        .await?;
+    let label = match sort_name {
+        Some(sort_name) => format!("Sort \"{}\" as \"{}\"", artist.name, sort_name),
+        None => format!("Clear the sort name of \"{}\"", artist.name),
+    };
+    store_retags(&mut tx, &label, retags, mtimes, None).await?;
+    tx.commit().await?;
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_artist_sort_name(
+    db: State<'_, DbPool>,
+    artist_id: i64,
+    sort_name: Option<String>,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    set_artist_sort_name_inner(db.inner(), artist_id, sort_name.as_deref(), dry_run).await
+}
+
+/// Distinct MusicBrainz release ids of the albums and their tracks' tags.
+async fn album_releases(conn: &mut SqliteConnection, list: &[&Album]) -> Result<HashSet<String>, AppError> {
+    let mut releases: HashSet<String> = list.iter().filter_map(|a| a.musicbrainz_id.clone()).collect();
+    for album in list {
+        let tagged: Vec<(String,)> = sqlx::query_as(
+            "SELECT DISTINCT e.value FROM track_extra_tags e JOIN tracks t ON t.id = e.track_id
+             WHERE e.frame_id = ? AND t.album_id = ?",
+        )
+        .bind(albums::MUSICBRAINZ_RELEASE_FRAME)
+        .bind(album.id)
+        .fetch_all(&mut *conn)
+        .await?;
+        releases.extend(tagged.into_iter().map(|(v,)| v.trim().to_string()).filter(|v| !v.is_empty()));
+    }
+    Ok(releases)
+}
+
+async fn artist_name(conn: &mut SqliteConnection, artist_id: Option<i64>) -> Result<Option<String>, AppError> {
+    let Some(artist_id) = artist_id else { return Ok(None) };
+    Ok(Some(fetch_artist(conn, artist_id).await?.name))
+}
+
+async fn clear_album_loudness(conn: &mut SqliteConnection, album_id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE albums SET loudness_lufs = NULL, true_peak = NULL, loudness_range_lu = NULL WHERE id = ?")
+        .bind(album_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Move every track of `album_ids` onto `target_id`, retagged with its title
+/// and album artist, and delete the emptied albums. The target takes a merged
+/// album's cover, year and genre where it has none.
+pub async fn merge_albums_inner(
+    db: &DbPool,
+    album_ids: &[i64],
+    target_id: i64,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    let mut conn = db.acquire().await?;
+    let target = fetch_album(&mut conn, target_id).await?;
+    let mut sources = Vec::new();
+    for id in other_ids(album_ids, target_id) {
+        sources.push(fetch_album(&mut conn, id).await?);
+    }
+    if sources.is_empty() {
+        return Err(AppError::InvalidInput("Choose at least one other album to merge".into()));
+    }
+    let all: Vec<&Album> = std::iter::once(&target).chain(&sources).collect();
+    if album_releases(&mut conn, &all).await?.len() > 1 {
+        // A rescan would split them again by release
+        return Err(AppError::InvalidInput("The albums are tagged as different MusicBrainz releases".into()));
+    }
+
+    let target_artist = artist_name(&mut conn, target.artist_id).await?;
+    let separators = credits::separators(&mut conn).await?;
+    let mut retags = Vec::new();
+    for source in &sources {
+        let ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM tracks WHERE album_id = ? ORDER BY id")
+            .bind(source.id)
+            .fetch_all(&mut *conn)
+            .await?;
+        let ids: Vec<i64> = ids.into_iter().map(|(id,)| id).collect();
+        for track in fetch_track_rows(&mut conn, &ids).await? {
+            let mut retag = Retag::new(track);
+            let after = &mut retag.after;
+            after.album_title = Some(target.title.clone());
+            // Name the album artist wherever the track artist alone would file
+            // the track elsewhere
+            if let Some(artist) = &target_artist {
+                let primary = after.artist_name.as_deref().map(|a| credits::primary_name(a, &separators));
+                if after.album_artist.is_some() || primary.as_deref() != Some(artist.as_str()) {
+                    after.album_artist = Some(artist.clone());
+                }
+            }
+            retags.push(retag);
+        }
+    }
+    drop(conn);
+    let summary = edit_summary(&retags, dry_run);
+    if dry_run {
+        return Ok(summary);
+    }
+
+    let mtimes = write_retags(db, &retags).await?;
+    let mut tx = db.begin().await?;
+    for source in &sources {
+        sqlx::query(
+            "UPDATE albums SET cover_path = COALESCE(cover_path, ?), year = COALESCE(year, ?),
+                    genre = COALESCE(genre, ?), musicbrainz_id = COALESCE(musicbrainz_id, ?)
+             WHERE id = ?",
+        )
+        .bind(&source.cover_path)
+        .bind(source.year)
+        .bind(&source.genre)
+        .bind(&source.musicbrainz_id)
+        .bind(target_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    clear_album_loudness(&mut tx, target_id).await?;
+    let label = format!("Merge {} albums into \"{}\"", sources.len() + 1, target.title);
+    store_retags(&mut tx, &label, retags, mtimes, Some(target_id)).await?;
+    for source in &sources {
+        sqlx::query("DELETE FROM albums WHERE id = ?").bind(source.id).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    prune_orphans(db).await?;
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn merge_albums(
+    db: State<'_, DbPool>,
+    album_ids: Vec<i64>,
+    target_id: i64,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    merge_albums_inner(db.inner(), &album_ids, target_id, dry_run).await
+}
+
+/// Move some of an album's tracks to the album `title` (by `album_artist`,
+/// when given), retagging them so a rescan keeps them apart. The new title
+/// or album artist must differ from the album's own.
+pub async fn split_album_inner(
+    db: &DbPool,
+    album_id: i64,
+    track_ids: &[i64],
+    title: &str,
+    album_artist: Option<&str>,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    let title = title.trim();
+    let album_artist = album_artist.map(str::trim).filter(|a| !a.is_empty());
+    if title.is_empty() {
+        return Err(AppError::InvalidInput("Album title cannot be empty".into()));
+    }
+    let mut conn = db.acquire().await?;
+    let album = fetch_album(&mut conn, album_id).await?;
+    let current_artist = artist_name(&mut conn, album.artist_id).await?;
+    if title == album.title && album_artist.is_none_or(|a| Some(a) == current_artist.as_deref()) {
+        return Err(AppError::InvalidInput("Give the new album a different title or album artist".into()));
+    }
+    let mut seen = HashSet::new();
+    let track_ids: Vec<i64> = track_ids.iter().copied().filter(|id| seen.insert(*id)).collect();
+    let (album_size,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tracks WHERE album_id = ?")
+        .bind(album_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if track_ids.is_empty() || track_ids.len() as i64 >= album_size {
+        return Err(AppError::InvalidInput("Choose some of the album's tracks, but not all of them".into()));
+    }
+
+    let mut retags = Vec::with_capacity(track_ids.len());
+    for track in fetch_track_rows(&mut conn, &track_ids).await? {
+        if track.album_id != Some(album_id) {
+            return Err(AppError::InvalidInput(format!("Track {} is not on \"{}\"", track.id, album.title)));
+        }
+        if album.musicbrainz_id.is_some() && albums::stored_album_tags(&mut conn, track.id).await?.1.is_some() {
+            // Rule 1 of `AlbumKey` would put it straight back
+            return Err(AppError::InvalidInput(format!(
+                "\"{}\" is tagged with the album's MusicBrainz release",
+                track.title
+            )));
+        }
+        let mut retag = Retag::new(track);
+        retag.after.album_title = Some(title.to_string());
+        if let Some(artist) = album_artist {
+            retag.after.album_artist = Some(artist.to_string());
+        }
+        retags.push(retag);
+    }
+    drop(conn);
+    let summary = edit_summary(&retags, dry_run);
+    if dry_run {
+        return Ok(summary);
+    }
+
+    let mtimes = write_retags(db, &retags).await?;
+    let mut tx = db.begin().await?;
+    // The album a rescan would file the first moved track under
+    let Retag { track: first, after, .. } = &retags[0];
+    let (compilation, _) = albums::stored_album_tags(&mut tx, first.id).await?;
+    let key = AlbumKey {
+        title,
+        album_artist: after.album_artist.as_deref(),
+        track_artist: after.artist_name.as_deref(),
+        compilation,
+        musicbrainz_release_id: None,
+        file_path: &first.file_path,
+    };
+    let new_album_id = albums::find_or_create_album(&mut tx, &key, album.year).await?;
+    sqlx::query("UPDATE albums SET cover_path = COALESCE(cover_path, ?) WHERE id = ?")
+        .bind(&album.cover_path)
+        .bind(new_album_id)
+        .execute(&mut *tx)
+        .await?;
+    clear_album_loudness(&mut tx, album_id).await?;
+    clear_album_loudness(&mut tx, new_album_id).await?;
+    let label = format!("Split {} tracks from \"{}\"", retags.len(), album.title);
+    store_retags(&mut tx, &label, retags, mtimes, Some(new_album_id)).await?;
+    tx.commit().await?;
+    prune_orphans(db).await?;
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn split_album(
+    db: State<'_, DbPool>,
+    album_id: i64,
+    track_ids: Vec<i64>,
+    title: String,
+    album_artist: Option<String>,
+    dry_run: bool,
+) -> Result<EntityEditSummary, AppError> {
+    split_album_inner(db.inner(), album_id, &track_ids, &title, album_artist.as_deref(), dry_run).await
+}
+
+/// Artists, and albums of matching artists, whose names are the same with
+/// every folding applied, whatever folding lookups use.
+pub async fn find_name_duplicates_inner(db: &DbPool) -> Result<NameDuplicates, AppError> {
+    let key = |name: &str| name_key(name, name_keys::Folding::ALL);
+    let artists = list_artist_rows_inner(db).await?;
+    let albums = list_album_rows_inner(db).await?;
+    Ok(NameDuplicates {
+        artists: name_keys::near_duplicates(artists, |a| key(&a.name)),
+        albums: name_keys::near_duplicates(albums, |a| {
+            // NUL can't occur in a tag value, so the pair can't collide
+            format!("{}\0{}", key(a.artist_name.as_deref().unwrap_or_default()), key(&a.title))
+        }),
+    })
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn find_name_duplicates(db: State<'_, DbPool>) -> Result<NameDuplicates, AppError> {
+    find_name_duplicates_inner(db.inner()).await
+}
+
+// ── Scan ──
+
+/// Number of tracks written per SQLite transaction during a scan.
+const SCAN_BATCH_SIZE: usize = 500;
+
+const AUDIO_EXTENSIONS: [&str; 7] = ["mp3", "m4a", "flac", "wav", "ogg", "opus", "wma"];
+
+/// Whether the path has one of the audio file extensions Chant scans, and
+/// isn't a temp file left by an interrupted tag write.
+pub(crate) fn is_audio_path(path: &Path) -> bool {
+    if path.file_name().is_some_and(|n| n.to_string_lossy().starts_with(TEMP_FILE_PREFIX)) {
+        return false;
+    }
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    AUDIO_EXTENSIONS.contains(&ext.as_str())
+}
+
+/// Tags and file facts read from one audio file, ready to be written to the DB.
+struct ScannedTrack {
+    path_str: String,
+    file_size: i64,
+    file_mtime: Option<i64>,
+    file_format: Option<String>,
+    title: String,
+    artist_name: Option<String>,
+    album_title: Option<String>,
+    year: Option<i32>,
+    track_number: Option<i32>,
+    disc_number: Option<i32>,
+    duration: Option<f64>,
+    /// First embedded picture as (bytes, file extension)
+    cover: Option<(Vec<u8>, String)>,
+    genre: Option<String>,
+    album_artist: Option<String>,
+    composer: Option<String>,
+    bpm: Option<i32>,
+    comment: Option<String>,
+    lyrics: Option<String>,
+    /// Non-standard text items as (frame id, value), see `read_extra_tags`
+    extra_tags: Vec<(String, String)>,
+    /// Every value of every field that credits artists, not yet split
+    credits: Vec<(CreditField, String)>,
+    /// None when the audio data couldn't be read
+    audio_hash: Option<String>,
+    properties: AudioProperties,
+}
+
+/// Stored (id, mtime, size, has audio hash) of a track row, keyed by path during a scan.
+type KnownTrack = (i64, Option<i64>, i64, bool);
+
+/// What the walker and tag workers hand to the scan writer.
+enum ScanItem {
+    /// File matched the stored size and mtime; nothing to write.
+    Unchanged,
+    /// Freshly read file; `existed` tells whether the DB already had a row for it.
+    Read { track: Box<ScannedTrack>, existed: bool },
+}
+
+pub async fn scan_collection_inner(
+    db: &DbPool,
+    collection_id: i64,
+    covers_dir: Option<&Path>,
+    control: Arc<JobControl>,
+    on_progress: impl Fn(u32),
+) -> Result<ScanSummary, AppError> {
+    let collection = sqlx::query_as::<_, Collection>(
+        "SELECT * FROM collections WHERE id = ?",
+    )
+    .bind(collection_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Collection {} not found", collection_id)))?;
+
+    let root_path = PathBuf::from(&collection.path);
+    if !root_path.exists() {
+        return Err(AppError::Io(format!("Directory not found: {:?}", root_path)));
+    }
+
+    // Ensure covers directory exists if provided
+    if let Some(dir) = covers_dir {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| AppError::Io(format!("Failed to create covers dir: {}", e)))?;
+    }
+
+    info!("Starting scan of collection: {:?}", root_path);
+
+    // What the DB already knows about this collection, keyed by normalized path.
+    // Used to skip files whose size and mtime are unchanged since the last scan
+    // (and whose audio hash is already stored).
+    let known: HashMap<String, KnownTrack> = sqlx::query_as::<_, (i64, String, Option<i64>, i64, bool)>(
+        "SELECT id, file_path, file_mtime, file_size_bytes, audio_hash IS NOT NULL FROM tracks WHERE collection_id = ?",
+    )
+    .bind(collection_id)
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|(id, file_path, mtime, size, hashed)| (file_path, (id, mtime, size, hashed)))
+    .collect();
+    let known = Arc::new(known);
+
+    // Pipeline: one walker feeds paths to a pool of blocking tag readers, which
+    // hand parsed tracks to this task. Only this task touches the DB, writing in
+    // large transactions instead of one per file.
+    let worker_count = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .clamp(2, 16);
+    let (path_tx, path_rx) = std::sync::mpsc::sync_channel::<(PathBuf, bool)>(worker_count * 4);
+    let path_rx = Arc::new(Mutex::new(path_rx));
+    let (item_tx, mut item_rx) = tokio::sync::mpsc::channel::<ScanItem>(SCAN_BATCH_SIZE * 2);
+
+    let walker = {
+        let root_path = root_path.clone();
+        let known = Arc::clone(&known);
+        let item_tx = item_tx.clone();
+        let control = Arc::clone(&control);
+        tokio::task::spawn_blocking(move || walk_collection(&root_path, &known, &control, path_tx, item_tx))
+    };
+
+    let mut workers = Vec::with_capacity(worker_count);
+    for _ in 0..worker_count {
+        let path_rx = Arc::clone(&path_rx);
+        let item_tx = item_tx.clone();
+        let control = Arc::clone(&control);
+        workers.push(tokio::task::spawn_blocking(move || loop {
+            if !control.wait_while_paused() {
+                break;
+            }
+            let next = path_rx.lock().map(|rx| rx.recv());
+            let Ok(Ok((path, existed))) = next else { break };
+            let track = Box::new(read_track_file(&path));
+            if item_tx.blocking_send(ScanItem::Read { track, existed }).is_err() {
+                break;
+            }
+        }));
+    }
+    // The channel closes once the walker and every worker have dropped their sender
+    drop(item_tx);
+
+    let mut summary = ScanSummary::default();
+    let mut visited: u32 = 0;
+    let mut batch: Vec<(Box<ScannedTrack>, bool)> = Vec::with_capacity(SCAN_BATCH_SIZE);
+
+    while let Some(item) = item_rx.recv().await {
+        match item {
+            ScanItem::Unchanged => {
+                summary.unchanged += 1;
+                visited += 1;
+                on_progress(visited);
+            }
+            ScanItem::Read { track, existed } => {
+                batch.push((track, existed));
+                if batch.len() >= SCAN_BATCH_SIZE {
+                    write_scan_batch(db, collection_id, covers_dir, &mut batch, &mut summary, &mut visited, &on_progress).await?;
+                }
+            }
+        }
+    }
+    write_scan_batch(db, collection_id, covers_dir, &mut batch, &mut summary, &mut visited, &on_progress).await?;
+
+    let seen = walker
+        .await
+        .map_err(|e| AppError::Io(format!("Scan walker failed: {}", e)))?;
+    for worker in workers {
+        worker
+            .await
+            .map_err(|e| AppError::Io(format!("Scan worker failed: {}", e)))?;
+    }
+
+    // A cancelled walk saw only part of the tree, so nothing may be pruned
+    if control.is_cancelled() {
+        summary.cancelled = true;
+        info!("Scan of collection {:?} cancelled after {} files", root_path, visited);
+        prune_orphans(db).await?;
+        return Ok(summary);
+    }
+
+    // Drop rows whose file is gone. Only paths that really no longer exist are
+    // removed, so a directory the walker couldn't read doesn't wipe its tracks.
+    // Rows that followed a moved file no longer have the old path and are kept.
+    let gone: Vec<(i64, &String)> = known
+        .iter()
+        .filter(|(path, _)| !seen.contains(*path) && !Path::new(path).exists())
+        .map(|(path, &(id, ..))| (id, path))
+        .collect();
+    if !gone.is_empty() {
+        let mut tx = db.begin().await?;
+        for (id, path) in &gone {
+            let result = sqlx::query("DELETE FROM tracks WHERE id = ? AND file_path = ?")
+                .bind(id)
+                .bind(path)
+                .execute(&mut *tx)
+                .await?;
+            summary.removed += result.rows_affected() as u32;
+        }
+        tx.commit().await?;
+    }
+    // Re-read tags may have moved tracks off their old artists and albums
+    prune_orphans(db).await?;
+
+    info!(
+        "Scan of collection {:?} complete: {} added, {} updated, {} unchanged, {} moved, {} removed",
+        root_path, summary.added, summary.updated, summary.unchanged, summary.moved, summary.removed
+    );
+    Ok(summary)
+}
+
+/// Walk the collection root, reporting unchanged files straight to the writer and
+/// queueing everything else for the tag workers. Returns every audio path seen.
+fn walk_collection(
+    root_path: &Path,
+    known: &HashMap<String, KnownTrack>,
+    control: &JobControl,
+    path_tx: std::sync::mpsc::SyncSender<(PathBuf, bool)>,
+    item_tx: tokio::sync::mpsc::Sender<ScanItem>,
+) -> HashSet<String> {
+    let mut seen: HashSet<String> = HashSet::new();
+
+    for entry in WalkDir::new(root_path).follow_links(true) {
+        if !control.wait_while_paused() {
+            break;
+        }
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                warn!("Scan: skipping inaccessible path: {}", e);
+                continue;
+            }
+        };
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.into_path();
+        if !is_audio_path(&path) {
+            continue;
+        }
+
+        let path_str = path.to_string_lossy().replace('\\', "/");
+        let existing = known.get(&path_str).copied();
+        seen.insert(path_str);
+
+        if let Some((_, stored_mtime, stored_size, hashed)) = existing {
+            let (size, mtime) = read_file_stat(&path);
+            if hashed && stored_mtime.is_some() && mtime == stored_mtime && size == stored_size {
+                if item_tx.blocking_send(ScanItem::Unchanged).is_err() {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        if path_tx.send((path, existing.is_some())).is_err() {
+            break;
+        }
+    }
+
+    seen
+}
+
+/// Write a batch of scanned tracks in a single transaction and drain it.
+async fn write_scan_batch(
+    db: &DbPool,
+    collection_id: i64,
+    covers_dir: Option<&Path>,
+    batch: &mut Vec<(Box<ScannedTrack>, bool)>,
+    summary: &mut ScanSummary,
+    visited: &mut u32,
+    on_progress: &impl Fn(u32),
+) -> Result<(), AppError> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let mut tx = db.begin().await?;
+    for (track, existed) in batch.drain(..) {
+        match store_scanned_track(&mut tx, collection_id, &track, covers_dir).await {
+            Ok((_, moved)) => {
+                if existed {
+                    summary.updated += 1;
+                } else if moved {
+                    summary.moved += 1;
+                } else {
+                    summary.added += 1;
+                }
+                *visited += 1;
+                on_progress(*visited);
+            }
+            Err(e) => error!("Error processing track {:?}: {:?}", track.path_str, e),
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn scan_collection(
+    app_handle: tauri::AppHandle,
+    db: State<'_, DbPool>,
+    jobs: State<'_, ScanJobs>,
+    collection_id: i64,
+) -> Result<ScanSummary, AppError> {
+    let covers_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Io(format!("Failed to get app data dir: {}", e)))?
+        .join("covers");
+    let (job, control) = jobs.start(collection_id)?;
+    let job_id = job.id;
+    let _guard = jobs.guard(job_id);
+    let _library = jobs.hold_library().await;
+    let _ = app_handle.emit("scan:started", &job);
+
+    let handle = app_handle.clone();
+    let result = scan_collection_inner(db.inner(), collection_id, Some(&covers_dir), control, |n| {
+        jobs.set_progress(job_id, n);
+        let _ = handle.emit("scan:progress", n);
+    })
+    .await;
+    jobs.finish(job_id, &result);
+
+    let summary = result?;
+    let _ = app_handle.emit("scan:complete", &summary);
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_scan(jobs: State<'_, ScanJobs>, job_id: u32) -> Result<ScanJob, AppError> {
+    jobs.cancel(job_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn pause_scan(jobs: State<'_, ScanJobs>, job_id: u32) -> Result<ScanJob, AppError> {
+    jobs.pause(job_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn resume_scan(jobs: State<'_, ScanJobs>, job_id: u32) -> Result<ScanJob, AppError> {
+    jobs.resume(job_id)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_scan_jobs(jobs: State<'_, ScanJobs>) -> Result<Vec<ScanJob>, AppError> {
+    Ok(jobs.list())
+}
+
+// ── Analysis Workers ──
+
+/// Run `work` over `items` on a pool of blocking workers that send their
+/// results down the returned channel. A worker stops once `work` returns false.
+fn spawn_analysis_workers<T, M>(
+    items: Vec<T>,
+    work: impl Fn(T, &tokio::sync::mpsc::Sender<M>) -> bool + Send + Sync + 'static,
+) -> (tokio::sync::mpsc::Receiver<M>, Vec<tokio::task::JoinHandle<()>>)
+where
+    T: Send + 'static,
+    M: Send + 'static,
+{
+    let worker_count = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .clamp(1, 8);
+    let queue = Arc::new(Mutex::new(items.into_iter()));
+    let work = Arc::new(work);
+    let (tx, rx) = tokio::sync::mpsc::channel::<M>(worker_count * 4);
+    let workers = (0..worker_count)
+        .map(|_| {
+            let (queue, work, tx) = (Arc::clone(&queue), Arc::clone(&work), tx.clone());
+            tokio::task::spawn_blocking(move || loop {
+                let next = queue.lock().map(|mut q| q.next());
+                let Ok(Some(item)) = next else { break };
+                if !work(item, &tx) {
+                    break;
+                }
+            })
+        })
+        .collect();
+    (rx, workers)
+}
+
+async fn join_analysis_workers(workers: Vec<tokio::task::JoinHandle<()>>) -> Result<(), AppError> {
+    for worker in workers {
+        worker
+            .await
+            .map_err(|e| AppError::Io(format!("Analysis worker failed: {}", e)))?;
+    }
+    Ok(())
+}
+
+// ── Loudness ──
+
+#[derive(FromRow)]
+struct LoudnessTrack {
+    id: i64,
+    album_id: Option<i64>,
+    file_path: String,
+    audio_hash: Option<String>,
+    codec: Option<String>,
+}
+
+/// Tracks measured together: a whole album, or one track without an album.
+struct LoudnessGroup {
+    album_id: Option<i64>,
+    tracks: Vec<LoudnessTrack>,
+}
+
+/// What the analysis workers hand to the writer.
+enum LoudnessItem {
+    /// One more track was decoded (or failed to decode).
+    Track,
+    /// A group is done: one result per track, plus the album values when every
+    /// track of an album could be measured.
+    Group {
+        group: LoudnessGroup,
+        results: Vec<Result<Measurement, String>>,
+        album: Option<Measurement>,
+    },
+}
+
+/// The tracks `options` selects, grouped by album. Albums are taken whole,
+/// including tracks that don't need measuring themselves.
+async fn loudness_groups(db: &DbPool, options: &LoudnessOptions) -> Result<Vec<LoudnessGroup>, AppError> {
+    let mut selected = sqlx::query_as::<_, LoudnessTrack>(
+        "SELECT id, album_id, file_path, audio_hash, codec FROM tracks
+         WHERE (? IS NULL OR collection_id = ?)
+           AND (? OR loudness_lufs IS NULL OR loudness_hash IS NOT audio_hash)
+         ORDER BY id",
+    )
+    .bind(options.collection_id)
+    .bind(options.collection_id)
+    .bind(options.reanalyze)
+    .fetch_all(db)
+    .await?;
+    if let Some(ids) = &options.track_ids {
+        let ids: HashSet<i64> = ids.iter().copied().collect();
+        selected.retain(|t| ids.contains(&t.id));
+    }
+
+    let mut groups = Vec::new();
+    let mut albums_seen = HashSet::new();
+    for track in selected {
+        match track.album_id {
+            None => groups.push(LoudnessGroup { album_id: None, tracks: vec![track] }),
+            Some(album_id) if albums_seen.insert(album_id) => {
+                let tracks = sqlx::query_as::<_, LoudnessTrack>(
+                    "SELECT id, album_id, file_path, audio_hash, codec FROM tracks WHERE album_id = ? ORDER BY id",
+                )
+                .bind(album_id)
+                .fetch_all(db)
+                .await?;
+                groups.push(LoudnessGroup { album_id: Some(album_id), tracks });
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(groups)
+}
+
+/// Measure every track of `group` into a `LoudnessItem::Group`.
+/// Returns None if the job was cancelled.
+fn analyze_loudness_group(group: LoudnessGroup, control: &JobControl, on_track: impl Fn()) -> Option<LoudnessItem> {
+    let mut analyses = Vec::with_capacity(group.tracks.len());
+    let mut results = Vec::with_capacity(group.tracks.len());
+    for track in &group.tracks {
+        match loudness::analyze_file(Path::new(&track.file_path), control) {
+            Ok(Some(analysis)) => {
+                results.push(Ok(analysis.measurement));
+                analyses.push(analysis);
+            }
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Loudness analysis of {:?} failed: {}", track.file_path, e);
+                results.push(Err(e.to_string()));
+            }
+        }
+        on_track();
+    }
+    // Album values from only some of its tracks would be wrong, so none are stored
+    let album = match group.album_id {
+        Some(_) if analyses.len() == group.tracks.len() => loudness::album_measurement(&analyses)
+            .map_err(|e| warn!("Album loudness of album {:?} failed: {}", group.album_id, e))
+            .ok(),
+        _ => None,
+    };
+    Some(LoudnessItem::Group { group, results, album })
+}
+
+/// Replace the track's gain tags in its file and in `track_extra_tags`.
+async fn write_gain_tags(
+    db: &DbPool,
+    track: &LoudnessTrack,
+    measurement: &Measurement,
+    album: Option<&Measurement>,
+) -> Result<(), AppError> {
+    let mut tags = get_track_extra_tags_inner(db, track.id).await?;
+    tags.retain(|t| !loudness::GAIN_FRAME_IDS.contains(&t.frame_id.as_str()));
+    let opus = track.codec.as_deref() == Some("Opus") || track.file_path.to_ascii_lowercase().ends_with(".opus");
+    tags.extend(loudness::gain_tags(opus, measurement, album));
+    set_track_extra_tags_inner(db, track.id, tags, false).await
+}
+
+async fn store_loudness_group(
+    db: &DbPool,
+    group: &LoudnessGroup,
+    results: &[Result<Measurement, String>],
+    album: Option<&Measurement>,
+    write_tags: bool,
+    summary: &mut LoudnessSummary,
+) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+    for (track, result) in group.tracks.iter().zip(results) {
+        match result {
+            Ok(m) => {
+                sqlx::query(
+                    "UPDATE tracks SET loudness_lufs = ?, true_peak = ?, loudness_range_lu = ?, loudness_hash = ?
+                     WHERE id = ?",
+                )
+                .bind(m.integrated_lufs)
+                .bind(m.true_peak)
+                .bind(m.loudness_range_lu)
+                .bind(&track.audio_hash)
+                .bind(track.id)
+                .execute(&mut *tx)
+                .await?;
+                summary.analyzed += 1;
+            }
+            Err(e) => summary.failed.push(AnalysisFailure {
+                track_id: track.id,
+                file_path: track.file_path.clone(),
+                error: e.clone(),
+            }),
+        }
+    }
+    if let (Some(album_id), Some(m)) = (group.album_id, album) {
+        sqlx::query("UPDATE albums SET loudness_lufs = ?, true_peak = ?, loudness_range_lu = ? WHERE id = ?")
+            .bind(m.integrated_lufs)
+            .bind(m.true_peak)
+            .bind(m.loudness_range_lu)
+            .bind(album_id)
+            .execute(&mut *tx)
+            .await?;
+        summary.albums += 1;
+    }
+    tx.commit().await?;
+
+    if write_tags {
+        for (track, result) in group.tracks.iter().zip(results) {
+            let Ok(m) = result else { continue };
+            match write_gain_tags(db, track, m, album).await {
+                Ok(()) => summary.tags_written += 1,
+                Err(e) => summary.failed.push(AnalysisFailure {
+                    track_id: track.id,
+                    file_path: track.file_path.clone(),
+                    error: format!("Measured, but tags not written: {}", e),
+                }),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode the selected tracks and store their EBU R128 loudness, true peak and
+/// loudness range, plus the same per album. Decoding runs on a pool of
+/// blocking workers; results are written one album at a time.
+pub async fn analyze_loudness_inner(
+    db: &DbPool,
+    options: &LoudnessOptions,
+    control: Arc<JobControl>,
+    on_progress: impl Fn(AnalysisProgress),
+) -> Result<LoudnessSummary, AppError> {
+    let groups = loudness_groups(db, options).await?;
+    let total = groups.iter().map(|g| g.tracks.len() as u32).sum();
+    info!("Starting loudness analysis of {} tracks", total);
+    on_progress(AnalysisProgress { processed: 0, total });
+
+    let (mut item_rx, workers) = spawn_analysis_workers(groups, {
+        let control = Arc::clone(&control);
+        move |group, item_tx| {
+            let on_track = || {
+                let _ = item_tx.blocking_send(LoudnessItem::Track);
+            };
+            match analyze_loudness_group(group, &control, on_track) {
+                Some(item) => item_tx.blocking_send(item).is_ok(),
+                None => false,
+            }
+        }
+    });
+
+    let mut summary = LoudnessSummary::default();
+    let mut processed = 0;
+    while let Some(item) = item_rx.recv().await {
+        match item {
+            LoudnessItem::Track => {
+                processed += 1;
+                on_progress(AnalysisProgress { processed, total });
+            }
+            LoudnessItem::Group { group, results, album } => {
+                store_loudness_group(db, &group, &results, album.as_ref(), options.write_tags, &mut summary).await?;
+            }
+        }
+    }
+    join_analysis_workers(workers).await?;
+
+    summary.cancelled = control.is_cancelled();
+    info!(
+        "Loudness analysis {}: {} tracks, {} albums, {} failed",
+        if summary.cancelled { "cancelled" } else { "complete" },
+        summary.analyzed,
+        summary.albums,
+        summary.failed.len()
+    );
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn analyze_loudness(
+    app_handle: tauri::AppHandle,
+    db: State<'_, DbPool>,
+    jobs: State<'_, AnalysisJobs>,
+    options: LoudnessOptions,
+) -> Result<LoudnessSummary, AppError> {
+    let job = jobs.loudness.start()?;
+    let handle = app_handle.clone();
+    let result = analyze_loudness_inner(db.inner(), &options, job.control(), |progress| {
+        let _ = handle.emit("loudness:progress", progress);
+    })
+    .await;
+    drop(job);
+
+    let summary = result?;
+    let _ = app_handle.emit("loudness:complete", &summary);
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_loudness_analysis(jobs: State<'_, AnalysisJobs>) -> Result<(), AppError> {
+    jobs.loudness.cancel()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn pause_loudness_analysis(jobs: State<'_, AnalysisJobs>) -> Result<(), AppError> {
+    jobs.loudness.pause()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn resume_loudness_analysis(jobs: State<'_, AnalysisJobs>) -> Result<(), AppError> {
+    jobs.loudness.resume()
+}
+
+// ── Fingerprints ──
+
+#[derive(FromRow)]
+struct FingerprintTrack {
+    id: i64,
+    file_path: String,
+    audio_hash: Option<String>,
+}
+
+/// Decode the selected tracks and store their Chromaprint fingerprints, for
+/// `find_similar_audio` and AcoustID lookups. Tracks whose audio is unchanged
+/// since they were last fingerprinted are skipped unless `recompute` is set.
+pub async fn fingerprint_tracks_inner(
+    db: &DbPool,
+    options: &FingerprintOptions,
+    control: Arc<JobControl>,
+    on_progress: impl Fn(AnalysisProgress),
+) -> Result<FingerprintSummary, AppError> {
+    let mut tracks = sqlx::query_as::<_, FingerprintTrack>(
+        "SELECT id, file_path, audio_hash FROM tracks
+         WHERE (? IS NULL OR collection_id = ?)
+           AND (? OR fingerprint IS NULL OR fingerprint_hash IS NOT audio_hash)
+         ORDER BY id",
+    )
+    .bind(options.collection_id)
+    .bind(options.collection_id)
+    .bind(options.recompute)
+    .fetch_all(db)
+    .await?;
+    if let Some(ids) = &options.track_ids {
+        let ids: HashSet<i64> = ids.iter().copied().collect();
+        tracks.retain(|t| ids.contains(&t.id));
+    }
+    let total = tracks.len() as u32;
+    info!("Starting fingerprinting of {} tracks", total);
+    on_progress(AnalysisProgress { processed: 0, total });
+
+    let (mut result_rx, workers) = spawn_analysis_workers(tracks, {
+        let control = Arc::clone(&control);
+        move |track: FingerprintTrack, result_tx| {
+            let result = match fingerprint::fingerprint_file(Path::new(&track.file_path), &control) {
+                Ok(Some(fingerprint)) => Ok(fingerprint),
+                Ok(None) => return false,
+                Err(e) => Err(e.to_string()),
+            };
+            result_tx.blocking_send((track, result)).is_ok()
+        }
+    });
+
+    let mut summary = FingerprintSummary::default();
+    let mut processed = 0;
+    while let Some((track, result)) = result_rx.recv().await {
+        match result {
+            Ok(fingerprint) => {
+                sqlx::query("UPDATE tracks SET fingerprint = ?, fingerprint_hash = ? WHERE id = ?")
+                    .bind(fingerprint::to_blob(&fingerprint))
+                    .bind(&track.audio_hash)
+                    .bind(track.id)
+                    .execute(db)
+                    .await?;
+                summary.fingerprinted += 1;
+            }
+            Err(error) => {
+                warn!("Fingerprinting {:?} failed: {}", track.file_path, error);
+                summary.failed.push(AnalysisFailure { track_id: track.id, file_path: track.file_path, error });
+            }
+        }
+        processed += 1;
+        on_progress(AnalysisProgress { processed, total });
+    }
+    join_analysis_workers(workers).await?;
+
+    summary.cancelled = control.is_cancelled();
+    info!(
+        "Fingerprinting {}: {} tracks, {} failed",
+        if summary.cancelled { "cancelled" } else { "complete" },
+        summary.fingerprinted,
+        summary.failed.len()
+    );
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn fingerprint_tracks(
+    app_handle: tauri::AppHandle,
+    db: State<'_, DbPool>,
+    jobs: State<'_, AnalysisJobs>,
+    options: FingerprintOptions,
+) -> Result<FingerprintSummary, AppError> {
+    let job = jobs.fingerprint.start()?;
+    let handle = app_handle.clone();
+    let result = fingerprint_tracks_inner(db.inner(), &options, job.control(), |progress| {
+        let _ = handle.emit("fingerprint:progress", progress);
+    })
+    .await;
+    drop(job);
+
+    let summary = result?;
+    let _ = app_handle.emit("fingerprint:complete", &summary);
+    Ok(summary)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_fingerprinting(jobs: State<'_, AnalysisJobs>) -> Result<(), AppError> {
+    jobs.fingerprint.cancel()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn pause_fingerprinting(jobs: State<'_, AnalysisJobs>) -> Result<(), AppError> {
+    jobs.fingerprint.pause()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn resume_fingerprinting(jobs: State<'_, AnalysisJobs>) -> Result<(), AppError> {
+    jobs.fingerprint.resume()
+}
+
+/// Tracks that hold the same recording, judged by their stored fingerprints.
+/// With `track_id`, only that track's matches; otherwise every group in the
+/// library (or one collection). Tracks not fingerprinted yet are left out.
+pub async fn find_similar_audio_inner(
+    db: &DbPool,
+    track_id: Option<i64>,
+    collection_id: Option<i64>,
+    min_similarity: Option<f64>,
+) -> Result<Vec<SimilarAudioGroup>, AppError> {
+    let min_similarity = min_similarity.unwrap_or(fingerprint::DEFAULT_MIN_SIMILARITY).clamp(0.0, 1.0);
+    let query = match track_id {
+        Some(id) => {
+            let row: Option<(Option<Vec<u8>>,)> = sqlx::query_as("SELECT fingerprint FROM tracks WHERE id = ?")
+                .bind(id)
+                .fetch_optional(db)
+                .await?;
+            let (blob,) = row.ok_or_else(|| AppError::NotFound(format!("Track {} not found", id)))?;
+            let blob = blob.ok_or_else(|| AppError::InvalidInput(format!("Track {} has not been fingerprinted", id)))?;
+            Some((id, fingerprint::from_blob(&blob)))
+        }
+        None => None,
+    };
+    let fingerprints: Vec<(i64, Vec<u32>)> = sqlx::query_as::<_, (i64, Vec<u8>)>(
+        "SELECT id, fingerprint FROM tracks
+         WHERE fingerprint IS NOT NULL AND (?1 IS NULL OR collection_id = ?1)
+         ORDER BY id",
+    )
+    .bind(collection_id)
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|(id, blob)| (id, fingerprint::from_blob(&blob)))
+    .collect();
+
+    let matches = tokio::task::spawn_blocking(move || {
+        let index = FingerprintIndex::new(fingerprints);
+        match query {
+            Some((id, fingerprint)) => index.matches(id, &fingerprint, min_similarity),
+            None => index.all_matches(min_similarity),
+        }
+    })
+    .await
+    .map_err(|e| AppError::Io(format!("Fingerprint search failed: {}", e)))?;
+
+    let mut conn = db.acquire().await?;
+    let mut groups = Vec::new();
+    for (track_ids, matches) in fingerprint::group_matches(matches) {
+        let mut tracks = Vec::with_capacity(track_ids.len());
+        for id in track_ids {
+            tracks.push(fetch_track_row(&mut conn, id).await?);
+        }
+        groups.push(SimilarAudioGroup { tracks, matches });
+    }
+    Ok(groups)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn find_similar_audio(
+    db: State<'_, DbPool>,
+    track_id: Option<i64>,
+    collection_id: Option<i64>,
+    min_similarity: Option<f64>,
+) -> Result<Vec<SimilarAudioGroup>, AppError> {
+    find_similar_audio_inner(db.inner(), track_id, collection_id, min_similarity).await
+}
+
+/// The track's fingerprint in the form an AcoustID lookup takes. None until the
+/// track has been fingerprinted, or while its duration is unknown.
+pub async fn get_acoustid_fingerprint_inner(
+    db: &DbPool,
+    track_id: i64,
+) -> Result<Option<AcoustIdFingerprint>, AppError> {
+    let row: Option<(Option<Vec<u8>>, Option<f64>)> =
+        sqlx::query_as("SELECT fingerprint, duration_secs FROM tracks WHERE id = ?")
+            .bind(track_id)
+            .fetch_optional(db)
+            .await?;
+    let (blob, duration) = row.ok_or_else(|| AppError::NotFound(format!("Track {} not found", track_id)))?;
+    Ok(blob.zip(duration).map(|(blob, duration)| AcoustIdFingerprint {
+        fingerprint: fingerprint::acoustid_string(&fingerprint::from_blob(&blob)),
+        duration_secs: duration.round() as i64,
+    }))
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_acoustid_fingerprint(
+    db: State<'_, DbPool>,
+    track_id: i64,
+) -> Result<Option<AcoustIdFingerprint>, AppError> {
+    get_acoustid_fingerprint_inner(db.inner(), track_id).await
+}
+
+// ── Live Sync ──
+
+/// Re-read a single file and upsert its row in its own transaction.
+/// Returns the id of the track row.
+pub(crate) async fn process_track(
+    db: &DbPool,
+    collection_id: i64,
+    path: &Path,
+    covers_dir: Option<&Path>,
+) -> Result<i64, AppError> {
+    let track = read_track_file(path);
+    let mut tx = db.begin().await?;
+    let (id, _) = store_scanned_track(&mut tx, collection_id, &track, covers_dir).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Apply a batch of filesystem changes under a watched collection: created or
+/// modified audio files are re-read, and rows for files (or whole directories)
+/// that are gone are deleted. Files whose size and mtime match the DB — such as
+/// ones Chant just wrote tags to — are left alone.
+pub async fn sync_changed_paths_inner(
+    db: &DbPool,
+    collection_id: i64,
+    paths: &[PathBuf],
+    covers_dir: Option<&Path>,
+) -> Result<Vec<LibraryChange>, AppError> {
+    let mut changes = Vec::new();
+
+    // Files that appeared are handled before the ones that vanished, so a
+    // renamed file can take over its old row before that row would be deleted
+    let (present, gone): (Vec<&PathBuf>, Vec<&PathBuf>) = paths.iter().partition(|p| p.exists());
+    for path in present {
+        if path.is_dir() {
+            // A directory moved or copied in: pick up every audio file below it
+            let files: Vec<PathBuf> = WalkDir::new(path)
+                .follow_links(true)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file() && is_audio_path(e.path()))
+                .map(|e| e.into_path())
+                .collect();
+            for file in files {
+                sync_one_file(db, collection_id, &file, covers_dir, &mut changes).await?;
+            }
+        } else if path.is_file() && is_audio_path(path) {
+            sync_one_file(db, collection_id, path, covers_dir, &mut changes).await?;
+        }
+    }
+
+    for path in gone {
+        // Either a single file or a directory that held tracks
+        let path_str = path.to_string_lossy().replace('\\', "/");
+        let dir_prefix = format!("{}/", path_str.trim_end_matches('/'));
+        let removed: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, file_path FROM tracks
+             WHERE collection_id = ? AND (file_path = ? OR substr(file_path, 1, length(?)) = ?)",
+        )
+        .bind(collection_id)
+        .bind(&path_str)
+        .bind(&dir_prefix)
+        .bind(&dir_prefix)
+        .fetch_all(db)
+        .await?;
+        for (track_id, file_path) in removed {
+            sqlx::query("DELETE FROM tracks WHERE id = ?")
+                .bind(track_id)
+                .execute(db)
+                .await?;
+            changes.push(LibraryChange {
+                collection_id,
+                track_id,
+                file_path,
+                kind: LibraryChangeKind::Removed,
+            });
+        }
+    }
+    prune_orphans(db).await?;
+
+    Ok(changes)
+}
+
+async fn sync_one_file(
+    db: &DbPool,
+    collection_id: i64,
+    path: &Path,
+    covers_dir: Option<&Path>,
+    changes: &mut Vec<LibraryChange>,
+) -> Result<(), AppError> {
+    let path_str = path.to_string_lossy().replace('\\', "/");
+    let stored: Option<(Option<i64>, i64)> =
+        sqlx::query_as("SELECT file_mtime, file_size_bytes FROM tracks WHERE file_path = ?")
+            .bind(&path_str)
+            .fetch_optional(db)
+            .await?;
+    if let Some((stored_mtime, stored_size)) = stored {
+        let (size, mtime) = read_file_stat(path);
+        if stored_mtime.is_some() && mtime == stored_mtime && size == stored_size {
+            return Ok(());
+        }
+    }
+
+    match process_track(db, collection_id, path, covers_dir).await {
+        Ok(track_id) => changes.push(LibraryChange {
+            collection_id,
+            track_id,
+            file_path: path_str,
+            kind: LibraryChangeKind::Upserted,
+        }),
+        Err(e) => error!("Error processing track {:?}: {:?}", path, e),
+    }
+    Ok(())
+}
+
+/// Tag items that credit artists. Multi-value frames (ID3v2.4 null-separated
+/// values, repeated Vorbis fields) arrive as one item per value.
+const CREDIT_ITEM_KEYS: &[(ItemKey, CreditField)] = &[
+    (ItemKey::TrackArtist, CreditField::Artist),
+    (ItemKey::TrackArtists, CreditField::Artist),
+    (ItemKey::Composer, CreditField::Composer),
+    (ItemKey::Lyricist, CreditField::Lyricist),
+    (ItemKey::Remixer, CreditField::Remixer),
+    (ItemKey::Conductor, CreditField::Conductor),
+    (ItemKey::Performer, CreditField::Performer),
+];
+
+/// All values of `key` as one string, "; "-separated when the tag holds several.
+fn joined_values(tag: &Tag, key: &ItemKey) -> Option<String> {
+    let values: Vec<&str> = tag.get_strings(key).filter(|v| !v.is_empty()).collect();
+    (!values.is_empty()).then(|| values.join("; "))
+}
+
+/// Read tags and file facts for one audio file. Never fails: unreadable tags fall
+/// back to the file name as title, like a file with no tag at all.
+fn read_track_file(path: &Path) -> ScannedTrack {
+    let path_str = path.to_string_lossy().replace('\\', "/");
+    let (file_size, file_mtime) = read_file_stat(path);
+
+    let mut track = ScannedTrack {
+        path_str,
+        file_size,
+        file_mtime,
+        file_format: path.extension().and_then(|s| s.to_str()).map(|s| s.to_string()),
+        title: String::new(),
+        artist_name: None,
+        album_title: None,
+        year: None,
+        track_number: None,
+        disc_number: None,
+        duration: None,
+        cover: None,
+        genre: None,
+        album_artist: None,
+        composer: None,
+        bpm: None,
+        comment: None,
+        lyrics: None,
+        extra_tags: Vec::new(),
+        credits: Vec::new(),
+        audio_hash: audio_hash::audio_hash(path)
+            .map_err(|e| warn!("Failed to hash audio of {:?}: {:?}", path, e))
+            .ok(),
+        properties: AudioProperties::default(),
+    };
+    let mut tag_title = None;
+
+    match Probe::open(path) {
+        Ok(probe) => {
+            match probe.read() {
+                Ok(tagged_file) => {
+                    let properties = tagged_file.properties();
+                    track.duration = Some(properties.duration().as_secs_f64());
+                    track.properties = audio_properties::read(path, &tagged_file);
+
+                    let tag = tagged_file.primary_tag()
+                        .or_else(|| tagged_file.first_tag());
+
+                    if let Some(t) = tag {
+                        // Extract first picture if present
+                        track.cover = t.pictures().first().map(|pic| {
+                            let ext = match pic.mime_type() {
+                                Some(lofty::picture::MimeType::Png) => "png",
+                                _ => "jpg",
+                            };
+                            (pic.data().to_vec(), ext.to_string())
+                        });
+
+                        tag_title = t.title().map(|s| s.to_string());
+                        track.artist_name = joined_values(t, &ItemKey::TrackArtist);
+                        track.album_title = t.album().map(|s| s.to_string());
+                        track.year = t.year().map(|y| y as i32);
+                        track.track_number = t.track().map(|tn| tn as i32);
+                        track.disc_number = t.disk().map(|dn| dn as i32);
+                        track.genre = t.genre().map(|s| s.to_string());
+                        track.album_artist = t.get_string(&ItemKey::AlbumArtist).map(|s| s.to_string());
+                        track.composer = joined_values(t, &ItemKey::Composer);
+                        track.bpm = t.get_string(&ItemKey::Bpm)
+                            .and_then(|s| s.parse::<i32>().ok());
+                        track.comment = t.get_string(&ItemKey::Comment).map(|s| s.to_string());
+                        track.lyrics = t.get_string(&ItemKey::Lyrics).map(|s| s.to_string());
+                        track.extra_tags = read_extra_tags(t);
+                        track.credits = CREDIT_ITEM_KEYS
+                            .iter()
+                            .flat_map(|(key, field)| t.get_strings(key).map(|v| (*field, v.to_string())))
+                            .collect();
+                    }
+                }
+                Err(e) => warn!("Failed to read tags for {:?}: {:?}", path, e),
+            }
+        }
+        Err(e) => warn!("Failed to probe file {:?}: {:?}", path, e),
+    }
+
+    track.title = tag_title.unwrap_or_else(|| {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Unknown Track")
+            .to_string()
+    });
+
+    track
+}
+
+/// Upsert one scanned track (plus its artist, album, cover and extra tags) inside
+/// `conn`'s transaction. Returns the id of the track row and whether that row
+/// was found under an old path of the same audio (a moved or renamed file).
+async fn store_scanned_track(
+    conn: &mut SqliteConnection,
+    collection_id: i64,
+    track: &ScannedTrack,
+    covers_dir: Option<&Path>,
+) -> Result<(i64, bool), AppError> {
+    let now = Utc::now().to_rfc3339();
+
+    // 0. A new path for a file whose old path is gone: re-point the existing row
+    // so its id and DB-only data survive
+    let moved = adopt_moved_track(&mut *conn, collection_id, track).await?;
+
+    // 1. Ensure Artist exists
+    let artist_id = match &track.artist_name {
+        Some(name) => Some(find_or_create_artist(&mut *conn, name).await?),
+        None => None,
+    };
+
+    // 2. Ensure Album exists, grouped by album artist (see `AlbumKey`)
+    let album_id = match &track.album_title {
+        Some(title) => {
+            let extra = |frame_id: &str| track.extra_tags.iter().find(|(k, _)| k == frame_id).map(|(_, v)| v.as_str());
+            let key = AlbumKey {
+                title,
+                album_artist: track.album_artist.as_deref(),
+                track_artist: track.artist_name.as_deref(),
+                compilation: extra(albums::COMPILATION_FRAME).is_some_and(albums::is_compilation_flag),
+                musicbrainz_release_id: extra(albums::MUSICBRAINZ_RELEASE_FRAME),
+                file_path: &track.path_str,
+            };
+            Some(albums::find_or_create_album(&mut *conn, &key, track.year).await?)
+        }
+        None => None,
+    };
+
+    // 2b. Save cover art if we have picture data, a covers dir, and album has no cover yet
+    if let (Some(album_id), Some((ref data, ref ext)), Some(dir)) =
+        (album_id, &track.cover, covers_dir)
+    {
+        let existing_cover: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT cover_path FROM albums WHERE id = ?")
+                .bind(album_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+
+        let needs_cover = existing_cover
+            .map(|(cp,)| cp.is_none())
+            .unwrap_or(false);
+
+        if needs_cover {
+            let cover_filename = format!("{}.{}", album_id, ext);
+            let cover_path = dir.join(&cover_filename);
+            if let Err(e) = std::fs::write(&cover_path, data) {
+                warn!("Failed to write cover art for album {}: {}", album_id, e);
+            } else {
+                let cover_path_str = cover_path.to_string_lossy().replace('\\', "/");
+                sqlx::query("UPDATE albums SET cover_path = ? WHERE id = ?")
+                    .bind(&cover_path_str)
+                    .bind(album_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+    }
+
+    // 3. Upsert Track
+    sqlx::query(
+        r#"
+        INSERT INTO tracks (
+            collection_id, album_id, artist_id, title,
+            track_number, disc_number, duration_secs,
+            file_path, file_size_bytes, file_format,
+            bitrate_kbps, overall_bitrate_kbps, sample_rate_hz, channels, bit_depth, codec, codec_profile,
+            genre, album_artist, composer, bpm, comment, lyrics,
+            file_mtime, audio_hash, created_at, updated_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(file_path) DO UPDATE SET
+            album_id = excluded.album_id,
+            artist_id = excluded.artist_id,
+            title = excluded.title,
+            track_number = excluded.track_number,
+            disc_number = excluded.disc_number,
+            duration_secs = excluded.duration_secs,
+            file_size_bytes = excluded.file_size_bytes,
+            bitrate_kbps = excluded.bitrate_kbps,
+            overall_bitrate_kbps = excluded.overall_bitrate_kbps,
+            sample_rate_hz = excluded.sample_rate_hz,
+            channels = excluded.channels,
+            bit_depth = excluded.bit_depth,
+            codec = excluded.codec,
+            codec_profile = excluded.codec_profile,
+            genre = excluded.genre,
+            album_artist = excluded.album_artist,
+            composer = excluded.composer,
+            bpm = excluded.bpm,
+            comment = excluded.comment,
+            lyrics = excluded.lyrics,
+            file_mtime = excluded.file_mtime,
+            audio_hash = excluded.audio_hash,
+            updated_at = excluded.updated_at
+        "#
+    )
+    .bind(collection_id)
+    .bind(album_id)
+    .bind(artist_id)
+    .bind(&track.title)
+    .bind(track.track_number)
+    .bind(track.disc_number)
+    .bind(track.duration)
+    .bind(&track.path_str)
+    .bind(track.file_size)
+    .bind(&track.file_format)
+    .bind(track.properties.bitrate_kbps)
+    .bind(track.properties.overall_bitrate_kbps)
+    .bind(track.properties.sample_rate_hz)
+    .bind(track.properties.channels)
+    .bind(track.properties.bit_depth)
+    .bind(&track.properties.codec)
+    .bind(&track.properties.codec_profile)
+    .bind(&track.genre)
+    .bind(&track.album_artist)
+    .bind(&track.composer)
+    .bind(track.bpm)
+    .bind(&track.comment)
+    .bind(&track.lyrics)
+    .bind(track.file_mtime)
+    .bind(&track.audio_hash)
+    .bind(&now)
+    .bind(&now)
+    .execute(&mut *conn)
+    .await?;
+
+    // 4. Replace extra tags with what the file holds now
+    let (track_id,): (i64,) = sqlx::query_as("SELECT id FROM tracks WHERE file_path = ?")
+        .bind(&track.path_str)
+        .fetch_one(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM track_extra_tags WHERE track_id = ?")
+        .bind(track_id)
+        .execute(&mut *conn)
+        .await?;
+    for (frame_id, value) in &track.extra_tags {
+        sqlx::query("INSERT INTO track_extra_tags (track_id, frame_id, value) VALUES (?, ?, ?)")
+            .bind(track_id)
+            .bind(frame_id)
+            .bind(value)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    // 5. Replace credits, split on the configured separators
+    let separators = credits::separators(&mut *conn).await?;
+    let track_credits = credits::credits_from_fields(&track.credits, &separators);
+    credits::replace_credits(&mut *conn, track_id, CreditField::ALL, &track_credits).await?;
+
+    Ok((track_id, moved))
+}
+
+/// Durations read from the same audio may differ slightly between lofty versions
+/// or after a retag shifts the stream offset.
+const MOVED_TRACK_DURATION_TOLERANCE_SECS: f64 = 0.5;
+
+#[derive(FromRow)]
+struct MoveCandidate {
+    id: i64,
+    file_path: String,
+    collection_path: String,
+    duration_secs: Option<f64>,
+    artist_name: Option<String>,
+    album_title: Option<String>,
+}
+
+/// If no row has the scanned path yet but one for the same file points at a path
+/// that no longer exists, move that row to the new path. Returns whether it did.
+///
+/// The same file means the same audio hash, or, for rows scanned before hashes
+/// were stored, the same size, duration, title, artist and album. An identity
+/// match must be unambiguous. Rows of a collection whose folder is unreachable,
+/// such as an unplugged drive, are left alone: their files only look gone.
+async fn adopt_moved_track(
+    conn: &mut SqliteConnection,
+    collection_id: i64,
+    track: &ScannedTrack,
+) -> Result<bool, AppError> {
+    let at_path: Option<(i64,)> = sqlx::query_as("SELECT id FROM tracks WHERE file_path = ?")
+        .bind(&track.path_str)
+        .fetch_optional(&mut *conn)
+        .await?;
+    if at_path.is_some() {
+        return Ok(false);
+    }
+    // A match whose file still exists is a copy, not a move
+    let gone = |(_, path, root): &(i64, String, String)| !Path::new(path).exists() && Path::new(root).exists();
+
+    let mut found = None;
+    if let Some(hash) = &track.audio_hash {
+        let by_hash: Vec<(i64, String, String)> = sqlx::query_as(
+            "SELECT t.id, t.file_path, c.path FROM tracks t
+             JOIN collections c ON c.id = t.collection_id
+             WHERE t.audio_hash = ? ORDER BY t.id",
+        )
+        .bind(hash)
+        .fetch_all(&mut *conn)
+        .await?;
+        found = by_hash.into_iter().find(gone);
+    }
+    if found.is_none() {
+        let by_identity: Vec<MoveCandidate> = sqlx::query_as(
+            "SELECT t.id, t.file_path, c.path AS collection_path, t.duration_secs,
+                    a.name AS artist_name, al.title AS album_title
+             FROM tracks t
+             JOIN collections c ON c.id = t.collection_id
+             LEFT JOIN artists a ON t.artist_id = a.id
+             LEFT JOIN albums al ON t.album_id = al.id
+             WHERE t.file_size_bytes = ? AND t.title = ? AND (t.audio_hash IS NULL OR ? IS NULL)",
+        )
+        .bind(track.file_size)
+        .bind(&track.title)
+        .bind(&track.audio_hash)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut matches = by_identity
+            .into_iter()
+            .filter(|c| {
+                let same_duration = match (c.duration_secs, track.duration) {
+                    (Some(a), Some(b)) => (a - b).abs() <= MOVED_TRACK_DURATION_TOLERANCE_SECS,
+                    (None, None) => true,
+                    _ => false,
+                };
+                same_duration && c.artist_name == track.artist_name && c.album_title == track.album_title
+            })
+            .map(|c| (c.id, c.file_path, c.collection_path))
+            .filter(gone);
+        found = match (matches.next(), matches.next()) {
+            (Some(only), None) => Some(only),
+            _ => None,
+        };
+    }
+    let Some((track_id, old_path, _)) = found else {
+        return Ok(false);
+    };
+
+    sqlx::query("UPDATE tracks SET file_path = ?, collection_id = ? WHERE id = ?")
+        .bind(&track.path_str)
+        .bind(collection_id)
+        .bind(track_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("UPDATE file_backups SET file_path = ? WHERE file_path = ?")
+        .bind(&track.path_str)
+        .bind(&old_path)
+        .execute(&mut *conn)
+        .await?;
+    info!("Track {} moved: {:?} -> {:?}", track_id, old_path, track.path_str);
+    Ok(true)
+}
+
+// ── Cover Art ──
+
+pub async fn get_cover_art_inner(
+    db: &DbPool,
+    track_id: i64,
+) -> Result<Option<CoverArt>, AppError> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT file_path FROM tracks WHERE id = ?")
+            .bind(track_id)
+            .fetch_optional(db)
+            .await?;
+
+    let file_path = row
+        .ok_or_else(|| AppError::NotFound(format!("Track {} not found", track_id)))?
+        .0;
+
+    // Normalize forward slashes back to native separators
+    let path = PathBuf::from(file_path.replace('/', std::path::MAIN_SEPARATOR_STR));
+
+    let tagged_file = Probe::open(&path)
+        .map_err(|e| AppError::Io(format!("Failed to open {:?}: {}", path, e)))?
+        .read()
+        .map_err(|e| AppError::Io(format!("Failed to read tags from {:?}: {}", path, e)))?;
+
+    let tag = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag());
+
+    let picture = tag.and_then(|t| t.pictures().first());
+
+    match picture {
+        Some(pic) => {
+            use base64::Engine;
+            let mime = match pic.mime_type() {
+                Some(lofty::picture::MimeType::Png) => "image/png",
+                Some(lofty::picture::MimeType::Bmp) => "image/bmp",
+                Some(lofty::picture::MimeType::Gif) => "image/gif",
+                Some(lofty::picture::MimeType::Tiff) => "image/tiff",
+                _ => "image/jpeg",
+            };
+            let b64 = base64::engine::general_purpose::STANDARD.encode(pic.data());
+            Ok(Some(CoverArt {
+                data: b64,
+                mime_type: mime.to_string(),
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+pub async fn get_album_cover_art_inner(
+    db: &DbPool,
+    album_id: i64,
+) -> Result<Option<CoverArt>, AppError> {
+    // Try up to 5 tracks — skip ones with unreadable files or no embedded art
+    let rows: Vec<(i64,)> =
+        sqlx::query_as("SELECT id FROM tracks WHERE album_id = ? LIMIT 5")
+            .bind(album_id)
+            .fetch_all(db)
+            .await?;
+
+    for (track_id,) in rows {
+        if let Ok(Some(art)) = get_cover_art_inner(db, track_id).await {
+            return Ok(Some(art));
+        }
+    }
+    Ok(None)
+}
+
+pub async fn get_artist_cover_art_inner(
+    db: &DbPool,
+    artist_id: i64,
+) -> Result<Option<CoverArt>, AppError> {
+    // Try up to 5 tracks — skip ones with unreadable files or no embedded art
+    let rows: Vec<(i64,)> =
+        sqlx::query_as("SELECT track_id FROM artist_tracks WHERE artist_id = ? LIMIT 5")
+            .bind(artist_id)
+            .fetch_all(db)
+            .await?;
+
+    for (track_id,) in rows {
+        if let Ok(Some(art)) = get_cover_art_inner(db, track_id).await {
+            return Ok(Some(art));
+        }
+    }
+    Ok(None)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_cover_art(
+    db: State<'_, DbPool>,
+    track_id: i64,
+) -> Result<Option<CoverArt>, AppError> {
+    get_cover_art_inner(db.inner(), track_id).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_album_cover_art(
+    db: State<'_, DbPool>,
+    album_id: i64,
+) -> Result<Option<CoverArt>, AppError> {
+    get_album_cover_art_inner(db.inner(), album_id).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_artist_cover_art(
+    db: State<'_, DbPool>,
+    artist_id: i64,
+) -> Result<Option<CoverArt>, AppError> {
+    get_artist_cover_art_inner(db.inner(), artist_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_helpers::setup_test_db;
+    use crate::models::TrackUpdateInput;
+
+    // ── Collection Tests ──
+
+    #[tokio::test]
+    async fn test_list_collections_empty() {
+        let db = setup_test_db().await;
+        let result = list_collections_inner(&db).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    /// Helper: returns a platform-appropriate absolute path for tests
+    fn abs_test_path(suffix: &str) -> String {
+        if cfg!(windows) {
+            format!("C:/music{}", suffix)
+        } else {
+            format!("/music{}", suffix)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_list_collection() {
+        let db = setup_test_db().await;
+        let path = abs_test_path("/library");
+        let input = CollectionInput {
+            path: path.clone(),
+            label: Some("My Music".to_string()),
+        };
+        let col = add_collection_inner(&db, input, true).await.unwrap();
+        assert_eq!(col.path, path);
+        assert_eq!(col.label, Some("My Music".to_string()));
+
+        let all = list_collections_inner(&db).await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].id, col.id);
+    }
+
+    #[tokio::test]
+    async fn test_add_collection_rejects_relative_path() {
+        let db = setup_test_db().await;
+        let input = CollectionInput {
+            path: "relative/path".to_string(),
+            label: None,
+        };
+        let result = add_collection_inner(&db, input, true).await;
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            AppError::InvalidInput(msg) => assert!(msg.contains("absolute")),
+            other => panic!("Expected InvalidInput, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_collection_duplicate_path_upserts() {
+        let db = setup_test_db().await;
+        let path = abs_test_path("/library");
+        let input1 = CollectionInput {
+            path: path.clone(),
+            label: Some("Label 1".to_string()),
+        };
+        let col1 = add_collection_inner(&db, input1, true).await.unwrap();
+
+        let input2 = CollectionInput {
+            path: path.clone(),
+            label: Some("Label 2".to_string()),
+        };
+        let col2 = add_collection_inner(&db, input2, true).await.unwrap();
+
+        assert_eq!(col1.id, col2.id);
+        assert_eq!(col2.label, Some("Label 2".to_string()));
+
+        let all = list_collections_inner(&db).await.unwrap();
+        assert_eq!(all.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_collection() {
+        let db = setup_test_db().await;
+        let path = abs_test_path("/library");
+        let input = CollectionInput {
+            path: path,
+            label: None,
+        };
+        let col = add_collection_inner(&db, input, true).await.unwrap();
+        delete_collection_inner(&db, col.id).await.unwrap();
+
+        let all = list_collections_inner(&db).await.unwrap();
+        assert!(all.is_empty());
+    }
+
+    // ── Settings Tests ──
+
+    #[tokio::test]
+    async fn test_get_setting_missing() {
+        let db = setup_test_db().await;
+        let result = get_setting_inner(&db, "nonexistent").await.unwrap();
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_set_and_get_setting() {
+        let db = setup_test_db().await;
+        set_setting_inner(&db, "theme", "dark").await.unwrap();
+        let val = get_setting_inner(&db, "theme").await.unwrap();
+        assert_eq!(val, Some("dark".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_set_setting_overwrites() {
+        let db = setup_test_db().await;
+        set_setting_inner(&db, "theme", "dark").await.unwrap();
+        set_setting_inner(&db, "theme", "light").await.unwrap();
+        let val = get_setting_inner(&db, "theme").await.unwrap();
+        assert_eq!(val, Some("light".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_get_all_settings() {
+        let db = setup_test_db().await;
+        set_setting_inner(&db, "a_key", "val1").await.unwrap();
+        set_setting_inner(&db, "b_key", "val2").await.unwrap();
+        let all = get_all_settings_inner(&db).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].key, "a_key");
+        assert_eq!(all[1].key, "b_key");
+    }
+
+    // ── Track Tests ──
+
+    #[tokio::test]
+    async fn test_list_tracks_empty() {
+        let db = setup_test_db().await;
+        let result = list_tracks_inner(&db).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_track_not_found() {
+        let db = setup_test_db().await;
+        let result = get_track_inner(&db, 999).await;
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            AppError::NotFound(msg) => assert!(msg.contains("999")),
+            other => panic!("Expected NotFound, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_track() {
+        let db = setup_test_db().await;
+
+        // Insert a collection + track manually
+        let col = add_collection_inner(&db, CollectionInput {
+            path: abs_test_path(""),
+            label: None,
+        }, true).await.unwrap();
+
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            "INSERT INTO tracks (collection_id, title, file_path, file_size_bytes, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(col.id)
+        .bind("Original Title")
+        .bind("/music/track.mp3")
+        .bind(1000i64)
+        .bind(&now)
+        .bind(&now)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let track_id = tracks[0].id;
+
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            title: Some("New Title".to_string()),
+            track_number: Some(5),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.title, "New Title");
+        assert_eq!(updated.track_number, Some(5));
+    }
+
+    /// Helper: insert a bare track and return its id.
+    async fn insert_bare_track(db: &DbPool, col_id: i64, title: &str, path: &str) -> i64 {
+        let now = Utc::now().to_rfc3339();
+        let res = sqlx::query(
+            "INSERT INTO tracks (collection_id, title, file_path, file_size_bytes, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(col_id)
+        .bind(title)
+        .bind(path)
+        .bind(1000i64)
+        .bind(&now)
+        .bind(&now)
+        .execute(db)
+        .await
+        .unwrap();
+        res.last_insert_rowid()
+    }
+
+    #[tokio::test]
+    async fn test_update_track_creates_new_artist() {
+        let db = setup_test_db().await;
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
+
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            artist_name: Some("New Artist".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.artist_name, Some("New Artist".to_string()));
+
+        // artist row should exist
+        let artists = list_artists_inner(&db).await.unwrap();
+        assert_eq!(artists.len(), 1);
+        assert_eq!(artists[0].name, "New Artist");
+    }
+
+    #[tokio::test]
+    async fn test_update_track_reuses_existing_artist() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+        sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Existing Artist").bind(&now).execute(&db).await.unwrap();
+
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
+
+        update_track_inner(&db, track_id, TrackUpdateInput {
+            artist_name: Some("Existing Artist".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        // should NOT have created a second artist row
+        let artists = list_artists_inner(&db).await.unwrap();
+        assert_eq!(artists.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_track_clears_artist_with_empty_string() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Artist").bind(&now).execute(&db).await.unwrap();
+        let artist_id = res.last_insert_rowid();
+
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let res = sqlx::query(
+            "INSERT INTO tracks (collection_id, artist_id, title, file_path, file_size_bytes, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(col.id).bind(artist_id).bind("Song").bind("/music/s.mp3").bind(1000i64).bind(&now).bind(&now)
+        .execute(&db).await.unwrap();
+        let track_id = res.last_insert_rowid();
+
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            artist_name: Some("".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.artist_id, None);
+        assert_eq!(updated.artist_name, None);
+    }
+
+    #[tokio::test]
+    async fn test_update_track_creates_new_album() {
+        let db = setup_test_db().await;
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
+
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            album_title: Some("New Album".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.album_title, Some("New Album".to_string()));
+
+        let albums = list_albums_inner(&db, None).await.unwrap();
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].title, "New Album");
+    }
+
+    #[tokio::test]
+    async fn test_update_track_artist_and_album_together() {
+        let db = setup_test_db().await;
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
+
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            artist_name: Some("Band".to_string()),
+            album_title: Some("Debut".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.artist_name, Some("Band".to_string()));
+        assert_eq!(updated.album_title, Some("Debut".to_string()));
+
+        // Album should be linked to the created artist
+        let albums = list_albums_inner(&db, None).await.unwrap();
+        assert_eq!(albums.len(), 1);
+        let artists = list_artists_inner(&db).await.unwrap();
+        assert_eq!(artists.len(), 1);
+        assert_eq!(albums[0].artist_id, Some(artists[0].id));
+    }
+
+    #[tokio::test]
+    async fn test_update_track_clears_album_with_empty_string() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+        let res = sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
+            .bind("Album").bind(&now).execute(&db).await.unwrap();
+        let album_id = res.last_insert_rowid();
+
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let res = sqlx::query(
+            "INSERT INTO tracks (collection_id, album_id, title, file_path, file_size_bytes, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(col.id).bind(album_id).bind("Song").bind("/music/s.mp3").bind(1000i64).bind(&now).bind(&now)
+        .execute(&db).await.unwrap();
+        let track_id = res.last_insert_rowid();
+
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            album_title: Some("".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.album_id, None);
+        assert_eq!(updated.album_title, None);
+    }
+
+    #[tokio::test]
+    async fn test_find_or_create_artist_idempotent() {
+        let db = setup_test_db().await;
+        let mut conn = db.acquire().await.unwrap();
+        let id1 = find_or_create_artist(&mut conn, "Same Artist").await.unwrap();
+        let id2 = find_or_create_artist(&mut conn, "Same Artist").await.unwrap();
+        drop(conn);
+        assert_eq!(id1, id2);
+        let artists = list_artists_inner(&db).await.unwrap();
+        assert_eq!(artists.len(), 1);
+    }
+
+    // ── Artist / Album Tests ──
+
+    #[tokio::test]
+    async fn test_list_artists_empty() {
+        let db = setup_test_db().await;
+        let result = list_artists_inner(&db).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_albums_empty() {
+        let db = setup_test_db().await;
+        let result = list_albums_inner(&db, None).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_artists_after_insert() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+        sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Artist A")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let artists = list_artists_inner(&db).await.unwrap();
+        assert_eq!(artists.len(), 1);
+        assert_eq!(artists[0].name, "Artist A");
+    }
+
+    #[tokio::test]
+    async fn test_list_albums_after_insert() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+        sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
+            .bind("Album X")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let albums = list_albums_inner(&db, None).await.unwrap();
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].title, "Album X");
+    }
+
+    #[tokio::test]
+    async fn test_list_albums_by_artist() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+
+        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Artist A")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        let artist_id = res.last_insert_rowid();
+
+        sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
+            .bind("Album by A")
+            .bind(artist_id)
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
+            .bind("Album no artist")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let filtered = list_albums_inner(&db, Some(artist_id)).await.unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].title, "Album by A");
+    }
+
+    // ── Artist Row / Album Row Tests ──
+
+    #[tokio::test]
+    async fn test_list_artist_rows_empty() {
+        let db = setup_test_db().await;
+        let result = list_artist_rows_inner(&db).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_artist_rows_aggregates() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+
+        // Create artist
+        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Artist A")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        let artist_id = res.last_insert_rowid();
+
+        // Create 2 albums for this artist
+        let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
+            .bind("Album 1")
+            .bind(artist_id)
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        let album1_id = res.last_insert_rowid();
+
+        let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
+            .bind("Album 2")
+            .bind(artist_id)
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        let _album2_id = res.last_insert_rowid();
+
+        // Create collection for tracks
+        let col = add_collection_inner(
+            &db,
+            CollectionInput {
+                path: abs_test_path(""),
+                label: None,
+            },
+            true,
+        )
+        .await
+        .unwrap();
+
+        // Insert 3 tracks for this artist (2 in album1, 1 with no album)
+        for (i, album_id) in [(1, Some(album1_id)), (2, Some(album1_id)), (3, None)] {
+            sqlx::query(
+                "INSERT INTO tracks (collection_id, album_id, artist_id, title, file_path, file_size_bytes, duration_secs, created_at, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(col.id)
+            .bind(album_id)
+            .bind(artist_id)
+            .bind(format!("Track {}", i))
+            .bind(format!("/music/track{}.mp3", i))
+            .bind(1000i64)
+            .bind(120.0)
+            .bind(&now)
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        let rows = list_artist_rows_inner(&db).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].name, "Artist A");
+        assert_eq!(rows[0].album_count, 2);
+        assert_eq!(rows[0].track_count, 3);
+        assert_eq!(rows[0].total_duration_secs, 360.0);
+    }
+
+    #[tokio::test]
+    async fn test_list_album_rows_empty() {
+        let db = setup_test_db().await;
+        let result = list_album_rows_inner(&db).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_album_rows_aggregates() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+
+        // Create artist
+        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Artist B")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        let artist_id = res.last_insert_rowid();
+
+        // Create album with year and genre
+        let res = sqlx::query(
+            "INSERT INTO albums (title, artist_id, year, genre, created_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind("Album X")
+        .bind(artist_id)
+        .bind(2020)
+        .bind("Rock")
+        .bind(&now)
+        .execute(&db)
+        .await
+        .unwrap();
+        let album_id = res.last_insert_rowid();
+
+        // Create collection
+        let col = add_collection_inner(
+            &db,
+            CollectionInput {
+                path: abs_test_path(""),
+                label: None,
+            },
+            true,
+        )
+        .await
+        .unwrap();
+
+        // Insert 2 tracks in this album
+        for i in 1..=2 {
+            sqlx::query(
+                "INSERT INTO tracks (collection_id, album_id, artist_id, title, file_path, file_size_bytes, duration_secs, created_at, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(col.id)
+            .bind(album_id)
+            .bind(artist_id)
+            .bind(format!("Track {}", i))
+            .bind(format!("/music/track{}.mp3", i))
+            .bind(5000i64)
+            .bind(200.5)
+            .bind(&now)
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        let rows = list_album_rows_inner(&db).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].title, "Album X");
+        assert_eq!(rows[0].artist_name, Some("Artist B".to_string()));
+        assert_eq!(rows[0].year, Some(2020));
+        assert_eq!(rows[0].genre, Some("Rock".to_string()));
+        assert_eq!(rows[0].track_count, 2);
+        assert_eq!(rows[0].total_duration_secs, 401.0);
+        assert_eq!(rows[0].total_size_bytes, 10000);
+    }
+
+    // ── Library Stats Tests ──
+
+    #[tokio::test]
+    async fn test_library_stats_empty() {
+        let db = setup_test_db().await;
+        let stats = get_library_stats_inner(&db).await.unwrap();
+        assert_eq!(stats.total_collections, 0);
+        assert_eq!(stats.total_artists, 0);
+        assert_eq!(stats.total_albums, 0);
+        assert_eq!(stats.total_tracks, 0);
+        assert_eq!(stats.total_size_bytes, 0);
+        assert_eq!(stats.total_duration_secs, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_library_stats_after_inserts() {
+        let db = setup_test_db().await;
+        let now = Utc::now().to_rfc3339();
+
+        let col = add_collection_inner(&db, CollectionInput {
+            path: abs_test_path(""),
+            label: None,
+        }, true).await.unwrap();
+
+        sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Artist")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
+            .bind("Album")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        sqlx::query(
+            "INSERT INTO tracks (collection_id, title, file_path, file_size_bytes, duration_secs, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(col.id)
+        .bind("Track")
+        .bind("/music/track.mp3")
+        .bind(5000i64)
+        .bind(180.5)
+        .bind(&now)
+        .bind(&now)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let stats = get_library_stats_inner(&db).await.unwrap();
+        assert_eq!(stats.total_collections, 1);
+        assert_eq!(stats.total_artists, 1);
+        assert_eq!(stats.total_albums, 1);
+        assert_eq!(stats.total_tracks, 1);
+        assert_eq!(stats.total_size_bytes, 5000);
+        assert_eq!(stats.total_duration_secs, 180.5);
     }
 
+    // ── Scan Test ──
+
     #[tokio::test]
-    async fn test_delete_collection() {
+    async fn test_scan_collection_with_fixture() {
+        use lofty::config::WriteOptions;
+        use lofty::picture::{Picture, PictureType, MimeType};
+        use lofty::tag::{Tag, TagType, Accessor};
+        use std::io::Write;
+
         let db = setup_test_db().await;
-        let path = abs_test_path("/library");
-        let input = CollectionInput {
-            path: path,
-            label: None,
-        };
-        let col = add_collection_inner(&db, input, true).await.unwrap();
-        delete_collection_inner(&db, col.id).await.unwrap();
+        let tmp_dir = tempfile::tempdir().unwrap();
 
-        let all = list_collections_inner(&db).await.unwrap();
-        assert!(all.is_empty());
+        // Create a minimal MP3 fixture: multiple valid MPEG1 Layer 3 frames
+        // so lofty recognizes it as a valid file
+        let mp3_path = tmp_dir.path().join("test.mp3");
+        {
+            let mut file = std::fs::File::create(&mp3_path).unwrap();
+            // MPEG1, Layer 3, 128kbps, 44100Hz, stereo = frame size 417 bytes
+            // Header: 0xFF 0xFB 0x90 0x64
+            let mut frame = [0u8; 417];
+            frame[0] = 0xFF;
+            frame[1] = 0xFB;
+            frame[2] = 0x90;
+            frame[3] = 0x64;
+            // Write 3 frames so lofty sees enough valid data
+            for _ in 0..3 {
+                file.write_all(&frame).unwrap();
+            }
+        }
+
+        // Minimal 1x1 PNG (67 bytes)
+        let png_bytes: Vec<u8> = vec![
+            0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, // PNG signature
+            0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, // IHDR chunk
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, // 1x1
+            0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53, 0xDE,
+            0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, // IDAT chunk
+            0x08, 0xD7, 0x63, 0xF8, 0xCF, 0xC0, 0x00, 0x00,
+            0x00, 0x02, 0x00, 0x01, 0xE2, 0x21, 0xBC, 0x33,
+            0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, // IEND chunk
+            0xAE, 0x42, 0x60, 0x82,
+        ];
+
+        // Write ID3v2 tags using lofty (including cover art)
+        {
+            let mut tagged_file = lofty::read_from_path(&mp3_path).unwrap();
+            tagged_file.insert_tag(Tag::new(TagType::Id3v2));
+            let tag = tagged_file.tag_mut(TagType::Id3v2).unwrap();
+            tag.set_title("Test Track".to_string());
+            tag.set_artist("Test Artist".to_string());
+            tag.set_album("Test Album".to_string());
+            tag.set_track(1);
+            tag.push_picture(Picture::new_unchecked(
+                PictureType::CoverFront,
+                Some(MimeType::Png),
+                None,
+                png_bytes.clone(),
+            ));
+            tagged_file.save_to_path(&mp3_path, WriteOptions::default()).unwrap();
+        }
+
+        // Add the temp dir as a collection (skip fs checks since it exists)
+        let col_path = tmp_dir.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput {
+            path: col_path,
+            label: Some("Test Collection".to_string()),
+        }, true).await.unwrap();
+
+        // Set up a covers directory inside tmp
+        let covers_dir = tmp_dir.path().join("covers");
+
+        // Run scan with covers_dir
+        scan_collection_inner(&db, col.id, Some(&covers_dir), Default::default(), |_| {}).await.unwrap();
+
+        // Verify results
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].title, "Test Track");
+        assert_eq!(tracks[0].artist_name, Some("Test Artist".to_string()));
+        assert_eq!(tracks[0].album_title, Some("Test Album".to_string()));
+        assert_eq!(tracks[0].track_number, Some(1));
+
+        let artists = list_artists_inner(&db).await.unwrap();
+        assert_eq!(artists.len(), 1);
+        assert_eq!(artists[0].name, "Test Artist");
+
+        let albums = list_albums_inner(&db, None).await.unwrap();
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].title, "Test Album");
+
+        // Verify cover art was extracted
+        assert!(albums[0].cover_path.is_some(), "Album should have cover_path set");
+        let cover_path = PathBuf::from(albums[0].cover_path.as_ref().unwrap().replace('/', std::path::MAIN_SEPARATOR_STR));
+        assert!(cover_path.exists(), "Cover file should exist on disk at {:?}", cover_path);
+        let saved_bytes = std::fs::read(&cover_path).unwrap();
+        assert_eq!(saved_bytes, png_bytes, "Saved cover should match embedded PNG");
     }
 
-    // ── Settings Tests ──
+    // ── Debug Query Tests ──
 
     #[tokio::test]
-    async fn test_get_setting_missing() {
+    async fn test_debug_query_table_rejects_invalid() {
         let db = setup_test_db().await;
-        let result = get_setting_inner(&db, "nonexistent").await.unwrap();
-        assert!(result.is_none());
+        let result = debug_query_table_inner(&db, "users").await;
+        assert!(result.is_err());
+        match result.unwrap_err() {
+            AppError::InvalidInput(msg) => assert!(msg.contains("users")),
+            other => panic!("Expected InvalidInput, got {:?}", other),
+        }
     }
 
     #[tokio::test]
-    async fn test_set_and_get_setting() {
+    async fn test_debug_query_table_allowed() {
         let db = setup_test_db().await;
-        set_setting_inner(&db, "theme", "dark").await.unwrap();
-        let val = get_setting_inner(&db, "theme").await.unwrap();
-        assert_eq!(val, Some("dark".to_string()));
+        let result = debug_query_table_inner(&db, "collections").await.unwrap();
+        assert!(result.is_empty());
     }
 
     #[tokio::test]
-    async fn test_set_setting_overwrites() {
+    async fn test_clear_all_data() {
         let db = setup_test_db().await;
-        set_setting_inner(&db, "theme", "dark").await.unwrap();
-        set_setting_inner(&db, "theme", "light").await.unwrap();
-        let val = get_setting_inner(&db, "theme").await.unwrap();
-        assert_eq!(val, Some("light".to_string()));
+        let now = Utc::now().to_rfc3339();
+
+        // Insert artist, album, collection, track
+        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
+            .bind("Artist X")
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        let artist_id = res.last_insert_rowid();
+
+        let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
+            .bind("Album X")
+            .bind(artist_id)
+            .bind(&now)
+            .execute(&db)
+            .await
+            .unwrap();
+        let album_id = res.last_insert_rowid();
+
+        let res = sqlx::query(
+            "INSERT INTO collections (path, label, created_at) VALUES (?, ?, ?)",
+        )
+        .bind("/music")
+        .bind("Test")
+        .bind(&now)
+        .execute(&db)
+        .await
+        .unwrap();
+        let collection_id = res.last_insert_rowid();
+
+        sqlx::query(
+            "INSERT INTO tracks (collection_id, album_id, artist_id, title, file_path, file_size_bytes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(collection_id)
+        .bind(album_id)
+        .bind(artist_id)
+        .bind("Track X")
+        .bind("/music/track.mp3")
+        .bind(1000i64)
+        .bind(&now)
+        .bind(&now)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        // Clear without a covers dir (no filesystem side-effect needed)
+        clear_all_data_inner(&db, None).await.unwrap();
+
+        let artists = list_artists_inner(&db).await.unwrap();
+        let albums = list_albums_inner(&db, None).await.unwrap();
+        let collections = list_collections_inner(&db).await.unwrap();
+        let tracks = list_tracks_inner(&db).await.unwrap();
+
+        assert!(artists.is_empty(), "artists should be empty after clear");
+        assert!(albums.is_empty(), "albums should be empty after clear");
+        assert!(collections.is_empty(), "collections should be empty after clear");
+        assert!(tracks.is_empty(), "tracks should be empty after clear");
     }
 
+    // ── New Field Tests ──
+
     #[tokio::test]
-    async fn test_get_all_settings() {
+    async fn test_update_track_new_fields() {
         let db = setup_test_db().await;
-        set_setting_inner(&db, "a_key", "val1").await.unwrap();
-        set_setting_inner(&db, "b_key", "val2").await.unwrap();
-        let all = get_all_settings_inner(&db).await.unwrap();
-        assert_eq!(all.len(), 2);
-        assert_eq!(all[0].key, "a_key");
-        assert_eq!(all[1].key, "b_key");
-    }
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/new_fields.mp3").await;
 
-    // ── Track Tests ──
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            genre: Some("Jazz".to_string()),
+            bpm: Some(120),
+            year: Some(2023),
+            composer: Some("Bach".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.genre, Some("Jazz".to_string()));
+        assert_eq!(updated.bpm, Some(120));
+        assert_eq!(updated.year, Some(2023));
+        assert_eq!(updated.composer, Some("Bach".to_string()));
+    }
 
     #[tokio::test]
-    async fn test_list_tracks_empty() {
+    async fn test_update_track_keeps_existing_new_fields() {
         let db = setup_test_db().await;
-        let result = list_tracks_inner(&db).await.unwrap();
-        assert!(result.is_empty());
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/keep_fields.mp3").await;
+
+        // First set genre and bpm
+        update_track_inner(&db, track_id, TrackUpdateInput {
+            genre: Some("Rock".to_string()),
+            bpm: Some(140),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        // Update only title — genre and bpm must be unchanged
+        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
+            title: Some("New Title".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        assert_eq!(updated.title, "New Title");
+        assert_eq!(updated.genre, Some("Rock".to_string()));
+        assert_eq!(updated.bpm, Some(140));
     }
 
     #[tokio::test]
-    async fn test_get_track_not_found() {
+    async fn test_batch_update_tracks_changes_all() {
         let db = setup_test_db().await;
-        let result = get_track_inner(&db, 999).await;
-        assert!(result.is_err());
-        match result.unwrap_err() {
-            AppError::NotFound(msg) => assert!(msg.contains("999")),
-            other => panic!("Expected NotFound, got {:?}", other),
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let id1 = insert_bare_track(&db, col.id, "Track A", "/music/ba1.mp3").await;
+        let id2 = insert_bare_track(&db, col.id, "Track B", "/music/ba2.mp3").await;
+        let id3 = insert_bare_track(&db, col.id, "Track C", "/music/ba3.mp3").await;
+
+        batch_update_tracks_inner(&db, vec![id1, id2, id3], TrackUpdateInput {
+            title: Some("Batch Title".to_string()),
+            ..Default::default()
+        }, true).await.unwrap();
+
+        for id in [id1, id2, id3] {
+            let t = get_track_inner(&db, id).await.unwrap();
+            assert_eq!(t.title, "Batch Title", "track {} should have new title", id);
         }
     }
 
     #[tokio::test]
-    async fn test_update_track() {
+    async fn test_batch_update_tracks_is_atomic() {
         let db = setup_test_db().await;
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let id1 = insert_bare_track(&db, col.id, "Original A", "/music/atom1.mp3").await;
+        let id2 = insert_bare_track(&db, col.id, "Original B", "/music/atom2.mp3").await;
 
-        // Insert a collection + track manually
-        let col = add_collection_inner(&db, CollectionInput {
-            path: abs_test_path(""),
-            label: None,
-        }, true).await.unwrap();
+        // Include a non-existent id — should cause rollback
+        let result = batch_update_tracks_inner(
+            &db,
+            vec![id1, 99999, id2],
+            TrackUpdateInput {
+                title: Some("Should Rollback".to_string()),
+                ..Default::default()
+            },
+            true,
+        ).await;
 
-        let now = Utc::now().to_rfc3339();
-        sqlx::query(
-            "INSERT INTO tracks (collection_id, title, file_path, file_size_bytes, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, ?)"
-        )
-        .bind(col.id)
-        .bind("Original Title")
-        .bind("/music/track.mp3")
-        .bind(1000i64)
-        .bind(&now)
-        .bind(&now)
-        .execute(&db)
-        .await
-        .unwrap();
+        assert!(result.is_err(), "batch with invalid id should fail");
 
-        let tracks = list_tracks_inner(&db).await.unwrap();
-        let track_id = tracks[0].id;
+        // Both valid tracks should be unchanged
+        let t1 = get_track_inner(&db, id1).await.unwrap();
+        let t2 = get_track_inner(&db, id2).await.unwrap();
+        assert_eq!(t1.title, "Original A");
+        assert_eq!(t2.title, "Original B");
+    }
 
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            title: Some("New Title".to_string()),
-            track_number: Some(5),
-            ..Default::default()
-        }, true).await.unwrap();
+    #[tokio::test]
+    async fn test_set_and_get_extra_tags() {
+        let db = setup_test_db().await;
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/extra1.mp3").await;
 
-        assert_eq!(updated.title, "New Title");
-        assert_eq!(updated.track_number, Some(5));
-    }
+        let tags = vec![
+            ExtraTag { frame_id: "TCOP".to_string(), value: "2024 Label".to_string() },
+            ExtraTag { frame_id: "TKEY".to_string(), value: "Am".to_string() },
+        ];
+        set_track_extra_tags_inner(&db, track_id, tags, true).await.unwrap();
 
-    /// Helper: insert a bare track and return its id.
-    async fn insert_bare_track(db: &DbPool, col_id: i64, title: &str, path: &str) -> i64 {
-        let now = Utc::now().to_rfc3339();
-        let res = sqlx::query(
-            "INSERT INTO tracks (collection_id, title, file_path, file_size_bytes, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, ?)"
-        )
-        .bind(col_id)
-        .bind(title)
-        .bind(path)
-        .bind(1000i64)
-        .bind(&now)
-        .bind(&now)
-        .execute(db)
-        .await
-        .unwrap();
-        res.last_insert_rowid()
+        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
+        assert_eq!(fetched.len(), 2);
+        // ORDER BY frame_id: TCOP < TKEY
+        assert_eq!(fetched[0].frame_id, "TCOP");
+        assert_eq!(fetched[0].value, "2024 Label");
+        assert_eq!(fetched[1].frame_id, "TKEY");
+        assert_eq!(fetched[1].value, "Am");
     }
 
     #[tokio::test]
-    async fn test_update_track_creates_new_artist() {
+    async fn test_set_extra_tags_replaces_all() {
         let db = setup_test_db().await;
         let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/extra2.mp3").await;
 
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            artist_name: Some("New Artist".to_string()),
-            ..Default::default()
-        }, true).await.unwrap();
+        // Set TKEY first
+        set_track_extra_tags_inner(&db, track_id, vec![
+            ExtraTag { frame_id: "TKEY".to_string(), value: "C".to_string() },
+        ], true).await.unwrap();
 
-        assert_eq!(updated.artist_name, Some("New Artist".to_string()));
+        // Replace with TCOP only — TKEY must be gone
+        set_track_extra_tags_inner(&db, track_id, vec![
+            ExtraTag { frame_id: "TCOP".to_string(), value: "Label".to_string() },
+        ], true).await.unwrap();
 
-        // artist row should exist
-        let artists = list_artists_inner(&db).await.unwrap();
-        assert_eq!(artists.len(), 1);
-        assert_eq!(artists[0].name, "New Artist");
+        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].frame_id, "TCOP");
+    }
+
+    // ── File Write-back Tests ──
+
+    /// Create a minimal tagged MP3 file in `dir` with the given filename.
+    /// Returns the path to the created file.
+    fn make_tagged_mp3(dir: &std::path::Path, name: &str, title: &str, artist: &str, album: &str) -> std::path::PathBuf {
+        use lofty::tag::{TagType, Accessor};
+        use std::io::Write;
+
+        let path = dir.join(name);
+        {
+            let mut file = std::fs::File::create(&path).unwrap();
+            let mut frame = [0u8; 417];
+            frame[0] = 0xFF; frame[1] = 0xFB; frame[2] = 0x90; frame[3] = 0x64;
+            for _ in 0..3 { file.write_all(&frame).unwrap(); }
+        }
+        {
+            let mut tagged = lofty::read_from_path(&path).unwrap();
+            tagged.insert_tag(lofty::tag::Tag::new(TagType::Id3v2));
+            let tag = tagged.tag_mut(TagType::Id3v2).unwrap();
+            tag.set_title(title.to_string());
+            tag.set_artist(artist.to_string());
+            tag.set_album(album.to_string());
+            tagged.save_to_path(&path, WriteOptions::default()).unwrap();
+        }
+        path
     }
 
     #[tokio::test]
-    async fn test_update_track_reuses_existing_artist() {
+    async fn test_update_track_writes_to_file() {
+        use lofty::prelude::*;
+
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
-        sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Existing Artist").bind(&now).execute(&db).await.unwrap();
+        let tmp = tempfile::tempdir().unwrap();
 
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
+        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Original Title", "Original Artist", "Original Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        let track_id = tracks[0].id;
 
+        // Update via the command
         update_track_inner(&db, track_id, TrackUpdateInput {
-            artist_name: Some("Existing Artist".to_string()),
+            title: Some("New Title".into()),
+            artist_name: Some("New Artist".into()),
+            album_title: Some("New Album".into()),
+            year: Some(2024),
+            track_number: Some(3),
+            genre: Some("Jazz".into()),
+            lyrics: Some("la la la".into()),
             ..Default::default()
-        }, true).await.unwrap();
+        }, false).await.unwrap();
 
-        // should NOT have created a second artist row
-        let artists = list_artists_inner(&db).await.unwrap();
-        assert_eq!(artists.len(), 1);
+        // Re-read the file directly with Lofty — DB must not be the only thing changed
+        let tagged = lofty::read_from_path(&mp3).unwrap();
+        let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
+        assert_eq!(tag.title().as_deref(), Some("New Title"));
+        assert_eq!(tag.artist().as_deref(), Some("New Artist"));
+        assert_eq!(tag.album().as_deref(), Some("New Album"));
+        assert_eq!(tag.year(), Some(2024));
+        assert_eq!(tag.track(), Some(3));
+        assert_eq!(tag.genre().as_deref(), Some("Jazz"));
+        assert_eq!(tag.get_string(&ItemKey::Lyrics).as_deref(), Some("la la la"));
     }
 
     #[tokio::test]
-    async fn test_update_track_clears_artist_with_empty_string() {
+    async fn test_batch_update_writes_to_file() {
+        use lofty::prelude::*;
+
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
-        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Artist").bind(&now).execute(&db).await.unwrap();
-        let artist_id = res.last_insert_rowid();
+        let tmp = tempfile::tempdir().unwrap();
 
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        sqlx::query(
-            "INSERT INTO tracks (collection_id, artist_id, title, file_path, file_size_bytes, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?)"
-        )
-        .bind(col.id).bind(artist_id).bind("Song").bind("/music/s.mp3").bind(1000i64).bind(&now).bind(&now)
-        .execute(&db).await.unwrap();
-        let (track_id,): (i64,) = sqlx::query_as("SELECT last_insert_rowid()").fetch_one(&db).await.unwrap();
+        let mp3a = make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist A", "Album A");
+        let mp3b = make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist B", "Album B");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            artist_name: Some("".to_string()),
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 2);
+        let ids: Vec<i64> = tracks.iter().map(|t| t.id).collect();
+
+        // Apply the same genre to both tracks via batch update
+        batch_update_tracks_inner(&db, ids, TrackUpdateInput {
+            genre: Some("Electronic".into()),
             ..Default::default()
-        }, true).await.unwrap();
+        }, false).await.unwrap();
 
-        assert_eq!(updated.artist_id, None);
-        assert_eq!(updated.artist_name, None);
+        // Both files should now have the new genre
+        for mp3 in [&mp3a, &mp3b] {
+            let tagged = lofty::read_from_path(mp3).unwrap();
+            let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
+            assert_eq!(tag.genre().as_deref(), Some("Electronic"), "file {:?} not updated", mp3);
+        }
     }
 
     #[tokio::test]
-    async fn test_update_track_creates_new_album() {
+    async fn test_undo_redo_batch_edit_restores_file_and_db() {
         let db = setup_test_db().await;
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
+        let tmp = tempfile::tempdir().unwrap();
 
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            album_title: Some("New Album".to_string()),
+        let mp3a = make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist", "Album A");
+        let mp3b = make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist", "Album B");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let ids: Vec<i64> = list_tracks_inner(&db).await.unwrap().iter().map(|t| t.id).collect();
+
+        // A mistaken batch edit across both tracks
+        batch_update_tracks_inner(&db, ids.clone(), TrackUpdateInput {
+            album_title: Some("Wrong Album".into()),
             ..Default::default()
-        }, true).await.unwrap();
+        }, false).await.unwrap();
 
-        assert_eq!(updated.album_title, Some("New Album".to_string()));
+        let history = list_edit_history_inner(&db).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].track_count, 2);
+        assert!(!history[0].undone);
 
-        let albums = list_albums_inner(&db, None).await.unwrap();
-        assert_eq!(albums.len(), 1);
-        assert_eq!(albums[0].title, "New Album");
+        let undone = undo_last_edit_inner(&db, false).await.unwrap();
+        assert!(undone.undone);
+        for (mp3, album) in [(&mp3a, "Album A"), (&mp3b, "Album B")] {
+            let tagged = lofty::read_from_path(mp3).unwrap();
+            let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
+            assert_eq!(tag.album().as_deref(), Some(album));
+        }
+        let titles: Vec<Option<String>> = list_tracks_inner(&db).await.unwrap().into_iter().map(|t| t.album_title).collect();
+        assert!(titles.contains(&Some("Album A".into())));
+        assert!(titles.contains(&Some("Album B".into())));
+        assert!(undo_last_edit_inner(&db, false).await.is_err(), "nothing left to undo");
+
+        let redone = redo_edit_inner(&db, false).await.unwrap();
+        assert!(!redone.undone);
+        for &id in &ids {
+            assert_eq!(get_track_inner(&db, id).await.unwrap().album_title.as_deref(), Some("Wrong Album"));
+        }
+        let tagged = lofty::read_from_path(&mp3a).unwrap();
+        let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
+        assert_eq!(tag.album().as_deref(), Some("Wrong Album"));
     }
 
     #[tokio::test]
-    async fn test_update_track_artist_and_album_together() {
+    async fn test_partial_undo_keeps_operation_undoable() {
         let db = setup_test_db().await;
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/song.mp3").await;
-
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            artist_name: Some("Band".to_string()),
-            album_title: Some("Debut".to_string()),
-            ..Default::default()
-        }, true).await.unwrap();
-
-        assert_eq!(updated.artist_name, Some("Band".to_string()));
-        assert_eq!(updated.album_title, Some("Debut".to_string()));
+        let tmp = tempfile::tempdir().unwrap();
 
-        // Album should be linked to the created artist
-        let albums = list_albums_inner(&db, None).await.unwrap();
-        assert_eq!(albums.len(), 1);
-        let artists = list_artists_inner(&db).await.unwrap();
-        assert_eq!(artists.len(), 1);
-        assert_eq!(albums[0].artist_id, Some(artists[0].id));
+        let mp3a = make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist", "Album A");
+        let mp3b = make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist", "Album B");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let ids: Vec<i64> = list_tracks_inner(&db).await.unwrap().iter().map(|t| t.id).collect();
+        batch_update_tracks_inner(&db, ids.clone(), TrackUpdateInput {
+            album_title: Some("Wrong Album".into()),
+            ..Default::default()
+        }, false).await.unwrap();
+
+        // One file is out of reach while undoing
+        let away = tmp.path().join("b.away");
+        std::fs::rename(&mp3b, &away).unwrap();
+        assert!(matches!(undo_last_edit_inner(&db, false).await, Err(AppError::Io(_))));
+        assert!(!list_edit_history_inner(&db).await.unwrap()[0].undone);
+        let tagged = lofty::read_from_path(&mp3a).unwrap();
+        let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
+        assert_eq!(tag.album().as_deref(), Some("Album A"));
+
+        // Undoing again once it is back finishes the job
+        std::fs::rename(&away, &mp3b).unwrap();
+        assert!(undo_last_edit_inner(&db, false).await.unwrap().undone);
+        let titles: Vec<Option<String>> = list_tracks_inner(&db).await.unwrap().into_iter().map(|t| t.album_title).collect();
+        assert!(titles.contains(&Some("Album A".into())));
+        assert!(titles.contains(&Some("Album B".into())));
     }
 
     #[tokio::test]
-    async fn test_update_track_clears_album_with_empty_string() {
+    async fn test_new_edit_discards_redo_history() {
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
-        let res = sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
-            .bind("Album").bind(&now).execute(&db).await.unwrap();
-        let album_id = res.last_insert_rowid();
-
         let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        sqlx::query(
-            "INSERT INTO tracks (collection_id, album_id, title, file_path, file_size_bytes, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?)"
-        )
-        .bind(col.id).bind(album_id).bind("Song").bind("/music/s.mp3").bind(1000i64).bind(&now).bind(&now)
-        .execute(&db).await.unwrap();
-        let (track_id,): (i64,) = sqlx::query_as("SELECT last_insert_rowid()").fetch_one(&db).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "First", "/music/undo.mp3").await;
 
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            album_title: Some("".to_string()),
-            ..Default::default()
-        }, true).await.unwrap();
+        let set_title = |title: &str| TrackUpdateInput { title: Some(title.into()), ..Default::default() };
+        update_track_inner(&db, track_id, set_title("Second"), true).await.unwrap();
+        // Saving without changes is not journaled
+        update_track_inner(&db, track_id, set_title("Second"), true).await.unwrap();
+        assert_eq!(list_edit_history_inner(&db).await.unwrap().len(), 1);
 
-        assert_eq!(updated.album_id, None);
-        assert_eq!(updated.album_title, None);
+        undo_last_edit_inner(&db, true).await.unwrap();
+        assert_eq!(get_track_inner(&db, track_id).await.unwrap().title, "First");
+
+        update_track_inner(&db, track_id, set_title("Third"), true).await.unwrap();
+        match redo_edit_inner(&db, true).await {
+            Err(AppError::NotFound(msg)) => assert!(msg.contains("Nothing to redo")),
+            other => panic!("Expected NotFound, got {:?}", other),
+        }
+        let history = list_edit_history_inner(&db).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].label, "Edit \"Third\"");
     }
 
-    #[tokio::test]
-    async fn test_find_or_create_artist_idempotent() {
+    /// Scan a temp dir holding one tagged MP3 with backups enabled in `mode`.
+    async fn setup_backup_library(mode: &str) -> (DbPool, tempfile::TempDir, std::path::PathBuf, i64) {
         let db = setup_test_db().await;
-        let id1 = find_or_create_artist(&db, "Same Artist").await.unwrap();
-        let id2 = find_or_create_artist(&db, "Same Artist").await.unwrap();
-        assert_eq!(id1, id2);
-        let artists = list_artists_inner(&db).await.unwrap();
-        assert_eq!(artists.len(), 1);
-    }
+        let tmp = tempfile::tempdir().unwrap();
+        let music = tmp.path().join("music");
+        std::fs::create_dir(&music).unwrap();
+        let mp3 = make_tagged_mp3(&music, "song.mp3", "Original", "Artist", "Album");
 
-    // ── Artist / Album Tests ──
+        set_setting_inner(&db, backups::BACKUP_MODE_SETTING, mode).await.unwrap();
+        let backup_dir = tmp.path().join("backups").to_string_lossy().replace('\\', "/");
+        set_setting_inner(&db, backups::BACKUP_DIR_SETTING, &backup_dir).await.unwrap();
 
-    #[tokio::test]
-    async fn test_list_artists_empty() {
-        let db = setup_test_db().await;
-        let result = list_artists_inner(&db).await.unwrap();
-        assert!(result.is_empty());
+        let col_path = music.to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let track_id = list_tracks_inner(&db).await.unwrap()[0].id;
+        (db, tmp, mp3, track_id)
     }
 
     #[tokio::test]
-    async fn test_list_albums_empty() {
-        let db = setup_test_db().await;
-        let result = list_albums_inner(&db, None).await.unwrap();
-        assert!(result.is_empty());
+    async fn test_full_backup_restores_original_bytes() {
+        let (db, _tmp, mp3, track_id) = setup_backup_library("full").await;
+        let original = std::fs::read(&mp3).unwrap();
+
+        let rename = |title: &str| TrackUpdateInput { title: Some(title.into()), ..Default::default() };
+        update_track_inner(&db, track_id, rename("First Edit"), false).await.unwrap();
+        // Only the state before the first write is kept
+        update_track_inner(&db, track_id, rename("Second Edit"), false).await.unwrap();
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM file_backups").fetch_one(&db).await.unwrap();
+        assert_eq!(count, 1);
+        assert_ne!(std::fs::read(&mp3).unwrap(), original);
+
+        let restored = restore_track_file_inner(&db, track_id, None).await.unwrap();
+        assert_eq!(std::fs::read(&mp3).unwrap(), original);
+        assert_eq!(restored.title, "Original");
+        // The backup is consumed; there is nothing left to restore
+        assert!(matches!(
+            restore_track_file_inner(&db, track_id, None).await,
+            Err(AppError::NotFound(_))
+        ));
     }
 
     #[tokio::test]
-    async fn test_list_artists_after_insert() {
-        let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
-        sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Artist A")
-            .bind(&now)
-            .execute(&db)
+    async fn test_tag_backup_restores_original_tags() {
+        let (db, _tmp, mp3, track_id) = setup_backup_library("tags").await;
+
+        batch_update_tracks_inner(&db, vec![track_id], TrackUpdateInput {
+            title: Some("Edited".into()),
+            genre: Some("Noise".into()),
+            ..Default::default()
+        }, false).await.unwrap();
+        let (backup_path,): (String,) = sqlx::query_as("SELECT backup_path FROM file_backups")
+            .fetch_one(&db)
             .await
             .unwrap();
+        assert!(backup_path.ends_with(".json"));
 
-        let artists = list_artists_inner(&db).await.unwrap();
-        assert_eq!(artists.len(), 1);
-        assert_eq!(artists[0].name, "Artist A");
+        let restored = restore_track_file_inner(&db, track_id, None).await.unwrap();
+        assert_eq!(restored.title, "Original");
+        assert_eq!(restored.genre, None);
+        let tagged = lofty::read_from_path(&mp3).unwrap();
+        let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
+        assert_eq!(tag.title().as_deref(), Some("Original"));
+        assert_eq!(tag.artist().as_deref(), Some("Artist"));
+        assert_eq!(tag.genre(), None);
+        assert!(!std::path::Path::new(&backup_path).exists());
     }
 
     #[tokio::test]
-    async fn test_list_albums_after_insert() {
-        let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
-        sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
-            .bind("Album X")
-            .bind(&now)
+    async fn test_backups_expire_after_retention() {
+        let (db, _tmp, _mp3, track_id) = setup_backup_library("full").await;
+        update_track_inner(&db, track_id, TrackUpdateInput {
+            title: Some("Edited".into()),
+            ..Default::default()
+        }, false).await.unwrap();
+        let (backup_path,): (String,) = sqlx::query_as("SELECT backup_path FROM file_backups")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE file_backups SET created_at = '2000-01-01T00:00:00+00:00'")
             .execute(&db)
             .await
             .unwrap();
 
-        let albums = list_albums_inner(&db, None).await.unwrap();
-        assert_eq!(albums.len(), 1);
-        assert_eq!(albums[0].title, "Album X");
+        // 0 keeps backups forever
+        set_setting_inner(&db, backups::BACKUP_RETENTION_SETTING, "0").await.unwrap();
+        assert_eq!(backups::prune_expired_backups(&db).await.unwrap(), 0);
+
+        set_setting_inner(&db, backups::BACKUP_RETENTION_SETTING, "7").await.unwrap();
+        assert_eq!(backups::prune_expired_backups(&db).await.unwrap(), 1);
+        assert!(!std::path::Path::new(&backup_path).exists());
     }
 
     #[tokio::test]
-    async fn test_list_albums_by_artist() {
+    async fn test_scan_reads_extra_tags_from_file() {
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
+        let tmp = tempfile::tempdir().unwrap();
 
-        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Artist A")
-            .bind(&now)
-            .execute(&db)
-            .await
-            .unwrap();
-        let artist_id = res.last_insert_rowid();
+        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Song", "Artist", "Album");
+        {
+            let mut tagged = lofty::read_from_path(&mp3).unwrap();
+            let tag = tagged.tag_mut(TagType::Id3v2).unwrap();
+            tag.insert(TagItem::new(ItemKey::InitialKey, ItemValue::Text("Am".into())));
+            tag.insert(TagItem::new(ItemKey::CopyrightMessage, ItemValue::Text("2024 Label".into())));
+            tagged.save_to_path(&mp3, WriteOptions::default()).unwrap();
+        }
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-        sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
-            .bind("Album by A")
-            .bind(artist_id)
-            .bind(&now)
-            .execute(&db)
-            .await
-            .unwrap();
+        let track_id = list_tracks_inner(&db).await.unwrap()[0].id;
+        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
+        let pairs: Vec<(&str, &str)> = fetched.iter().map(|t| (t.frame_id.as_str(), t.value.as_str())).collect();
+        // Standard fields (title/artist/album) stay in their own columns
+        assert_eq!(pairs, vec![("TCOP", "2024 Label"), ("TKEY", "Am")]);
+    }
 
-        sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
-            .bind("Album no artist")
-            .bind(&now)
-            .execute(&db)
+    #[tokio::test]
+    async fn test_set_extra_tags_writes_to_file() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Song", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let track_id = list_tracks_inner(&db).await.unwrap()[0].id;
+
+        set_track_extra_tags_inner(&db, track_id, vec![
+            ExtraTag { frame_id: "TCOP".to_string(), value: "Label".to_string() },
+            ExtraTag { frame_id: "TKEY".to_string(), value: "C".to_string() },
+            ExtraTag { frame_id: "MOOD_CUSTOM".to_string(), value: "Calm".to_string() },
+        ], false).await.unwrap();
+        // Dropping TCOP must remove it from the file as well
+        set_track_extra_tags_inner(&db, track_id, vec![
+            ExtraTag { frame_id: "TKEY".to_string(), value: "Dm".to_string() },
+            ExtraTag { frame_id: "MOOD_CUSTOM".to_string(), value: "Calm".to_string() },
+        ], false).await.unwrap();
+
+        let tagged = lofty::read_from_path(&mp3).unwrap();
+        let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
+        assert_eq!(tag.get_string(&ItemKey::InitialKey), Some("Dm"));
+        assert_eq!(tag.get_string(&ItemKey::CopyrightMessage), None);
+        assert_eq!(tag.get_string(&ItemKey::Unknown("MOOD_CUSTOM".into())), Some("Calm"));
+
+        // The DB mtime matches the rewritten file, and a rebuild from the file
+        // yields the same extra tags
+        let row: (Option<i64>,) = sqlx::query_as("SELECT file_mtime FROM tracks WHERE id = ?")
+            .bind(track_id)
+            .fetch_one(&db)
             .await
             .unwrap();
+        assert_eq!(row.0, read_file_mtime(&mp3.to_string_lossy()));
 
-        let filtered = list_albums_inner(&db, Some(artist_id)).await.unwrap();
-        assert_eq!(filtered.len(), 1);
-        assert_eq!(filtered[0].title, "Album by A");
+        sqlx::query("DELETE FROM track_extra_tags").execute(&db).await.unwrap();
+        process_track(&db, col.id, &mp3, None).await.unwrap();
+        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
+        let pairs: Vec<(&str, &str)> = fetched.iter().map(|t| (t.frame_id.as_str(), t.value.as_str())).collect();
+        assert_eq!(pairs, vec![("MOOD_CUSTOM", "Calm"), ("TKEY", "Dm")]);
     }
 
-    // ── Artist Row / Album Row Tests ──
-
     #[tokio::test]
-    async fn test_list_artist_rows_empty() {
+    async fn test_custom_extra_tag_is_a_txxx_frame_in_mp3() {
+        use lofty::file::AudioFile;
+        use lofty::mpeg::MpegFile;
+
         let db = setup_test_db().await;
-        let result = list_artist_rows_inner(&db).await.unwrap();
-        assert!(result.is_empty());
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Song", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let track_id = list_tracks_inner(&db).await.unwrap()[0].id;
+
+        set_track_extra_tags_inner(&db, track_id, vec![
+            ExtraTag { frame_id: "MOOD_CUSTOM".to_string(), value: "Calm".to_string() },
+        ], false).await.unwrap();
+
+        // Other players see a TXXX frame described "MOOD_CUSTOM"
+        let mut file = std::fs::File::open(&mp3).unwrap();
+        let mpeg = MpegFile::read_from(&mut file, lofty::config::ParseOptions::new()).unwrap();
+        let id3v2 = mpeg.id3v2().unwrap();
+        assert_eq!(id3v2.get_user_text("MOOD_CUSTOM"), Some("Calm"));
+
+        // and a rescan reads it back under the same key
+        sqlx::query("DELETE FROM track_extra_tags").execute(&db).await.unwrap();
+        process_track(&db, col.id, &mp3, None).await.unwrap();
+        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
+        let pairs: Vec<(&str, &str)> = fetched.iter().map(|t| (t.frame_id.as_str(), t.value.as_str())).collect();
+        assert_eq!(pairs, vec![("MOOD_CUSTOM", "Calm")]);
     }
 
     #[tokio::test]
-    async fn test_list_artist_rows_aggregates() {
+    async fn test_extra_tags_deleted_with_track() {
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
+        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
+        let track_id = insert_bare_track(&db, col.id, "Song", "/music/extra3.mp3").await;
 
-        // Create artist
-        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Artist A")
-            .bind(&now)
-            .execute(&db)
-            .await
-            .unwrap();
-        let artist_id = res.last_insert_rowid();
+        set_track_extra_tags_inner(&db, track_id, vec![
+            ExtraTag { frame_id: "TKEY".to_string(), value: "G".to_string() },
+        ], true).await.unwrap();
 
-        // Create 2 albums for this artist
-        let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
-            .bind("Album 1")
-            .bind(artist_id)
-            .bind(&now)
+        // Delete the track
+        sqlx::query("DELETE FROM tracks WHERE id = ?")
+            .bind(track_id)
             .execute(&db)
             .await
             .unwrap();
-        let album1_id = res.last_insert_rowid();
 
-        let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
-            .bind("Album 2")
-            .bind(artist_id)
-            .bind(&now)
-            .execute(&db)
-            .await
-            .unwrap();
-        let _album2_id = res.last_insert_rowid();
+        // Extra tags should be cascaded away
+        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
+        assert!(fetched.is_empty(), "extra tags should be deleted when track is deleted");
+    }
 
-        // Create collection for tracks
-        let col = add_collection_inner(
-            &db,
-            CollectionInput {
-                path: abs_test_path(""),
-                label: None,
-            },
-            true,
-        )
-        .await
-        .unwrap();
+    // ── File mtime / DB-as-cache hardening tests ──
 
-        // Insert 3 tracks for this artist (2 in album1, 1 with no album)
-        for (i, album_id) in [(1, Some(album1_id)), (2, Some(album1_id)), (3, None)] {
-            sqlx::query(
-                "INSERT INTO tracks (collection_id, album_id, artist_id, title, file_path, file_size_bytes, duration_secs, created_at, updated_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            )
-            .bind(col.id)
-            .bind(album_id)
-            .bind(artist_id)
-            .bind(format!("Track {}", i))
-            .bind(format!("/music/track{}.mp3", i))
-            .bind(1000i64)
-            .bind(120.0)
-            .bind(&now)
-            .bind(&now)
-            .execute(&db)
-            .await
-            .unwrap();
-        }
+    #[tokio::test]
+    async fn test_batch_update_skips_db_on_file_write_failure() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
 
-        let rows = list_artist_rows_inner(&db).await.unwrap();
-        assert_eq!(rows.len(), 1);
-        assert_eq!(rows[0].name, "Artist A");
-        assert_eq!(rows[0].album_count, 2);
-        assert_eq!(rows[0].track_count, 3);
-        assert_eq!(rows[0].total_duration_secs, 360.0);
+        let _mp3a = make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist A", "Album A");
+        let _mp3b = make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist B", "Album B");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 2);
+
+        // Identify the two tracks and delete one file so its write will fail
+        let (gone_id, live_id) = if tracks[0].file_path.ends_with("a.mp3") {
+            (tracks[0].id, tracks[1].id)
+        } else {
+            (tracks[1].id, tracks[0].id)
+        };
+        let gone_path = get_track_inner(&db, gone_id).await.unwrap().file_path;
+        std::fs::remove_file(&gone_path).unwrap();
+
+        batch_update_tracks_inner(&db, vec![gone_id, live_id], TrackUpdateInput {
+            title: Some("New Title".into()),
+            ..Default::default()
+        }, false).await.unwrap();
+
+        let gone_after = get_track_inner(&db, gone_id).await.unwrap();
+        let live_after = get_track_inner(&db, live_id).await.unwrap();
+
+        // File write for "gone" failed → DB must NOT be updated
+        assert_ne!(gone_after.title, "New Title",
+            "DB should not be updated when file write fails");
+        // File write for "live" succeeded → DB must be updated
+        assert_eq!(live_after.title, "New Title",
+            "DB should be updated when file write succeeds");
+    }
+
+    #[tokio::test]
+    async fn test_scan_stores_file_mtime() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Title", "Artist", "Album");
+        let expected_mtime = std::fs::metadata(&mp3)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        let stored = tracks[0].file_mtime.expect("file_mtime should be stored after scan");
+        assert_eq!(stored, expected_mtime, "stored mtime should match file mtime");
     }
 
     #[tokio::test]
-    async fn test_list_album_rows_empty() {
+    async fn test_scan_skips_leftover_temp_files() {
         let db = setup_test_db().await;
-        let result = list_album_rows_inner(&db).await.unwrap();
-        assert!(result.is_empty());
+        let tmp = tempfile::tempdir().unwrap();
+        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Title", "Artist", "Album");
+        // What a tag write killed before its rename leaves behind
+        let leftover = tmp.path().join(".chant-a1B2c3.mp3");
+        std::fs::copy(&mp3, &leftover).unwrap();
+
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert!(tracks[0].file_path.ends_with("/song.mp3"));
+
+        // Nor does the watcher pick it up
+        let changes = sync_changed_paths_inner(&db, col.id, &[leftover], None).await.unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(list_tracks_inner(&db).await.unwrap().len(), 1);
     }
 
     #[tokio::test]
-    async fn test_list_album_rows_aggregates() {
+    async fn test_scan_stores_audio_properties() {
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
+        let tmp = tempfile::tempdir().unwrap();
+        make_tagged_mp3(tmp.path(), "song.mp3", "Title", "Artist", "Album");
 
-        // Create artist
-        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Artist B")
-            .bind(&now)
-            .execute(&db)
-            .await
-            .unwrap();
-        let artist_id = res.last_insert_rowid();
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let track = &list_tracks_inner(&db).await.unwrap()[0];
+        assert_eq!(track.codec.as_deref(), Some("MP3"));
+        assert_eq!(track.codec_profile.as_deref(), Some("CBR"));
+        assert_eq!(track.sample_rate_hz, Some(44100));
+        assert_eq!(track.channels, Some(2));
+        assert_eq!(track.bitrate_kbps, Some(128));
+        assert!(track.overall_bitrate_kbps.is_some());
+        assert_eq!(track.bit_depth, None);
+
+        let albums = list_album_rows_inner(&db).await.unwrap();
+        assert_eq!(albums[0].codecs.as_deref(), Some("MP3"));
+        assert_eq!(albums[0].max_sample_rate_hz, Some(44100));
+    }
 
-        // Create album with year and genre
-        let res = sqlx::query(
-            "INSERT INTO albums (title, artist_id, year, genre, created_at) VALUES (?, ?, ?, ?, ?)",
-        )
-        .bind("Album X")
-        .bind(artist_id)
-        .bind(2020)
-        .bind("Rock")
-        .bind(&now)
-        .execute(&db)
-        .await
-        .unwrap();
-        let album_id = res.last_insert_rowid();
+    #[tokio::test]
+    async fn test_analyze_loudness_stores_album_and_writes_tags() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+        let a = make_tagged_mp3(tmp.path(), "a.mp3", "One", "Artist", "Album");
+        make_tagged_mp3(tmp.path(), "b.mp3", "Two", "Artist", "Album");
 
-        // Create collection
-        let col = add_collection_inner(
-            &db,
-            CollectionInput {
-                path: abs_test_path(""),
-                label: None,
-            },
-            true,
-        )
-        .await
-        .unwrap();
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-        // Insert 2 tracks in this album
-        for i in 1..=2 {
-            sqlx::query(
-                "INSERT INTO tracks (collection_id, album_id, artist_id, title, file_path, file_size_bytes, duration_secs, created_at, updated_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-            )
-            .bind(col.id)
-            .bind(album_id)
-            .bind(artist_id)
-            .bind(format!("Track {}", i))
-            .bind(format!("/music/track{}.mp3", i))
-            .bind(5000i64)
-            .bind(200.5)
-            .bind(&now)
-            .bind(&now)
-            .execute(&db)
+        let options = LoudnessOptions { write_tags: true, ..Default::default() };
+        let last = Mutex::new(None);
+        let summary = analyze_loudness_inner(&db, &options, Default::default(), |p| *last.lock().unwrap() = Some(p))
             .await
             .unwrap();
-        }
+        assert!(summary.failed.is_empty(), "{:?}", summary.failed);
+        assert_eq!((summary.analyzed, summary.albums, summary.tags_written), (2, 1, 2));
+        let last = last.into_inner().unwrap().unwrap();
+        assert_eq!((last.processed, last.total), (2, 2));
 
-        let rows = list_album_rows_inner(&db).await.unwrap();
-        assert_eq!(rows.len(), 1);
-        assert_eq!(rows[0].title, "Album X");
-        assert_eq!(rows[0].artist_name, Some("Artist B".to_string()));
-        assert_eq!(rows[0].year, Some(2020));
-        assert_eq!(rows[0].genre, Some("Rock".to_string()));
-        assert_eq!(rows[0].track_count, 2);
-        assert_eq!(rows[0].total_duration_secs, 401.0);
-        assert_eq!(rows[0].total_size_bytes, 10000);
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert!(tracks.iter().all(|t| t.loudness_lufs.is_some() && t.album_loudness_lufs.is_some()));
+
+        // The gain tags land in the file and in the extra tag mirror
+        let tagged = lofty::read_from_path(&a).unwrap();
+        let gain = tagged.primary_tag().unwrap().get_string(&ItemKey::ReplayGainTrackGain).map(str::to_string);
+        assert!(gain.as_deref().is_some_and(|g| g.ends_with(" dB")), "{:?}", gain);
+        let track_id = tracks.iter().find(|t| t.title == "One").unwrap().id;
+        let extra = get_track_extra_tags_inner(&db, track_id).await.unwrap();
+        assert!(extra.iter().any(|t| t.frame_id == "REPLAYGAIN_ALBUM_PEAK"));
+
+        // Nothing is stale any more, and tag writes don't make it stale
+        let again = analyze_loudness_inner(&db, &options, Default::default(), |_| {}).await.unwrap();
+        assert_eq!(again.analyzed, 0);
     }
 
-    // ── Library Stats Tests ──
-
     #[tokio::test]
-    async fn test_library_stats_empty() {
+    async fn test_analyze_loudness_writes_r128_tags_for_opus() {
         let db = setup_test_db().await;
-        let stats = get_library_stats_inner(&db).await.unwrap();
-        assert_eq!(stats.total_collections, 0);
-        assert_eq!(stats.total_artists, 0);
-        assert_eq!(stats.total_albums, 0);
-        assert_eq!(stats.total_tracks, 0);
-        assert_eq!(stats.total_size_bytes, 0);
-        assert_eq!(stats.total_duration_secs, 0.0);
+        let tmp = tempfile::tempdir().unwrap();
+        make_tagged_mp3(tmp.path(), "a.mp3", "One", "Artist", "Album");
+        let sine = (0..48_000 * 2).map(|i| 0.5 * (2.0 * std::f64::consts::PI * 997.0 * i as f64 / 48_000.0).sin());
+        crate::decode::write_test_opus(&tmp.path().join("b.opus"), 2, sine);
+
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let options = LoudnessOptions { write_tags: true, ..Default::default() };
+        let summary = analyze_loudness_inner(&db, &options, Default::default(), |_| {}).await.unwrap();
+        assert!(summary.failed.is_empty(), "{:?}", summary.failed);
+        assert_eq!((summary.analyzed, summary.tags_written), (2, 2));
+
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let opus = tracks.iter().find(|t| t.file_path.ends_with("b.opus")).unwrap();
+        assert!(opus.loudness_lufs.is_some_and(|l| (l + 6.0).abs() < 1.0), "{:?}", opus.loudness_lufs);
+        let extra = get_track_extra_tags_inner(&db, opus.id).await.unwrap();
+        let gain = extra.iter().find(|t| t.frame_id == "R128_TRACK_GAIN").map(|t| t.value.clone());
+        // -23 - (-6) LUFS is -17 dB, about -4352 in Q7.8
+        assert!(gain.as_deref().and_then(|g| g.parse::<i32>().ok()).is_some_and(|g| (g + 4352).abs() < 256), "{:?}", gain);
+        assert!(!extra.iter().any(|t| t.frame_id.starts_with("REPLAYGAIN_")), "{:?}", extra);
     }
 
+    // ── Fingerprint Tests ──
+
     #[tokio::test]
-    async fn test_library_stats_after_inserts() {
+    async fn test_fingerprint_tracks_and_find_similar_audio() {
+        use crate::decode::write_test_melody;
+        use crate::models::FingerprintOptions;
+
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
+        let tmp = tempfile::tempdir().unwrap();
+        write_test_melody(&tmp.path().join("a.wav"), 1, 44_100, 0.6);
+        write_test_melody(&tmp.path().join("a (other rip).wav"), 1, 48_000, 0.3);
+        write_test_melody(&tmp.path().join("b.wav"), 2, 44_100, 0.6);
+        make_tagged_mp3(tmp.path(), "short.mp3", "Short", "Artist", "Album");
 
-        let col = add_collection_inner(&db, CollectionInput {
-            path: abs_test_path(""),
-            label: None,
-        }, true).await.unwrap();
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let id_of = |name: &str| tracks.iter().find(|t| t.file_path.ends_with(name)).unwrap().id;
 
-        sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Artist")
-            .bind(&now)
-            .execute(&db)
+        let summary = fingerprint_tracks_inner(&db, &FingerprintOptions::default(), Default::default(), |_| {})
             .await
             .unwrap();
-
-        sqlx::query("INSERT INTO albums (title, created_at) VALUES (?, ?)")
-            .bind("Album")
-            .bind(&now)
-            .execute(&db)
+        assert_eq!(summary.fingerprinted, 3);
+        // A few MP3 frames are too short to fingerprint
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].track_id, id_of("short.mp3"));
+
+        let groups = find_similar_audio_inner(&db, None, None, None).await.unwrap();
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<i64> = groups[0].tracks.iter().map(|t| t.id).collect();
+        let mut expected = vec![id_of("/a.wav"), id_of("a (other rip).wav")];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert!(groups[0].matches[0].similarity >= 0.5);
+
+        assert!(find_similar_audio_inner(&db, Some(id_of("b.wav")), None, None).await.unwrap().is_empty());
+        let err = find_similar_audio_inner(&db, Some(id_of("short.mp3")), None, None).await.unwrap_err();
+        assert!(matches!(err, AppError::InvalidInput(_)));
+
+        let acoustid = get_acoustid_fingerprint_inner(&db, id_of("/a.wav")).await.unwrap().unwrap();
+        assert_eq!(acoustid.duration_secs, 15);
+        assert!(acoustid.fingerprint.starts_with("AQ"), "{}", acoustid.fingerprint);
+
+        // Unchanged audio is not fingerprinted again
+        let again = fingerprint_tracks_inner(&db, &FingerprintOptions::default(), Default::default(), |_| {})
             .await
             .unwrap();
+        assert_eq!(again.fingerprinted, 0);
+    }
 
-        sqlx::query(
-            "INSERT INTO tracks (collection_id, title, file_path, file_size_bytes, duration_secs, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?)"
-        )
-        .bind(col.id)
-        .bind("Track")
-        .bind("/music/track.mp3")
-        .bind(5000i64)
-        .bind(180.5)
-        .bind(&now)
-        .bind(&now)
-        .execute(&db)
-        .await
-        .unwrap();
+    #[tokio::test]
+    async fn test_update_track_updates_mtime() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
 
-        let stats = get_library_stats_inner(&db).await.unwrap();
-        assert_eq!(stats.total_collections, 1);
-        assert_eq!(stats.total_artists, 1);
-        assert_eq!(stats.total_albums, 1);
-        assert_eq!(stats.total_tracks, 1);
-        assert_eq!(stats.total_size_bytes, 5000);
-        assert_eq!(stats.total_duration_secs, 180.5);
-    }
+        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Original", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-    // ── Scan Test ──
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let track_id = tracks[0].id;
 
-    #[tokio::test]
-    async fn test_scan_collection_with_fixture() {
-        use lofty::config::WriteOptions;
-        use lofty::picture::{Picture, PictureType, MimeType};
-        use lofty::tag::{Tag, TagType, Accessor};
-        use std::io::Write;
+        update_track_inner(&db, track_id, TrackUpdateInput {
+            title: Some("Updated".into()),
+            ..Default::default()
+        }, false).await.unwrap();
+
+        let after = get_track_inner(&db, track_id).await.unwrap();
+        let db_mtime = after.file_mtime.expect("file_mtime should be set after update");
+        let actual_mtime = std::fs::metadata(&mp3)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        assert_eq!(db_mtime, actual_mtime,
+            "DB mtime should match actual file mtime after update");
+    }
 
+    #[tokio::test]
+    async fn test_stale_track_ids_detects_external_edit() {
         let db = setup_test_db().await;
-        let tmp_dir = tempfile::tempdir().unwrap();
+        let tmp = tempfile::tempdir().unwrap();
 
-        // Create a minimal MP3 fixture: multiple valid MPEG1 Layer 3 frames
-        // so lofty recognizes it as a valid file
-        let mp3_path = tmp_dir.path().join("test.mp3");
-        {
-            let mut file = std::fs::File::create(&mp3_path).unwrap();
-            // MPEG1, Layer 3, 128kbps, 44100Hz, stereo = frame size 417 bytes
-            // Header: 0xFF 0xFB 0x90 0x64
-            let mut frame = [0u8; 417];
-            frame[0] = 0xFF;
-            frame[1] = 0xFB;
-            frame[2] = 0x90;
-            frame[3] = 0x64;
-            // Write 3 frames so lofty sees enough valid data
-            for _ in 0..3 {
-                file.write_all(&frame).unwrap();
-            }
-        }
+        let _mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Title", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-        // Minimal 1x1 PNG (67 bytes)
-        let png_bytes: Vec<u8> = vec![
-            0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, // PNG signature
-            0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, // IHDR chunk
-            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, // 1x1
-            0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53, 0xDE,
-            0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, // IDAT chunk
-            0x08, 0xD7, 0x63, 0xF8, 0xCF, 0xC0, 0x00, 0x00,
-            0x00, 0x02, 0x00, 0x01, 0xE2, 0x21, 0xBC, 0x33,
-            0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, // IEND chunk
-            0xAE, 0x42, 0x60, 0x82,
-        ];
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let track_id = tracks[0].id;
 
-        // Write ID3v2 tags using lofty (including cover art)
-        {
-            let mut tagged_file = lofty::read_from_path(&mp3_path).unwrap();
-            tagged_file.insert_tag(Tag::new(TagType::Id3v2));
-            let tag = tagged_file.tag_mut(TagType::Id3v2).unwrap();
-            tag.set_title("Test Track".to_string());
-            tag.set_artist("Test Artist".to_string());
-            tag.set_album("Test Album".to_string());
-            tag.set_track(1);
-            tag.push_picture(Picture::new_unchecked(
-                PictureType::CoverFront,
-                Some(MimeType::Png),
-                None,
-                png_bytes.clone(),
-            ));
-            tagged_file.save_to_path(&mp3_path, WriteOptions::default()).unwrap();
-        }
+        // No stale tracks right after scan
+        let stale = stale_track_ids_inner(&db).await.unwrap();
+        assert!(!stale.contains(&track_id), "track should not be stale right after scan");
 
-        // Add the temp dir as a collection (skip fs checks since it exists)
-        let col_path = tmp_dir.path().to_string_lossy().replace('\\', "/");
-        let col = add_collection_inner(&db, CollectionInput {
-            path: col_path,
-            label: Some("Test Collection".to_string()),
-        }, true).await.unwrap();
+        // Simulate external edit by setting DB mtime 1 second in the past
+        sqlx::query("UPDATE tracks SET file_mtime = file_mtime - 1 WHERE id = ?")
+            .bind(track_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let stale = stale_track_ids_inner(&db).await.unwrap();
+        assert!(stale.contains(&track_id),
+            "track should be detected as stale when file is newer than DB mtime");
+    }
 
-        // Set up a covers directory inside tmp
-        let covers_dir = tmp_dir.path().join("covers");
+    // ── Incremental Rescan Tests ──
 
-        // Run scan with covers_dir
-        scan_collection_inner(&db, col.id, Some(&covers_dir), |_| {}).await.unwrap();
+    #[tokio::test]
+    async fn test_rescan_skips_unchanged_files() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
 
-        // Verify results
-        let tracks = list_tracks_inner(&db).await.unwrap();
-        assert_eq!(tracks.len(), 1);
-        assert_eq!(tracks[0].title, "Test Track");
-        assert_eq!(tracks[0].artist_name, Some("Test Artist".to_string()));
-        assert_eq!(tracks[0].album_title, Some("Test Album".to_string()));
-        assert_eq!(tracks[0].track_number, Some(1));
+        make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist", "Album");
+        make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
 
-        let artists = list_artists_inner(&db).await.unwrap();
-        assert_eq!(artists.len(), 1);
-        assert_eq!(artists[0].name, "Test Artist");
+        let first = scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        assert_eq!((first.added, first.updated, first.unchanged, first.removed), (2, 0, 0, 0));
 
-        let albums = list_albums_inner(&db, None).await.unwrap();
-        assert_eq!(albums.len(), 1);
-        assert_eq!(albums[0].title, "Test Album");
+        // Edit a DB-only value — an unchanged file must not be re-read over it
+        let track_id = list_tracks_inner(&db).await.unwrap()[0].id;
+        sqlx::query("UPDATE tracks SET title = 'DB Only' WHERE id = ?")
+            .bind(track_id)
+            .execute(&db)
+            .await
+            .unwrap();
 
-        // Verify cover art was extracted
-        assert!(albums[0].cover_path.is_some(), "Album should have cover_path set");
-        let cover_path = PathBuf::from(albums[0].cover_path.as_ref().unwrap().replace('/', std::path::MAIN_SEPARATOR_STR));
-        assert!(cover_path.exists(), "Cover file should exist on disk at {:?}", cover_path);
-        let saved_bytes = std::fs::read(&cover_path).unwrap();
-        assert_eq!(saved_bytes, png_bytes, "Saved cover should match embedded PNG");
+        let second = scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        assert_eq!((second.added, second.updated, second.unchanged, second.removed), (0, 0, 2, 0));
+        assert_eq!(get_track_inner(&db, track_id).await.unwrap().title, "DB Only");
     }
 
-    // ── Debug Query Tests ──
-
     #[tokio::test]
-    async fn test_debug_query_table_rejects_invalid() {
+    async fn test_rescan_rereads_changed_files() {
         let db = setup_test_db().await;
-        let result = debug_query_table_inner(&db, "users").await;
-        assert!(result.is_err());
-        match result.unwrap_err() {
-            AppError::InvalidInput(msg) => assert!(msg.contains("users")),
-            other => panic!("Expected InvalidInput, got {:?}", other),
-        }
+        let tmp = tempfile::tempdir().unwrap();
+
+        make_tagged_mp3(tmp.path(), "song.mp3", "Title", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        // Stored mtime older than the file → treated as modified and re-read
+        sqlx::query("UPDATE tracks SET title = 'Stale', file_mtime = file_mtime - 1")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let summary = scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        assert_eq!((summary.added, summary.updated, summary.unchanged), (0, 1, 0));
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks[0].title, "Title");
     }
 
     #[tokio::test]
-    async fn test_debug_query_table_allowed() {
+    async fn test_rescan_prunes_deleted_files() {
         let db = setup_test_db().await;
-        let result = debug_query_table_inner(&db, "collections").await.unwrap();
-        assert!(result.is_empty());
+        let tmp = tempfile::tempdir().unwrap();
+
+        make_tagged_mp3(tmp.path(), "keep.mp3", "Keep", "Artist", "Album");
+        let gone = make_tagged_mp3(tmp.path(), "gone.mp3", "Gone", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        std::fs::remove_file(&gone).unwrap();
+        let summary = scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        assert_eq!(summary.removed, 1);
+        assert_eq!(summary.unchanged, 1);
+
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].title, "Keep");
     }
 
     #[tokio::test]
-    async fn test_clear_all_data() {
+    async fn test_rescan_follows_moved_file() {
         let db = setup_test_db().await;
-        let now = Utc::now().to_rfc3339();
+        let tmp = tempfile::tempdir().unwrap();
 
-        // Insert artist, album, collection, track
-        let res = sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)")
-            .bind("Artist X")
-            .bind(&now)
+        let old = make_tagged_mp3(tmp.path(), "old name.mp3", "Song", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        let before = list_tracks_inner(&db).await.unwrap().remove(0);
+        assert!(before.audio_hash.is_some());
+        // A DB-only field, lost if the file were taken for a new track
+        sqlx::query("UPDATE tracks SET comment_lang = 'eng' WHERE id = ?")
+            .bind(before.id)
             .execute(&db)
             .await
             .unwrap();
-        let artist_id = res.last_insert_rowid();
 
-        let res = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES (?, ?, ?)")
-            .bind("Album X")
-            .bind(artist_id)
-            .bind(&now)
-            .execute(&db)
-            .await
-            .unwrap();
-        let album_id = res.last_insert_rowid();
+        // Renamed into a subfolder and retagged: same audio, different bytes
+        let dir = tmp.path().join("Artist");
+        std::fs::create_dir(&dir).unwrap();
+        let new = dir.join("01 Song.mp3");
+        std::fs::rename(&old, &new).unwrap();
+        edit_file_tag(&new.to_string_lossy(), |tag| tag.set_title("Song (2024 Remaster)".to_string())).unwrap();
 
-        let res = sqlx::query(
-            "INSERT INTO collections (path, label, created_at) VALUES (?, ?, ?)",
-        )
-        .bind("/music")
-        .bind("Test")
-        .bind(&now)
-        .execute(&db)
-        .await
-        .unwrap();
-        let collection_id = res.last_insert_rowid();
+        let summary = scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        assert_eq!((summary.added, summary.moved, summary.removed), (0, 1, 0));
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].id, before.id);
+        assert_eq!(tracks[0].file_path, new.to_string_lossy().replace('\\', "/"));
+        assert_eq!(tracks[0].audio_hash, before.audio_hash);
+        assert_eq!(tracks[0].comment_lang.as_deref(), Some("eng"));
+        assert!(verify_audio_integrity_inner(&db, None).await.unwrap().is_empty());
+
+        // Damaged audio is reported even though the tags still read fine
+        let mut bytes = std::fs::read(&new).unwrap();
+        let last = bytes.len() - 10;
+        bytes[last] ^= 0xFF;
+        std::fs::write(&new, bytes).unwrap();
+        assert_eq!(verify_audio_integrity_inner(&db, Some(col.id)).await.unwrap(), [before.id]);
+    }
 
-        sqlx::query(
-            "INSERT INTO tracks (collection_id, album_id, artist_id, title, file_path, file_size_bytes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
-        )
-        .bind(collection_id)
-        .bind(album_id)
-        .bind(artist_id)
-        .bind("Track X")
-        .bind("/music/track.mp3")
-        .bind(1000i64)
-        .bind(&now)
-        .bind(&now)
-        .execute(&db)
-        .await
-        .unwrap();
+    #[tokio::test]
+    async fn test_rescan_leaves_tracks_of_unreachable_collection() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+        let (drive, local) = (tmp.path().join("drive"), tmp.path().join("local"));
+        std::fs::create_dir(&drive).unwrap();
+        std::fs::create_dir(&local).unwrap();
+
+        let on_drive = make_tagged_mp3(&drive, "song.mp3", "Song", "Artist", "Album");
+        let add = |dir: &std::path::Path| CollectionInput { path: dir.to_string_lossy().replace('\\', "/"), label: None };
+        let drive_col = add_collection_inner(&db, add(&drive), true).await.unwrap();
+        let local_col = add_collection_inner(&db, add(&local), true).await.unwrap();
+        scan_collection_inner(&db, drive_col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        // The drive is unplugged while a copy of the song sits in the other collection
+        std::fs::copy(&on_drive, local.join("song.mp3")).unwrap();
+        std::fs::rename(&drive, tmp.path().join("unplugged")).unwrap();
+        let summary = scan_collection_inner(&db, local_col.id, None, Default::default(), |_| {}).await.unwrap();
+        assert_eq!((summary.added, summary.moved), (1, 0));
+        let paths: Vec<String> = list_tracks_inner(&db).await.unwrap().into_iter().map(|t| t.file_path).collect();
+        assert!(paths.contains(&on_drive.to_string_lossy().replace('\\', "/")));
+    }
 
-        // Clear without a covers dir (no filesystem side-effect needed)
-        clear_all_data_inner(&db, None).await.unwrap();
+    #[tokio::test]
+    async fn test_scan_reads_nested_files_in_parallel() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+
+        for disc in 1..=3 {
+            let dir = tmp.path().join(format!("Disc {}", disc));
+            std::fs::create_dir_all(&dir).unwrap();
+            for n in 1..=8 {
+                make_tagged_mp3(&dir, &format!("{:02}.mp3", n), &format!("Song {}-{}", disc, n), "Artist", "Album");
+            }
+        }
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+
+        let progress = std::cell::RefCell::new(Vec::new());
+        let summary = scan_collection_inner(&db, col.id, None, Default::default(), |n| progress.borrow_mut().push(n)).await.unwrap();
+        assert_eq!(summary.added, 24);
+
+        // One progress event per file, counting up without gaps
+        let progress = progress.into_inner();
+        assert_eq!(progress, (1..=24).collect::<Vec<u32>>());
 
-        let artists = list_artists_inner(&db).await.unwrap();
-        let albums = list_albums_inner(&db, None).await.unwrap();
-        let collections = list_collections_inner(&db).await.unwrap();
         let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 24);
+        // All tracks share one artist and one album row
+        assert_eq!(list_artists_inner(&db).await.unwrap().len(), 1);
+        assert_eq!(list_albums_inner(&db, None).await.unwrap().len(), 1);
+    }
 
-        assert!(artists.is_empty(), "artists should be empty after clear");
-        assert!(albums.is_empty(), "albums should be empty after clear");
-        assert!(collections.is_empty(), "collections should be empty after clear");
-        assert!(tracks.is_empty(), "tracks should be empty after clear");
+    #[tokio::test]
+    async fn test_cancelled_scan_stops_and_keeps_missing_rows() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+
+        make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist", "Album");
+        let gone = make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist", "Album");
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        std::fs::remove_file(&gone).unwrap();
+        make_tagged_mp3(tmp.path(), "c.mp3", "Track C", "Artist", "Album");
+
+        let control = Arc::new(JobControl::default());
+        control.cancel();
+        let summary = scan_collection_inner(&db, col.id, None, control, |_| {}).await.unwrap();
+
+        assert!(summary.cancelled);
+        assert_eq!((summary.added, summary.removed), (0, 0));
+        // The partial walk must not prune b.mp3 or pick up c.mp3
+        let titles: Vec<String> = list_tracks_inner(&db).await.unwrap().into_iter().map(|t| t.title).collect();
+        assert_eq!(titles, vec!["Track A".to_string(), "Track B".to_string()]);
     }
 
-    // ── New Field Tests ──
+    // ── Live Sync Tests ──
 
     #[tokio::test]
-    async fn test_update_track_new_fields() {
+    async fn test_set_collection_watch_persists() {
         let db = setup_test_db().await;
         let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/new_fields.mp3").await;
+        assert!(!col.watch, "watch mode is off by default");
 
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            genre: Some("Jazz".to_string()),
-            bpm: Some(120),
-            year: Some(2023),
-            composer: Some("Bach".to_string()),
-            ..Default::default()
-        }, true).await.unwrap();
+        let updated = set_collection_watch_inner(&db, col.id, true).await.unwrap();
+        assert!(updated.watch);
+        assert!(list_collections_inner(&db).await.unwrap()[0].watch);
 
-        assert_eq!(updated.genre, Some("Jazz".to_string()));
-        assert_eq!(updated.bpm, Some(120));
-        assert_eq!(updated.year, Some(2023));
-        assert_eq!(updated.composer, Some("Bach".to_string()));
+        assert!(matches!(
+            set_collection_watch_inner(&db, 999, true).await,
+            Err(AppError::NotFound(_))
+        ));
     }
 
     #[tokio::test]
-    async fn test_update_track_keeps_existing_new_fields() {
+    async fn test_sync_changed_paths_upserts_and_removes() {
         let db = setup_test_db().await;
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/keep_fields.mp3").await;
+        let tmp = tempfile::tempdir().unwrap();
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
 
-        // First set genre and bpm
-        update_track_inner(&db, track_id, TrackUpdateInput {
-            genre: Some("Rock".to_string()),
-            bpm: Some(140),
-            ..Default::default()
-        }, true).await.unwrap();
+        // A new file shows up
+        let a = make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist", "Album");
+        let changes = sync_changed_paths_inner(&db, col.id, std::slice::from_ref(&a), None).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(changes[0].kind, LibraryChangeKind::Upserted));
+        assert_eq!(get_track_inner(&db, changes[0].track_id).await.unwrap().title, "Track A");
+
+        // Re-notifying an unchanged file is a no-op
+        let changes = sync_changed_paths_inner(&db, col.id, std::slice::from_ref(&a), None).await.unwrap();
+        assert!(changes.is_empty());
+
+        // Non-audio files are ignored
+        let txt = tmp.path().join("notes.txt");
+        std::fs::write(&txt, "hi").unwrap();
+        assert!(sync_changed_paths_inner(&db, col.id, &[txt], None).await.unwrap().is_empty());
+
+        // The file is deleted
+        std::fs::remove_file(&a).unwrap();
+        let changes = sync_changed_paths_inner(&db, col.id, &[a], None).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(changes[0].kind, LibraryChangeKind::Removed));
+        assert!(list_tracks_inner(&db).await.unwrap().is_empty());
+    }
 
-        // Update only title — genre and bpm must be unchanged
-        let updated = update_track_inner(&db, track_id, TrackUpdateInput {
-            title: Some("New Title".to_string()),
-            ..Default::default()
-        }, true).await.unwrap();
+    #[tokio::test]
+    async fn test_sync_rename_keeps_track_identity() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
 
-        assert_eq!(updated.title, "New Title");
-        assert_eq!(updated.genre, Some("Rock".to_string()));
-        assert_eq!(updated.bpm, Some(140));
+        let old = make_tagged_mp3(tmp.path(), "track.mp3", "Song", "Artist", "Album");
+        let other = make_tagged_mp3(tmp.path(), "other.mp3", "Other", "Artist", "Album");
+        sync_changed_paths_inner(&db, col.id, &[old.clone(), other.clone()], None).await.unwrap();
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let id_of = |title: &str| tracks.iter().find(|t| t.title == title).unwrap().id;
+        let (song, other_id) = (id_of("Song"), id_of("Other"));
+        // Rows from before audio hashes were stored are matched by size, duration and tags
+        sqlx::query("UPDATE tracks SET audio_hash = NULL, lyrics_lang = 'eng'")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        // The watcher reports the old path first, as most platforms do
+        let new = tmp.path().join("renamed.mp3");
+        std::fs::rename(&old, &new).unwrap();
+        let changes = sync_changed_paths_inner(&db, col.id, &[old, new.clone()], None).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(changes[0].kind, LibraryChangeKind::Upserted));
+        let renamed = get_track_inner(&db, song).await.unwrap();
+        assert_eq!(renamed.file_path, new.to_string_lossy().replace('\\', "/"));
+        assert_eq!(renamed.lyrics_lang.as_deref(), Some("eng"));
+
+        // Different tags: the new file is a new track and the old row goes
+        edit_file_tag(&other.to_string_lossy(), |tag| tag.set_title("Changed".to_string())).unwrap();
+        let moved_other = tmp.path().join("moved other.mp3");
+        std::fs::rename(&other, &moved_other).unwrap();
+        sync_changed_paths_inner(&db, col.id, &[other, moved_other], None).await.unwrap();
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert!(tracks.iter().all(|t| t.id != other_id));
     }
 
     #[tokio::test]
-    async fn test_batch_update_tracks_changes_all() {
+    async fn test_sync_changed_paths_handles_directories() {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+
+        // An album folder copied in at once
+        let album_dir = tmp.path().join("Album");
+        std::fs::create_dir_all(&album_dir).unwrap();
+        make_tagged_mp3(&album_dir, "01.mp3", "One", "Artist", "Album");
+        make_tagged_mp3(&album_dir, "02.mp3", "Two", "Artist", "Album");
+        let sibling = make_tagged_mp3(tmp.path(), "Album2.mp3", "Sibling", "Artist", "Other");
+        sync_changed_paths_inner(&db, col.id, &[album_dir.clone(), sibling], None).await.unwrap();
+        assert_eq!(list_tracks_inner(&db).await.unwrap().len(), 3);
+
+        // Removing the folder drops its tracks but not a file that merely shares the prefix
+        std::fs::remove_dir_all(&album_dir).unwrap();
+        let changes = sync_changed_paths_inner(&db, col.id, &[album_dir], None).await.unwrap();
+        assert_eq!(changes.len(), 2);
+        let remaining = list_tracks_inner(&db).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].title, "Sibling");
+    }
+
+    async fn setup_organize_library() -> (DbPool, tempfile::TempDir, std::path::PathBuf) {
+        let db = setup_test_db().await;
+        let tmp = tempfile::tempdir().unwrap();
+        let music = tmp.path().join("music");
+        let rip = music.join("rip");
+        std::fs::create_dir_all(&rip).unwrap();
+        make_tagged_mp3(&rip, "a.mp3", "One", "Band", "First");
+        make_tagged_mp3(&rip, "b.mp3", "Two", "Band", "First");
+        std::fs::write(rip.join("cover.jpg"), b"jpeg").unwrap();
+
+        let col_path = music.to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        (db, tmp, music)
+    }
+
+    #[tokio::test]
+    async fn test_organize_files_dry_run_then_move() {
+        let (db, _tmp, music) = setup_organize_library().await;
+        let ids: Vec<i64> = list_tracks_inner(&db).await.unwrap().iter().map(|t| t.id).collect();
+        let template = "{artist}/{album}/{title}.{ext}";
+
+        let preview = organize_files_inner(&db, ids.clone(), template, true).await.unwrap();
+        assert_eq!(preview.len(), 3, "two tracks and the cover: {:?}", preview);
+        assert!(preview.iter().all(|m| m.problem.is_none()));
+        assert!(music.join("rip/a.mp3").exists(), "dry run must not move files");
+
+        organize_files_inner(&db, ids, template, false).await.unwrap();
+
+        let album_dir = music.join("Band/First");
+        assert!(album_dir.join("One.mp3").exists());
+        assert!(album_dir.join("Two.mp3").exists());
+        assert!(album_dir.join("cover.jpg").exists());
+        assert!(!music.join("rip").exists(), "emptied folder should be removed");
+        let paths: Vec<String> = list_tracks_inner(&db).await.unwrap().into_iter().map(|t| t.file_path).collect();
+        let expected = album_dir.join("One.mp3").to_string_lossy().replace('\\', "/");
+        assert!(paths.contains(&expected), "{:?}", paths);
+    }
+
+    #[tokio::test]
+    async fn test_organize_files_reports_collisions() {
+        let (db, _tmp, music) = setup_organize_library().await;
+        let ids: Vec<i64> = list_tracks_inner(&db).await.unwrap().iter().map(|t| t.id).collect();
+
+        // Both tracks share artist and album, so they map to the same file
+        let moves = organize_files_inner(&db, ids, "{artist}/{album}.{ext}", false).await.unwrap();
+
+        let track_moves: Vec<_> = moves.iter().filter(|m| m.track_id.is_some()).collect();
+        assert_eq!(track_moves.len(), 2);
+        assert!(track_moves.iter().all(|m| m.problem.is_some()));
+        assert!(music.join("rip/a.mp3").exists());
+        assert!(music.join("rip/b.mp3").exists());
+        assert!(!music.join("Band").exists());
+    }
+
+    /// Five tracks by two artists on two albums, years 2001..=2005.
+    async fn setup_query_library() -> DbPool {
         let db = setup_test_db().await;
         let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let id1 = insert_bare_track(&db, col.id, "Track A", "/music/ba1.mp3").await;
-        let id2 = insert_bare_track(&db, col.id, "Track B", "/music/ba2.mp3").await;
-        let id3 = insert_bare_track(&db, col.id, "Track C", "/music/ba3.mp3").await;
+        let specs = [
+            ("Echo", "Alpha", "Red", 2003),
+            ("alpha song", "Alpha", "Red", 2001),
+            ("Bravo", "Beta", "Blue", 2005),
+            ("Delta", "Beta", "Blue", 2002),
+            ("charlie", "Alpha", "Red", 2004),
+        ];
+        for (i, (title, artist, album, year)) in specs.into_iter().enumerate() {
+            let id = insert_bare_track(&db, col.id, title, &abs_test_path(&format!("/{}.mp3", i))).await;
+            update_track_inner(&db, id, TrackUpdateInput {
+                artist_name: Some(artist.to_string()),
+                album_title: Some(album.to_string()),
+                year: Some(year),
+                ..Default::default()
+            }, true).await.unwrap();
+        }
+        db
+    }
 
-        batch_update_tracks_inner(&db, vec![id1, id2, id3], TrackUpdateInput {
-            title: Some("Batch Title".to_string()),
+    #[tokio::test]
+    async fn test_query_tracks_filters_and_sorts() {
+        use crate::models::{FieldPredicate, FilterValue};
+        let db = setup_query_library().await;
+
+        let filter = LibraryFilter {
+            predicates: vec![
+                FieldPredicate::Equals { field: "artistName".into(), value: FilterValue::Text("alpha".into()) },
+                FieldPredicate::Between { field: "year".into(), min: Some(FilterValue::Number(2002.0)), max: None },
+            ],
             ..Default::default()
-        }, true).await.unwrap();
+        };
+        let sort = SortSpec { field: "year".into(), descending: true };
+        let page = query_tracks_inner(&db, &filter, Some(&sort), &PageRequest::default()).await.unwrap();
+        let titles: Vec<&str> = page.rows.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["charlie", "Echo"]);
+        assert_eq!(page.total, 2);
+        assert!(page.next_cursor.is_none());
+
+        // Default sort is by title, case-insensitively; text matches title, artist or album
+        let filter = LibraryFilter { text: Some("BLUE".into()), ..Default::default() };
+        let page = query_tracks_inner(&db, &filter, None, &PageRequest::default()).await.unwrap();
+        let titles: Vec<&str> = page.rows.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["Bravo", "Delta"]);
+
+        let bad = LibraryFilter {
+            predicates: vec![FieldPredicate::IsNull { field: "password".into() }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            query_tracks_inner(&db, &bad, None, &PageRequest::default()).await,
+            Err(AppError::InvalidInput(_))
+        ));
+    }
 
-        for id in [id1, id2, id3] {
-            let t = get_track_inner(&db, id).await.unwrap();
-            assert_eq!(t.title, "Batch Title", "track {} should have new title", id);
+    #[tokio::test]
+    async fn test_query_tracks_pages_by_cursor_and_offset() {
+        let db = setup_query_library().await;
+        let filter = LibraryFilter::default();
+        let sort = SortSpec { field: "year".into(), descending: false };
+
+        let mut years = Vec::new();
+        let mut cursor = None;
+        loop {
+            let request = PageRequest { limit: Some(2), cursor: cursor.take(), offset: None };
+            let page = query_tracks_inner(&db, &filter, Some(&sort), &request).await.unwrap();
+            assert_eq!(page.total, 5);
+            years.extend(page.rows.iter().map(|t| t.year.unwrap()));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
         }
+        assert_eq!(years, [2001, 2002, 2003, 2004, 2005]);
+
+        let request = PageRequest { limit: Some(2), offset: Some(3), cursor: None };
+        let page = query_tracks_inner(&db, &filter, Some(&sort), &request).await.unwrap();
+        let years: Vec<i32> = page.rows.iter().map(|t| t.year.unwrap()).collect();
+        assert_eq!(years, [2004, 2005]);
     }
 
     #[tokio::test]
-    async fn test_batch_update_tracks_is_atomic() {
-        let db = setup_test_db().await;
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let id1 = insert_bare_track(&db, col.id, "Original A", "/music/atom1.mp3").await;
-        let id2 = insert_bare_track(&db, col.id, "Original B", "/music/atom2.mp3").await;
+    async fn test_query_album_and_artist_rows() {
+        use crate::models::{FieldPredicate, FilterValue};
+        let db = setup_query_library().await;
+
+        let filter = LibraryFilter {
+            predicates: vec![FieldPredicate::Between {
+                field: "trackCount".into(),
+                min: Some(FilterValue::Number(3.0)),
+                max: None,
+            }],
+            ..Default::default()
+        };
+        let albums = query_album_rows_inner(&db, &filter, None, &PageRequest::default()).await.unwrap();
+        assert_eq!(albums.total, 1);
+        assert_eq!(albums.rows[0].title, "Red");
 
-        // Include a non-existent id — should cause rollback
-        let result = batch_update_tracks_inner(
-            &db,
-            vec![id1, 99999, id2],
-            TrackUpdateInput {
-                title: Some("Should Rollback".to_string()),
-                ..Default::default()
-            },
-            true,
-        ).await;
+        let sort = SortSpec { field: "name".into(), descending: true };
+        let artists = query_artist_rows_inner(&db, &LibraryFilter::default(), Some(&sort), &PageRequest::default())
+            .await
+            .unwrap();
+        let names: Vec<&str> = artists.rows.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["Beta", "Alpha"]);
+    }
 
-        assert!(result.is_err(), "batch with invalid id should fail");
+    #[tokio::test]
+    async fn test_search_library_ranks_and_groups_hits() {
+        let db = setup_query_library().await;
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let delta = tracks.iter().find(|t| t.title == "Delta").unwrap().id;
+        update_track_inner(&db, delta, TrackUpdateInput {
+            lyrics: Some("we sail across the crimson sea".into()),
+            ..Default::default()
+        }, true).await.unwrap();
 
-        // Both valid tracks should be unchanged
-        let t1 = get_track_inner(&db, id1).await.unwrap();
-        let t2 = get_track_inner(&db, id2).await.unwrap();
-        assert_eq!(t1.title, "Original A");
-        assert_eq!(t2.title, "Original B");
+        // Prefix match inside lyrics, with the matched word marked
+        let results = search_library_inner(&db, "crims", None).await.unwrap();
+        assert_eq!(results.tracks.len(), 1);
+        assert_eq!(results.tracks[0].track_id, delta);
+        assert!(results.tracks[0].snippet.contains(&SnippetPart { text: "crimson".into(), highlight: true }));
+        assert!(results.albums.is_empty());
+
+        // Artist and album names group into their own hits
+        let results = search_library_inner(&db, "alp", None).await.unwrap();
+        assert_eq!(results.tracks[0].title, "alpha song", "title matches rank first");
+        assert_eq!(results.tracks.len(), 3);
+        let artists: Vec<&str> = results.artists.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(artists, ["Alpha"]);
+        assert_eq!(results.artists[0].highlighted[0], SnippetPart { text: "Alpha".into(), highlight: true });
+
+        let results = search_library_inner(&db, "blue", None).await.unwrap();
+        let albums: Vec<&str> = results.albums.iter().map(|a| a.title.as_str()).collect();
+        assert_eq!(albums, ["Blue"]);
+
+        // FTS syntax in the input is taken literally
+        assert!(search_library_inner(&db, "\"AND (* NEAR", None).await.is_ok());
+        assert!(search_library_inner(&db, "  ", None).await.unwrap().tracks.is_empty());
     }
 
     #[tokio::test]
-    async fn test_set_and_get_extra_tags() {
-        let db = setup_test_db().await;
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/extra1.mp3").await;
+    async fn test_search_index_follows_renames_and_deletes() {
+        let db = setup_query_library().await;
+        let echo = list_tracks_inner(&db).await.unwrap().into_iter().find(|t| t.title == "Echo").unwrap();
 
-        let tags = vec![
-            ExtraTag { frame_id: "TCOP".to_string(), value: "2024 Label".to_string() },
-            ExtraTag { frame_id: "TKEY".to_string(), value: "Am".to_string() },
-        ];
-        set_track_extra_tags_inner(&db, track_id, tags).await.unwrap();
+        sqlx::query("UPDATE artists SET name = 'Omega' WHERE id = ?")
+            .bind(echo.artist_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        assert_eq!(search_library_inner(&db, "omega", None).await.unwrap().tracks.len(), 3);
+        assert!(search_library_inner(&db, "alpha", None).await.unwrap().artists.is_empty());
 
-        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
-        assert_eq!(fetched.len(), 2);
-        // ORDER BY frame_id: TCOP < TKEY
-        assert_eq!(fetched[0].frame_id, "TCOP");
-        assert_eq!(fetched[0].value, "2024 Label");
-        assert_eq!(fetched[1].frame_id, "TKEY");
-        assert_eq!(fetched[1].value, "Am");
+        sqlx::query("DELETE FROM tracks WHERE id = ?").bind(echo.id).execute(&db).await.unwrap();
+        let results = search_library_inner(&db, "echo", None).await.unwrap();
+        assert!(results.tracks.is_empty());
     }
 
     #[tokio::test]
-    async fn test_set_extra_tags_replaces_all() {
-        let db = setup_test_db().await;
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/extra2.mp3").await;
+    async fn test_smart_playlist_rules_sort_and_limit() {
+        use crate::models::{FieldPredicate, FilterValue, RuleGroup};
+        let db = setup_query_library().await;
+
+        // (artist = Alpha AND year > 2001) OR album = Blue, newest first, at most 3
+        let input = SmartPlaylistInput {
+            name: "Picks".into(),
+            rules: RuleGroup {
+                match_all: false,
+                predicates: vec![FieldPredicate::Equals {
+                    field: "albumTitle".into(),
+                    value: FilterValue::Text("Blue".into()),
+                }],
+                groups: vec![RuleGroup {
+                    match_all: true,
+                    predicates: vec![
+                        FieldPredicate::Equals { field: "artistName".into(), value: FilterValue::Text("Alpha".into()) },
+                        FieldPredicate::GreaterThan { field: "year".into(), value: FilterValue::Number(2001.0) },
+                    ],
+                    groups: vec![],
+                }],
+            },
+            sort: Some(SortSpec { field: "year".into(), descending: true }),
+            limit: Some(3),
+        };
+        let playlist = create_smart_playlist_inner(&db, input.clone()).await.unwrap();
+        assert_eq!(list_smart_playlists_inner(&db).await.unwrap().len(), 1);
+
+        let tracks = list_smart_playlist_tracks_inner(&db, playlist.id).await.unwrap();
+        let years: Vec<i32> = tracks.iter().map(|t| t.year.unwrap()).collect();
+        assert_eq!(years, [2005, 2004, 2003]);
+
+        // Added in the last 30 days matches everything just inserted
+        let recent = SmartPlaylistInput {
+            name: "Recently added".into(),
+            rules: RuleGroup {
+                match_all: true,
+                predicates: vec![FieldPredicate::InLastDays { field: "createdAt".into(), days: 30 }],
+                groups: vec![],
+            },
+            sort: None,
+            limit: None,
+        };
+        let updated = update_smart_playlist_inner(&db, playlist.id, recent).await.unwrap();
+        assert_eq!(updated.name, "Recently added");
+        assert_eq!(list_smart_playlist_tracks_inner(&db, playlist.id).await.unwrap().len(), 5);
+
+        delete_smart_playlist_inner(&db, playlist.id).await.unwrap();
+        assert!(matches!(
+            list_smart_playlist_tracks_inner(&db, playlist.id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
 
-        // Set TKEY first
-        set_track_extra_tags_inner(&db, track_id, vec![
-            ExtraTag { frame_id: "TKEY".to_string(), value: "C".to_string() },
-        ]).await.unwrap();
+    #[tokio::test]
+    async fn test_smart_playlist_rejects_invalid_rules() {
+        use crate::models::{FieldPredicate, FilterValue, RuleGroup};
+        let db = setup_test_db().await;
+        let with_rule = |predicate| SmartPlaylistInput {
+            name: "Broken".into(),
+            rules: RuleGroup { match_all: true, predicates: vec![predicate], groups: vec![] },
+            sort: None,
+            limit: None,
+        };
 
-        // Replace with TCOP only — TKEY must be gone
-        set_track_extra_tags_inner(&db, track_id, vec![
-            ExtraTag { frame_id: "TCOP".to_string(), value: "Label".to_string() },
-        ]).await.unwrap();
+        let unknown = with_rule(FieldPredicate::IsNull { field: "nope".into() });
+        assert!(matches!(create_smart_playlist_inner(&db, unknown).await, Err(AppError::InvalidInput(_))));
+        let contains_number = with_rule(FieldPredicate::Contains { field: "year".into(), value: "19".into() });
+        assert!(matches!(create_smart_playlist_inner(&db, contains_number).await, Err(AppError::InvalidInput(_))));
+        let forever = with_rule(FieldPredicate::InLastDays { field: "createdAt".into(), days: i64::MAX });
+        assert!(matches!(create_smart_playlist_inner(&db, forever).await, Err(AppError::InvalidInput(_))));
 
-        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
-        assert_eq!(fetched.len(), 1);
-        assert_eq!(fetched[0].frame_id, "TCOP");
+        let ok = with_rule(FieldPredicate::Equals { field: "fileFormat".into(), value: FilterValue::Text("flac".into()) });
+        create_smart_playlist_inner(&db, ok.clone()).await.unwrap();
+        assert!(matches!(create_smart_playlist_inner(&db, ok).await, Err(AppError::InvalidInput(_))));
     }
 
-    // ── File Write-back Tests ──
-
-    /// Create a minimal tagged MP3 file in `dir` with the given filename.
-    /// Returns the path to the created file.
-    fn make_tagged_mp3(dir: &std::path::Path, name: &str, title: &str, artist: &str, album: &str) -> std::path::PathBuf {
-        use lofty::tag::{TagType, Accessor};
-        use std::io::Write;
+    // ── Album Grouping Tests ──
 
-        let path = dir.join(name);
-        {
-            let mut file = std::fs::File::create(&path).unwrap();
-            let mut frame = [0u8; 417];
-            frame[0] = 0xFF; frame[1] = 0xFB; frame[2] = 0x90; frame[3] = 0x64;
-            for _ in 0..3 { file.write_all(&frame).unwrap(); }
-        }
-        {
-            let mut tagged = lofty::read_from_path(&path).unwrap();
-            tagged.insert_tag(lofty::tag::Tag::new(TagType::Id3v2));
-            let tag = tagged.tag_mut(TagType::Id3v2).unwrap();
-            tag.set_title(title.to_string());
-            tag.set_artist(artist.to_string());
-            tag.set_album(album.to_string());
-            tagged.save_to_path(&path, WriteOptions::default()).unwrap();
+    /// Add `items` to the ID3v2 tag of an MP3 made by `make_tagged_mp3`.
+    fn add_mp3_items(path: &std::path::Path, items: &[(ItemKey, &str)]) {
+        let mut tagged = lofty::read_from_path(path).unwrap();
+        let tag = tagged.tag_mut(TagType::Id3v2).unwrap();
+        for (key, value) in items {
+            tag.insert_text(key.clone(), value.to_string());
         }
-        path
+        tagged.save_to_path(path, WriteOptions::default()).unwrap();
     }
 
-    #[tokio::test]
-    async fn test_update_track_writes_to_file() {
-        use lofty::prelude::*;
+    /// (album title, album artist name) of every album, sorted.
+    async fn album_artists(db: &DbPool) -> Vec<(String, Option<String>)> {
+        let mut rows: Vec<(String, Option<String>)> = sqlx::query_as(
+            "SELECT al.title, ar.name FROM albums al LEFT JOIN artists ar ON ar.id = al.artist_id",
+        )
+        .fetch_all(db)
+        .await
+        .unwrap();
+        rows.sort();
+        rows
+    }
 
+    #[tokio::test]
+    async fn test_scan_groups_albums_by_album_artist() {
         let db = setup_test_db().await;
         let tmp = tempfile::tempdir().unwrap();
+        let dir = |name: &str| {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        };
+        // No album artist: one folder of different artists is a compilation,
+        // a guest on one track doesn't split the album
+        make_tagged_mp3(&dir("Hits"), "1.mp3", "One", "Alpha", "Hits");
+        make_tagged_mp3(&dir("Hits"), "2.mp3", "Two", "Beta", "Hits");
+        make_tagged_mp3(&dir("Solo"), "1.mp3", "One", "Gamma", "Solo");
+        make_tagged_mp3(&dir("Solo"), "2.mp3", "Two", "Gamma feat. Delta", "Solo");
+        // The album artist tag holds an album together across folders
+        let split = make_tagged_mp3(&dir("Split/CD1"), "1.mp3", "One", "Epsilon", "Split");
+        add_mp3_items(&split, &[(ItemKey::AlbumArtist, "Band")]);
+        let split = make_tagged_mp3(&dir("Split/CD2"), "1.mp3", "Two", "Zeta", "Split");
+        add_mp3_items(&split, &[(ItemKey::AlbumArtist, "Band")]);
+        // Flagged compilations go under Various Artists
+        let flagged = make_tagged_mp3(&dir("Flagged"), "1.mp3", "One", "Eta", "Mix");
+        add_mp3_items(&flagged, &[(ItemKey::FlagCompilation, "1")]);
+        // Same title and artist, different releases
+        for (folder, release) in [("Live 1", "11111111-1111-1111-1111-111111111111"), ("Live 2", "22222222-2222-2222-2222-222222222222")] {
+            let live = make_tagged_mp3(&dir(folder), "1.mp3", "One", "Theta", "Live");
+            add_mp3_items(&live, &[(ItemKey::MusicBrainzReleaseId, release)]);
+        }
 
-        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Original Title", "Original Artist", "Original Album");
         let col_path = tmp.path().to_string_lossy().replace('\\', "/");
         let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
-        scan_collection_inner(&db, col.id, None, |_| {}).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let some = |name: &str| Some(name.to_string());
+        let expected = vec![
+            ("Hits".to_string(), some("Various Artists")),
+            ("Live".to_string(), some("Theta")),
+            ("Live".to_string(), some("Theta")),
+            ("Mix".to_string(), some("Various Artists")),
+            ("Solo".to_string(), some("Gamma")),
+            ("Split".to_string(), some("Band")),
+        ];
+        assert_eq!(album_artists(&db).await, expected);
+        let (releases,): (i64,) = sqlx::query_as("SELECT COUNT(DISTINCT musicbrainz_id) FROM albums").fetch_one(&db).await.unwrap();
+        assert_eq!(releases, 2);
 
+        // Editing the album artist moves the track by the same rule
         let tracks = list_tracks_inner(&db).await.unwrap();
-        assert_eq!(tracks.len(), 1);
-        let track_id = tracks[0].id;
-
-        // Update via the command
-        update_track_inner(&db, track_id, TrackUpdateInput {
-            title: Some("New Title".into()),
-            artist_name: Some("New Artist".into()),
-            album_title: Some("New Album".into()),
-            year: Some(2024),
-            track_number: Some(3),
-            genre: Some("Jazz".into()),
-            lyrics: Some("la la la".into()),
-            ..Default::default()
-        }, false).await.unwrap();
-
-        // Re-read the file directly with Lofty — DB must not be the only thing changed
-        let tagged = lofty::read_from_path(&mp3).unwrap();
-        let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
-        assert_eq!(tag.title().as_deref(), Some("New Title"));
-        assert_eq!(tag.artist().as_deref(), Some("New Artist"));
-        assert_eq!(tag.album().as_deref(), Some("New Album"));
-        assert_eq!(tag.year(), Some(2024));
-        assert_eq!(tag.track(), Some(3));
-        assert_eq!(tag.genre().as_deref(), Some("Jazz"));
-        assert_eq!(tag.get_string(&ItemKey::Lyrics).as_deref(), Some("la la la"));
+        let solo_two = tracks.iter().find(|t| t.file_path.ends_with("Solo/2.mp3")).unwrap();
+        let input = TrackUpdateInput { album_artist: Some("Delta".into()), ..Default::default() };
+        let edited = update_track_inner(&db, solo_two.id, input, true).await.unwrap();
+        assert_ne!(edited.album_id, solo_two.album_id);
+        assert!(album_artists(&db).await.contains(&("Solo".to_string(), some("Delta"))));
+
+        // Rows grouped the old way (title plus track artist) are regrouped by the migration's backfill
+        let hits: Vec<(i64, i64)> = sqlx::query_as(
+            "SELECT t.id, t.artist_id FROM tracks t JOIN albums al ON al.id = t.album_id WHERE al.title = 'Hits'",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        for (track_id, artist_id) in &hits {
+            let album = sqlx::query("INSERT INTO albums (title, artist_id, created_at) VALUES ('Hits', ?, '')")
+                .bind(artist_id)
+                .execute(&db)
+                .await
+                .unwrap();
+            sqlx::query("UPDATE tracks SET album_id = ? WHERE id = ?")
+                .bind(album.last_insert_rowid())
+                .bind(track_id)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        let mut conn = db.acquire().await.unwrap();
+        albums::regroup_albums(&mut conn).await.unwrap();
+        drop(conn);
+        let hits: Vec<_> = album_artists(&db).await.into_iter().filter(|(title, _)| title == "Hits").collect();
+        assert_eq!(hits, vec![("Hits".to_string(), some("Various Artists"))]);
     }
 
     #[tokio::test]
-    async fn test_batch_update_writes_to_file() {
-        use lofty::prelude::*;
-
+    async fn test_album_grouping_splits_on_configured_separators() {
         let db = setup_test_db().await;
         let tmp = tempfile::tempdir().unwrap();
+        set_setting_inner(&db, credits::ARTIST_SEPARATORS_SETTING, r#"[" & "]"#).await.unwrap();
+        for (folder, artist) in [("CD1", "Simon & Garfunkel"), ("CD2", "Simon")] {
+            let dir = tmp.path().join(folder);
+            std::fs::create_dir_all(&dir).unwrap();
+            make_tagged_mp3(&dir, "1.mp3", "One", artist, "Duo");
+        }
 
-        let mp3a = make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist A", "Album A");
-        let mp3b = make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist B", "Album B");
         let col_path = tmp.path().to_string_lossy().replace('\\', "/");
         let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
-        scan_collection_inner(&db, col.id, None, |_| {}).await.unwrap();
-
-        let tracks = list_tracks_inner(&db).await.unwrap();
-        assert_eq!(tracks.len(), 2);
-        let ids: Vec<i64> = tracks.iter().map(|t| t.id).collect();
-
-        // Apply the same genre to both tracks via batch update
-        batch_update_tracks_inner(&db, ids, TrackUpdateInput {
-            genre: Some("Electronic".into()),
-            ..Default::default()
-        }, false).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-        // Both files should now have the new genre
-        for mp3 in [&mp3a, &mp3b] {
-            let tagged = lofty::read_from_path(mp3).unwrap();
-            let tag = tagged.primary_tag().or_else(|| tagged.first_tag()).unwrap();
-            assert_eq!(tag.genre().as_deref(), Some("Electronic"), "file {:?} not updated", mp3);
-        }
+        assert_eq!(album_artists(&db).await, vec![("Duo".to_string(), Some("Simon".to_string()))]);
     }
 
     #[tokio::test]
-    async fn test_extra_tags_deleted_with_track() {
+    async fn test_scan_credits_multi_valued_artists() {
         let db = setup_test_db().await;
-        let col = add_collection_inner(&db, CollectionInput { path: abs_test_path(""), label: None }, true).await.unwrap();
-        let track_id = insert_bare_track(&db, col.id, "Song", "/music/extra3.mp3").await;
+        let tmp = tempfile::tempdir().unwrap();
+        let first = make_tagged_mp3(tmp.path(), "1.mp3", "One", "Alpha feat. Beta", "Record");
+        // Two composers in one null-separated ID3v2.4 frame
+        add_mp3_items(&first, &[(ItemKey::Composer, "Gamma\0Delta"), (ItemKey::Conductor, "Eta")]);
+        make_tagged_mp3(tmp.path(), "2.mp3", "Two", "Beta", "Record");
 
-        set_track_extra_tags_inner(&db, track_id, vec![
-            ExtraTag { frame_id: "TKEY".to_string(), value: "G".to_string() },
-        ]).await.unwrap();
+        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-        // Delete the track
-        sqlx::query("DELETE FROM tracks WHERE id = ?")
-            .bind(track_id)
-            .execute(&db)
+        let tracks = list_tracks_inner(&db).await.unwrap();
+        let track = |title: &str| tracks.iter().find(|t| t.title == title).unwrap().clone();
+        let one = track("One");
+        assert_eq!(one.composer.as_deref(), Some("Gamma; Delta"));
+        let credits: Vec<(String, String, i64)> = get_track_credits_inner(&db, one.id)
             .await
-            .unwrap();
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.role, c.artist_name, c.position))
+            .collect();
+        let expected = [
+            ("artist", "Alpha", 0),
+            ("featured", "Beta", 0),
+            ("composer", "Gamma", 0),
+            ("composer", "Delta", 1),
+            ("conductor", "Eta", 0),
+        ];
+        let expected: Vec<(String, String, i64)> =
+            expected.iter().map(|(r, n, p)| (r.to_string(), n.to_string(), *p)).collect();
+        assert_eq!(credits, expected);
 
-        // Extra tags should be cascaded away
-        let fetched = get_track_extra_tags_inner(&db, track_id).await.unwrap();
-        assert!(fetched.is_empty(), "extra tags should be deleted when track is deleted");
+        // Artist views count every credited track, in any role
+        let counts = |rows: Vec<ArtistRow>| -> HashMap<String, i64> {
+            rows.into_iter().map(|r| (r.name, r.track_count)).collect()
+        };
+        let rows = counts(list_artist_rows_inner(&db).await.unwrap());
+        assert_eq!(rows["Beta"], 2);
+        assert_eq!(rows["Gamma"], 1);
+        assert_eq!(rows["Alpha"], 1);
+        // The whole "Alpha feat. Beta" string is no artist of its own once credited
+        assert!(!rows.contains_key("Alpha feat. Beta"));
+        let beta_id = list_artists_inner(&db).await.unwrap().into_iter().find(|a| a.name == "Beta").unwrap().id;
+        let filter = LibraryFilter { artist_id: Some(beta_id), ..Default::default() };
+        let page = query_tracks_inner(&db, &filter, None, &PageRequest::default()).await.unwrap();
+        assert_eq!(page.total, 2);
+
+        // Edits split on the configured separators
+        set_setting_inner(&db, credits::ARTIST_SEPARATORS_SETTING, r#"[" & "]"#).await.unwrap();
+        let two = track("Two");
+        let input = TrackUpdateInput { artist_name: Some("Beta & Zed".to_string()), ..Default::default() };
+        update_track_inner(&db, two.id, input, true).await.unwrap();
+        let names: Vec<String> =
+            get_track_credits_inner(&db, two.id).await.unwrap().into_iter().map(|c| c.artist_name).collect();
+        assert_eq!(names, vec!["Beta", "Zed"]);
+        let rows = counts(query_artist_rows_inner(&db, &Default::default(), None, &PageRequest::default()).await.unwrap().rows);
+        assert_eq!(rows["Zed"], 1);
+        assert_eq!(rows["Beta"], 2);
     }
 
-    // ── File mtime / DB-as-cache hardening tests ──
-
     #[tokio::test]
-    async fn test_batch_update_skips_db_on_file_write_failure() {
+    async fn test_artist_and_album_edits_retag_files() {
         let db = setup_test_db().await;
         let tmp = tempfile::tempdir().unwrap();
-
-        let _mp3a = make_tagged_mp3(tmp.path(), "a.mp3", "Track A", "Artist A", "Album A");
-        let _mp3b = make_tagged_mp3(tmp.path(), "b.mp3", "Track B", "Artist B", "Album B");
+        let one = make_tagged_mp3(tmp.path(), "1.mp3", "One", "Beatles, The", "Abbey");
+        make_tagged_mp3(tmp.path(), "2.mp3", "Two", "Beatles, The", "Abbey");
+        make_tagged_mp3(tmp.path(), "3.mp3", "Three", "The Beatles", "Abbey Road");
+        let four = make_tagged_mp3(tmp.path(), "4.mp3", "Four", "Alpha feat. Beatles, The", "Other");
+        add_mp3_items(&four, &[(ItemKey::Lyricist, "Beatles, The"), (ItemKey::Conductor, "Beatles, The")]);
         let col_path = tmp.path().to_string_lossy().replace('\\', "/");
         let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
-        scan_collection_inner(&db, col.id, None, |_| {}).await.unwrap();
-
-        let tracks = list_tracks_inner(&db).await.unwrap();
-        assert_eq!(tracks.len(), 2);
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
 
-        // Identify the two tracks and delete one file so its write will fail
-        let (gone_id, live_id) = if tracks[0].file_path.ends_with("a.mp3") {
-            (tracks[0].id, tracks[1].id)
-        } else {
-            (tracks[1].id, tracks[0].id)
+        let artist_id = |name: &'static str| {
+            let db = db.clone();
+            async move { list_artists_inner(&db).await.unwrap().into_iter().find(|a| a.name == name).map(|a| a.id) }
         };
-        let gone_path = get_track_inner(&db, gone_id).await.unwrap().file_path;
-        std::fs::remove_file(&gone_path).unwrap();
-
-        batch_update_tracks_inner(&db, vec![gone_id, live_id], TrackUpdateInput {
-            title: Some("New Title".into()),
-            ..Default::default()
-        }, false).await.unwrap();
-
-        let gone_after = get_track_inner(&db, gone_id).await.unwrap();
-        let live_after = get_track_inner(&db, live_id).await.unwrap();
+        let file_tag = |path: &std::path::Path, key: ItemKey| {
+            let tagged = lofty::read_from_path(path).unwrap();
+            tagged.primary_tag().unwrap().get_string(&key).map(str::to_string)
+        };
+        let comma = artist_id("Beatles, The").await.unwrap();
+        let beatles = artist_id("The Beatles").await.unwrap();
+
+        // Renaming onto an existing name asks for a merge instead
+        assert!(rename_artist_inner(&db, comma, "The Beatles", false).await.is_err());
+        let preview = merge_artists_inner(&db, &[comma], beatles, true).await.unwrap();
+        assert_eq!((preview.track_count, preview.file_count, preview.dry_run), (3, 3, true));
+        assert_eq!(file_tag(&one, ItemKey::TrackArtist).as_deref(), Some("Beatles, The"));
+
+        merge_artists_inner(&db, &[comma, beatles], beatles, false).await.unwrap();
+        assert_eq!(artist_id("Beatles, The").await, None);
+        assert_eq!(file_tag(&one, ItemKey::TrackArtist).as_deref(), Some("The Beatles"));
+        assert_eq!(file_tag(&four, ItemKey::TrackArtist).as_deref(), Some("Alpha feat. The Beatles"));
+        assert_eq!(file_tag(&four, ItemKey::Lyricist).as_deref(), Some("The Beatles"));
+        assert_eq!(file_tag(&four, ItemKey::Conductor).as_deref(), Some("The Beatles"));
+        let rows: HashMap<String, i64> =
+            list_artist_rows_inner(&db).await.unwrap().into_iter().map(|r| (r.name, r.track_count)).collect();
+        assert_eq!(rows["The Beatles"], 4);
+
+        // A rename keeps the other names of a credit
+        let alpha = artist_id("Alpha").await.unwrap();
+        let summary = rename_artist_inner(&db, alpha, "Alfa", false).await.unwrap();
+        assert_eq!((summary.track_count, summary.file_count), (1, 1));
+        assert_eq!(file_tag(&four, ItemKey::TrackArtist).as_deref(), Some("Alfa feat. The Beatles"));
+        assert_eq!(artist_id("Alfa").await, Some(alpha));
+
+        let summary = set_artist_sort_name_inner(&db, beatles, Some("Beatles, The"), false).await.unwrap();
+        assert_eq!(summary.file_count, 3);
+        assert_eq!(file_tag(&one, ItemKey::TrackArtistSortOrder).as_deref(), Some("Beatles, The"));
+        let artist = list_artists_inner(&db).await.unwrap().into_iter().find(|a| a.id == beatles).unwrap();
+        assert_eq!(artist.sort_name.as_deref(), Some("Beatles, The"));
+        // Sort order tags are journaled like any other edit
+        undo_last_edit_inner(&db, false).await.unwrap();
+        assert_eq!(file_tag(&one, ItemKey::TrackArtistSortOrder), None);
+        redo_edit_inner(&db, false).await.unwrap();
+        assert_eq!(file_tag(&one, ItemKey::TrackArtistSortOrder).as_deref(), Some("Beatles, The"));
 
-        // File write for "gone" failed → DB must NOT be updated
-        assert_ne!(gone_after.title, "New Title",
-            "DB should not be updated when file write fails");
-        // File write for "live" succeeded → DB must be updated
-        assert_eq!(live_after.title, "New Title",
-            "DB should be updated when file write succeeds");
+        let albums = list_albums_inner(&db, None).await.unwrap();
+        let album_id = |title: &str| albums.iter().find(|a| a.title == title).unwrap().id;
+        let (abbey, road) = (album_id("Abbey"), album_id("Abbey Road"));
+        let summary = merge_albums_inner(&db, &[abbey], road, false).await.unwrap();
+        assert_eq!((summary.track_count, summary.file_count), (2, 2));
+        assert_eq!(file_tag(&one, ItemKey::AlbumTitle).as_deref(), Some("Abbey Road"));
+        assert_eq!(list_tracks_by_album_inner(&db, road).await.unwrap().len(), 3);
+        assert!(list_albums_inner(&db, None).await.unwrap().iter().all(|a| a.id != abbey));
+
+        // A split must leave tracks behind
+        let road_tracks = list_tracks_by_album_inner(&db, road).await.unwrap();
+        let ids: Vec<i64> = road_tracks.iter().map(|t| t.id).collect();
+        assert!(split_album_inner(&db, road, &ids, "Bonus", None, false).await.is_err());
+        let one_id = road_tracks.iter().find(|t| t.title == "One").unwrap().id;
+        assert!(split_album_inner(&db, road, &[one_id], "Abbey Road", None, false).await.is_err());
+        split_album_inner(&db, road, &[one_id], "Abbey Road (Bonus)", None, false).await.unwrap();
+        assert_eq!(file_tag(&one, ItemKey::AlbumTitle).as_deref(), Some("Abbey Road (Bonus)"));
+        assert_eq!(list_tracks_by_album_inner(&db, road).await.unwrap().len(), 2);
+        let bonus = list_albums_inner(&db, None).await.unwrap().into_iter().find(|a| a.title == "Abbey Road (Bonus)");
+        assert_eq!(bonus.unwrap().artist_id, Some(beatles));
+
+        // A rescan files everything where the edits put it, and credits what they wrote
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+        assert_eq!(list_tracks_by_album_inner(&db, road).await.unwrap().len(), 2);
+        let four_id = list_tracks_inner(&db).await.unwrap().into_iter().find(|t| t.title == "Four").unwrap().id;
+        let credits: Vec<(String, String)> = get_track_credits_inner(&db, four_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.role, c.artist_name))
+            .collect();
+        assert!(credits.contains(&("lyricist".to_string(), "The Beatles".to_string())), "{:?}", credits);
+        assert!(credits.contains(&("conductor".to_string(), "The Beatles".to_string())), "{:?}", credits);
+        assert_eq!(artist_id("Beatles, The").await, None);
     }
 
     #[tokio::test]
-    async fn test_scan_stores_file_mtime() {
+    async fn test_orphans_pruned_after_edits_and_vacuum() {
         let db = setup_test_db().await;
         let tmp = tempfile::tempdir().unwrap();
-
-        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Title", "Artist", "Album");
-        let expected_mtime = std::fs::metadata(&mp3)
-            .unwrap()
-            .modified()
-            .unwrap()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-
-        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let lib = tmp.path().join("lib");
+        let covers = tmp.path().join("covers");
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::create_dir_all(&covers).unwrap();
+        make_tagged_mp3(&lib, "1.mp3", "One", "Alpha feat. Beta", "Record");
+        make_tagged_mp3(&lib, "2.mp3", "Two", "Gamma", "Other");
+        let col_path = lib.to_string_lossy().replace('\\', "/");
         let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
-        scan_collection_inner(&db, col.id, None, |_| {}).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let other = list_albums_inner(&db, None).await.unwrap().into_iter().find(|a| a.title == "Other").unwrap();
+        let other_cover = covers.join(format!("{}.png", other.id));
+        std::fs::write(&other_cover, b"png").unwrap();
+        sqlx::query("UPDATE albums SET cover_path = ? WHERE id = ?")
+            .bind(other_cover.to_string_lossy().replace('\\', "/"))
+            .bind(other.id)
+            .execute(&db)
+            .await
+            .unwrap();
 
         let tracks = list_tracks_inner(&db).await.unwrap();
-        assert_eq!(tracks.len(), 1);
-        let stored = tracks[0].file_mtime.expect("file_mtime should be stored after scan");
-        assert_eq!(stored, expected_mtime, "stored mtime should match file mtime");
+        let track_id = |title: &str| tracks.iter().find(|t| t.title == title).unwrap().id;
+        let input = TrackUpdateInput {
+            artist_name: Some("Alpha".to_string()),
+            album_title: Some("Record".to_string()),
+            ..Default::default()
+        };
+        update_track_inner(&db, track_id("Two"), input.clone(), true).await.unwrap();
+        update_track_inner(&db, track_id("One"), input, true).await.unwrap();
+
+        let names: Vec<String> = list_artists_inner(&db).await.unwrap().into_iter().map(|a| a.name).collect();
+        assert_eq!(names, vec!["Alpha"]);
+        let titles: Vec<String> = list_albums_inner(&db, None).await.unwrap().into_iter().map(|a| a.title).collect();
+        assert_eq!(titles, vec!["Record"]);
+        assert!(!other_cover.exists());
+
+        // Leftovers from before cleanup existed are only caught by a vacuum
+        std::fs::write(covers.join("99.jpg"), b"jpg").unwrap();
+        let mut conn = db.acquire().await.unwrap();
+        albums::find_or_create_artist(&mut conn, "Loose").await.unwrap();
+        // What the user set on an artist outlives its tracks
+        let sorted = albums::find_or_create_artist(&mut conn, "Sorted").await.unwrap();
+        sqlx::query("UPDATE artists SET sort_name = 'Sorted, The' WHERE id = ?")
+            .bind(sorted)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        let summary = vacuum_library_inner(&db, Some(&covers)).await.unwrap();
+        assert_eq!((summary.removed_artists, summary.removed_albums, summary.removed_covers), (1, 0, 1));
+        assert_eq!(std::fs::read_dir(&covers).unwrap().count(), 0);
+        assert!(list_artists_inner(&db).await.unwrap().iter().any(|a| a.id == sorted));
+
+        // The artist rows only list artists that still have music
+        let input = TrackUpdateInput { artist_name: Some("Sorted".into()), ..Default::default() };
+        update_track_inner(&db, track_id("One"), input, true).await.unwrap();
+        let rows = |rows: Vec<ArtistRow>| rows.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        assert!(rows(list_artist_rows_inner(&db).await.unwrap()).contains(&"Sorted".to_string()));
+        let input = TrackUpdateInput { artist_name: Some("Alpha".into()), ..Default::default() };
+        update_track_inner(&db, track_id("One"), input, true).await.unwrap();
+        assert!(list_artists_inner(&db).await.unwrap().iter().any(|a| a.id == sorted));
+        assert!(!rows(list_artist_rows_inner(&db).await.unwrap()).contains(&"Sorted".to_string()));
+        let page = query_artist_rows_inner(&db, &Default::default(), None, &Default::default()).await.unwrap();
+        assert!(!rows(page.rows).contains(&"Sorted".to_string()));
     }
 
     #[tokio::test]
-    async fn test_update_track_updates_mtime() {
+    async fn test_name_keys_match_case_and_unicode() {
         let db = setup_test_db().await;
         let tmp = tempfile::tempdir().unwrap();
-
-        let mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Original", "Artist", "Album");
+        make_tagged_mp3(tmp.path(), "1.mp3", "One", "AC/DC", "Back in Black");
+        make_tagged_mp3(tmp.path(), "2.mp3", "Two", "Ac/Dc", "back in black");
+        make_tagged_mp3(tmp.path(), "3.mp3", "Three", "Bj\u{f6}rk", "Debut");
+        make_tagged_mp3(tmp.path(), "4.mp3", "Four", "Bjo\u{308}rk", "Post");
+        make_tagged_mp3(tmp.path(), "5.mp3", "Five", "AC-DC", "Back In Black!");
         let col_path = tmp.path().to_string_lossy().replace('\\', "/");
         let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
-        scan_collection_inner(&db, col.id, None, |_| {}).await.unwrap();
-
-        let tracks = list_tracks_inner(&db).await.unwrap();
-        let track_id = tracks[0].id;
-
-        update_track_inner(&db, track_id, TrackUpdateInput {
-            title: Some("Updated".into()),
-            ..Default::default()
-        }, false).await.unwrap();
-
-        let after = get_track_inner(&db, track_id).await.unwrap();
-        let db_mtime = after.file_mtime.expect("file_mtime should be set after update");
-        let actual_mtime = std::fs::metadata(&mp3)
-            .unwrap()
-            .modified()
-            .unwrap()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        assert_eq!(db_mtime, actual_mtime,
-            "DB mtime should match actual file mtime after update");
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        // The spelling scanned first names the shared artist or album
+        let mut names: Vec<String> =
+            list_artists_inner(&db).await.unwrap().into_iter().map(|a| name_key(&a.name, Default::default())).collect();
+        names.sort();
+        assert_eq!(names, vec!["ac-dc", "ac/dc", "bj\u{f6}rk"]);
+        let albums = list_album_rows_inner(&db).await.unwrap();
+        let back = albums.iter().find(|a| a.title.eq_ignore_ascii_case("Back in Black")).unwrap();
+        assert_eq!((back.artist_name.as_deref().map(str::to_lowercase), back.track_count), (Some("ac/dc".into()), 2));
+
+        // Punctuation differences are only reported, until folding is turned on
+        let report = find_name_duplicates_inner(&db).await.unwrap();
+        let artist_names: Vec<Vec<String>> =
+            report.artists.iter().map(|g| g.iter().map(|a| a.name.to_lowercase()).collect()).collect();
+        assert_eq!(artist_names, vec![vec!["ac-dc", "ac/dc"]]);
+        assert_eq!(report.albums.len(), 1);
+        assert_eq!(report.albums[0].len(), 2);
+
+        set_setting_inner(&db, name_keys::NAME_FOLDING_SETTING, r#"["punctuation"]"#).await.unwrap();
+        let mut conn = db.acquire().await.unwrap();
+        let acdc = albums::find_or_create_artist(&mut conn, "AC DC").await.unwrap();
+        drop(conn);
+        let artist = list_artists_inner(&db).await.unwrap().into_iter().find(|a| a.id == acdc).unwrap();
+        assert!(artist.name.to_lowercase().starts_with("ac"));
+        assert_eq!(list_artists_inner(&db).await.unwrap().len(), 3);
+
+        // A case-only rename keeps the artist; a rename onto another key asks for a merge
+        let bjork = list_artists_inner(&db).await.unwrap().into_iter().find(|a| a.name.starts_with("Bj")).unwrap();
+        rename_artist_inner(&db, bjork.id, "BJ\u{d6}RK", false).await.unwrap();
+        let err = rename_artist_inner(&db, bjork.id, "ACDC", true).await.unwrap_err();
+        assert!(matches!(err, AppError::InvalidInput(_)));
     }
 
+    // ── Duplicate Tests ──
+
     #[tokio::test]
-    async fn test_stale_track_ids_detects_external_edit() {
+    async fn test_find_and_remove_duplicates() {
+        use crate::models::{DuplicateAction, DuplicateCriteria};
         let db = setup_test_db().await;
         let tmp = tempfile::tempdir().unwrap();
+        let lib = tmp.path().join("lib");
+        std::fs::create_dir(&lib).unwrap();
 
-        let _mp3 = make_tagged_mp3(tmp.path(), "song.mp3", "Title", "Artist", "Album");
-        let col_path = tmp.path().to_string_lossy().replace('\\', "/");
+        let a = make_tagged_mp3(&lib, "a.mp3", "Song", "Band", "Album");
+        std::fs::copy(&a, lib.join("a copy.mp3")).unwrap();
+        make_tagged_mp3(&lib, "retagged.mp3", "Song (Remaster)", "Band", "Another Album");
+        let col_path = lib.to_string_lossy().replace('\\', "/");
         let col = add_collection_inner(&db, CollectionInput { path: col_path, label: None }, true).await.unwrap();
-        scan_collection_inner(&db, col.id, None, |_| {}).await.unwrap();
+        scan_collection_inner(&db, col.id, None, Default::default(), |_| {}).await.unwrap();
+
+        let same_file = find_duplicates_inner(&db, DuplicateCriteria::FileContent, None).await.unwrap();
+        assert_eq!(same_file.len(), 1);
+        assert_eq!(same_file[0].tracks.len(), 2);
+
+        let same_audio = find_duplicates_inner(&db, DuplicateCriteria::AudioContent, Some(col.id)).await.unwrap();
+        assert_eq!(same_audio.len(), 1);
+        assert_eq!(same_audio[0].tracks.len(), 3);
+        assert_eq!(same_audio[0].best_track_id, same_audio[0].tracks[0].id);
+
+        let by_tags = find_duplicates_inner(&db, DuplicateCriteria::Metadata { tolerance_secs: 1.0 }, None).await.unwrap();
+        assert_eq!(by_tags[0].tracks.len(), 2);
+
+        let quarantine = tmp.path().join("quarantine");
+        let losers: Vec<i64> = same_audio[0].tracks[1..].iter().map(|t| t.id).collect();
+        let action = DuplicateAction::MoveTo { directory: quarantine.to_string_lossy().into_owned() };
+        let removed = remove_duplicates_inner(&db, losers, action).await.unwrap();
+        assert!(removed.iter().all(|r| r.problem.is_none()));
+        assert!(removed.iter().all(|r| Path::new(r.moved_to.as_ref().unwrap()).exists()));
+        assert!(removed.iter().all(|r| !Path::new(&r.file_path).exists()));
+        assert_eq!(list_tracks_inner(&db).await.unwrap().len(), 1);
+
+        let relative = DuplicateAction::MoveTo { directory: "quarantine".into() };
+        assert!(matches!(remove_duplicates_inner(&db, vec![], relative).await, Err(AppError::InvalidInput(_))));
+    }
 
-        let tracks = list_tracks_inner(&db).await.unwrap();
-        let track_id = tracks[0].id;
+    #[tokio::test]
+    async fn test_deleting_duplicate_backs_up_file() {
+        use crate::models::DuplicateAction;
+        let (db, _tmp, mp3, track_id) = setup_backup_library("full").await;
+        let original = std::fs::read(&mp3).unwrap();
+
+        let removed = remove_duplicates_inner(&db, vec![track_id], DuplicateAction::Delete).await.unwrap();
+        assert!(removed[0].problem.is_none());
+        assert!(!mp3.exists());
+
+        let file_path = mp3.to_string_lossy().replace('\\', "/");
+        let (backup_path,): (String,) = sqlx::query_as("SELECT backup_path FROM file_backups WHERE file_path = ?")
+            .bind(&file_path)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(backup_path).unwrap(), original);
+    }
 
-        // No stale tracks right after scan
-        let stale = stale_track_ids_inner(&db).await.unwrap();
-        assert!(!stale.contains(&track_id), "track should not be stale right after scan");
+    // ── Playlist Tests ──
 
-        // Simulate external edit by setting DB mtime 1 second in the past
-        sqlx::query("UPDATE tracks SET file_mtime = file_mtime - 1 WHERE id = ?")
-            .bind(track_id)
-            .execute(&db)
+    #[tokio::test]
+    async fn test_playlist_add_reorder_remove() {
+        let db = setup_query_library().await;
+        let playlist = create_playlist_inner(&db, "  Road trip ").await.unwrap();
+        assert_eq!(playlist.name, "Road trip");
+        assert!(matches!(create_playlist_inner(&db, "Road trip").await, Err(AppError::InvalidInput(_))));
+
+        let titles = |entries: &[PlaylistEntry]| entries.iter().map(|e| e.track.title.clone()).collect::<Vec<_>>();
+        add_tracks_to_playlist_inner(&db, playlist.id, &[1, 3], None).await.unwrap();
+        // Insert in the middle; the same track may appear twice
+        let entries = add_tracks_to_playlist_inner(&db, playlist.id, &[4, 1], Some(1)).await.unwrap();
+        assert_eq!(titles(&entries), ["Echo", "Delta", "Echo", "Bravo"]);
+        assert_eq!(entries.iter().map(|e| e.position).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert!(matches!(
+            add_tracks_to_playlist_inner(&db, playlist.id, &[99], None).await,
+            Err(AppError::NotFound(_))
+        ));
+
+        let mut order: Vec<i64> = entries.iter().map(|e| e.id).collect();
+        order.reverse();
+        let entries = reorder_playlist_inner(&db, playlist.id, &order).await.unwrap();
+        assert_eq!(titles(&entries), ["Bravo", "Echo", "Delta", "Echo"]);
+        assert!(matches!(
+            reorder_playlist_inner(&db, playlist.id, &order[1..]).await,
+            Err(AppError::InvalidInput(_))
+        ));
+
+        let entries = remove_playlist_entries_inner(&db, playlist.id, &[entries[1].id]).await.unwrap();
+        assert_eq!(titles(&entries), ["Bravo", "Delta", "Echo"]);
+        assert_eq!(entries.iter().map(|e| e.position).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(list_playlists_inner(&db).await.unwrap()[0].track_count, 3);
+
+        delete_playlist_inner(&db, playlist.id).await.unwrap();
+        assert!(matches!(
+            list_playlist_entries_inner(&db, playlist.id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_playlist_import_and_export() {
+        let db = setup_test_db().await;
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().replace('\\', "/");
+        let col = add_collection_inner(&db, CollectionInput { path: root.clone(), label: None }, true).await.unwrap();
+        for name in ["One", "Two", "Three"] {
+            insert_bare_track(&db, col.id, name, &format!("{}/music/{}.mp3", root, name)).await;
+        }
+        std::fs::create_dir(dir.path().join("lists")).unwrap();
+
+        let m3u = format!(
+            "#EXTM3U\n#EXTINF:10,Someone - Two\n../music/Two.mp3\n{}/music/One.mp3\n../MUSIC/three.mp3\nmissing.mp3\n",
+            root
+        );
+        let m3u_path = dir.path().join("lists/Mix.m3u8");
+        std::fs::write(&m3u_path, m3u).unwrap();
+        let import = import_playlist_inner(&db, &m3u_path.to_string_lossy(), None).await.unwrap();
+        assert_eq!(import.playlist.name, "Mix");
+        assert_eq!(import.playlist.track_count, 3);
+        assert_eq!(import.unresolved, ["missing.mp3"]);
+        let titles = |entries: Vec<PlaylistEntry>| entries.into_iter().map(|e| e.track.title).collect::<Vec<_>>();
+        let imported = titles(list_playlist_entries_inner(&db, import.playlist.id).await.unwrap());
+        assert_eq!(imported, ["Two", "One", "Three"]);
+
+        for ext in ["m3u8", "pls", "xspf"] {
+            let out = dir.path().join(format!("lists/out.{}", ext));
+            export_playlist_inner(&db, import.playlist.id, &out.to_string_lossy(), None, None).await.unwrap();
+            let written = std::fs::read_to_string(&out).unwrap();
+            assert!(written.contains("../music/Two.mp3"), "{}", written);
+
+            let again = import_playlist_inner(&db, &out.to_string_lossy(), Some(&format!("Again {}", ext))).await.unwrap();
+            assert!(again.unresolved.is_empty(), "{:?}", again.unresolved);
+            assert_eq!(titles(list_playlist_entries_inner(&db, again.playlist.id).await.unwrap()), imported);
+        }
+
+        // Relative to another directory
+        let out = dir.path().join("lists/abs.m3u");
+        export_playlist_inner(&db, import.playlist.id, &out.to_string_lossy(), Some(PlaylistFormat::M3u), Some(&root))
             .await
             .unwrap();
-
-        let stale = stale_track_ids_inner(&db).await.unwrap();
-        assert!(stale.contains(&track_id),
-            "track should be detected as stale when file is newer than DB mtime");
+        assert!(std::fs::read_to_string(&out).unwrap().contains("\nmusic/One.mp3\n"));
     }
 }
@@ -0,0 +1,358 @@
+use super::credits;
+use super::name_keys::NameMatch;
+use crate::models::AppError;
+use chrono::Utc;
+use log::info;
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
+
+/// Album artist of compilations: albums whose tracks are by different artists.
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+/// `track_extra_tags` frame id of the MusicBrainz release id (`MUSICBRAINZ_ALBUMID` in Vorbis comments).
+pub const MUSICBRAINZ_RELEASE_FRAME: &str = "MusicBrainz Album Id";
+/// `track_extra_tags` frame id of the compilation flag (`COMPILATION`, MP4 `cpil`).
+pub const COMPILATION_FRAME: &str = "TCMP";
+
+/// What decides which album a track belongs to, in order of precedence:
+///
+/// 1. The MusicBrainz release id, when tagged: one album per release.
+/// 2. The album artist, when tagged. The compilation flag and album artists
+///    like "VA" or "Various" mean "Various Artists".
+/// 3. An album of the same title that already holds a track from the same
+///    folder. If that album's tracks are by different artists and none names an
+///    album artist, it becomes a "Various Artists" album.
+/// 4. The track artist's first name, split on the configured artist
+///    separators, so without any "feat." guests.
+#[derive(Debug, Clone, Copy)]
+pub struct AlbumKey<'a> {
+    pub title: &'a str,
+    pub album_artist: Option<&'a str>,
+    pub track_artist: Option<&'a str>,
+    pub compilation: bool,
+    pub musicbrainz_release_id: Option<&'a str>,
+    /// The track's own (forward-slash) path, for rule 3
+    pub file_path: &'a str,
+}
+
+/// Whether an extra tag value sets the compilation flag.
+pub fn is_compilation_flag(value: &str) -> bool {
+    matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes")
+}
+
+/// The artist an album is filed under for `name`: "Various Artists" for the
+/// usual spellings of it, else the name itself.
+fn album_artist_name(name: &str) -> &str {
+    match name.trim().to_lowercase().as_str() {
+        "va" | "v.a." | "various" | "various artists" => VARIOUS_ARTISTS,
+        _ => name.trim(),
+    }
+}
+
+/// Find an artist by name key, preferring the exact spelling among artists
+/// that share it, or insert a new one and return its id.
+pub async fn find_or_create_artist(conn: &mut SqliteConnection, name: &str) -> Result<i64, AppError> {
+    let matching = NameMatch::configured(conn).await?;
+    find_or_create_artist_by(conn, name, matching).await
+}
+
+/// `find_or_create_artist` matching names by `matching`.
+pub async fn find_or_create_artist_by(
+    conn: &mut SqliteConnection,
+    name: &str,
+    matching: NameMatch,
+) -> Result<i64, AppError> {
+    let value = matching.value(name);
+    let row: Option<(i64,)> = sqlx::query_as(&format!(
+        "SELECT id FROM artists WHERE {} = ? ORDER BY name = ? DESC, id LIMIT 1",
+        matching.artist_column()
+    ))
+    .bind(&value)
+    .bind(name)
+    .fetch_optional(&mut *conn)
+    .await?;
+    if let Some((id,)) = row {
+        return Ok(id);
+    }
+    let insert = match matching {
+        NameMatch::Key(_) => {
+            sqlx::query("INSERT INTO artists (name, name_key, created_at) VALUES (?, ?, ?)").bind(name).bind(value)
+        }
+        NameMatch::Exact => sqlx::query("INSERT INTO artists (name, created_at) VALUES (?, ?)").bind(name),
+    };
+    let res = insert.bind(Utc::now().to_rfc3339()).execute(&mut *conn).await?;
+    Ok(res.last_insert_rowid())
+}
+
+/// The album `key` belongs to, created if missing. `year` only fills in a new album.
+pub async fn find_or_create_album(
+    conn: &mut SqliteConnection,
+    key: &AlbumKey<'_>,
+    year: Option<i32>,
+) -> Result<i64, AppError> {
+    let matching = NameMatch::configured(conn).await?;
+    find_or_create_album_by(conn, key, year, matching).await
+}
+
+/// `find_or_create_album` matching names by `matching`.
+async fn find_or_create_album_by(
+    conn: &mut SqliteConnection,
+    key: &AlbumKey<'_>,
+    year: Option<i32>,
+    matching: NameMatch,
+) -> Result<i64, AppError> {
+    let release = key.musicbrainz_release_id.map(str::trim).filter(|id| !id.is_empty());
+    if let Some(release) = release {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT id FROM albums WHERE musicbrainz_id = ? ORDER BY id")
+            .bind(release)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if let Some((id,)) = row {
+            return Ok(id);
+        }
+    }
+
+    let named_artist = match key.album_artist.map(str::trim).filter(|a| !a.is_empty()) {
+        Some(name) => Some(album_artist_name(name)),
+        None if key.compilation => Some(VARIOUS_ARTISTS),
+        None => None,
+    };
+    let found = match named_artist {
+        Some(name) => {
+            let artist_id = find_or_create_artist_by(conn, name, matching).await?;
+            find_album(conn, key.title, Some(artist_id), release, matching).await?.ok_or(Some(artist_id))
+        }
+        None => match album_in_folder(conn, key, release, matching).await? {
+            Some(id) => Ok(id),
+            None => {
+                let separators = credits::separators(conn).await?;
+                let primary = key.track_artist.map(|a| credits::primary_name(a, &separators));
+                let artist_id = match primary.filter(|a| !a.is_empty()) {
+                    Some(name) => Some(find_or_create_artist_by(conn, &name, matching).await?),
+                    None => None,
+                };
+                find_album(conn, key.title, artist_id, release, matching).await?.ok_or(artist_id)
+            }
+        },
+    };
+
+    match found {
+        Ok(id) => {
+            if release.is_some() {
+                sqlx::query("UPDATE albums SET musicbrainz_id = ? WHERE id = ? AND musicbrainz_id IS NULL")
+                    .bind(release)
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            Ok(id)
+        }
+        Err(artist_id) => {
+            let insert = match matching {
+                NameMatch::Key(_) => sqlx::query(
+                    "INSERT INTO albums (title, title_key, artist_id, year, musicbrainz_id, created_at)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(key.title)
+                .bind(matching.value(key.title)),
+                NameMatch::Exact => sqlx::query(
+                    "INSERT INTO albums (title, artist_id, year, musicbrainz_id, created_at) VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(key.title),
+            };
+            let res = insert
+                .bind(artist_id)
+                .bind(year)
+                .bind(release)
+                .bind(Utc::now().to_rfc3339())
+                .execute(&mut *conn)
+                .await?;
+            Ok(res.last_insert_rowid())
+        }
+    }
+}
+
+/// An album with this title (by key) and artist that isn't tied to a different release.
+async fn find_album(
+    conn: &mut SqliteConnection,
+    title: &str,
+    artist_id: Option<i64>,
+    release: Option<&str>,
+    matching: NameMatch,
+) -> Result<Option<i64>, AppError> {
+    let row: Option<(i64,)> = sqlx::query_as(&format!(
+        "SELECT id FROM albums
+         WHERE {} = ? AND artist_id IS ? AND (? IS NULL OR musicbrainz_id IS NULL OR musicbrainz_id = ?)
+         ORDER BY title = ? DESC, id",
+        matching.album_column()
+    ))
+    .bind(matching.value(title))
+    .bind(artist_id)
+    .bind(release)
+    .bind(release)
+    .bind(title)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(row.map(|(id,)| id))
+}
+
+/// Rule 3: the album of this title that already has another track in the
+/// key's folder, turned into a "Various Artists" album when this track's artist differs.
+async fn album_in_folder(
+    conn: &mut SqliteConnection,
+    key: &AlbumKey<'_>,
+    release: Option<&str>,
+    matching: NameMatch,
+) -> Result<Option<i64>, AppError> {
+    let prefix = match key.file_path.rsplit_once('/') {
+        Some((dir, _)) => format!("{}/", dir),
+        None => return Ok(None),
+    };
+    let row: Option<(i64, Option<String>, bool)> = sqlx::query_as(&format!(
+        "SELECT al.id, ar.name,
+                EXISTS(SELECT 1 FROM tracks o WHERE o.album_id = al.id AND o.album_artist IS NOT NULL)
+         FROM tracks t
+         JOIN albums al ON al.id = t.album_id
+         LEFT JOIN artists ar ON ar.id = al.artist_id
+         WHERE al.{} = ? AND (? IS NULL OR al.musicbrainz_id IS NULL OR al.musicbrainz_id = ?)
+           AND t.file_path != ?
+           AND substr(t.file_path, 1, length(?)) = ? AND instr(substr(t.file_path, length(?) + 1), '/') = 0
+         ORDER BY al.id
+         LIMIT 1",
+        matching.album_column()
+    ))
+    .bind(matching.value(key.title))
+    .bind(release)
+    .bind(release)
+    .bind(key.file_path)
+    .bind(&prefix)
+    .bind(&prefix)
+    .bind(&prefix)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some((album_id, album_artist, named)) = row else {
+        return Ok(None);
+    };
+    let album_artist = album_artist.unwrap_or_default();
+    let separators = credits::separators(conn).await?;
+    let artist = key.track_artist.map(|a| credits::primary_name(a, &separators)).unwrap_or_default();
+    if !named && album_artist != VARIOUS_ARTISTS && matching.value(&album_artist) != matching.value(&artist) {
+        let various = find_or_create_artist_by(conn, VARIOUS_ARTISTS, matching).await?;
+        sqlx::query("UPDATE albums SET artist_id = ? WHERE id = ?")
+            .bind(various)
+            .bind(album_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(Some(album_id))
+}
+
+/// The compilation flag and MusicBrainz release id stored for a track.
+pub async fn stored_album_tags(
+    conn: &mut SqliteConnection,
+    track_id: i64,
+) -> Result<(bool, Option<String>), AppError> {
+    let tags: Vec<(String, String)> =
+        sqlx::query_as("SELECT frame_id, value FROM track_extra_tags WHERE track_id = ? AND frame_id IN (?, ?)")
+            .bind(track_id)
+            .bind(COMPILATION_FRAME)
+            .bind(MUSICBRAINZ_RELEASE_FRAME)
+            .fetch_all(&mut *conn)
+            .await?;
+    let compilation = tags.iter().any(|(k, v)| k == COMPILATION_FRAME && is_compilation_flag(v));
+    let release = tags.into_iter().find(|(k, _)| k == MUSICBRAINZ_RELEASE_FRAME).map(|(_, v)| v);
+    Ok((compilation, release))
+}
+
+// ── Regrouping ──
+
+#[derive(sqlx::FromRow)]
+struct GroupedTrack {
+    id: i64,
+    album_id: Option<i64>,
+    file_path: String,
+    album_title: Option<String>,
+    album_year: Option<i32>,
+    album_artist: Option<String>,
+    artist_name: Option<String>,
+}
+
+/// Re-assign every track to its album under the current `AlbumKey` rules.
+/// Albums left without tracks are deleted; albums whose tracks changed lose
+/// their loudness values, which no longer cover the whole album. This is the
+/// backfill of migration 12, so names are matched exactly.
+pub async fn regroup_albums(conn: &mut SqliteConnection) -> Result<(), AppError> {
+    let tracks = sqlx::query_as::<_, GroupedTrack>(
+        "SELECT t.id, t.album_id, t.file_path, al.title AS album_title, al.year AS album_year,
+                t.album_artist, ar.name AS artist_name
+         FROM tracks t
+         LEFT JOIN albums al ON al.id = t.album_id
+         LEFT JOIN artists ar ON ar.id = t.artist_id
+         WHERE t.album_id IS NOT NULL
+         ORDER BY t.file_path",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    // Detach first so the folder rule only sees tracks already regrouped
+    sqlx::query("UPDATE tracks SET album_id = NULL WHERE album_id IS NOT NULL").execute(&mut *conn).await?;
+    // (old album, new album) of every track that changed album
+    let mut moves: Vec<(i64, i64)> = Vec::new();
+    for track in &tracks {
+        let Some(title) = track.album_title.as_deref() else { continue };
+        let (compilation, release) = stored_album_tags(conn, track.id).await?;
+        let key = AlbumKey {
+            title,
+            album_artist: track.album_artist.as_deref(),
+            track_artist: track.artist_name.as_deref(),
+            compilation,
+            musicbrainz_release_id: release.as_deref(),
+            file_path: &track.file_path,
+        };
+        let album_id = find_or_create_album_by(conn, &key, track.album_year, NameMatch::Exact).await?;
+        sqlx::query("UPDATE tracks SET album_id = ? WHERE id = ?")
+            .bind(album_id)
+            .bind(track.id)
+            .execute(&mut *conn)
+            .await?;
+        if let Some(old) = track.album_id.filter(|&old| old != album_id) {
+            moves.push((old, album_id));
+        }
+    }
+
+    // A regrouped album without a cover takes one from an album it absorbed
+    for &(old, new) in &moves {
+        sqlx::query(
+            "UPDATE albums SET cover_path = (SELECT cover_path FROM albums WHERE id = ?)
+             WHERE id = ? AND cover_path IS NULL",
+        )
+        .bind(old)
+        .bind(new)
+        .execute(&mut *conn)
+        .await?;
+    }
+    let changed: HashSet<i64> = moves.iter().flat_map(|&(old, new)| [old, new]).collect();
+    for album_id in changed {
+        sqlx::query("UPDATE albums SET loudness_lufs = NULL, true_peak = NULL, loudness_range_lu = NULL WHERE id = ?")
+            .bind(album_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let removed = sqlx::query("DELETE FROM albums WHERE id NOT IN (SELECT album_id FROM tracks WHERE album_id IS NOT NULL)")
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    info!("Regrouped albums: {} tracks moved, {} empty albums removed", moves.len(), removed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_album_artist_name_spellings() {
+        assert_eq!(album_artist_name(" VA "), VARIOUS_ARTISTS);
+        assert_eq!(album_artist_name("Various"), VARIOUS_ARTISTS);
+        assert_eq!(album_artist_name(" Vangelis "), "Vangelis");
+    }
+}
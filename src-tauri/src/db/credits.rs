@@ -0,0 +1,435 @@
+use super::albums::find_or_create_artist_by;
+use super::name_keys::NameMatch;
+use crate::models::AppError;
+use sqlx::SqliteConnection;
+use std::ops::Range;
+
+/// Setting: JSON array of the strings that separate names within one tag value,
+/// e.g. `["; ", " feat. "]`. Unset or invalid means `DEFAULT_SEPARATORS`.
+pub const ARTIST_SEPARATORS_SETTING: &str = "artist_separators";
+pub const DEFAULT_SEPARATORS: &[&str] = &["; ", " feat. ", " ft. ", " featuring "];
+/// Separators (trimmed, any case) after which track artists are credited as featured.
+const FEATURING_MARKERS: &[&str] = &["feat.", "feat", "ft.", "ft", "featuring"];
+
+pub const ROLE_ARTIST: &str = "artist";
+pub const ROLE_FEATURED: &str = "featured";
+
+/// A tag field that credits artists. Each maps to one role, except that
+/// featured guests of the track artist get `featured` and performers carry
+/// their instrument (`performer:cello`) when the value names one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreditField {
+    Artist,
+    Composer,
+    Lyricist,
+    Remixer,
+    Conductor,
+    Performer,
+}
+
+impl CreditField {
+    pub const ALL: &'static [CreditField] = &[
+        CreditField::Artist,
+        CreditField::Composer,
+        CreditField::Lyricist,
+        CreditField::Remixer,
+        CreditField::Conductor,
+        CreditField::Performer,
+    ];
+    /// The fields kept in `track_extra_tags` rather than a `tracks` column.
+    pub const EXTRA: &'static [CreditField] =
+        &[CreditField::Lyricist, CreditField::Remixer, CreditField::Conductor, CreditField::Performer];
+
+    fn role(self) -> &'static str {
+        match self {
+            CreditField::Artist => ROLE_ARTIST,
+            CreditField::Composer => "composer",
+            CreditField::Lyricist => "lyricist",
+            CreditField::Remixer => "remixer",
+            CreditField::Conductor => "conductor",
+            CreditField::Performer => "performer",
+        }
+    }
+
+    /// `track_extra_tags` frame id holding the field after the scan.
+    pub fn extra_frame(self) -> Option<&'static str> {
+        match self {
+            CreditField::Lyricist => Some("TEXT"),
+            CreditField::Remixer => Some("TPE4"),
+            CreditField::Conductor => Some("TPE3"),
+            // Vorbis comments and APE; ID3v2 has no plain performer frame
+            CreditField::Performer => Some("PERFORMER"),
+            CreditField::Artist | CreditField::Composer => None,
+        }
+    }
+
+    /// SQL condition matching the `track_artists` rows this field produces.
+    fn role_condition(self) -> &'static str {
+        match self {
+            CreditField::Artist => "role IN ('artist', 'featured')",
+            CreditField::Composer => "role = 'composer'",
+            CreditField::Lyricist => "role = 'lyricist'",
+            CreditField::Remixer => "role = 'remixer'",
+            CreditField::Conductor => "role = 'conductor'",
+            CreditField::Performer => "(role = 'performer' OR role LIKE 'performer:%')",
+        }
+    }
+}
+
+/// One credited name, in the order the tag lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credit {
+    pub role: String,
+    pub name: String,
+}
+
+fn is_featuring(separator: &str) -> bool {
+    FEATURING_MARKERS.contains(&separator.trim().to_lowercase().as_str())
+}
+
+/// Split one tag value into names, each flagged if it follows a featuring
+/// separator. A trailing "(feat. B)" or "[ft. B]" counts as featuring B.
+pub fn split_names(value: &str, separators: &[String]) -> Vec<(String, bool)> {
+    name_spans(value, separators)
+        .into_iter()
+        .map(|(span, featured)| (value[span].to_string(), featured))
+        .collect()
+}
+
+/// The first name of an artist tag value, which albums are filed under:
+/// "A feat. B", "A (ft. B)" and "A; B" all give "A".
+pub fn primary_name(value: &str, separators: &[String]) -> String {
+    split_names(value, separators).into_iter().next().map(|(name, _)| name).unwrap_or_default()
+}
+
+/// `value` with each name in `old` replaced by `new`, separators untouched.
+/// A replaced name that would repeat `new` is dropped with its separator.
+/// None if no name matched.
+pub fn replace_names(value: &str, old: &[&str], new: &str, separators: &[String]) -> Option<String> {
+    replace_spans(value, name_spans(value, separators), old, new)
+}
+
+/// `replace_names` for a performer value, whose names may each carry an
+/// instrument: "Yo-Yo Ma (cello)" has the name "Yo-Yo Ma".
+pub fn replace_performer_names(value: &str, old: &[&str], new: &str, separators: &[String]) -> Option<String> {
+    let spans = name_spans(value, separators)
+        .into_iter()
+        .map(|(span, featured)| {
+            let name = &value[span.clone()];
+            match (name.rfind('('), name.ends_with(')')) {
+                (Some(open), true) if !name[..open].trim().is_empty() => {
+                    (span.start..span.start + name[..open].trim_end().len(), featured)
+                }
+                _ => (span, featured),
+            }
+        })
+        .collect();
+    replace_spans(value, spans, old, new)
+}
+
+fn replace_spans(value: &str, spans: Vec<(Range<usize>, bool)>, old: &[&str], new: &str) -> Option<String> {
+    if !spans.iter().any(|(span, _)| old.contains(&&value[span.clone()])) {
+        return None;
+    }
+    let mut kept: Vec<(Range<usize>, &str)> = Vec::new();
+    for (span, _) in spans {
+        let name = &value[span.clone()];
+        let name = if old.contains(&name) { new } else { name };
+        kept.push((span, name));
+    }
+    // Drop repeats of `new` where only a separator (no bracket) goes with them
+    let mut out = String::new();
+    let mut pos = 0;
+    let mut seen_new = false;
+    for (i, (span, name)) in kept.iter().enumerate() {
+        let gap = &value[pos..span.start];
+        let is_bracketed = |gap: &str| gap.contains(['(', '[']);
+        if name == &new && seen_new && i > 0 && !is_bracketed(gap) {
+            pos = span.end;
+            continue;
+        }
+        seen_new |= name == &new;
+        out.push_str(gap);
+        out.push_str(name);
+        pos = span.end;
+    }
+    out.push_str(&value[pos..]);
+    Some(out)
+}
+
+/// Byte ranges of the names in `value`, in order, each flagged if featured.
+fn name_spans(value: &str, separators: &[String]) -> Vec<(Range<usize>, bool)> {
+    let end = value.trim_end().len();
+    let mut main = 0..end;
+    let mut guests = None;
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        let head = &value[..end];
+        if let (Some(start), true) = (head.rfind(open), head.ends_with(close)) {
+            let inner = &head[start + 1..end - 1];
+            let inner_start = start + 1 + (inner.len() - inner.trim_start().len());
+            let marker = inner.split_whitespace().next().unwrap_or("");
+            if !head[..start].trim().is_empty() && is_featuring(marker) {
+                guests = Some(inner_start + marker.len()..end - 1);
+                main = 0..start;
+                break;
+            }
+        }
+    }
+    let mut spans = Vec::new();
+    spans_into(value, main, separators, false, &mut spans);
+    if let Some(guests) = guests {
+        spans_into(value, guests, separators, true, &mut spans);
+    }
+    spans
+}
+
+fn spans_into(
+    value: &str,
+    range: Range<usize>,
+    separators: &[String],
+    mut featured: bool,
+    spans: &mut Vec<(Range<usize>, bool)>,
+) {
+    // ASCII lowercasing keeps byte offsets, so matches index the original too
+    let lower = value[range.clone()].to_ascii_lowercase();
+    let separators: Vec<(String, bool)> = separators
+        .iter()
+        .filter(|s| !s.is_empty())
+        .map(|s| (s.to_ascii_lowercase(), is_featuring(s)))
+        .collect();
+    let mut start = 0;
+    loop {
+        let next = separators
+            .iter()
+            .filter_map(|(sep, feat)| lower[start..].find(sep.as_str()).map(|at| (start + at, sep.len(), *feat)))
+            .min_by_key(|&(at, len, _)| (at, std::cmp::Reverse(len)));
+        let end = next.map_or(lower.len(), |(at, _, _)| at);
+        let part = &lower[start..end];
+        let name_start = start + (part.len() - part.trim_start().len());
+        let name_end = start + part.trim_end().len();
+        if name_start < name_end {
+            spans.push((range.start + name_start..range.start + name_end, featured));
+        }
+        match next {
+            Some((at, len, feat)) => {
+                featured |= feat;
+                start = at + len;
+            }
+            None => break,
+        }
+    }
+}
+
+/// "Yo-Yo Ma (cello)" gives the name and `performer:cello`.
+fn performer_credit(name: &str) -> Credit {
+    if let (Some(start), true) = (name.rfind('('), name.ends_with(')')) {
+        let instrument = name[start + 1..name.len() - 1].trim().to_lowercase();
+        let name = name[..start].trim();
+        if !instrument.is_empty() && !name.is_empty() {
+            return Credit { role: format!("performer:{}", instrument), name: name.to_string() };
+        }
+    }
+    Credit { role: "performer".to_string(), name: name.to_string() }
+}
+
+/// Credits for every value of every field, split on `separators`. A name is
+/// credited once per role; one already credited as artist or featured is not
+/// credited again as the other.
+pub fn credits_from_fields(fields: &[(CreditField, String)], separators: &[String]) -> Vec<Credit> {
+    let mut credits: Vec<Credit> = Vec::new();
+    for (field, value) in fields {
+        for (name, featured) in split_names(value, separators) {
+            let credit = match field {
+                CreditField::Performer => performer_credit(&name),
+                CreditField::Artist if featured => Credit { role: ROLE_FEATURED.to_string(), name },
+                _ => Credit { role: field.role().to_string(), name },
+            };
+            let artist_role = |role: &str| role == ROLE_ARTIST || role == ROLE_FEATURED;
+            let duplicate = credits.iter().any(|c| {
+                c.name.eq_ignore_ascii_case(&credit.name)
+                    && (c.role == credit.role || (artist_role(&c.role) && artist_role(&credit.role)))
+            });
+            if !duplicate {
+                credits.push(credit);
+            }
+        }
+    }
+    credits
+}
+
+// ── Storage ──
+
+/// The separators setting, or the defaults.
+pub async fn separators(conn: &mut SqliteConnection) -> Result<Vec<String>, AppError> {
+    let value: Option<(String,)> = sqlx::query_as("SELECT value FROM settings WHERE key = ?")
+        .bind(ARTIST_SEPARATORS_SETTING)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(value
+        .and_then(|(v,)| serde_json::from_str::<Vec<String>>(&v).ok())
+        .unwrap_or_else(|| DEFAULT_SEPARATORS.iter().map(|s| s.to_string()).collect()))
+}
+
+/// Replace the track's credits from `fields` with `credits`, numbering each
+/// role from 0 in order. Credits from other fields are kept.
+pub async fn replace_credits(
+    conn: &mut SqliteConnection,
+    track_id: i64,
+    fields: &[CreditField],
+    credits: &[Credit],
+) -> Result<(), AppError> {
+    let matching = NameMatch::configured(conn).await?;
+    replace_credits_by(conn, track_id, fields, credits, matching).await
+}
+
+async fn replace_credits_by(
+    conn: &mut SqliteConnection,
+    track_id: i64,
+    fields: &[CreditField],
+    credits: &[Credit],
+    matching: NameMatch,
+) -> Result<(), AppError> {
+    for field in fields {
+        sqlx::query(&format!("DELETE FROM track_artists WHERE track_id = ? AND {}", field.role_condition()))
+            .bind(track_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for (i, credit) in credits.iter().enumerate() {
+        let position = credits[..i].iter().filter(|c| c.role == credit.role).count() as i64;
+        let artist_id = find_or_create_artist_by(conn, &credit.name, matching).await?;
+        sqlx::query("INSERT INTO track_artists (track_id, artist_id, role, position) VALUES (?, ?, ?, ?)")
+            .bind(track_id)
+            .bind(artist_id)
+            .bind(&credit.role)
+            .bind(position)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Re-derive the track's credits for `fields` from what the DB stores.
+pub async fn refresh_credits(
+    conn: &mut SqliteConnection,
+    track_id: i64,
+    fields: &[CreditField],
+) -> Result<(), AppError> {
+    let credits = stored_credits(conn, track_id, fields).await?;
+    replace_credits(conn, track_id, fields, &credits).await
+}
+
+/// The track's credits for `fields` from the artist credit, the composer
+/// column and the extra tags.
+async fn stored_credits(
+    conn: &mut SqliteConnection,
+    track_id: i64,
+    fields: &[CreditField],
+) -> Result<Vec<Credit>, AppError> {
+    let mut values = Vec::new();
+    for &field in fields {
+        let value: Option<(Option<String>,)> = match field {
+            CreditField::Artist => {
+                sqlx::query_as("SELECT a.name FROM tracks t JOIN artists a ON a.id = t.artist_id WHERE t.id = ?")
+                    .bind(track_id)
+                    .fetch_optional(&mut *conn)
+                    .await?
+            }
+            CreditField::Composer => {
+                sqlx::query_as("SELECT composer FROM tracks WHERE id = ?")
+                    .bind(track_id)
+                    .fetch_optional(&mut *conn)
+                    .await?
+            }
+            _ => {
+                sqlx::query_as("SELECT value FROM track_extra_tags WHERE track_id = ? AND frame_id = ?")
+                    .bind(track_id)
+                    .bind(field.extra_frame())
+                    .fetch_optional(&mut *conn)
+                    .await?
+            }
+        };
+        values.extend(value.and_then(|(v,)| v).map(|v| (field, v)));
+    }
+    let separators = separators(conn).await?;
+    Ok(credits_from_fields(&values, &separators))
+}
+
+/// Credit every existing track from its stored tags (the backfill of
+/// migration 13, so artists are matched by exact name).
+pub async fn backfill_credits(conn: &mut SqliteConnection) -> Result<(), AppError> {
+    let track_ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM tracks").fetch_all(&mut *conn).await?;
+    for (track_id,) in track_ids {
+        let credits = stored_credits(conn, track_id, CreditField::ALL).await?;
+        replace_credits_by(conn, track_id, CreditField::ALL, &credits, NameMatch::Exact).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_SEPARATORS.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_credits_split_separators_and_roles() {
+        let fields = vec![
+            (CreditField::Artist, "Alice; Bob Feat. Carol".to_string()),
+            (CreditField::Artist, "Dave (ft. Erin)".to_string()),
+            // An ARTISTS list repeating names already credited
+            (CreditField::Artist, "Carol".to_string()),
+            (CreditField::Composer, "Alice".to_string()),
+            (CreditField::Performer, "Yo-Yo Ma (Cello)".to_string()),
+            (CreditField::Performer, "Frank".to_string()),
+        ];
+        let credits: Vec<(String, String)> = credits_from_fields(&fields, &defaults())
+            .into_iter()
+            .map(|c| (c.role, c.name))
+            .collect();
+        let expected = [
+            ("artist", "Alice"),
+            ("artist", "Bob"),
+            ("featured", "Carol"),
+            ("artist", "Dave"),
+            ("featured", "Erin"),
+            ("composer", "Alice"),
+            ("performer:cello", "Yo-Yo Ma"),
+            ("performer", "Frank"),
+        ];
+        let expected: Vec<(String, String)> = expected.iter().map(|(r, n)| (r.to_string(), n.to_string())).collect();
+        assert_eq!(credits, expected);
+
+        // Without separators a value is one name; AC/DC is never split
+        assert_eq!(split_names("AC/DC", &defaults()), vec![("AC/DC".to_string(), false)]);
+        assert_eq!(split_names("Simon & Garfunkel", &[" & ".to_string()]).len(), 2);
+    }
+
+    #[test]
+    fn test_primary_name_drops_guests() {
+        assert_eq!(primary_name("Daft Punk feat. Pharrell Williams", &defaults()), "Daft Punk");
+        assert_eq!(primary_name("Artist (ft. Someone)", &defaults()), "Artist");
+        assert_eq!(primary_name("Featuring Band", &defaults()), "Featuring Band");
+        assert_eq!(primary_name("Simon & Garfunkel", &[" & ".to_string()]), "Simon");
+        assert_eq!(primary_name("", &defaults()), "");
+    }
+
+    #[test]
+    fn test_replace_names_keeps_separators() {
+        let seps = defaults();
+        assert_eq!(replace_names("Beatles, The", &["Beatles, The"], "The Beatles", &seps).as_deref(), Some("The Beatles"));
+        assert_eq!(replace_names("Alpha feat. Beta", &["Beta"], "Gamma", &seps).as_deref(), Some("Alpha feat. Gamma"));
+        assert_eq!(replace_names("Alpha (ft. Beta)", &["Beta"], "Gamma", &seps).as_deref(), Some("Alpha (ft. Gamma)"));
+        // Merging two credited names into one keeps it once
+        assert_eq!(replace_names("Alpha; Beta", &["Beta"], "Alpha", &seps).as_deref(), Some("Alpha"));
+        assert_eq!(replace_names("Beta; Alpha", &["Beta"], "Alpha", &seps).as_deref(), Some("Alpha"));
+        assert_eq!(replace_names("Alphabet", &["Alpha"], "Gamma", &seps), None);
+        assert_eq!(
+            replace_performer_names("Alpha (cello); Beta", &["Alpha"], "Gamma", &seps).as_deref(),
+            Some("Gamma (cello); Beta")
+        );
+        assert_eq!(replace_performer_names("Alpha (cello)", &["Alpha (cello)"], "Gamma", &seps), None);
+    }
+}
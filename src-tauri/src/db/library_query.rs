@@ -0,0 +1,442 @@
+use super::DbPool;
+use crate::models::{
+    AppError, FieldPredicate, FilterValue, LibraryFilter, Page, PageRequest, RuleGroup, SortSpec,
+};
+use base64::Engine;
+use chrono::{TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, QueryBuilder, Row, Sqlite};
+
+const DEFAULT_PAGE_SIZE: i64 = 200;
+const MAX_PAGE_SIZE: i64 = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Number,
+}
+
+/// A row type that can be filtered, sorted and paged. Its `base` SELECT is
+/// wrapped as `r`, so every condition is written against the row's own columns.
+pub struct Source {
+    base: &'static str,
+    /// Filterable and sortable columns (snake_case, as in `base`)
+    columns: &'static [(&'static str, Kind)],
+    /// Columns matched by `LibraryFilter::text`
+    text_columns: &'static [&'static str],
+    /// Conditions for the filter's collection, artist and album ids (one `?` each)
+    collection_scope: &'static str,
+    artist_scope: &'static str,
+    album_scope: &'static str,
+    default_sort: &'static str,
+}
+
+pub const TRACKS: Source = Source {
+    base: "SELECT t.*, a.name as artist_name, al.title as album_title, al.cover_path as album_cover_path,
+                  al.loudness_lufs as album_loudness_lufs, al.true_peak as album_true_peak
+           FROM tracks t
+           LEFT JOIN artists a ON t.artist_id = a.id
+           LEFT JOIN albums al ON t.album_id = al.id",
+    columns: &[
+        ("title", Kind::Text),
+        ("artist_name", Kind::Text),
+        ("album_title", Kind::Text),
+        ("album_artist", Kind::Text),
+        ("genre", Kind::Text),
+        ("composer", Kind::Text),
+        ("comment", Kind::Text),
+        ("file_path", Kind::Text),
+        ("file_format", Kind::Text),
+        ("created_at", Kind::Text),
+        ("updated_at", Kind::Text),
+        ("year", Kind::Number),
+        ("track_number", Kind::Number),
+        ("track_total", Kind::Number),
+        ("disc_number", Kind::Number),
+        ("disc_total", Kind::Number),
+        ("bpm", Kind::Number),
+        ("duration_secs", Kind::Number),
+        ("file_size_bytes", Kind::Number),
+        ("bitrate_kbps", Kind::Number),
+        ("overall_bitrate_kbps", Kind::Number),
+        ("sample_rate_hz", Kind::Number),
+        ("channels", Kind::Number),
+        ("bit_depth", Kind::Number),
+        ("codec", Kind::Text),
+        ("codec_profile", Kind::Text),
+        ("loudness_lufs", Kind::Number),
+        ("true_peak", Kind::Number),
+        ("loudness_range_lu", Kind::Number),
+    ],
+    text_columns: &["title", "artist_name", "album_title"],
+    collection_scope: "r.collection_id = ?",
+    artist_scope: "r.id IN (SELECT track_id FROM artist_tracks WHERE artist_id = ?)",
+    album_scope: "r.album_id = ?",
+    default_sort: "title",
+};
+
+pub const ALBUM_ROWS: Source = Source {
+    base: "SELECT al.id, al.title, al.artist_id, ar.name as artist_name, al.year, al.genre,
+                  COUNT(t.id) as track_count,
+                  COALESCE(SUM(t.duration_secs), 0.0) as total_duration_secs,
+                  COALESCE(SUM(t.file_size_bytes), 0) as total_size_bytes,
+                  GROUP_CONCAT(DISTINCT t.codec) as codecs,
+                  CAST(ROUND(AVG(t.bitrate_kbps)) AS INTEGER) as avg_bitrate_kbps,
+                  MAX(t.sample_rate_hz) as max_sample_rate_hz,
+                  MAX(t.bit_depth) as max_bit_depth
+           FROM albums al
+           LEFT JOIN artists ar ON al.artist_id = ar.id
+           LEFT JOIN tracks t ON t.album_id = al.id
+           GROUP BY al.id",
+    columns: &[
+        ("title", Kind::Text),
+        ("artist_name", Kind::Text),
+        ("genre", Kind::Text),
+        ("year", Kind::Number),
+        ("track_count", Kind::Number),
+        ("total_duration_secs", Kind::Number),
+        ("total_size_bytes", Kind::Number),
+        ("codecs", Kind::Text),
+        ("avg_bitrate_kbps", Kind::Number),
+        ("max_sample_rate_hz", Kind::Number),
+        ("max_bit_depth", Kind::Number),
+    ],
+    text_columns: &["title", "artist_name"],
+    collection_scope: "EXISTS (SELECT 1 FROM tracks ct WHERE ct.album_id = r.id AND ct.collection_id = ?)",
+    artist_scope: "r.artist_id = ?",
+    album_scope: "r.id = ?",
+    default_sort: "title",
+};
+
+pub const ARTIST_ROWS: Source = Source {
+    base: "SELECT a.id, a.name, a.sort_name,
+                  (SELECT COUNT(*) FROM albums al WHERE al.artist_id = a.id) as album_count,
+                  (SELECT COUNT(*) FROM artist_tracks at WHERE at.artist_id = a.id) as track_count,
+                  (SELECT COALESCE(SUM(t.duration_secs), 0.0) FROM artist_tracks at
+                   JOIN tracks t ON t.id = at.track_id WHERE at.artist_id = a.id) as total_duration_secs
+           FROM artists a
+           WHERE EXISTS (SELECT 1 FROM albums al WHERE al.artist_id = a.id)
+              OR EXISTS (SELECT 1 FROM artist_tracks at WHERE at.artist_id = a.id)",
+    columns: &[
+        ("name", Kind::Text),
+        ("sort_name", Kind::Text),
+        ("album_count", Kind::Number),
+        ("track_count", Kind::Number),
+        ("total_duration_secs", Kind::Number),
+    ],
+    text_columns: &["name", "sort_name"],
+    collection_scope: "EXISTS (SELECT 1 FROM artist_tracks at JOIN tracks ct ON ct.id = at.track_id
+                               WHERE at.artist_id = r.id AND ct.collection_id = ?)",
+    artist_scope: "r.id = ?",
+    album_scope: "EXISTS (SELECT 1 FROM artist_tracks at JOIN tracks ct ON ct.id = at.track_id
+                          WHERE at.artist_id = r.id AND ct.album_id = ?)",
+    default_sort: "name",
+};
+
+/// Position after the last row of a page. Opaque to the frontend.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    field: String,
+    descending: bool,
+    key: FilterValue,
+    id: i64,
+}
+
+impl Cursor {
+    fn encode(&self) -> Result<String, AppError> {
+        Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+    }
+
+    fn decode(cursor: &str) -> Result<Self, AppError> {
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| AppError::InvalidInput("Malformed page cursor".to_string()))?;
+        serde_json::from_slice(&bytes).map_err(|_| AppError::InvalidInput("Malformed page cursor".to_string()))
+    }
+}
+
+impl Source {
+    fn column(&self, field: &str) -> Result<(&'static str, Kind), AppError> {
+        let snake = to_snake_case(field);
+        self.columns
+            .iter()
+            .find(|(name, _)| *name == snake)
+            .copied()
+            .ok_or_else(|| AppError::InvalidInput(format!("Cannot filter or sort by {:?}", field)))
+    }
+
+    /// Non-null sort expression, so keyset comparisons never meet a NULL.
+    /// Missing text sorts as empty, missing numbers as -1.
+    fn sort_expr(&self, field: &str) -> Result<String, AppError> {
+        Ok(match self.column(field)? {
+            (name, Kind::Text) => format!("COALESCE(r.{}, '') COLLATE NOCASE", name),
+            (name, Kind::Number) => format!("CAST(COALESCE(r.{}, -1) AS REAL)", name),
+        })
+    }
+
+    fn push_where(&self, qb: &mut QueryBuilder<'_, Sqlite>, filter: &LibraryFilter) -> Result<(), AppError> {
+        qb.push(" WHERE 1 = 1");
+        for (scope, id) in [
+            (self.collection_scope, filter.collection_id),
+            (self.artist_scope, filter.artist_id),
+            (self.album_scope, filter.album_id),
+        ] {
+            if let Some(id) = id {
+                let (before, after) = scope.split_once('?').unwrap_or((scope, ""));
+                qb.push(" AND ").push(before).push_bind(id).push(after);
+            }
+        }
+
+        if let Some(text) = filter.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            let pattern = format!("%{}%", escape_like(text));
+            qb.push(" AND (");
+            for (i, column) in self.text_columns.iter().enumerate() {
+                if i > 0 {
+                    qb.push(" OR ");
+                }
+                qb.push(format!("r.{} LIKE ", column)).push_bind(pattern.clone()).push(" ESCAPE '\\'");
+            }
+            qb.push(")");
+        }
+
+        for predicate in &filter.predicates {
+            qb.push(" AND ");
+            self.push_predicate(qb, predicate)?;
+        }
+        Ok(())
+    }
+
+    fn push_predicate(&self, qb: &mut QueryBuilder<'_, Sqlite>, predicate: &FieldPredicate) -> Result<(), AppError> {
+        match predicate {
+            FieldPredicate::Equals { field, value } => {
+                let (column, kind) = self.column(field)?;
+                qb.push(format!("r.{} = ", column));
+                push_value(qb, value);
+                if kind == Kind::Text {
+                    qb.push(" COLLATE NOCASE");
+                }
+            }
+            FieldPredicate::NotEquals { field, value } => {
+                let (column, kind) = self.column(field)?;
+                qb.push(format!("(r.{0} IS NULL OR r.{0} <> ", column));
+                push_value(qb, value);
+                if kind == Kind::Text {
+                    qb.push(" COLLATE NOCASE");
+                }
+                qb.push(")");
+            }
+            FieldPredicate::Contains { field, value } | FieldPredicate::StartsWith { field, value } => {
+                let column = match self.column(field)? {
+                    (column, Kind::Text) => column,
+                    _ => {
+                        return Err(AppError::InvalidInput(format!(
+                            "{:?} is not a text field",
+                            field
+                        )))
+                    }
+                };
+                let pattern = match predicate {
+                    FieldPredicate::Contains { .. } => format!("%{}%", escape_like(value)),
+                    _ => format!("{}%", escape_like(value)),
+                };
+                qb.push(format!("r.{} LIKE ", column)).push_bind(pattern).push(" ESCAPE '\\'");
+            }
+            FieldPredicate::Between { field, min, max } => {
+                let (column, _) = self.column(field)?;
+                qb.push("1 = 1");
+                if let Some(min) = min {
+                    qb.push(format!(" AND r.{} >= ", column));
+                    push_value(qb, min);
+                }
+                if let Some(max) = max {
+                    qb.push(format!(" AND r.{} <= ", column));
+                    push_value(qb, max);
+                }
+            }
+            FieldPredicate::GreaterThan { field, value } | FieldPredicate::LessThan { field, value } => {
+                let (column, _) = self.column(field)?;
+                let op = if matches!(predicate, FieldPredicate::GreaterThan { .. }) { ">" } else { "<" };
+                qb.push(format!("r.{} {} ", column, op));
+                push_value(qb, value);
+            }
+            FieldPredicate::InLastDays { field, days } => {
+                let (column, _) = self.column(field)?;
+                if !column.ends_with("_at") {
+                    return Err(AppError::InvalidInput(format!("{:?} is not a timestamp field", field)));
+                }
+                let since = TimeDelta::try_days((*days).max(0))
+                    .and_then(|span| Utc::now().checked_sub_signed(span))
+                    .ok_or_else(|| AppError::InvalidInput(format!("{} days is too long a span", days)))?;
+                qb.push(format!("r.{} >= ", column)).push_bind(since.to_rfc3339());
+            }
+            FieldPredicate::IsNull { field } => {
+                let (column, _) = self.column(field)?;
+                qb.push(format!("r.{} IS NULL", column));
+            }
+            FieldPredicate::IsNotNull { field } => {
+                let (column, _) = self.column(field)?;
+                qb.push(format!("r.{} IS NOT NULL", column));
+            }
+        }
+        Ok(())
+    }
+
+    fn push_group(&self, qb: &mut QueryBuilder<'_, Sqlite>, group: &RuleGroup) -> Result<(), AppError> {
+        if group.predicates.is_empty() && group.groups.is_empty() {
+            qb.push("1 = 1");
+            return Ok(());
+        }
+        let joiner = if group.match_all { " AND " } else { " OR " };
+        qb.push("(");
+        for (i, predicate) in group.predicates.iter().enumerate() {
+            if i > 0 {
+                qb.push(joiner);
+            }
+            self.push_predicate(qb, predicate)?;
+        }
+        for (i, nested) in group.groups.iter().enumerate() {
+            if i > 0 || !group.predicates.is_empty() {
+                qb.push(joiner);
+            }
+            self.push_group(qb, nested)?;
+        }
+        qb.push(")");
+        Ok(())
+    }
+
+    /// Check that every field in `rules` and `sort` exists and suits its predicate.
+    pub fn validate_rules(&self, rules: &RuleGroup, sort: Option<&SortSpec>) -> Result<(), AppError> {
+        self.push_group(&mut QueryBuilder::new(""), rules)?;
+        if let Some(sort) = sort {
+            self.column(&sort.field)?;
+        }
+        Ok(())
+    }
+}
+
+/// All `source` rows matching `rules`, ordered by `sort` (then id), keeping at
+/// most `limit` of them.
+pub async fn fetch_matching<T>(
+    db: &DbPool,
+    source: &Source,
+    rules: &RuleGroup,
+    sort: Option<&SortSpec>,
+    limit: Option<i64>,
+) -> Result<Vec<T>, AppError>
+where
+    T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let (sort_field, descending) = match sort {
+        Some(s) => (s.field.as_str(), s.descending),
+        None => (source.default_sort, false),
+    };
+    let sort_expr = source.sort_expr(sort_field)?;
+    let dir = if descending { "DESC" } else { "ASC" };
+
+    let mut select = QueryBuilder::<Sqlite>::new(format!("SELECT r.* FROM ({}) r WHERE ", source.base));
+    source.push_group(&mut select, rules)?;
+    select.push(format!(" ORDER BY {} {}, r.id {}", sort_expr, dir, dir));
+    if let Some(limit) = limit {
+        select.push(" LIMIT ").push_bind(limit.max(0));
+    }
+    Ok(select.build_query_as::<T>().fetch_all(db).await?)
+}
+
+/// Fetch one page of `source` rows matching `filter`, ordered by `sort` (then id)
+/// and positioned by either an offset or a cursor from the previous page.
+pub async fn fetch_page<T>(
+    db: &DbPool,
+    source: &Source,
+    filter: &LibraryFilter,
+    sort: Option<&SortSpec>,
+    page: &PageRequest,
+) -> Result<Page<T>, AppError>
+where
+    T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let (sort_field, descending) = match sort {
+        Some(s) => (s.field.as_str(), s.descending),
+        None => (source.default_sort, false),
+    };
+    let sort_expr = source.sort_expr(sort_field)?;
+    let sort_kind = source.column(sort_field)?.1;
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let cursor = page.cursor.as_deref().map(Cursor::decode).transpose()?;
+    if cursor.is_some() && page.offset.is_some() {
+        return Err(AppError::InvalidInput("Use either an offset or a cursor, not both".to_string()));
+    }
+
+    let mut count = QueryBuilder::<Sqlite>::new(format!("SELECT COUNT(*) FROM ({}) r", source.base));
+    source.push_where(&mut count, filter)?;
+    let (total,): (i64,) = count.build_query_as().fetch_one(db).await?;
+
+    let mut select =
+        QueryBuilder::<Sqlite>::new(format!("SELECT r.*, {} AS sort_key FROM ({}) r", sort_expr, source.base));
+    source.push_where(&mut select, filter)?;
+    let (cmp, dir) = if descending { ("<", "DESC") } else { (">", "ASC") };
+    if let Some(cursor) = &cursor {
+        if to_snake_case(&cursor.field) != to_snake_case(sort_field) || cursor.descending != descending {
+            return Err(AppError::InvalidInput("Page cursor belongs to a different sort order".to_string()));
+        }
+        select.push(format!(" AND ({} {} ", sort_expr, cmp));
+        push_value(&mut select, &cursor.key);
+        select.push(format!(" OR ({} = ", sort_expr));
+        push_value(&mut select, &cursor.key);
+        select.push(format!(" AND r.id {} ", cmp)).push_bind(cursor.id).push("))");
+    }
+    // One extra row tells us whether there is a next page
+    select
+        .push(format!(" ORDER BY {} {}, r.id {} LIMIT ", sort_expr, dir, dir))
+        .push_bind(limit + 1)
+        .push(" OFFSET ")
+        .push_bind(page.offset.unwrap_or(0).max(0));
+
+    let mut rows = select.build().fetch_all(db).await?;
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+
+    let next_cursor = match rows.last().filter(|_| has_more) {
+        Some(last) => {
+            let key = match sort_kind {
+                Kind::Text => FilterValue::Text(last.try_get("sort_key")?),
+                Kind::Number => FilterValue::Number(last.try_get("sort_key")?),
+            };
+            let cursor = Cursor {
+                field: sort_field.to_string(),
+                descending,
+                key,
+                id: last.try_get("id")?,
+            };
+            Some(cursor.encode()?)
+        }
+        None => None,
+    };
+
+    let rows = rows.iter().map(T::from_row).collect::<Result<Vec<T>, _>>()?;
+    Ok(Page { rows, total, next_cursor })
+}
+
+fn push_value(qb: &mut QueryBuilder<'_, Sqlite>, value: &FilterValue) {
+    match value {
+        FilterValue::Number(n) => qb.push_bind(*n),
+        FilterValue::Text(t) => qb.push_bind(t.clone()),
+    };
+}
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+fn to_snake_case(field: &str) -> String {
+    let mut out = String::with_capacity(field.len() + 4);
+    for c in field.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('_');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,404 @@
+use super::albums;
+use super::credits;
+use super::name_keys;
+use super::queries::*;
+use super::DbPool;
+use crate::models::AppError;
+use chrono::Utc;
+use log::info;
+use sqlx::SqliteConnection;
+use std::future::Future;
+use std::pin::Pin;
+
+/// A data migration written in Rust, run inside the migration's transaction.
+pub type Backfill = for<'c> fn(&'c mut SqliteConnection) -> Pin<Box<dyn Future<Output = Result<(), AppError>> + Send + 'c>>;
+
+/// One change within a migration.
+pub enum Step {
+    /// Plain SQL, e.g. `CREATE TABLE`, `CREATE INDEX` or a data backfill.
+    Sql(&'static str),
+    /// `ALTER TABLE .. ADD COLUMN`, skipped when the column already exists.
+    /// Databases created before versioning may or may not have the column.
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    },
+    /// A backfill too involved for SQL, e.g. re-deriving rows with app logic.
+    Backfill(Backfill),
+}
+
+/// A numbered schema change. Versions are applied in ascending order, each in its
+/// own transaction, and recorded in `schema_migrations`.
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// Every schema change, oldest first. Never edit or reorder an entry once it has
+/// shipped — append a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline schema",
+        steps: &[
+            Step::Sql(CREATE_COLLECTIONS_TABLE),
+            Step::Sql(CREATE_ARTISTS_TABLE),
+            Step::Sql(CREATE_ARTISTS_NAME_INDEX),
+            Step::Sql(CREATE_ALBUMS_TABLE),
+            Step::Sql(CREATE_ALBUMS_TITLE_INDEX),
+            Step::Sql(CREATE_ALBUMS_ARTIST_INDEX),
+            Step::Sql(CREATE_TRACKS_TABLE),
+            Step::Sql(CREATE_TRACKS_COLLECTION_INDEX),
+            Step::Sql(CREATE_TRACKS_ALBUM_INDEX),
+            Step::Sql(CREATE_TRACKS_ARTIST_INDEX),
+            Step::Sql(CREATE_TRACKS_FILE_PATH_INDEX),
+            Step::Sql(CREATE_SETTINGS_TABLE),
+            // Columns added before schema versioning existed
+            Step::AddColumn { table: "tracks", column: "genre", definition: "TEXT" },
+            Step::AddColumn { table: "tracks", column: "album_artist", definition: "TEXT" },
+            Step::AddColumn { table: "tracks", column: "composer", definition: "TEXT" },
+            Step::AddColumn { table: "tracks", column: "bpm", definition: "INTEGER" },
+            Step::AddColumn { table: "tracks", column: "comment", definition: "TEXT" },
+            Step::AddColumn { table: "tracks", column: "comment_lang", definition: "TEXT" },
+            Step::AddColumn { table: "tracks", column: "year", definition: "INTEGER" },
+            Step::AddColumn { table: "tracks", column: "lyrics_lang", definition: "TEXT" },
+            Step::AddColumn { table: "tracks", column: "track_total", definition: "INTEGER" },
+            Step::AddColumn { table: "tracks", column: "disc_total", definition: "INTEGER" },
+            Step::AddColumn { table: "tracks", column: "file_mtime", definition: "INTEGER" },
+            Step::AddColumn {
+                table: "collections",
+                column: "watch",
+                definition: "INTEGER NOT NULL DEFAULT 0",
+            },
+            Step::Sql(CREATE_TRACK_EXTRA_TAGS_TABLE),
+            Step::Sql(CREATE_TRACK_EXTRA_TAGS_INDEX),
+        ],
+    },
+    Migration {
+        version: 2,
+        description: "edit journal",
+        steps: &[
+            Step::Sql(CREATE_EDIT_OPERATIONS_TABLE),
+            Step::Sql(CREATE_EDIT_ENTRIES_TABLE),
+            Step::Sql(CREATE_EDIT_ENTRIES_OPERATION_INDEX),
+            Step::Sql(CREATE_EDIT_ENTRIES_TRACK_INDEX),
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "file backups",
+        steps: &[Step::Sql(CREATE_FILE_BACKUPS_TABLE)],
+    },
+    Migration {
+        version: 4,
+        description: "full-text search",
+        steps: &[
+            Step::Sql(CREATE_TRACKS_FTS_TABLE),
+            Step::Sql(CREATE_TRACKS_FTS_INSERT_TRIGGER),
+            Step::Sql(CREATE_TRACKS_FTS_UPDATE_TRIGGER),
+            Step::Sql(CREATE_TRACKS_FTS_DELETE_TRIGGER),
+            Step::Sql(CREATE_ARTISTS_FTS_RENAME_TRIGGER),
+            Step::Sql(CREATE_ALBUMS_FTS_RENAME_TRIGGER),
+            Step::Sql(BACKFILL_TRACKS_FTS),
+        ],
+    },
+    Migration {
+        version: 5,
+        description: "smart playlists",
+        steps: &[Step::Sql(CREATE_SMART_PLAYLISTS_TABLE)],
+    },
+    Migration {
+        version: 6,
+        description: "playlists",
+        steps: &[
+            Step::Sql(CREATE_PLAYLISTS_TABLE),
+            Step::Sql(CREATE_PLAYLIST_ENTRIES_TABLE),
+            Step::Sql(CREATE_PLAYLIST_ENTRIES_POSITION_INDEX),
+            Step::Sql(CREATE_PLAYLIST_ENTRIES_TRACK_INDEX),
+        ],
+    },
+    Migration {
+        version: 7,
+        description: "audio hashes",
+        steps: &[
+            Step::AddColumn { table: "tracks", column: "audio_hash", definition: "TEXT" },
+            Step::Sql(CREATE_TRACKS_AUDIO_HASH_INDEX),
+        ],
+    },
+    Migration {
+        version: 8,
+        description: "moved file matching",
+        steps: &[Step::Sql(CREATE_TRACKS_FILE_SIZE_INDEX)],
+    },
+    Migration {
+        version: 9,
+        description: "technical audio properties",
+        steps: &[
+            Step::AddColumn { table: "tracks", column: "overall_bitrate_kbps", definition: "INTEGER" },
+            Step::AddColumn { table: "tracks", column: "channels", definition: "INTEGER" },
+            Step::AddColumn { table: "tracks", column: "bit_depth", definition: "INTEGER" },
+            Step::AddColumn { table: "tracks", column: "codec", definition: "TEXT" },
+            Step::AddColumn { table: "tracks", column: "codec_profile", definition: "TEXT" },
+            Step::Sql(RESCAN_ALL_TRACKS),
+        ],
+    },
+    Migration {
+        version: 10,
+        description: "loudness analysis",
+        steps: &[
+            Step::AddColumn { table: "tracks", column: "loudness_lufs", definition: "REAL" },
+            Step::AddColumn { table: "tracks", column: "true_peak", definition: "REAL" },
+            Step::AddColumn { table: "tracks", column: "loudness_range_lu", definition: "REAL" },
+            // Audio hash of the file when it was measured; a mismatch means re-measure
+            Step::AddColumn { table: "tracks", column: "loudness_hash", definition: "TEXT" },
+            Step::AddColumn { table: "albums", column: "loudness_lufs", definition: "REAL" },
+            Step::AddColumn { table: "albums", column: "true_peak", definition: "REAL" },
+            Step::AddColumn { table: "albums", column: "loudness_range_lu", definition: "REAL" },
+        ],
+    },
+    Migration {
+        version: 11,
+        description: "audio fingerprints",
+        steps: &[
+            // Chromaprint sub-fingerprints, 32-bit little-endian each
+            Step::AddColumn { table: "tracks", column: "fingerprint", definition: "BLOB" },
+            Step::AddColumn { table: "tracks", column: "fingerprint_hash", definition: "TEXT" },
+        ],
+    },
+    Migration {
+        version: 12,
+        description: "album artist grouping",
+        steps: &[
+            Step::Sql(CREATE_ALBUMS_MUSICBRAINZ_INDEX),
+            Step::Backfill(|conn| Box::pin(albums::regroup_albums(conn))),
+        ],
+    },
+    Migration {
+        version: 13,
+        description: "track artist credits",
+        steps: &[
+            Step::Sql(CREATE_TRACK_ARTISTS_TABLE),
+            Step::Sql(CREATE_TRACK_ARTISTS_ARTIST_INDEX),
+            Step::Sql(CREATE_ARTIST_TRACKS_VIEW),
+            Step::Backfill(|conn| Box::pin(credits::backfill_credits(conn))),
+        ],
+    },
+    Migration {
+        version: 14,
+        description: "name keys",
+        steps: &[
+            // `name_keys::name_key` of the name or title, which lookups match on
+            Step::AddColumn { table: "artists", column: "name_key", definition: "TEXT" },
+            Step::AddColumn { table: "albums", column: "title_key", definition: "TEXT" },
+            Step::Backfill(|conn| Box::pin(name_keys::rekey_names(conn))),
+            Step::Sql(CREATE_ARTISTS_NAME_KEY_INDEX),
+            Step::Sql(CREATE_ALBUMS_TITLE_KEY_INDEX),
+        ],
+    },
+    Migration {
+        version: 15,
+        description: "artist tracks from credits",
+        steps: &[Step::Sql(DROP_ARTIST_TRACKS_VIEW), Step::Sql(CREATE_CREDITED_ARTIST_TRACKS_VIEW)],
+    },
+];
+
+/// Highest schema version this build knows about.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Bring the database up to `latest_version()`.
+/// Refuses to touch a database written by a newer build.
+pub async fn run_migrations(pool: &DbPool) -> Result<(), AppError> {
+    run(pool, MIGRATIONS).await
+}
+
+async fn run(pool: &DbPool, migrations: &[Migration]) -> Result<(), AppError> {
+    sqlx::query(CREATE_SCHEMA_MIGRATIONS_TABLE).execute(pool).await?;
+
+    let (current,): (i64,) = sqlx::query_as("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+        .fetch_one(pool)
+        .await?;
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    if current > latest {
+        return Err(AppError::Database(format!(
+            "Database schema version {} is newer than this build supports ({}); \
+             please update Chant",
+            current, latest
+        )));
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await?;
+        for step in migration.steps {
+            apply_step(&mut tx, step).await?;
+        }
+        sqlx::query("INSERT INTO schema_migrations (version, description, applied_at) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        info!("Applied migration {} ({})", migration.version, migration.description);
+    }
+
+    Ok(())
+}
+
+async fn apply_step(conn: &mut SqliteConnection, step: &Step) -> Result<(), AppError> {
+    match step {
+        Step::Sql(sql) => {
+            sqlx::query(sql).execute(&mut *conn).await?;
+        }
+        Step::AddColumn { table, column, definition } => {
+            let (exists,): (bool,) = sqlx::query_as(
+                "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?) WHERE name = ?)",
+            )
+            .bind(table)
+            .bind(column)
+            .fetch_one(&mut *conn)
+            .await?;
+            if !exists {
+                sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+        Step::Backfill(backfill) => backfill(conn).await?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn empty_pool() -> DbPool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    async fn applied_versions(pool: &DbPool) -> Vec<i64> {
+        sqlx::query_as::<_, (i64,)>("SELECT version FROM schema_migrations ORDER BY version")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(v,)| v)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_gets_every_migration_once() {
+        let pool = empty_pool().await;
+        run_migrations(&pool).await.unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        let expected: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(applied_versions(&pool).await, expected);
+        assert_eq!(*expected.last().unwrap(), latest_version());
+    }
+
+    #[tokio::test]
+    async fn test_upgrades_unversioned_database() {
+        // A database from before versioning: old tracks table without later columns
+        let pool = empty_pool().await;
+        sqlx::query(
+            "CREATE TABLE tracks (id INTEGER PRIMARY KEY AUTOINCREMENT, collection_id INTEGER NOT NULL, \
+             album_id INTEGER, artist_id INTEGER, title TEXT NOT NULL, lyrics TEXT, file_path TEXT NOT NULL UNIQUE, \
+             file_size_bytes INTEGER NOT NULL, created_at TEXT NOT NULL, updated_at TEXT NOT NULL, genre TEXT)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        run_migrations(&pool).await.unwrap();
+
+        let columns: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info('tracks')")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let names: Vec<&str> = columns.iter().map(|(n,)| n.as_str()).collect();
+        assert!(names.contains(&"genre"));
+        assert!(names.contains(&"file_mtime"));
+        assert!(names.contains(&"disc_total"));
+    }
+
+    #[tokio::test]
+    async fn test_backfills_before_name_keys() {
+        // A v11 database: regrouping and crediting run before the key columns exist
+        let pool = empty_pool().await;
+        run(&pool, &MIGRATIONS[..11]).await.unwrap();
+        for sql in [
+            "INSERT INTO collections (path, created_at) VALUES ('/music', '')",
+            "INSERT INTO artists (name, created_at) VALUES ('BJÖRK', '')",
+            "INSERT INTO albums (title, artist_id, created_at) VALUES ('Post', 1, '')",
+            "INSERT INTO tracks (collection_id, album_id, artist_id, title, file_path, file_size_bytes,
+                                 created_at, updated_at)
+             VALUES (1, 1, 1, 'Army of Me', '/music/Post/1.mp3', 1, '', '')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        run_migrations(&pool).await.unwrap();
+
+        let (artists, credits): (i64, i64) =
+            sqlx::query_as("SELECT (SELECT COUNT(*) FROM artists), (SELECT COUNT(*) FROM track_artists)")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((artists, credits), (1, 1));
+        let (name_key,): (Option<String>,) =
+            sqlx::query_as("SELECT name_key FROM artists").fetch_one(&pool).await.unwrap();
+        assert_eq!(name_key.as_deref(), Some("björk"));
+        // Every album the regrouping created is keyed too
+        let keys: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT title, title_key FROM albums").fetch_all(&pool).await.unwrap();
+        assert_eq!(keys, vec![("Post".to_string(), Some("post".to_string()))]);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_newer_database() {
+        let pool = empty_pool().await;
+        run_migrations(&pool).await.unwrap();
+        sqlx::query("INSERT INTO schema_migrations (version, description, applied_at) VALUES (?, 'future', '')")
+            .bind(latest_version() + 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        match run_migrations(&pool).await {
+            Err(AppError::Database(msg)) => assert!(msg.contains("newer than this build")),
+            other => panic!("Expected Database error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_rolls_back() {
+        const BROKEN: &[Migration] = &[Migration {
+            version: 1,
+            description: "broken",
+            steps: &[
+                Step::Sql("CREATE TABLE half_done (id INTEGER)"),
+                Step::Sql("THIS IS NOT SQL"),
+            ],
+        }];
+        let pool = empty_pool().await;
+        assert!(run(&pool, BROKEN).await.is_err());
+
+        let (tables,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE name = 'half_done'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(tables, 0);
+        assert!(applied_versions(&pool).await.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::models::AppError;
+use sqlx::SqliteConnection;
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// Setting: JSON array of the optional foldings applied when matching artist
+/// names and album titles, any of `"articles"` and `"punctuation"`. Unset or
+/// invalid means neither.
+pub const NAME_FOLDING_SETTING: &str = "name_folding";
+/// The article dropped by article folding, leading or moved to the end
+/// ("Beatles, The"). "A" and "An" are kept: "A Perfect Circle" isn't "Perfect Circle".
+const ARTICLE: &str = "the";
+
+/// Optional steps of `name_key` on top of Unicode and case normalization.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Folding {
+    pub articles: bool,
+    pub punctuation: bool,
+}
+
+impl Folding {
+    /// Every folding, as used for the near-duplicate report.
+    pub const ALL: Folding = Folding { articles: true, punctuation: true };
+
+    fn parse(value: &str) -> Folding {
+        let steps: Vec<String> = serde_json::from_str(value).unwrap_or_default();
+        Folding {
+            articles: steps.iter().any(|s| s == "articles"),
+            punctuation: steps.iter().any(|s| s == "punctuation"),
+        }
+    }
+}
+
+/// The key names are matched by: NFKC normalized, case folded and with
+/// whitespace trimmed and collapsed, so "AC/DC" and "Ac/Dc", or a composed
+/// and a decomposed "Björk", share one key. Article folding drops a leading
+/// or trailing "The"; punctuation folding keeps only letters and digits,
+/// unless that leaves nothing ("!!!").
+pub fn name_key(name: &str, folding: Folding) -> String {
+    // Case folding can denormalize ("ǅ"), so normalize again after it
+    let folded: String = name.nfkc().fold(String::new(), fold_case).nfkc().collect();
+    let mut key = folded.split_whitespace().collect::<Vec<_>>().join(" ");
+    if folding.articles {
+        key = strip_article(&key).to_string();
+    }
+    if folding.punctuation {
+        let letters: String = key.chars().filter(|c| c.is_alphanumeric()).collect();
+        if !letters.is_empty() {
+            key = letters;
+        }
+    }
+    key
+}
+
+/// Full case folding, which differs from lowercasing for a few letters.
+fn fold_case(mut out: String, c: char) -> String {
+    match c {
+        'ß' | 'ẞ' => out.push_str("ss"),
+        'ς' => out.push('σ'),
+        _ => out.extend(c.to_lowercase()),
+    }
+    out
+}
+
+fn strip_article(key: &str) -> &str {
+    key.strip_prefix(ARTICLE)
+        .and_then(|r| r.strip_prefix(' '))
+        .or_else(|| key.strip_suffix(ARTICLE).and_then(|r| r.strip_suffix(", ")))
+        .filter(|r| !r.trim().is_empty())
+        .unwrap_or(key)
+}
+
+/// The configured folding.
+pub async fn folding(conn: &mut SqliteConnection) -> Result<Folding, AppError> {
+    let value: Option<(String,)> = sqlx::query_as("SELECT value FROM settings WHERE key = ?")
+        .bind(NAME_FOLDING_SETTING)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(value.map(|(v,)| Folding::parse(&v)).unwrap_or_default())
+}
+
+/// How a name is matched to a stored artist or album.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameMatch {
+    /// By `name_key` under this folding, on the key columns.
+    Key(Folding),
+    /// By exact name. The backfills of migrations 12 and 13 match this way:
+    /// they run before migration 14 adds the key columns, and rows they insert
+    /// get their keys from its rekey.
+    Exact,
+}
+
+impl NameMatch {
+    /// Matching by key under the configured folding.
+    pub async fn configured(conn: &mut SqliteConnection) -> Result<NameMatch, AppError> {
+        Ok(NameMatch::Key(folding(conn).await?))
+    }
+
+    /// What `name` is compared by.
+    pub fn value(self, name: &str) -> String {
+        match self {
+            NameMatch::Key(folding) => name_key(name, folding),
+            NameMatch::Exact => name.to_string(),
+        }
+    }
+
+    /// The `artists` column holding `value`.
+    pub fn artist_column(self) -> &'static str {
+        match self {
+            NameMatch::Key(_) => "name_key",
+            NameMatch::Exact => "name",
+        }
+    }
+
+    /// The `albums` column holding `value`.
+    pub fn album_column(self) -> &'static str {
+        match self {
+            NameMatch::Key(_) => "title_key",
+            NameMatch::Exact => "title",
+        }
+    }
+}
+
+/// Recompute every stored artist and album key, after the folding setting
+/// changed. Entities that now share a key are left apart; the near-duplicate
+/// report lists them for merging.
+pub async fn rekey_names(conn: &mut SqliteConnection) -> Result<(), AppError> {
+    let folding = folding(conn).await?;
+    let artists: Vec<(i64, String)> = sqlx::query_as("SELECT id, name FROM artists").fetch_all(&mut *conn).await?;
+    for (id, name) in artists {
+        sqlx::query("UPDATE artists SET name_key = ? WHERE id = ?")
+            .bind(name_key(&name, folding))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let albums: Vec<(i64, String)> = sqlx::query_as("SELECT id, title FROM albums").fetch_all(&mut *conn).await?;
+    for (id, title) in albums {
+        sqlx::query("UPDATE albums SET title_key = ? WHERE id = ?")
+            .bind(name_key(&title, folding))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Groups of two or more items sharing a key, ordered by key.
+pub fn near_duplicates<T>(items: Vec<T>, key: impl Fn(&T) -> String) -> Vec<Vec<T>> {
+    let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for item in items {
+        groups.entry(key(&item)).or_default().push(item);
+    }
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_key_folding() {
+        let plain = Folding::default();
+        assert_eq!(name_key("AC/DC", plain), name_key("Ac/Dc", plain));
+        assert_eq!(name_key("Bjo\u{308}rk", plain), name_key("Bj\u{f6}rk", plain));
+        assert_eq!(name_key("  Stra\u{df}e   Band ", plain), "strasse band");
+        assert_eq!(name_key("ＡＢＢＡ", plain), "abba");
+        assert_ne!(name_key("The Beatles", plain), name_key("Beatles, The", plain));
+
+        assert_eq!(name_key("The Beatles", Folding::ALL), "beatles");
+        assert_eq!(name_key("Beatles, The", Folding::ALL), "beatles");
+        assert_eq!(name_key("AC DC", Folding::ALL), name_key("AC/DC", Folding::ALL));
+        assert_eq!(name_key("The The", Folding::ALL), "the");
+        assert_eq!(name_key("!!!", Folding::ALL), "!!!");
+        assert_eq!(name_key("A Perfect Circle", Folding::ALL), "aperfectcircle");
+    }
+}
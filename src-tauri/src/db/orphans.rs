@@ -0,0 +1,86 @@
+use crate::models::AppError;
+use log::warn;
+use sqlx::SqliteConnection;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// What `prune_orphans` deleted.
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub artists: u32,
+    pub albums: u32,
+    /// Cover files of the deleted albums that no other album uses. Delete them
+    /// with `remove_cover_files` once the transaction has committed.
+    pub cover_paths: Vec<String>,
+}
+
+/// Delete albums without tracks, then artists that no track, credit or album
+/// refers to, such as the "A feat. B" row of a credit that was edited away.
+/// Artists given a sort name or MusicBrainz id are kept for when they return.
+pub async fn prune_orphans(conn: &mut SqliteConnection) -> Result<Pruned, AppError> {
+    let albums: Vec<(i64, Option<String>)> = sqlx::query_as(
+        "SELECT id, cover_path FROM albums al
+         WHERE NOT EXISTS (SELECT 1 FROM tracks t WHERE t.album_id = al.id)",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    for (album_id, _) in &albums {
+        sqlx::query("DELETE FROM albums WHERE id = ?").bind(album_id).execute(&mut *conn).await?;
+    }
+
+    let artists = sqlx::query(
+        "DELETE FROM artists WHERE
+             NOT EXISTS (SELECT 1 FROM tracks t WHERE t.artist_id = artists.id)
+             AND NOT EXISTS (SELECT 1 FROM albums al WHERE al.artist_id = artists.id)
+             AND NOT EXISTS (SELECT 1 FROM track_artists ta WHERE ta.artist_id = artists.id)
+             AND sort_name IS NULL AND musicbrainz_id IS NULL",
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    // Merged and split albums can share one cover file
+    let album_count = albums.len() as u32;
+    let mut cover_paths = Vec::new();
+    let candidates: HashSet<String> = albums.into_iter().filter_map(|(_, cover)| cover).collect();
+    for path in candidates {
+        let (in_use,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM albums WHERE cover_path = ?)")
+            .bind(&path)
+            .fetch_one(&mut *conn)
+            .await?;
+        if !in_use {
+            cover_paths.push(path);
+        }
+    }
+
+    Ok(Pruned { artists: artists as u32, albums: album_count, cover_paths })
+}
+
+/// Files in `covers_dir` that no album uses as its cover.
+pub async fn unused_cover_files(conn: &mut SqliteConnection, covers_dir: &Path) -> Result<Vec<String>, AppError> {
+    let Ok(entries) = std::fs::read_dir(covers_dir) else { return Ok(Vec::new()) };
+    let used: Vec<(String,)> = sqlx::query_as("SELECT cover_path FROM albums WHERE cover_path IS NOT NULL")
+        .fetch_all(&mut *conn)
+        .await?;
+    let used: HashSet<String> = used.into_iter().map(|(path,)| path).collect();
+    Ok(entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .map(|e| e.path().to_string_lossy().replace('\\', "/"))
+        .filter(|path| !used.contains(path))
+        .collect())
+}
+
+/// Delete cover files, returning how many were removed. Failures are logged
+/// and skipped; a leftover file is picked up by the next vacuum.
+pub fn remove_cover_files(paths: &[String]) -> u32 {
+    let mut removed = 0;
+    for path in paths {
+        match std::fs::remove_file(path) {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not remove cover file {:?}: {}", path, e),
+        }
+    }
+    removed
+}
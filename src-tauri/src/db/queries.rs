@@ -1,3 +1,13 @@
+// ── Schema versioning ──
+
+pub const CREATE_SCHEMA_MIGRATIONS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS schema_migrations (
+    version      INTEGER PRIMARY KEY,
+    description  TEXT NOT NULL,
+    applied_at   TEXT NOT NULL
+)
+"#;
+
 // ── Collections ──
 
 pub const CREATE_COLLECTIONS_TABLE: &str = r#"
@@ -5,7 +15,8 @@ CREATE TABLE IF NOT EXISTS collections (
     id          INTEGER PRIMARY KEY AUTOINCREMENT,
     path        TEXT NOT NULL UNIQUE,
     label       TEXT,
-    created_at  TEXT NOT NULL
+    created_at  TEXT NOT NULL,
+    watch       INTEGER NOT NULL DEFAULT 0
 )
 "#;
 
@@ -48,6 +59,10 @@ pub const CREATE_ALBUMS_ARTIST_INDEX: &str = r#"
 CREATE INDEX IF NOT EXISTS idx_albums_artist_id ON albums(artist_id)
 "#;
 
+pub const CREATE_ALBUMS_MUSICBRAINZ_INDEX: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_albums_musicbrainz_id ON albums(musicbrainz_id)
+"#;
+
 // ── Tracks ──
 
 pub const CREATE_TRACKS_TABLE: &str = r#"
@@ -107,33 +122,6 @@ CREATE TABLE IF NOT EXISTS settings (
 )
 "#;
 
-// ── Track column migrations ──
-// For existing databases that were created before these columns existed.
-// Execute each separately; ignore "already has a column named" errors (idempotent).
-
-pub const MIGRATE_TRACKS_ADD_GENRE: &str =
-    "ALTER TABLE tracks ADD COLUMN genre TEXT";
-pub const MIGRATE_TRACKS_ADD_ALBUM_ARTIST: &str =
-    "ALTER TABLE tracks ADD COLUMN album_artist TEXT";
-pub const MIGRATE_TRACKS_ADD_COMPOSER: &str =
-    "ALTER TABLE tracks ADD COLUMN composer TEXT";
-pub const MIGRATE_TRACKS_ADD_BPM: &str =
-    "ALTER TABLE tracks ADD COLUMN bpm INTEGER";
-pub const MIGRATE_TRACKS_ADD_COMMENT: &str =
-    "ALTER TABLE tracks ADD COLUMN comment TEXT";
-pub const MIGRATE_TRACKS_ADD_COMMENT_LANG: &str =
-    "ALTER TABLE tracks ADD COLUMN comment_lang TEXT";
-pub const MIGRATE_TRACKS_ADD_YEAR: &str =
-    "ALTER TABLE tracks ADD COLUMN year INTEGER";
-pub const MIGRATE_TRACKS_ADD_LYRICS_LANG: &str =
-    "ALTER TABLE tracks ADD COLUMN lyrics_lang TEXT";
-pub const MIGRATE_TRACKS_ADD_TRACK_TOTAL: &str =
-    "ALTER TABLE tracks ADD COLUMN track_total INTEGER";
-pub const MIGRATE_TRACKS_ADD_DISC_TOTAL: &str =
-    "ALTER TABLE tracks ADD COLUMN disc_total INTEGER";
-pub const MIGRATE_TRACKS_ADD_FILE_MTIME: &str =
-    "ALTER TABLE tracks ADD COLUMN file_mtime INTEGER";
-
 // ── Extra tags table ──
 
 pub const CREATE_TRACK_EXTRA_TAGS_TABLE: &str = r#"
@@ -147,3 +135,215 @@ CREATE TABLE IF NOT EXISTS track_extra_tags (
 
 pub const CREATE_TRACK_EXTRA_TAGS_INDEX: &str =
     "CREATE INDEX IF NOT EXISTS idx_track_extra_tags_track_id ON track_extra_tags(track_id)";
+
+// ── Edit history ──
+
+pub const CREATE_EDIT_OPERATIONS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS edit_operations (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    label       TEXT NOT NULL,
+    created_at  TEXT NOT NULL,
+    undone      INTEGER NOT NULL DEFAULT 0
+)
+"#;
+
+pub const CREATE_EDIT_ENTRIES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS edit_entries (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation_id  INTEGER NOT NULL REFERENCES edit_operations(id) ON DELETE CASCADE,
+    track_id      INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    before_tags   TEXT NOT NULL,
+    after_tags    TEXT NOT NULL
+)
+"#;
+
+pub const CREATE_EDIT_ENTRIES_OPERATION_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_edit_entries_operation_id ON edit_entries(operation_id)";
+pub const CREATE_EDIT_ENTRIES_TRACK_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_edit_entries_track_id ON edit_entries(track_id)";
+
+// ── File backups ──
+
+pub const CREATE_FILE_BACKUPS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS file_backups (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_path    TEXT NOT NULL UNIQUE,
+    mode         TEXT NOT NULL,
+    backup_path  TEXT NOT NULL,
+    created_at   TEXT NOT NULL
+)
+"#;
+
+// ── Full-text search ──
+
+/// One row per track (rowid = track id) with the joined artist and album names,
+/// kept in sync by the triggers below.
+pub const CREATE_TRACKS_FTS_TABLE: &str = r#"
+CREATE VIRTUAL TABLE IF NOT EXISTS tracks_fts USING fts5(
+    title, artist, album, album_artist, composer, genre, comment, lyrics,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+)
+"#;
+
+pub const CREATE_TRACKS_FTS_INSERT_TRIGGER: &str = r#"
+CREATE TRIGGER IF NOT EXISTS tracks_fts_insert AFTER INSERT ON tracks BEGIN
+    INSERT INTO tracks_fts (rowid, title, artist, album, album_artist, composer, genre, comment, lyrics)
+    VALUES (
+        new.id, new.title,
+        (SELECT name FROM artists WHERE id = new.artist_id),
+        (SELECT title FROM albums WHERE id = new.album_id),
+        new.album_artist, new.composer, new.genre, new.comment, new.lyrics
+    );
+END
+"#;
+
+pub const CREATE_TRACKS_FTS_UPDATE_TRIGGER: &str = r#"
+CREATE TRIGGER IF NOT EXISTS tracks_fts_update AFTER UPDATE ON tracks BEGIN
+    DELETE FROM tracks_fts WHERE rowid = old.id;
+    INSERT INTO tracks_fts (rowid, title, artist, album, album_artist, composer, genre, comment, lyrics)
+    VALUES (
+        new.id, new.title,
+        (SELECT name FROM artists WHERE id = new.artist_id),
+        (SELECT title FROM albums WHERE id = new.album_id),
+        new.album_artist, new.composer, new.genre, new.comment, new.lyrics
+    );
+END
+"#;
+
+pub const CREATE_TRACKS_FTS_DELETE_TRIGGER: &str = r#"
+CREATE TRIGGER IF NOT EXISTS tracks_fts_delete AFTER DELETE ON tracks BEGIN
+    DELETE FROM tracks_fts WHERE rowid = old.id;
+END
+"#;
+
+pub const CREATE_ARTISTS_FTS_RENAME_TRIGGER: &str = r#"
+CREATE TRIGGER IF NOT EXISTS artists_fts_rename AFTER UPDATE OF name ON artists BEGIN
+    UPDATE tracks_fts SET artist = new.name
+    WHERE rowid IN (SELECT id FROM tracks WHERE artist_id = new.id);
+END
+"#;
+
+pub const CREATE_ALBUMS_FTS_RENAME_TRIGGER: &str = r#"
+CREATE TRIGGER IF NOT EXISTS albums_fts_rename AFTER UPDATE OF title ON albums BEGIN
+    UPDATE tracks_fts SET album = new.title
+    WHERE rowid IN (SELECT id FROM tracks WHERE album_id = new.id);
+END
+"#;
+
+/// Index tracks that existed before the table did.
+pub const BACKFILL_TRACKS_FTS: &str = r#"
+INSERT INTO tracks_fts (rowid, title, artist, album, album_artist, composer, genre, comment, lyrics)
+SELECT t.id, t.title, a.name, al.title, t.album_artist, t.composer, t.genre, t.comment, t.lyrics
+FROM tracks t
+LEFT JOIN artists a ON t.artist_id = a.id
+LEFT JOIN albums al ON t.album_id = al.id
+"#;
+
+// ── Smart playlists ──
+
+/// `rules` is a JSON `RuleGroup`; the sort and limit live in their own columns.
+pub const CREATE_SMART_PLAYLISTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS smart_playlists (
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    name             TEXT NOT NULL UNIQUE,
+    rules            TEXT NOT NULL,
+    sort_field       TEXT,
+    sort_descending  INTEGER NOT NULL DEFAULT 0,
+    track_limit      INTEGER,
+    created_at       TEXT NOT NULL,
+    updated_at       TEXT NOT NULL
+)
+"#;
+
+// ── Audio hashes ──
+
+pub const CREATE_TRACKS_AUDIO_HASH_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_tracks_audio_hash ON tracks(audio_hash)";
+
+/// Narrows the size/duration/tag match for moved files that have no stored hash.
+pub const CREATE_TRACKS_FILE_SIZE_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_tracks_file_size ON tracks(file_size_bytes)";
+
+/// Forget every stored mtime so the next scan re-reads each file, filling in
+/// columns that older scans never wrote.
+pub const RESCAN_ALL_TRACKS: &str = "UPDATE tracks SET file_mtime = NULL";
+
+// ── Playlists ──
+
+pub const CREATE_PLAYLISTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS playlists (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    name        TEXT NOT NULL UNIQUE,
+    created_at  TEXT NOT NULL,
+    updated_at  TEXT NOT NULL
+)
+"#;
+
+/// A track may appear more than once. `position` orders the entries of a playlist;
+/// edits renumber them from 0, but deleting a track leaves gaps until the next edit.
+pub const CREATE_PLAYLIST_ENTRIES_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS playlist_entries (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    playlist_id  INTEGER NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+    track_id     INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    position     INTEGER NOT NULL
+)
+"#;
+
+pub const CREATE_PLAYLIST_ENTRIES_POSITION_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_playlist_entries_position ON playlist_entries(playlist_id, position)";
+
+pub const CREATE_PLAYLIST_ENTRIES_TRACK_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_playlist_entries_track ON playlist_entries(track_id)";
+
+// ── Track credits ──
+
+/// Every artist a track credits, by role: `artist`, `featured`, `composer`,
+/// `lyricist`, `remixer`, `conductor`, `performer` or `performer:<instrument>`.
+/// `position` orders the names within a role, from 0.
+pub const CREATE_TRACK_ARTISTS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS track_artists (
+    track_id   INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    artist_id  INTEGER NOT NULL REFERENCES artists(id) ON DELETE CASCADE,
+    role       TEXT NOT NULL,
+    position   INTEGER NOT NULL,
+    PRIMARY KEY (track_id, role, position)
+)
+"#;
+
+pub const CREATE_TRACK_ARTISTS_ARTIST_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS idx_track_artists_artist ON track_artists(artist_id, role)";
+
+/// The v13 `artist_tracks`, which also counted `tracks.artist_id` of credited
+/// tracks: an "A feat. B" artist got the track on top of "A" and "B". Replaced
+/// in v15 by `CREATE_CREDITED_ARTIST_TRACKS_VIEW`.
+pub const CREATE_ARTIST_TRACKS_VIEW: &str = r#"
+CREATE VIEW IF NOT EXISTS artist_tracks (artist_id, track_id) AS
+SELECT artist_id, id FROM tracks WHERE artist_id IS NOT NULL
+UNION
+SELECT artist_id, track_id FROM track_artists
+"#;
+
+pub const DROP_ARTIST_TRACKS_VIEW: &str = "DROP VIEW IF EXISTS artist_tracks";
+
+/// Each track an artist is linked to, once: through any role in
+/// `track_artists`, or the track's artist (`tracks.artist_id`) when the track
+/// has no credits.
+pub const CREATE_CREDITED_ARTIST_TRACKS_VIEW: &str = r#"
+CREATE VIEW IF NOT EXISTS artist_tracks (artist_id, track_id) AS
+SELECT artist_id, id FROM tracks t
+WHERE artist_id IS NOT NULL AND NOT EXISTS (SELECT 1 FROM track_artists ta WHERE ta.track_id = t.id)
+UNION
+SELECT artist_id, track_id FROM track_artists
+"#;
+
+// ── Name keys ──
+
+pub const CREATE_ARTISTS_NAME_KEY_INDEX: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_artists_name_key ON artists(name_key)
+"#;
+
+pub const CREATE_ALBUMS_TITLE_KEY_INDEX: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_albums_title_key ON albums(title_key, artist_id)
+"#;
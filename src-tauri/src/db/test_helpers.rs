@@ -1,8 +1,11 @@
 use sqlx::SqlitePool;
+use super::migrations::run_migrations;
 use super::DbPool;
-use super::queries::*;
+use crate::models::TrackRow;
 
 pub async fn setup_test_db() -> DbPool {
+    // Pooled like init_db; sqlx opens `:memory:` as a shared-cache database,
+    // so every connection of the pool sees the same data
     let pool = SqlitePool::connect("sqlite::memory:")
         .await
         .expect("Failed to create in-memory SQLite pool");
@@ -12,23 +15,56 @@ pub async fn setup_test_db() -> DbPool {
         .await
         .expect("Failed to enable foreign keys");
 
-    // Create all tables (same as init_db)
-    sqlx::query(CREATE_COLLECTIONS_TABLE).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_ARTISTS_TABLE).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_ARTISTS_NAME_INDEX).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_ALBUMS_TABLE).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_ALBUMS_TITLE_INDEX).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_ALBUMS_ARTIST_INDEX).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_TRACKS_TABLE).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_TRACKS_COLLECTION_INDEX).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_TRACKS_ALBUM_INDEX).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_TRACKS_ARTIST_INDEX).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_TRACKS_FILE_PATH_INDEX).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_SETTINGS_TABLE).execute(&pool).await.unwrap();
-    // CREATE_TRACKS_TABLE already includes all extended columns;
-    // no ALTER TABLE migrations needed for a fresh in-memory DB.
-    sqlx::query(CREATE_TRACK_EXTRA_TAGS_TABLE).execute(&pool).await.unwrap();
-    sqlx::query(CREATE_TRACK_EXTRA_TAGS_INDEX).execute(&pool).await.unwrap();
+    // Same migration runner as init_db, so tests always see the production schema
+    run_migrations(&pool).await.expect("Failed to run migrations");
 
     pool
 }
+
+/// A track with only an id, title and path set; override the rest with
+/// `TrackRow { year: Some(1999), ..track_row(1, "Song") }`.
+pub fn track_row(id: i64, title: &str) -> TrackRow {
+    TrackRow {
+        id,
+        collection_id: 1,
+        album_id: None,
+        artist_id: None,
+        title: title.to_string(),
+        track_number: None,
+        disc_number: None,
+        duration_secs: None,
+        file_path: format!("/nonexistent/{}.mp3", id),
+        file_size_bytes: 0,
+        file_format: None,
+        bitrate_kbps: None,
+        sample_rate_hz: None,
+        overall_bitrate_kbps: None,
+        channels: None,
+        bit_depth: None,
+        codec: None,
+        codec_profile: None,
+        lyrics: None,
+        created_at: String::new(),
+        updated_at: String::new(),
+        genre: None,
+        album_artist: None,
+        composer: None,
+        bpm: None,
+        comment: None,
+        comment_lang: None,
+        year: None,
+        lyrics_lang: None,
+        track_total: None,
+        disc_total: None,
+        file_mtime: None,
+        audio_hash: None,
+        loudness_lufs: None,
+        true_peak: None,
+        loudness_range_lu: None,
+        artist_name: None,
+        album_title: None,
+        album_cover_path: None,
+        album_loudness_lufs: None,
+        album_true_peak: None,
+    }
+}
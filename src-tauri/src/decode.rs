@@ -0,0 +1,272 @@
+use crate::jobs::JobControl;
+use crate::models::AppError;
+use audiopus::coder::Decoder as LibOpusDecoder;
+use audiopus::packet::Packet;
+use audiopus::{Channels, MutSignals, SampleRate};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS};
+use symphonia::core::conv::ConvertibleSample;
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Format of the decoded audio handed to the `decode_file` callback.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamFormat {
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+/// Opus frames are at most 120 ms, at 48 kHz.
+const OPUS_MAX_FRAMES_PER_PACKET: usize = 5760;
+
+/// Ogg Opus through libopus, since symphonia has no Opus decoder: mono or
+/// stereo at 48 kHz, with the header's output gain applied and its pre-skip
+/// dropped, as a player would.
+struct OpusDecoder {
+    decoder: LibOpusDecoder,
+    channels: usize,
+    /// Frames still to drop from the start of the stream
+    skip: usize,
+    pcm: Vec<f32>,
+}
+
+impl OpusDecoder {
+    fn new(params: &CodecParameters) -> Result<OpusDecoder, String> {
+        let channels = params.channels.map(|c| c.count()).unwrap_or(0);
+        let layout = match channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            n => return Err(format!("Opus with {} channels is not supported", n)),
+        };
+        let decoder = LibOpusDecoder::new(SampleRate::Hz48000, layout).map_err(|e| e.to_string())?;
+        // Output gain: Q7.8 dB at bytes 16-17 of the OpusHead packet
+        if let Some(&[lo, hi]) = params.extra_data.as_deref().and_then(|head| head.get(16..18)) {
+            decoder.set_gain(i16::from_le_bytes([lo, hi]) as i32).map_err(|e| e.to_string())?;
+        }
+        Ok(OpusDecoder {
+            decoder,
+            channels,
+            skip: params.delay.unwrap_or(0) as usize,
+            pcm: vec![0.0; OPUS_MAX_FRAMES_PER_PACKET * channels],
+        })
+    }
+
+    /// The packet's interleaved samples, or None for a corrupt packet.
+    fn decode(&mut self, packet: &[u8]) -> Option<&[f32]> {
+        let packet = Packet::try_from(packet).ok()?;
+        let output = MutSignals::try_from(&mut self.pcm).ok()?;
+        let frames = self.decoder.decode_float(Some(packet), output, false).ok()?;
+        let skipped = frames.min(self.skip);
+        self.skip -= skipped;
+        Some(&self.pcm[skipped * self.channels..frames * self.channels])
+    }
+}
+
+enum AudioDecoder {
+    Symphonia(Box<dyn Decoder>),
+    Opus(OpusDecoder),
+}
+
+/// Decode the first audio track of `path`, passing each packet's interleaved
+/// samples to `on_samples` until it breaks or the stream ends. Returns false if
+/// the job was cancelled part-way; pauses are honoured between packets.
+/// Formats neither symphonia nor libopus decode (APE, WavPack, Musepack) fail with an error.
+pub fn decode_file<S: ConvertibleSample>(
+    path: &Path,
+    control: &JobControl,
+    mut on_samples: impl FnMut(StreamFormat, &[S]) -> Result<ControlFlow<()>, AppError>,
+) -> Result<bool, AppError> {
+    let decode_error = |e: DecodeError| AppError::Io(format!("Cannot decode {:?}: {}", path, e));
+    let file = File::open(path).map_err(|e| AppError::Io(format!("Failed to open {:?}: {}", path, e)))?;
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(Box::new(file), Default::default()),
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(decode_error)?;
+    let mut format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| AppError::Io(format!("No audio track in {:?}", path)))?;
+    let track_id = track.id;
+    let mut decoder = if track.codec_params.codec == CODEC_TYPE_OPUS {
+        let opus = OpusDecoder::new(&track.codec_params)
+            .map_err(|e| AppError::Io(format!("Cannot decode {:?}: {}", path, e)))?;
+        AudioDecoder::Opus(opus)
+    } else {
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(decode_error)?;
+        AudioDecoder::Symphonia(decoder)
+    };
+
+    let mut samples: Option<SampleBuffer<S>> = None;
+    let mut converted: Vec<S> = Vec::new();
+    loop {
+        if !control.wait_while_paused() {
+            return Ok(false);
+        }
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(decode_error(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let flow = match &mut decoder {
+            AudioDecoder::Symphonia(decoder) => {
+                let decoded = match decoder.decode(&packet) {
+                    Ok(decoded) => decoded,
+                    // A corrupt frame is skipped, as a player would
+                    Err(DecodeError::DecodeError(_)) => continue,
+                    Err(e) => return Err(decode_error(e)),
+                };
+                let spec = *decoded.spec();
+                let buf = match &mut samples {
+                    Some(buf) if buf.capacity() >= decoded.capacity() * spec.channels.count() => buf,
+                    _ => samples.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+                };
+                buf.copy_interleaved_ref(decoded);
+                let stream = StreamFormat { sample_rate: spec.rate, channels: spec.channels.count() };
+                on_samples(stream, buf.samples())?
+            }
+            AudioDecoder::Opus(opus) => {
+                let Some(pcm) = opus.decode(packet.buf()) else { continue };
+                converted.clear();
+                converted.extend(pcm.iter().map(|&v| S::from_sample(v)));
+                let stream = StreamFormat { sample_rate: 48_000, channels: opus.channels };
+                on_samples(stream, &converted)?
+            }
+        };
+        if flow.is_break() {
+            break;
+        }
+    }
+    Ok(true)
+}
+
+/// Write a 16-bit PCM WAV of `frames` (one sample per channel each).
+#[cfg(test)]
+pub(crate) fn write_test_wav(path: &Path, sample_rate: u32, channels: u16, frames: impl Iterator<Item = f64>) {
+    let mut data = Vec::new();
+    for v in frames {
+        let v = (v.clamp(-1.0, 1.0) * 32767.0) as i16;
+        for _ in 0..channels {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    let block_align = channels * 2;
+    let mut wav = b"RIFF".to_vec();
+    wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(&data);
+    std::fs::write(path, wav).unwrap();
+}
+
+/// Write a 48 kHz Ogg Opus file of `frames` (one sample per channel each),
+/// encoded in 20 ms packets with libopus.
+#[cfg(test)]
+pub(crate) fn write_test_opus(path: &Path, channels: u16, frames: impl Iterator<Item = f64>) {
+    use audiopus::coder::Encoder;
+    const PRE_SKIP: u16 = 312;
+    const FRAME: usize = 960;
+    let layout = if channels == 1 { Channels::Mono } else { Channels::Stereo };
+    let encoder = Encoder::new(SampleRate::Hz48000, layout, audiopus::Application::Audio).unwrap();
+
+    let mut head = b"OpusHead\x01".to_vec();
+    head.push(channels as u8);
+    head.extend_from_slice(&PRE_SKIP.to_le_bytes());
+    head.extend_from_slice(&48_000u32.to_le_bytes());
+    head.extend_from_slice(&[0, 0, 0]);
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&4u32.to_le_bytes());
+    tags.extend_from_slice(b"test");
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    let mut ogg = Vec::new();
+    write_test_ogg_page(&mut ogg, 0x02, 0, 0, &head);
+    write_test_ogg_page(&mut ogg, 0x00, 0, 1, &tags);
+
+    let mut pcm: Vec<i16> = Vec::new();
+    for v in frames {
+        let v = (v.clamp(-1.0, 1.0) * 32767.0) as i16;
+        pcm.extend(std::iter::repeat_n(v, channels as usize));
+    }
+    // Pad to whole packets, plus the pre-skip the decoder drops
+    let total = pcm.len() / channels as usize;
+    pcm.resize((total + PRE_SKIP as usize).div_ceil(FRAME) * FRAME * channels as usize, 0);
+    let chunks: Vec<&[i16]> = pcm.chunks(FRAME * channels as usize).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let mut packet = [0u8; 4000];
+        let len = encoder.encode(chunk, &mut packet).unwrap();
+        let last = i + 1 == chunks.len();
+        let granule = if last { total as u64 + PRE_SKIP as u64 } else { ((i + 1) * FRAME) as u64 };
+        write_test_ogg_page(&mut ogg, if last { 0x04 } else { 0x00 }, granule, i as u32 + 2, &packet[..len]);
+    }
+    std::fs::write(path, ogg).unwrap();
+}
+
+/// Append one Ogg page holding the whole of `packet`, with its CRC.
+#[cfg(test)]
+fn write_test_ogg_page(out: &mut Vec<u8>, flags: u8, granule: u64, sequence: u32, packet: &[u8]) {
+    let start = out.len();
+    out.extend_from_slice(b"OggS\x00");
+    out.push(flags);
+    out.extend_from_slice(&granule.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&sequence.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    let mut lacing = vec![255u8; packet.len() / 255];
+    lacing.push((packet.len() % 255) as u8);
+    out.push(lacing.len() as u8);
+    out.extend_from_slice(&lacing);
+    out.extend_from_slice(packet);
+    let crc = out[start..].iter().fold(0u32, |crc, &byte| {
+        (0..8).fold(crc ^ (byte as u32) << 24, |c, _| if c & 0x8000_0000 != 0 { c << 1 ^ 0x04c1_1db7 } else { c << 1 })
+    });
+    out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// 15 seconds of two-note chords that change every quarter second, picked by
+/// a simple generator from `seed`: music enough to fingerprint.
+#[cfg(test)]
+pub(crate) fn write_test_melody(path: &Path, seed: u64, sample_rate: u32, amplitude: f64) {
+    let mut state = seed;
+    let notes: Vec<(f64, f64)> = (0..60)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let note = |shift: u32| 220.0 * 2f64.powf(((state >> shift) % 24) as f64 / 12.0);
+            (note(33), note(45))
+        })
+        .collect();
+    let frames = (0..sample_rate as usize * 15).map(|i| {
+        let t = i as f64 / sample_rate as f64;
+        let (f1, f2) = notes[(t * 4.0) as usize % notes.len()];
+        let tau = 2.0 * std::f64::consts::PI;
+        amplitude * 0.5 * ((tau * f1 * t).sin() + (tau * f2 * t).sin())
+    });
+    write_test_wav(path, sample_rate, 2, frames);
+}
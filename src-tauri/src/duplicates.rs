@@ -0,0 +1,274 @@
+use crate::audio_hash;
+use crate::backups;
+use crate::db::DbPool;
+use crate::models::{AppError, DuplicateAction, DuplicateCriteria, DuplicateGroup, RemovedDuplicate, TrackRow};
+use crate::organize::move_file;
+use lofty::config::ParseOptions;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// ── Grouping ──
+
+/// Lowercase words joined by single spaces, punctuation dropped:
+/// "Don't  Stop (Live)" and "dont stop live" give the same key.
+fn match_key(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '’')
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Split `tracks` (in ascending order of duration) into runs whose durations
+/// are all within `tolerance` of the run's shortest, so a chain of small steps
+/// doesn't join tracks far apart. Tracks without a duration form their own run.
+fn split_by_duration(mut tracks: Vec<TrackRow>, tolerance: f64) -> Vec<Vec<TrackRow>> {
+    tracks.sort_by(|a, b| a.duration_secs.partial_cmp(&b.duration_secs).unwrap_or(std::cmp::Ordering::Equal));
+    let mut runs: Vec<Vec<TrackRow>> = Vec::new();
+    for track in tracks {
+        let joins_last = runs.last().and_then(|run| run.first()).is_some_and(|first| {
+            match (first.duration_secs, track.duration_secs) {
+                (Some(a), Some(b)) => b - a <= tolerance,
+                (None, None) => true,
+                _ => false,
+            }
+        });
+        match runs.last_mut() {
+            Some(run) if joins_last => run.push(track),
+            _ => runs.push(vec![track]),
+        }
+    }
+    runs
+}
+
+/// Keep only the buckets that could still hold duplicates.
+fn multi<K>(buckets: HashMap<K, Vec<TrackRow>>) -> impl Iterator<Item = Vec<TrackRow>> {
+    buckets.into_values().filter(|b| b.len() > 1)
+}
+
+fn split_by_hash(
+    tracks: Vec<TrackRow>,
+    hash: impl Fn(&TrackRow) -> Result<String, AppError>,
+) -> Vec<Vec<TrackRow>> {
+    let mut by_hash: HashMap<String, Vec<TrackRow>> = HashMap::new();
+    for track in tracks {
+        match hash(&track) {
+            Ok(h) => by_hash.entry(h).or_default().push(track),
+            Err(e) => warn!("Skipping track {} in duplicate check: {}", track.id, e),
+        }
+    }
+    multi(by_hash).collect()
+}
+
+/// Group `tracks` into duplicate sets, each ranked best copy first.
+/// Reads files for the content criteria, so call off the async runtime.
+pub fn find_duplicates(tracks: Vec<TrackRow>, criteria: &DuplicateCriteria) -> Vec<DuplicateGroup> {
+    let groups: Vec<Vec<TrackRow>> = match criteria {
+        DuplicateCriteria::Metadata { tolerance_secs } => {
+            let mut buckets: HashMap<(String, String), Vec<TrackRow>> = HashMap::new();
+            for track in tracks {
+                let key = (match_key(&track.title), match_key(track.artist_name.as_deref().unwrap_or("")));
+                if !key.0.is_empty() {
+                    buckets.entry(key).or_default().push(track);
+                }
+            }
+            multi(buckets).flat_map(|b| split_by_duration(b, tolerance_secs.max(0.0))).collect()
+        }
+        DuplicateCriteria::FileContent => {
+            let mut by_size: HashMap<i64, Vec<TrackRow>> = HashMap::new();
+            for track in tracks {
+                by_size.entry(track.file_size_bytes).or_default().push(track);
+            }
+            multi(by_size)
+                .flat_map(|b| split_by_hash(b, |t| audio_hash::file_hash(Path::new(&t.file_path))))
+                .collect()
+        }
+        // Uses the hash stored by the last scan; only files scanned before
+        // hashing existed are read now
+        DuplicateCriteria::AudioContent => split_by_hash(tracks, |t| match &t.audio_hash {
+            Some(hash) => Ok(hash.clone()),
+            None => audio_hash::audio_hash(Path::new(&t.file_path)),
+        }),
+    };
+
+    let mut ranked: Vec<DuplicateGroup> = groups.into_iter().filter(|g| g.len() > 1).map(rank).collect();
+    ranked.sort_by_key(|g| g.best_track_id);
+    ranked
+}
+
+// ── Ranking ──
+
+/// Compared field by field, so being lossless outweighs any bitrate.
+#[derive(Debug, PartialEq, PartialOrd)]
+struct Quality {
+    lossless: bool,
+    bitrate_kbps: u32,
+    sample_rate_hz: u32,
+    bit_depth: u8,
+    file_size_bytes: i64,
+}
+
+fn quality(track: &TrackRow) -> Quality {
+    // Rows scanned before technical properties were stored fall back to the file
+    let properties = track
+        .sample_rate_hz
+        .is_none()
+        .then(|| Probe::open(&track.file_path))
+        .and_then(Result::ok)
+        .map(|p| p.options(ParseOptions::new().read_tags(false).read_cover_art(false)))
+        .and_then(|p| p.read().ok())
+        .map(|f| f.properties().clone());
+    let from_file = |get: fn(&lofty::properties::FileProperties) -> Option<u32>| properties.as_ref().and_then(get);
+    let bit_depth = track
+        .bit_depth
+        .map(|b| b as u8)
+        .or_else(|| properties.as_ref().and_then(|p| p.bit_depth()));
+
+    Quality {
+        // Only PCM-based formats (FLAC, ALAC, WAV, AIFF, APE, WavPack) report a bit depth
+        lossless: bit_depth.is_some(),
+        bitrate_kbps: track
+            .bitrate_kbps
+            .map(|b| b as u32)
+            .or_else(|| from_file(|p| p.audio_bitrate()))
+            .unwrap_or(0),
+        sample_rate_hz: track
+            .sample_rate_hz
+            .map(|s| s as u32)
+            .or_else(|| from_file(|p| p.sample_rate()))
+            .unwrap_or(0),
+        bit_depth: bit_depth.unwrap_or(0),
+        file_size_bytes: track.file_size_bytes,
+    }
+}
+
+fn rank(tracks: Vec<TrackRow>) -> DuplicateGroup {
+    let mut scored: Vec<(Quality, TrackRow)> = tracks.into_iter().map(|t| (quality(&t), t)).collect();
+    // Best first; the oldest row wins a tie
+    scored.sort_by(|(qa, a), (qb, b)| {
+        qb.partial_cmp(qa).unwrap_or(std::cmp::Ordering::Equal).then(a.id.cmp(&b.id))
+    });
+    let tracks: Vec<TrackRow> = scored.into_iter().map(|(_, t)| t).collect();
+    DuplicateGroup { best_track_id: tracks[0].id, tracks }
+}
+
+// ── Removal ──
+
+/// `dir/name`, or `dir/stem (2).ext`, `dir/stem (3).ext`, … if taken.
+fn free_target(dir: &Path, from: &Path) -> PathBuf {
+    let name = from.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut target = dir.join(&name);
+    let stem = from.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = from.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut n = 2;
+    while target.exists() {
+        target = dir.join(format!("{} ({}){}", stem, n, ext));
+        n += 1;
+    }
+    target
+}
+
+/// Delete or move each track's file, then drop its row. A track whose file
+/// can't be handled keeps its row and reports why.
+pub async fn remove_tracks(
+    db: &DbPool,
+    tracks: Vec<(i64, String)>,
+    action: &DuplicateAction,
+) -> Result<Vec<RemovedDuplicate>, AppError> {
+    let mut removed = Vec::with_capacity(tracks.len());
+    for (track_id, file_path) in tracks {
+        if matches!(action, DuplicateAction::Delete) {
+            if let Err(e) = backups::backup_file(db, &file_path).await {
+                warn!("Not deleting duplicate {:?}: backup failed: {}", file_path, e);
+                let problem = Some(format!("Backup failed: {}", e));
+                removed.push(RemovedDuplicate { track_id, file_path, moved_to: None, problem });
+                continue;
+            }
+        }
+        let path = PathBuf::from(&file_path);
+        let outcome = match action {
+            DuplicateAction::Delete => match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(None),
+            },
+            DuplicateAction::MoveTo { directory } => {
+                let target = free_target(Path::new(directory), &path);
+                move_file(&path, &target).map(|()| Some(target.to_string_lossy().replace('\\', "/")))
+            }
+        };
+        let entry = match outcome {
+            Ok(moved_to) => {
+                sqlx::query("DELETE FROM tracks WHERE id = ?").bind(track_id).execute(db).await?;
+                RemovedDuplicate { track_id, file_path, moved_to, problem: None }
+            }
+            Err(e) => {
+                warn!("Could not remove duplicate {:?}: {}", file_path, e);
+                RemovedDuplicate { track_id, file_path, moved_to: None, problem: Some(e.to_string()) }
+            }
+        };
+        removed.push(entry);
+    }
+    info!("Removed {} duplicate tracks", removed.iter().filter(|r| r.problem.is_none()).count());
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_helpers::track_row;
+
+    fn track(id: i64, title: &str, artist: &str, duration: f64) -> TrackRow {
+        TrackRow {
+            duration_secs: Some(duration),
+            artist_name: Some(artist.to_string()),
+            ..track_row(id, title)
+        }
+    }
+
+    #[test]
+    fn test_match_key() {
+        assert_eq!(match_key("Don't  Stop (Live)"), "dont stop live");
+        assert_eq!(match_key("AC/DC"), "ac dc");
+    }
+
+    #[test]
+    fn test_metadata_groups_respect_tolerance() {
+        let tracks = vec![
+            track(1, "Song", "Band", 200.0),
+            track(2, "song!", "BAND", 201.5),
+            track(3, "Song", "Band", 240.0),
+            track(4, "Other", "Band", 200.0),
+        ];
+        let groups = find_duplicates(tracks, &DuplicateCriteria::Metadata { tolerance_secs: 2.0 });
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<i64> = groups[0].tracks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(groups[0].best_track_id, 1);
+    }
+
+    #[test]
+    fn test_duration_groups_measure_from_first_track() {
+        // Each step is within tolerance, but 200 and 203 are not
+        let tracks = vec![
+            track(1, "Song", "Band", 200.0),
+            track(2, "Song", "Band", 201.5),
+            track(3, "Song", "Band", 203.0),
+        ];
+        let groups = find_duplicates(tracks, &DuplicateCriteria::Metadata { tolerance_secs: 2.0 });
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<i64> = groups[0].tracks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn test_rank_prefers_higher_bitrate() {
+        let mut low = track(1, "Song", "Band", 200.0);
+        low.bitrate_kbps = Some(128);
+        let mut high = track(2, "Song", "Band", 200.0);
+        high.bitrate_kbps = Some(320);
+        assert_eq!(rank(vec![low, high]).best_track_id, 2);
+    }
+}
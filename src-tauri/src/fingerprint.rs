@@ -0,0 +1,258 @@
+use crate::decode::decode_file;
+use crate::jobs::JobControl;
+use crate::models::{AppError, AudioMatch};
+use base64::Engine;
+use rusty_chromaprint::{match_fingerprints, Configuration, FingerprintCompressor, Fingerprinter};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+/// Audio fingerprinted from the start of each track; the same as `fpcalc`,
+/// which AcoustID lookups expect.
+const FINGERPRINT_SECS: usize = 120;
+/// Sub-fingerprints that more tracks than this share (mostly silence) don't
+/// pick candidates; they would pair up nearly every track.
+const MAX_TRACKS_PER_ITEM: usize = 64;
+/// Sub-fingerprints of each track that go in the index, so it stays the same
+/// small size per track however long the fingerprints are.
+const INDEXED_ITEMS_PER_TRACK: usize = 64;
+/// Indexed sub-fingerprints two tracks must share before they are compared in full.
+const MIN_SHARED_ITEMS: usize = 4;
+/// Share of the shorter track that must match when the caller gives no threshold.
+pub const DEFAULT_MIN_SIMILARITY: f64 = 0.5;
+
+/// Chromaprint's default algorithm (TEST2), the one AcoustID indexes.
+fn config() -> Configuration {
+    Configuration::preset_test2()
+}
+
+/// Chromaprint fingerprint of the first two minutes of the file. Returns None
+/// if the job was cancelled part-way.
+pub fn fingerprint_file(path: &Path, control: &JobControl) -> Result<Option<Vec<u32>>, AppError> {
+    let mut printer = Fingerprinter::new(&config());
+    let mut remaining: Option<usize> = None;
+    let finished = decode_file::<i16>(path, control, |stream, samples| {
+        let remaining = match &mut remaining {
+            Some(remaining) => remaining,
+            None => {
+                printer
+                    .start(stream.sample_rate, stream.channels as u32)
+                    .map_err(|e| AppError::Io(format!("Cannot fingerprint {:?}: {}", path, e.to_string().trim())))?;
+                remaining.insert(FINGERPRINT_SECS * stream.sample_rate as usize * stream.channels)
+            }
+        };
+        let take = samples.len().min(*remaining);
+        printer.consume(&samples[..take]);
+        *remaining -= take;
+        Ok(if *remaining == 0 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) })
+    })?;
+    if !finished {
+        return Ok(None);
+    }
+    if remaining.is_none() {
+        return Err(AppError::Io(format!("No audio decoded from {:?}", path)));
+    }
+    printer.finish();
+    let fingerprint = printer.fingerprint().to_vec();
+    if fingerprint.is_empty() {
+        return Err(AppError::Io(format!("{:?} is too short to fingerprint", path)));
+    }
+    Ok(Some(fingerprint))
+}
+
+/// Raw sub-fingerprints as stored in `tracks.fingerprint`.
+pub fn to_blob(fingerprint: &[u32]) -> Vec<u8> {
+    fingerprint.iter().flat_map(|item| item.to_le_bytes()).collect()
+}
+
+pub fn from_blob(blob: &[u8]) -> Vec<u32> {
+    blob.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+/// The compressed, URL-safe base64 form that `fpcalc` prints and AcoustID takes.
+pub fn acoustid_string(fingerprint: &[u32]) -> String {
+    let config = config();
+    let compressed = FingerprintCompressor::from(&config).compress(fingerprint);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(compressed)
+}
+
+// ── Matching ──
+
+/// How much of two fingerprints matches: the share of the shorter one covered
+/// by matching segments, and how long those segments are.
+fn compare(a: &[u32], b: &[u32]) -> Option<(f64, f64)> {
+    let config = config();
+    let segments = match_fingerprints(a, b, &config).ok()?;
+    let matched: usize = segments.iter().map(|s| s.items_count).sum();
+    let shorter = a.len().min(b.len());
+    if matched == 0 || shorter == 0 {
+        return None;
+    }
+    let similarity = (matched as f64 / shorter as f64).min(1.0);
+    Some((similarity, matched as f64 * config.item_duration_in_seconds() as f64))
+}
+
+/// The sub-fingerprints of `fingerprint` that get indexed: those that sort
+/// first by a scrambled value (a bottom-k sketch). Recordings with much audio
+/// in common have many of these in common, too.
+fn sketch(fingerprint: &[u32]) -> Vec<u32> {
+    let key = |item: &u32| item.wrapping_mul(0x9e37_79b1);
+    let mut items = fingerprint.to_vec();
+    items.sort_unstable_by_key(key);
+    items.dedup();
+    items.truncate(INDEXED_ITEMS_PER_TRACK);
+    items
+}
+
+/// Fingerprints of many tracks, indexed by a sample of their sub-fingerprints
+/// so a track is only compared in full with the few that share some of its audio.
+pub struct FingerprintIndex {
+    fingerprints: Vec<(i64, Vec<u32>)>,
+    by_item: HashMap<u32, Vec<usize>>,
+}
+
+impl FingerprintIndex {
+    pub fn new(fingerprints: Vec<(i64, Vec<u32>)>) -> Self {
+        let mut by_item: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (pos, (_, fingerprint)) in fingerprints.iter().enumerate() {
+            for item in sketch(fingerprint) {
+                by_item.entry(item).or_default().push(pos);
+            }
+        }
+        by_item.retain(|_, tracks| tracks.len() <= MAX_TRACKS_PER_ITEM);
+        Self { fingerprints, by_item }
+    }
+
+    /// Positions of the tracks sharing enough sub-fingerprints with `fingerprint`.
+    fn candidates(&self, fingerprint: &[u32]) -> Vec<usize> {
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for item in sketch(fingerprint) {
+            for &pos in self.by_item.get(&item).into_iter().flatten() {
+                *shared.entry(pos).or_default() += 1;
+            }
+        }
+        let mut candidates: Vec<usize> =
+            shared.into_iter().filter(|&(_, n)| n >= MIN_SHARED_ITEMS).map(|(pos, _)| pos).collect();
+        candidates.sort_unstable();
+        candidates
+    }
+
+    fn compare_with(&self, track_id: i64, fingerprint: &[u32], pos: usize, min_similarity: f64) -> Option<AudioMatch> {
+        let (other_track_id, other) = &self.fingerprints[pos];
+        let (similarity, matched_secs) = compare(fingerprint, other)?;
+        (similarity >= min_similarity).then_some(AudioMatch {
+            track_id,
+            other_track_id: *other_track_id,
+            similarity,
+            matched_secs,
+        })
+    }
+
+    /// Tracks in the index that match `fingerprint`, best first.
+    pub fn matches(&self, track_id: i64, fingerprint: &[u32], min_similarity: f64) -> Vec<AudioMatch> {
+        let mut matches: Vec<AudioMatch> = self
+            .candidates(fingerprint)
+            .into_iter()
+            .filter(|&pos| self.fingerprints[pos].0 != track_id)
+            .filter_map(|pos| self.compare_with(track_id, fingerprint, pos, min_similarity))
+            .collect();
+        matches.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        matches
+    }
+
+    /// Every matching pair in the index, each reported once.
+    pub fn all_matches(&self, min_similarity: f64) -> Vec<AudioMatch> {
+        let mut matches = Vec::new();
+        for (pos, (track_id, fingerprint)) in self.fingerprints.iter().enumerate() {
+            for other in self.candidates(fingerprint).into_iter().filter(|&other| other > pos) {
+                matches.extend(self.compare_with(*track_id, fingerprint, other, min_similarity));
+            }
+        }
+        matches
+    }
+}
+
+/// Link `matches` into groups of tracks that all hold the same recording.
+/// Each group's track ids are sorted; groups come in order of their first track.
+pub fn group_matches(matches: Vec<AudioMatch>) -> Vec<(Vec<i64>, Vec<AudioMatch>)> {
+    fn root(parent: &mut HashMap<i64, i64>, id: i64) -> i64 {
+        let up = *parent.entry(id).or_insert(id);
+        if up == id {
+            return id;
+        }
+        let top = root(parent, up);
+        parent.insert(id, top);
+        top
+    }
+
+    let mut parent = HashMap::new();
+    for m in &matches {
+        let (a, b) = (root(&mut parent, m.track_id), root(&mut parent, m.other_track_id));
+        parent.insert(a.max(b), a.min(b));
+    }
+    let mut groups: HashMap<i64, (Vec<i64>, Vec<AudioMatch>)> = HashMap::new();
+    for id in parent.keys().copied().collect::<Vec<_>>() {
+        let top = root(&mut parent, id);
+        groups.entry(top).or_default().0.push(id);
+    }
+    for m in matches {
+        let top = root(&mut parent, m.track_id);
+        groups.entry(top).or_default().1.push(m);
+    }
+    let mut groups: Vec<(Vec<i64>, Vec<AudioMatch>)> = groups.into_values().collect();
+    for (ids, _) in &mut groups {
+        ids.sort_unstable();
+    }
+    groups.sort_by_key(|(ids, _)| ids[0]);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::write_test_melody;
+
+    #[test]
+    fn test_matches_same_recording_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original.wav");
+        let other_encode = dir.path().join("quieter at 48k.wav");
+        let different = dir.path().join("different.wav");
+        write_test_melody(&original, 1, 44_100, 0.6);
+        write_test_melody(&other_encode, 1, 48_000, 0.3);
+        write_test_melody(&different, 2, 44_100, 0.6);
+
+        let control = JobControl::default();
+        let print = |p: &Path| fingerprint_file(p, &control).unwrap().unwrap();
+        let (a, b, c) = (print(&original), print(&other_encode), print(&different));
+        assert_eq!(from_blob(&to_blob(&a)), a);
+
+        let index = FingerprintIndex::new(vec![(1, a.clone()), (2, b), (3, c)]);
+        let matches = index.matches(1, &a, 0.5);
+        assert_eq!(matches.len(), 1, "{:?}", matches);
+        assert_eq!(matches[0].other_track_id, 2);
+        assert!(matches[0].matched_secs > 5.0, "{:?}", matches[0]);
+
+        let pairs = index.all_matches(0.5);
+        assert_eq!(pairs.len(), 1, "{:?}", pairs);
+        assert_eq!((pairs[0].track_id, pairs[0].other_track_id), (1, 2));
+    }
+
+    #[test]
+    fn test_index_holds_a_bounded_sample_per_track() {
+        // Ten minutes of distinct sub-fingerprints, five times what is fingerprinted
+        let long: Vec<u32> = (0..5000u32).map(|i| i.wrapping_mul(2_654_435_761)).collect();
+        let index = FingerprintIndex::new(vec![(1, long.clone()), (2, long[..2500].to_vec())]);
+        let indexed: usize = index.by_item.values().map(Vec::len).sum();
+        assert!(indexed <= 2 * INDEXED_ITEMS_PER_TRACK, "{}", indexed);
+        assert_eq!(sketch(&long).len(), INDEXED_ITEMS_PER_TRACK);
+    }
+
+    #[test]
+    fn test_acoustid_string_has_algorithm_header() {
+        let encoded = acoustid_string(&[0x1234_5678, 0x1234_5679, 0x9abc_def0]);
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(encoded).unwrap();
+        // Algorithm 1 (TEST2), then the item count as 24 bits
+        assert_eq!(bytes[..4], [1, 0, 0, 3]);
+    }
+}
@@ -0,0 +1,466 @@
+use crate::models::{AppError, ScanJob, ScanJobState, ScanSummary};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// How many finished jobs are kept around for `list_scan_jobs`.
+const FINISHED_JOBS_KEPT: usize = 20;
+
+// ── Job Control ──
+
+/// Cancel/pause flags shared between a running job and the commands that steer it.
+/// Workers poll it between files, so a pause or cancel takes effect within one file.
+#[derive(Debug, Default)]
+pub struct JobControl {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+}
+
+impl JobControl {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Block the calling thread while the job is paused.
+    /// Returns `false` once the job is cancelled, `true` when it may continue.
+    /// Only call this from blocking threads, never from an async task.
+    pub fn wait_while_paused(&self) -> bool {
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            if !self.is_paused() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+// ── Scan Job Registry ──
+
+struct JobEntry {
+    info: ScanJob,
+    control: Arc<JobControl>,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u32,
+    jobs: HashMap<u32, JobEntry>,
+}
+
+/// Tauri-managed registry of scan jobs. Allows at most one active scan per collection.
+#[derive(Default)]
+pub struct ScanJobs {
+    inner: Mutex<Registry>,
+    /// Held shared by scans and watcher syncs, and alone by a vacuum
+    library: RwLock<()>,
+}
+
+fn is_active(state: &ScanJobState) -> bool {
+    matches!(
+        state,
+        ScanJobState::Running | ScanJobState::Paused | ScanJobState::Cancelling
+    )
+}
+
+impl ScanJobs {
+    /// Register a new scan of `collection_id`. Fails if that collection is already being scanned.
+    pub fn start(&self, collection_id: i64) -> Result<(ScanJob, Arc<JobControl>), AppError> {
+        let mut reg = self.inner.lock().unwrap();
+        if let Some(active) = reg
+            .jobs
+            .values()
+            .find(|j| j.info.collection_id == collection_id && is_active(&j.info.state))
+        {
+            return Err(AppError::InvalidInput(format!(
+                "Collection {} is already being scanned (job {})",
+                collection_id, active.info.id
+            )));
+        }
+
+        reg.next_id += 1;
+        let info = ScanJob {
+            id: reg.next_id,
+            collection_id,
+            state: ScanJobState::Running,
+            processed: 0,
+            started_at: Utc::now().to_rfc3339(),
+            finished_at: None,
+            summary: None,
+            error: None,
+        };
+        let control = Arc::new(JobControl::default());
+        reg.jobs.insert(
+            info.id,
+            JobEntry {
+                info: info.clone(),
+                control: Arc::clone(&control),
+            },
+        );
+        Ok((info, control))
+    }
+
+    /// Guard that finishes the job if the scan stops without calling `finish`,
+    /// e.g. because the command's future was dropped.
+    pub fn guard(&self, job_id: u32) -> ScanJobGuard<'_> {
+        ScanJobGuard { jobs: self, job_id }
+    }
+
+    /// Shared hold on the library for a scan or watcher sync, which write cover
+    /// files before committing the albums that use them. Waits while a vacuum runs.
+    pub async fn hold_library(&self) -> RwLockReadGuard<'_, ()> {
+        self.library.read().await
+    }
+
+    /// Sole hold on the library for a vacuum: waits for the scans and syncs
+    /// holding it, and keeps new ones waiting until dropped.
+    pub async fn lock_library(&self) -> RwLockWriteGuard<'_, ()> {
+        self.library.write().await
+    }
+
+    pub fn set_progress(&self, job_id: u32, processed: u32) {
+        if let Some(job) = self.inner.lock().unwrap().jobs.get_mut(&job_id) {
+            job.info.processed = processed;
+        }
+    }
+
+    /// Record the outcome of a job and return its final state.
+    pub fn finish(&self, job_id: u32, result: &Result<ScanSummary, AppError>) -> Option<ScanJob> {
+        let mut reg = self.inner.lock().unwrap();
+        let job = reg.jobs.get_mut(&job_id)?;
+        match result {
+            Ok(summary) => {
+                job.info.state = if summary.cancelled {
+                    ScanJobState::Cancelled
+                } else {
+                    ScanJobState::Completed
+                };
+                job.info.summary = Some(summary.clone());
+            }
+            Err(e) => {
+                job.info.state = ScanJobState::Failed;
+                job.info.error = Some(e.to_string());
+            }
+        }
+        job.info.finished_at = Some(Utc::now().to_rfc3339());
+        let finished = job.info.clone();
+
+        // Forget the oldest finished jobs beyond the history limit
+        let mut done: Vec<u32> = reg
+            .jobs
+            .values()
+            .filter(|j| !is_active(&j.info.state))
+            .map(|j| j.info.id)
+            .collect();
+        if done.len() > FINISHED_JOBS_KEPT {
+            done.sort_unstable();
+            for id in &done[..done.len() - FINISHED_JOBS_KEPT] {
+                reg.jobs.remove(id);
+            }
+        }
+
+        Some(finished)
+    }
+
+    pub fn cancel(&self, job_id: u32) -> Result<ScanJob, AppError> {
+        self.update_active(job_id, |job, control| {
+            control.cancel();
+            // Workers stop at the next file; `finish` records the final state.
+            control.set_paused(false);
+            job.state = ScanJobState::Cancelling;
+        })
+    }
+
+    pub fn pause(&self, job_id: u32) -> Result<ScanJob, AppError> {
+        self.update_active(job_id, |job, control| {
+            control.set_paused(true);
+            job.state = ScanJobState::Paused;
+        })
+    }
+
+    pub fn resume(&self, job_id: u32) -> Result<ScanJob, AppError> {
+        self.update_active(job_id, |job, control| {
+            control.set_paused(false);
+            job.state = ScanJobState::Running;
+        })
+    }
+
+    /// Whether any scan is running, paused or being cancelled.
+    pub fn is_busy(&self) -> bool {
+        let reg = self.inner.lock().unwrap();
+        reg.jobs.values().any(|j| is_active(&j.info.state))
+    }
+
+    /// All known jobs, oldest first.
+    pub fn list(&self) -> Vec<ScanJob> {
+        let reg = self.inner.lock().unwrap();
+        let mut jobs: Vec<ScanJob> = reg.jobs.values().map(|j| j.info.clone()).collect();
+        jobs.sort_by_key(|j| j.id);
+        jobs
+    }
+
+    fn update_active(
+        &self,
+        job_id: u32,
+        f: impl FnOnce(&mut ScanJob, &JobControl),
+    ) -> Result<ScanJob, AppError> {
+        let mut reg = self.inner.lock().unwrap();
+        let job = reg
+            .jobs
+            .get_mut(&job_id)
+            .ok_or_else(|| AppError::NotFound(format!("Scan job {} not found", job_id)))?;
+        if !is_active(&job.info.state) || job.control.is_cancelled() {
+            return Err(AppError::InvalidInput(format!(
+                "Scan job {} is no longer running",
+                job_id
+            )));
+        }
+        f(&mut job.info, &job.control);
+        Ok(job.info.clone())
+    }
+}
+
+/// Cancels its scan job and records it as failed when dropped while the job is
+/// still active, so an abandoned scan doesn't block the collection forever.
+pub struct ScanJobGuard<'a> {
+    jobs: &'a ScanJobs,
+    job_id: u32,
+}
+
+impl Drop for ScanJobGuard<'_> {
+    fn drop(&mut self) {
+        let active = {
+            let reg = self.jobs.inner.lock().unwrap();
+            reg.jobs
+                .get(&self.job_id)
+                .filter(|j| is_active(&j.info.state))
+                .map(|j| Arc::clone(&j.control))
+        };
+        if let Some(control) = active {
+            control.cancel();
+            self.jobs.finish(
+                self.job_id,
+                &Err(AppError::Io("Scan stopped before it finished".to_string())),
+            );
+        }
+    }
+}
+
+// ── Analysis Jobs ──
+
+/// A background job of which only one may run at a time.
+pub struct ExclusiveJob {
+    /// e.g. "Loudness analysis", for error messages
+    name: &'static str,
+    active: Mutex<Option<Arc<JobControl>>>,
+}
+
+impl ExclusiveJob {
+    fn new(name: &'static str) -> Self {
+        Self { name, active: Mutex::new(None) }
+    }
+
+    /// Start the job, or fail if it's already running. The job counts as running
+    /// until the returned guard is dropped.
+    pub fn start(&self) -> Result<ExclusiveJobGuard<'_>, AppError> {
+        let mut active = self.active.lock().unwrap();
+        if active.is_some() {
+            return Err(AppError::InvalidInput(format!("{} is already running", self.name)));
+        }
+        let control = Arc::new(JobControl::default());
+        *active = Some(Arc::clone(&control));
+        Ok(ExclusiveJobGuard { job: self, control })
+    }
+
+    pub fn cancel(&self) -> Result<(), AppError> {
+        self.with_active(JobControl::cancel)
+    }
+
+    pub fn pause(&self) -> Result<(), AppError> {
+        self.with_active(|control| control.set_paused(true))
+    }
+
+    pub fn resume(&self) -> Result<(), AppError> {
+        self.with_active(|control| control.set_paused(false))
+    }
+
+    fn with_active(&self, f: impl FnOnce(&JobControl)) -> Result<(), AppError> {
+        let active = self.active.lock().unwrap();
+        let control = active
+            .as_ref()
+            .ok_or_else(|| AppError::InvalidInput(format!("No {} is running", self.name.to_lowercase())))?;
+        f(control);
+        Ok(())
+    }
+}
+
+/// A running `ExclusiveJob`. Dropping it cancels the job's workers and frees the
+/// slot, also when the command future is dropped part-way.
+pub struct ExclusiveJobGuard<'a> {
+    job: &'a ExclusiveJob,
+    control: Arc<JobControl>,
+}
+
+impl ExclusiveJobGuard<'_> {
+    pub fn control(&self) -> Arc<JobControl> {
+        Arc::clone(&self.control)
+    }
+}
+
+impl Drop for ExclusiveJobGuard<'_> {
+    fn drop(&mut self) {
+        self.control.cancel();
+        *self.job.active.lock().unwrap() = None;
+    }
+}
+
+/// Tauri-managed slots for the library-wide analysis jobs.
+pub struct AnalysisJobs {
+    pub loudness: ExclusiveJob,
+    pub fingerprint: ExclusiveJob,
+}
+
+impl Default for AnalysisJobs {
+    fn default() -> Self {
+        Self {
+            loudness: ExclusiveJob::new("Loudness analysis"),
+            fingerprint: ExclusiveJob::new("Audio fingerprinting"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refuses_overlapping_scan_of_same_collection() {
+        let jobs = ScanJobs::default();
+        let (first, _) = jobs.start(1).unwrap();
+
+        match jobs.start(1) {
+            Err(AppError::InvalidInput(msg)) => assert!(msg.contains("already being scanned")),
+            other => panic!("Expected InvalidInput, got {:?}", other.map(|(j, _)| j)),
+        }
+        // A different collection may scan concurrently
+        assert!(jobs.start(2).is_ok());
+
+        // Once the first job finishes the collection can be scanned again
+        jobs.finish(first.id, &Ok(ScanSummary::default()));
+        assert!(jobs.start(1).is_ok());
+    }
+
+    #[test]
+    fn test_dropped_guard_finishes_abandoned_job() {
+        let jobs = ScanJobs::default();
+        let (job, control) = jobs.start(1).unwrap();
+        drop(jobs.guard(job.id));
+
+        assert!(control.is_cancelled());
+        assert!(!jobs.is_busy());
+        assert!(matches!(jobs.list()[0].state, ScanJobState::Failed));
+        assert!(jobs.start(1).is_ok());
+
+        // A guard dropped after `finish` leaves the recorded outcome alone
+        let (job, _) = jobs.start(2).unwrap();
+        let guard = jobs.guard(job.id);
+        jobs.finish(job.id, &Ok(ScanSummary::default()));
+        drop(guard);
+        assert!(matches!(jobs.list()[2].state, ScanJobState::Completed));
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_waits_for_library_holds() {
+        let jobs = ScanJobs::default();
+        let sync = jobs.hold_library().await;
+        let scan = jobs.hold_library().await;
+        assert!(jobs.library.try_write().is_err());
+
+        drop((sync, scan));
+        let vacuum = jobs.lock_library().await;
+        assert!(jobs.library.try_read().is_err(), "syncs wait for the vacuum");
+        drop(vacuum);
+        assert!(jobs.library.try_read().is_ok());
+    }
+
+    #[test]
+    fn test_pause_resume_cancel_drive_control_flags() {
+        let jobs = ScanJobs::default();
+        let (job, control) = jobs.start(1).unwrap();
+
+        let paused = jobs.pause(job.id).unwrap();
+        assert!(matches!(paused.state, ScanJobState::Paused));
+        assert!(control.is_paused());
+
+        let resumed = jobs.resume(job.id).unwrap();
+        assert!(matches!(resumed.state, ScanJobState::Running));
+        assert!(!control.is_paused());
+
+        jobs.pause(job.id).unwrap();
+        jobs.cancel(job.id).unwrap();
+        assert!(control.is_cancelled());
+        // A cancelled job must not block its workers in the pause loop
+        assert!(!control.wait_while_paused());
+        assert!(jobs.resume(job.id).is_err());
+    }
+
+    #[test]
+    fn test_exclusive_job_runs_once_until_guard_dropped() {
+        let job = ExclusiveJob::new("Loudness analysis");
+        assert!(job.pause().is_err(), "nothing to pause yet");
+
+        let guard = job.start().unwrap();
+        let control = guard.control();
+        assert!(job.start().is_err());
+        job.pause().unwrap();
+        assert!(control.is_paused());
+        job.resume().unwrap();
+        assert!(!control.is_paused());
+
+        // Dropping the guard, e.g. with its command future, stops the workers
+        drop(guard);
+        assert!(control.is_cancelled());
+        assert!(job.cancel().is_err());
+        assert!(job.start().is_ok());
+    }
+
+    #[test]
+    fn test_finish_records_outcome() {
+        let jobs = ScanJobs::default();
+        let (a, _) = jobs.start(1).unwrap();
+        let (b, _) = jobs.start(2).unwrap();
+        let (c, _) = jobs.start(3).unwrap();
+
+        jobs.set_progress(a.id, 7);
+        jobs.finish(a.id, &Ok(ScanSummary { added: 7, ..Default::default() }));
+        jobs.finish(b.id, &Ok(ScanSummary { cancelled: true, ..Default::default() }));
+        jobs.finish(c.id, &Err(AppError::Io("disk gone".into())));
+
+        let list = jobs.list();
+        assert_eq!(list.len(), 3);
+        assert!(matches!(list[0].state, ScanJobState::Completed));
+        assert_eq!(list[0].processed, 7);
+        assert_eq!(list[0].summary.as_ref().unwrap().added, 7);
+        assert!(matches!(list[1].state, ScanJobState::Cancelled));
+        assert!(matches!(list[2].state, ScanJobState::Failed));
+        assert!(list[2].error.as_ref().unwrap().contains("disk gone"));
+        assert!(jobs.cancel(a.id).is_err(), "finished jobs cannot be cancelled");
+    }
+}
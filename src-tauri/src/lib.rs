@@ -1,6 +1,18 @@
+mod audio_hash;
+mod audio_properties;
+mod backups;
 mod commands;
+mod decode;
 pub mod db;
+mod duplicates;
+mod fingerprint;
+mod jobs;
+mod loudness;
 mod models;
+mod organize;
+mod playlist_formats;
+mod safe_write;
+mod watcher;
 use log::info;
 use tauri::Manager;
 use tauri_specta::{collect_commands, Builder};
@@ -41,7 +53,9 @@ pub fn run() {
         commands::list_collections,
         commands::add_collection,
         commands::delete_collection,
+        commands::set_collection_watch,
         commands::clear_all_data,
+        commands::vacuum_library,
         commands::get_library_stats,
         commands::get_database_path,
         // Settings
@@ -52,20 +66,70 @@ pub fn run() {
         commands::debug_query_table,
         // Tracks
         commands::list_tracks,
+        commands::query_tracks,
         commands::get_track,
         commands::update_track,
         commands::batch_update_tracks,
+        commands::undo_last_edit,
+        commands::redo_edit,
+        commands::list_edit_history,
+        commands::restore_track_file,
+        commands::organize_files,
+        commands::find_duplicates,
+        commands::remove_duplicates,
         commands::stale_track_ids,
+        commands::verify_audio_integrity,
+        commands::search_library,
         commands::get_track_extra_tags,
         commands::set_track_extra_tags,
+        // Smart playlists
+        commands::list_smart_playlists,
+        commands::create_smart_playlist,
+        commands::update_smart_playlist,
+        commands::delete_smart_playlist,
+        commands::list_smart_playlist_tracks,
+        // Playlists
+        commands::list_playlists,
+        commands::create_playlist,
+        commands::rename_playlist,
+        commands::delete_playlist,
+        commands::list_playlist_entries,
+        commands::add_tracks_to_playlist,
+        commands::remove_playlist_entries,
+        commands::reorder_playlist,
+        commands::import_playlist,
+        commands::export_playlist,
         // Artists
         commands::list_artists,
         commands::list_artist_rows,
+        commands::query_artist_rows,
+        commands::get_track_credits,
+        commands::rename_artist,
+        commands::merge_artists,
+        commands::set_artist_sort_name,
+        commands::find_name_duplicates,
         // Albums
         commands::list_albums,
         commands::list_album_rows,
+        commands::query_album_rows,
         commands::list_tracks_by_album,
+        commands::merge_albums,
+        commands::split_album,
         commands::scan_collection,
+        commands::cancel_scan,
+        commands::pause_scan,
+        commands::resume_scan,
+        commands::list_scan_jobs,
+        commands::analyze_loudness,
+        commands::cancel_loudness_analysis,
+        commands::pause_loudness_analysis,
+        commands::resume_loudness_analysis,
+        commands::fingerprint_tracks,
+        commands::cancel_fingerprinting,
+        commands::pause_fingerprinting,
+        commands::resume_fingerprinting,
+        commands::find_similar_audio,
+        commands::get_acoustid_fingerprint,
         commands::get_cover_art,
         commands::get_album_cover_art,
         commands::get_artist_cover_art,
@@ -86,6 +150,9 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
+        .manage(jobs::ScanJobs::default())
+        .manage(jobs::AnalysisJobs::default())
+        .manage(watcher::CollectionWatchers::default())
         .setup(|app| {
             let handle = app.handle().clone();
 
@@ -93,6 +160,7 @@ pub fn run() {
             setup_logging(&handle);
 
             // Initialize database
+            let watch_handle = handle.clone();
             tauri::async_runtime::block_on(async move {
                 match db::init_db(&handle).await {
                     Ok(pool) => {
@@ -105,6 +173,11 @@ pub fn run() {
                     }
                 }
             });
+
+            // Resume live sync for collections in watch mode
+            tauri::async_runtime::spawn(async move {
+                watcher::restore_watchers(&watch_handle).await;
+            });
             Ok(())
         })
         .invoke_handler(builder.invoke_handler())
@@ -0,0 +1,186 @@
+use crate::decode::decode_file;
+use crate::jobs::JobControl;
+use crate::models::{AppError, ExtraTag};
+use ebur128::{EbuR128, Mode};
+use std::ops::ControlFlow;
+use std::path::Path;
+
+/// ReplayGain 2.0 target loudness.
+const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+/// Target of the `R128_*_GAIN` tags Opus players read (RFC 7845).
+const R128_REFERENCE_LUFS: f64 = -23.0;
+/// Integrated loudness below the absolute gate (silence) is stored as the gate itself.
+const LOUDNESS_FLOOR_LUFS: f64 = -70.0;
+
+/// Tag fields written by `gain_tags`, replaced as a set on every write.
+pub const GAIN_FRAME_IDS: &[&str] = &[
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+    "R128_TRACK_GAIN",
+    "R128_ALBUM_GAIN",
+];
+
+/// EBU R128 measurement of a track or album.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub integrated_lufs: f64,
+    /// Linear, 1.0 = digital full scale
+    pub true_peak: f64,
+    pub loudness_range_lu: f64,
+}
+
+/// A measured track. Keeps its meter so album values can be gated over all
+/// of an album's audio, not averaged from the track results.
+pub struct TrackAnalysis {
+    pub measurement: Measurement,
+    meter: EbuR128,
+}
+
+fn meter_error(e: ebur128::Error) -> AppError {
+    AppError::Io(format!("Loudness measurement failed: {}", e))
+}
+
+/// Decode the file and measure it. Returns None if the job was cancelled
+/// part-way.
+pub fn analyze_file(path: &Path, control: &JobControl) -> Result<Option<TrackAnalysis>, AppError> {
+    let mut meter: Option<EbuR128> = None;
+    let finished = decode_file::<f32>(path, control, |stream, samples| {
+        let meter = match &mut meter {
+            Some(meter) => meter,
+            None => {
+                let mode = Mode::I | Mode::LRA | Mode::TRUE_PEAK | Mode::HISTOGRAM;
+                meter.insert(EbuR128::new(stream.channels as u32, stream.sample_rate, mode).map_err(meter_error)?)
+            }
+        };
+        meter.add_frames_f32(samples).map_err(meter_error)?;
+        Ok(ControlFlow::Continue(()))
+    })?;
+    if !finished {
+        return Ok(None);
+    }
+
+    let meter = meter.ok_or_else(|| AppError::Io(format!("No audio decoded from {:?}", path)))?;
+    let measurement = Measurement {
+        integrated_lufs: meter.loudness_global().map_err(meter_error)?,
+        true_peak: true_peak(&meter)?,
+        loudness_range_lu: meter.loudness_range().map_err(meter_error)?,
+    };
+    Ok(Some(TrackAnalysis { measurement: clamp(measurement), meter }))
+}
+
+fn true_peak(meter: &EbuR128) -> Result<f64, AppError> {
+    (0..meter.channels()).try_fold(0.0f64, |peak, ch| Ok(peak.max(meter.true_peak(ch).map_err(meter_error)?)))
+}
+
+fn clamp(m: Measurement) -> Measurement {
+    let finite_or = |v: f64, fallback: f64| if v.is_finite() { v } else { fallback };
+    Measurement {
+        integrated_lufs: finite_or(m.integrated_lufs, LOUDNESS_FLOOR_LUFS).max(LOUDNESS_FLOOR_LUFS),
+        true_peak: finite_or(m.true_peak, 0.0),
+        loudness_range_lu: finite_or(m.loudness_range_lu, 0.0),
+    }
+}
+
+/// Album loudness and range gated over every track's blocks; peak is the loudest track's.
+pub fn album_measurement(tracks: &[TrackAnalysis]) -> Result<Measurement, AppError> {
+    let meters = || tracks.iter().map(|t| &t.meter);
+    Ok(clamp(Measurement {
+        integrated_lufs: EbuR128::loudness_global_multiple(meters()).map_err(meter_error)?,
+        true_peak: tracks.iter().map(|t| t.measurement.true_peak).fold(0.0, f64::max),
+        loudness_range_lu: EbuR128::loudness_range_multiple(meters()).map_err(meter_error)?,
+    }))
+}
+
+// ── Tags ──
+
+/// The gain tags for one track: `R128_*_GAIN` (Q7.8 dB relative to -23 LUFS)
+/// for Opus, `REPLAYGAIN_*` (relative to -18 LUFS) for everything else.
+pub fn gain_tags(opus: bool, track: &Measurement, album: Option<&Measurement>) -> Vec<ExtraTag> {
+    let tag = |frame_id: &str, value: String| ExtraTag { frame_id: frame_id.to_string(), value };
+    if opus {
+        let q78 = |m: &Measurement| {
+            (((R128_REFERENCE_LUFS - m.integrated_lufs) * 256.0).round() as i64).clamp(i16::MIN as i64, i16::MAX as i64)
+        };
+        let mut tags = vec![tag("R128_TRACK_GAIN", q78(track).to_string())];
+        if let Some(album) = album {
+            tags.push(tag("R128_ALBUM_GAIN", q78(album).to_string()));
+        }
+        return tags;
+    }
+    let gain = |m: &Measurement| format!("{:.2} dB", REPLAYGAIN_REFERENCE_LUFS - m.integrated_lufs);
+    let peak = |m: &Measurement| format!("{:.6}", m.true_peak);
+    let mut tags = vec![
+        tag("REPLAYGAIN_TRACK_GAIN", gain(track)),
+        tag("REPLAYGAIN_TRACK_PEAK", peak(track)),
+    ];
+    if let Some(album) = album {
+        tags.push(tag("REPLAYGAIN_ALBUM_GAIN", gain(album)));
+        tags.push(tag("REPLAYGAIN_ALBUM_PEAK", peak(album)));
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_sine_wav(path: &Path, amplitude: f64, secs: u32) {
+        let rate = 48_000;
+        let sine = (0..rate * secs).map(|i| amplitude * (2.0 * std::f64::consts::PI * 997.0 * i as f64 / rate as f64).sin());
+        crate::decode::write_test_wav(path, rate, 2, sine);
+    }
+
+    fn write_sine_opus(path: &Path, amplitude: f64, secs: u32) {
+        let rate = 48_000;
+        let sine = (0..rate * secs).map(|i| amplitude * (2.0 * std::f64::consts::PI * 997.0 * i as f64 / rate as f64).sin());
+        crate::decode::write_test_opus(path, 2, sine);
+    }
+
+    #[test]
+    fn test_measures_sine_and_album() {
+        let dir = tempfile::tempdir().unwrap();
+        let loud = dir.path().join("loud.wav");
+        let quiet = dir.path().join("quiet.wav");
+        write_sine_wav(&loud, 0.5, 3);
+        write_sine_wav(&quiet, 0.05, 3);
+        let control = JobControl::default();
+
+        let a = analyze_file(&loud, &control).unwrap().unwrap();
+        let b = analyze_file(&quiet, &control).unwrap().unwrap();
+        // A 997 Hz stereo sine at -6 dBFS reads about -6 LUFS; 20 dB quieter is -26
+        assert!((a.measurement.integrated_lufs + 6.0).abs() < 0.5, "{:?}", a.measurement);
+        assert!((b.measurement.integrated_lufs - a.measurement.integrated_lufs + 20.0).abs() < 0.5);
+        assert!((a.measurement.true_peak - 0.5).abs() < 0.02, "{:?}", a.measurement);
+
+        let album = album_measurement(&[a, b]).unwrap();
+        assert!((album.true_peak - 0.5).abs() < 0.02);
+        assert!(album.integrated_lufs < -6.0 && album.integrated_lufs > -26.0, "{:?}", album);
+    }
+
+    #[test]
+    fn test_gain_tags() {
+        let m = Measurement { integrated_lufs: -11.5, true_peak: 0.98, loudness_range_lu: 5.0 };
+        let tags = gain_tags(false, &m, Some(&m));
+        assert_eq!(tags[0].value, "-6.50 dB");
+        assert_eq!(tags[1].value, "0.980000");
+        assert_eq!(tags.len(), 4);
+        assert_eq!(gain_tags(false, &m, None).len(), 2);
+
+        let r128 = gain_tags(true, &m, None);
+        assert_eq!(r128.len(), 1);
+        assert_eq!(r128[0].frame_id, "R128_TRACK_GAIN");
+        assert_eq!(r128[0].value, "-2944");
+    }
+
+    #[test]
+    fn test_measures_opus() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sine.opus");
+        write_sine_opus(&path, 0.5, 3);
+        let a = analyze_file(&path, &JobControl::default()).unwrap().unwrap();
+        // Same -6 dBFS sine as the WAV test, through a lossy codec
+        assert!((a.measurement.integrated_lufs + 6.0).abs() < 1.0, "{:?}", a.measurement);
+    }
+}
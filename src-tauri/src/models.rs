@@ -11,6 +11,8 @@ pub struct Collection {
     pub path: String,
     pub label: Option<String>,
     pub created_at: String,
+    /// Live-sync the collection from filesystem notifications
+    pub watch: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -30,6 +32,19 @@ pub struct Artist {
     pub created_at: String,
 }
 
+/// One artist credited on a track.
+#[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackCredit {
+    pub artist_id: i64,
+    pub artist_name: String,
+    /// `artist`, `featured`, `composer`, `lyricist`, `remixer`, `conductor`,
+    /// `performer` or `performer:<instrument>`
+    pub role: String,
+    /// Order within the role, from 0
+    pub position: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct Album {
@@ -41,6 +56,11 @@ pub struct Album {
     pub cover_path: Option<String>,
     pub musicbrainz_id: Option<String>,
     pub created_at: String,
+    /// Integrated loudness of the whole album (EBU R128)
+    pub loudness_lufs: Option<f64>,
+    /// Highest true peak of the album's tracks, linear (1.0 = full scale)
+    pub true_peak: Option<f64>,
+    pub loudness_range_lu: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
@@ -86,6 +106,85 @@ pub struct LibraryStats {
     pub total_duration_secs: f64,
 }
 
+/// What `vacuum_library` removed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct VacuumSummary {
+    /// Artists no track, credit or album referred to
+    pub removed_artists: u32,
+    /// Albums without tracks
+    pub removed_albums: u32,
+    /// Cover files no album used
+    pub removed_covers: u32,
+    /// How much smaller the database file got
+    pub reclaimed_bytes: i64,
+}
+
+// ── Scan ──
+
+/// Outcome of a (re)scan: how many files were new, re-read, skipped as unchanged
+/// or found under a new path, and how many rows were dropped because their file
+/// is gone from disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanSummary {
+    pub added: u32,
+    pub updated: u32,
+    pub unchanged: u32,
+    pub removed: u32,
+    /// Rows re-pointed to a file that was moved or renamed
+    pub moved: u32,
+    /// True when the scan was cancelled; counts cover only the work done so far
+    /// and no rows were removed.
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum ScanJobState {
+    Running,
+    Paused,
+    Cancelling,
+    Cancelled,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanJob {
+    pub id: u32,
+    pub collection_id: i64,
+    pub state: ScanJobState,
+    /// Files handled so far (written or skipped as unchanged)
+    pub processed: u32,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub summary: Option<ScanSummary>,
+    pub error: Option<String>,
+}
+
+// ── Live Sync ──
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub enum LibraryChangeKind {
+    /// Track row was inserted or re-read from its file
+    Upserted,
+    /// Track row was deleted because its file is gone
+    Removed,
+}
+
+/// Payload of the `library:changed` event emitted by collection watchers.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryChange {
+    pub collection_id: i64,
+    pub track_id: i64,
+    pub file_path: String,
+    pub kind: LibraryChangeKind,
+}
+
 // ── Settings ──
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
@@ -120,6 +219,485 @@ pub struct TrackUpdateInput {
     pub disc_total: Option<i32>,
 }
 
+/// Every editable tag field of a track, with `None` meaning "not set".
+/// The edit journal stores one of these (as JSON) before and after each edit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackTags {
+    pub title: String,
+    pub artist_name: Option<String>,
+    pub album_title: Option<String>,
+    pub track_number: Option<i32>,
+    pub disc_number: Option<i32>,
+    pub track_total: Option<i32>,
+    pub disc_total: Option<i32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub album_artist: Option<String>,
+    pub composer: Option<String>,
+    pub bpm: Option<i32>,
+    pub comment: Option<String>,
+    pub comment_lang: Option<String>,
+    pub lyrics: Option<String>,
+    pub lyrics_lang: Option<String>,
+    /// Extra tag frames an artist edit sets, "" meaning removed: sort order and
+    /// the credits kept in `track_extra_tags`. Empty for plain tag edits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_tags: Vec<ExtraTag>,
+}
+
+impl TrackTags {
+    pub fn from_row(row: &TrackRow) -> Self {
+        Self {
+            title: row.title.clone(),
+            artist_name: row.artist_name.clone(),
+            album_title: row.album_title.clone(),
+            track_number: row.track_number,
+            disc_number: row.disc_number,
+            track_total: row.track_total,
+            disc_total: row.disc_total,
+            year: row.year,
+            genre: row.genre.clone(),
+            album_artist: row.album_artist.clone(),
+            composer: row.composer.clone(),
+            bpm: row.bpm,
+            comment: row.comment.clone(),
+            comment_lang: row.comment_lang.clone(),
+            lyrics: row.lyrics.clone(),
+            lyrics_lang: row.lyrics_lang.clone(),
+            extra_tags: Vec::new(),
+        }
+    }
+
+    /// Apply an update on top of these tags: `None` keeps a field, `Some("")` clears a string.
+    pub fn merged(&self, input: &TrackUpdateInput) -> Self {
+        fn opt_str(input: &Option<String>, existing: &Option<String>) -> Option<String> {
+            match input {
+                None => existing.clone(),
+                Some(s) if s.is_empty() => None,
+                Some(s) => Some(s.clone()),
+            }
+        }
+        Self {
+            title: input.title.clone().unwrap_or_else(|| self.title.clone()),
+            artist_name: opt_str(&input.artist_name, &self.artist_name),
+            album_title: opt_str(&input.album_title, &self.album_title),
+            track_number: input.track_number.or(self.track_number),
+            disc_number: input.disc_number.or(self.disc_number),
+            track_total: input.track_total.or(self.track_total),
+            disc_total: input.disc_total.or(self.disc_total),
+            year: input.year.or(self.year),
+            genre: opt_str(&input.genre, &self.genre),
+            album_artist: opt_str(&input.album_artist, &self.album_artist),
+            composer: opt_str(&input.composer, &self.composer),
+            bpm: input.bpm.or(self.bpm),
+            comment: opt_str(&input.comment, &self.comment),
+            comment_lang: opt_str(&input.comment_lang, &self.comment_lang),
+            lyrics: input.lyrics.clone().or_else(|| self.lyrics.clone()),
+            lyrics_lang: opt_str(&input.lyrics_lang, &self.lyrics_lang),
+            extra_tags: self.extra_tags.clone(),
+        }
+    }
+}
+
+// ── Edit History ──
+
+/// One journaled tag edit (a single-track update or a batch update).
+#[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EditOperation {
+    pub id: i64,
+    /// Short human-readable description, e.g. "Edit 12 tracks"
+    pub label: String,
+    pub track_count: i64,
+    pub created_at: String,
+    /// True once undone; undone operations can be redone until the next new edit
+    pub undone: bool,
+}
+
+// ── File Organizer ──
+
+/// One file move computed from a path template.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizeMove {
+    /// None for a cover image moving along with its album's tracks
+    pub track_id: Option<i64>,
+    pub from_path: String,
+    pub to_path: String,
+    /// Why the move was (or would be) skipped: a collision or a failed move
+    pub problem: Option<String>,
+}
+
+// ── Artist and Album Edits ──
+
+/// What an artist or album edit changed, or would change when previewed.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityEditSummary {
+    /// Tracks re-linked to another artist or album
+    pub track_count: i64,
+    /// Files whose tags are (or would be) rewritten
+    pub file_count: i64,
+    pub dry_run: bool,
+}
+
+/// Artists and albums whose names differ only in case, Unicode form, spacing,
+/// punctuation or a leading article ("AC/DC" and "Ac-Dc"), for merging.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct NameDuplicates {
+    /// Groups of two or more artists
+    pub artists: Vec<Vec<ArtistRow>>,
+    /// Groups of two or more albums whose artists' names match as well
+    pub albums: Vec<Vec<AlbumRow>>,
+}
+
+// ── Library Queries ──
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(untagged)]
+pub enum FilterValue {
+    Number(f64),
+    Text(String),
+}
+
+/// One condition on a row field. `field` is the camelCase name used by the row
+/// type, e.g. `albumTitle` or `durationSecs`.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "op", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum FieldPredicate {
+    /// Case-insensitive for text fields
+    Equals { field: String, value: FilterValue },
+    /// Also matches rows where the field is empty
+    NotEquals { field: String, value: FilterValue },
+    /// Case-insensitive substring match (text fields only)
+    Contains { field: String, value: String },
+    /// Case-insensitive prefix match (text fields only)
+    StartsWith { field: String, value: String },
+    /// Inclusive range; either bound may be omitted
+    Between { field: String, min: Option<FilterValue>, max: Option<FilterValue> },
+    GreaterThan { field: String, value: FilterValue },
+    LessThan { field: String, value: FilterValue },
+    /// Timestamp fields (`createdAt`, `updatedAt`) within the last `days` days
+    InLastDays { field: String, days: i64 },
+    IsNull { field: String },
+    IsNotNull { field: String },
+}
+
+/// Narrows a track, album or artist query. All parts are combined with AND.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryFilter {
+    pub collection_id: Option<i64>,
+    pub artist_id: Option<i64>,
+    pub album_id: Option<i64>,
+    /// Case-insensitive substring match over the row's name fields
+    /// (title, artist and album for tracks)
+    pub text: Option<String>,
+    pub predicates: Vec<FieldPredicate>,
+}
+
+/// Predicates and nested groups combined with AND (`matchAll`) or OR.
+/// An empty group matches everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleGroup {
+    pub match_all: bool,
+    pub predicates: Vec<FieldPredicate>,
+    pub groups: Vec<RuleGroup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SortSpec {
+    /// camelCase row field, as in `FieldPredicate`
+    pub field: String,
+    pub descending: bool,
+}
+
+/// Either `offset` or `cursor` (from a previous page's `nextCursor`) may be set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRequest {
+    pub offset: Option<i64>,
+    /// Defaults to 200; at most 5000
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub rows: Vec<T>,
+    /// Rows matching the filter, across all pages
+    pub total: i64,
+    /// Pass back as `PageRequest.cursor` for the next page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+// ── Smart Playlists ──
+
+/// A saved rule set over track fields, evaluated whenever its tracks are listed.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartPlaylist {
+    pub id: i64,
+    pub name: String,
+    pub rules: RuleGroup,
+    /// Order of the tracks (and which ones are kept by `limit`); title when None
+    pub sort: Option<SortSpec>,
+    /// Keep at most this many tracks
+    pub limit: Option<i64>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartPlaylistInput {
+    pub name: String,
+    pub rules: RuleGroup,
+    pub sort: Option<SortSpec>,
+    pub limit: Option<i64>,
+}
+
+// ── Playlists ──
+
+/// A hand-curated, ordered list of tracks.
+#[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Playlist {
+    pub id: i64,
+    pub name: String,
+    pub track_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// One slot in a playlist. The same track may fill several slots, so edits
+/// address entries by their own id.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistEntry {
+    pub id: i64,
+    /// 0-based sort key; may skip numbers where a deleted track's entry was
+    pub position: i64,
+    pub track: TrackRow,
+}
+
+/// Playlist file formats for import and export; `M3u` covers `.m3u` and `.m3u8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistImport {
+    pub playlist: Playlist,
+    /// Entries that matched no track in the library, as written in the file
+    pub unresolved: Vec<String>,
+}
+
+// ── Duplicates ──
+
+/// What makes two tracks duplicates of each other.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum DuplicateCriteria {
+    /// Same title and artist ignoring case and punctuation, durations within `toleranceSecs`
+    Metadata { tolerance_secs: f64 },
+    /// Byte-identical files
+    FileContent,
+    /// Identical audio data; tags and embedded pictures may differ
+    AudioContent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// The copy to keep: lossless over lossy, then higher bitrate, sample rate and bit depth
+    pub best_track_id: i64,
+    /// Best copy first
+    pub tracks: Vec<TrackRow>,
+}
+
+/// What to do with the files of duplicates being removed from the library.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum DuplicateAction {
+    Delete,
+    /// Move into `directory`, e.g. a quarantine folder outside the collections
+    MoveTo { directory: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedDuplicate {
+    pub track_id: i64,
+    pub file_path: String,
+    /// Where the file went for `MoveTo`
+    pub moved_to: Option<String>,
+    /// Why the track was kept: the file could not be deleted or moved
+    pub problem: Option<String>,
+}
+
+// ── Loudness ──
+
+/// Which tracks `analyze_loudness` measures. Tracks are analysed together with
+/// the rest of their album, so album values always cover the whole album.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnessOptions {
+    /// Limit to one collection
+    pub collection_id: Option<i64>,
+    /// Limit to these tracks
+    pub track_ids: Option<Vec<i64>>,
+    /// Measure tracks again even if their audio is unchanged since the last analysis
+    pub reanalyze: bool,
+    /// Also write ReplayGain tags to the files (R128 gain tags for Opus).
+    pub write_tags: bool,
+}
+
+/// Payload of the `loudness:progress` and `fingerprint:progress` events.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisProgress {
+    pub processed: u32,
+    pub total: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisFailure {
+    pub track_id: i64,
+    pub file_path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnessSummary {
+    /// Tracks measured and stored
+    pub analyzed: u32,
+    /// Albums whose album values were stored
+    pub albums: u32,
+    pub tags_written: u32,
+    pub failed: Vec<AnalysisFailure>,
+    /// True when the job was cancelled; albums still in progress were not stored
+    pub cancelled: bool,
+}
+
+// ── Fingerprints ──
+
+/// Which tracks `fingerprint_tracks` fingerprints.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintOptions {
+    /// Limit to one collection
+    pub collection_id: Option<i64>,
+    /// Limit to these tracks
+    pub track_ids: Option<Vec<i64>>,
+    /// Fingerprint tracks again even if their audio is unchanged since the last run
+    pub recompute: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct FingerprintSummary {
+    pub fingerprinted: u32,
+    pub failed: Vec<AnalysisFailure>,
+    pub cancelled: bool,
+}
+
+/// Two tracks whose fingerprints match.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioMatch {
+    pub track_id: i64,
+    pub other_track_id: i64,
+    /// Share of the shorter fingerprint covered by matching audio, 0 to 1
+    pub similarity: f64,
+    /// Length of the matching audio
+    pub matched_secs: f64,
+}
+
+/// Tracks that hold the same recording, linked by their pairwise matches.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarAudioGroup {
+    pub tracks: Vec<TrackRow>,
+    pub matches: Vec<AudioMatch>,
+}
+
+/// What an AcoustID lookup needs for one track.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct AcoustIdFingerprint {
+    /// Compressed and base64 encoded, as `fpcalc` prints it
+    pub fingerprint: String,
+    /// Whole seconds, as the lookup expects
+    pub duration_secs: i64,
+}
+
+// ── Search ──
+
+/// A run of search result text; `highlight` marks the words that matched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackSearchHit {
+    pub track_id: i64,
+    pub title: String,
+    pub artist_id: Option<i64>,
+    pub artist_name: Option<String>,
+    pub album_id: Option<i64>,
+    pub album_title: Option<String>,
+    /// Excerpt of the best-matching field (title, lyrics, comment, …)
+    #[sqlx(skip)]
+    pub snippet: Vec<SnippetPart>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumSearchHit {
+    pub album_id: i64,
+    pub title: String,
+    pub artist_name: Option<String>,
+    /// The album title with the matched words marked
+    #[sqlx(skip)]
+    pub highlighted: Vec<SnippetPart>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtistSearchHit {
+    pub artist_id: i64,
+    pub name: String,
+    /// The artist name with the matched words marked
+    #[sqlx(skip)]
+    pub highlighted: Vec<SnippetPart>,
+}
+
+/// Search hits grouped by kind, each group ordered best match first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    pub tracks: Vec<TrackSearchHit>,
+    pub albums: Vec<AlbumSearchHit>,
+    pub artists: Vec<ArtistSearchHit>,
+}
+
 // ── Track Row (joined query result) ──
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
@@ -136,8 +714,18 @@ pub struct TrackRow {
     pub file_path: String,
     pub file_size_bytes: i64,
     pub file_format: Option<String>,
+    /// Bitrate of the audio stream alone
     pub bitrate_kbps: Option<i32>,
+    /// File size over duration, container and tags included
+    pub overall_bitrate_kbps: Option<i32>,
     pub sample_rate_hz: Option<i32>,
+    pub channels: Option<i32>,
+    /// Only set for PCM-based (lossless) formats
+    pub bit_depth: Option<i32>,
+    /// e.g. "MP3", "AAC", "ALAC", "FLAC", "Opus"
+    pub codec: Option<String>,
+    /// e.g. "LC" or "HE-AAC" for AAC, "CBR", "VBR" or "ABR" for MP3
+    pub codec_profile: Option<String>,
     pub lyrics: Option<String>,
     pub created_at: String,
     pub updated_at: String,
@@ -153,15 +741,24 @@ pub struct TrackRow {
     pub track_total: Option<i32>,
     pub disc_total: Option<i32>,
     pub file_mtime: Option<i64>,
+    /// Hash of the audio data alone (see `audio_hash`); unchanged by tag edits
+    pub audio_hash: Option<String>,
+    /// Integrated loudness (EBU R128); None until analysed
+    pub loudness_lufs: Option<f64>,
+    /// Linear, 1.0 = digital full scale
+    pub true_peak: Option<f64>,
+    pub loudness_range_lu: Option<f64>,
     // Joined columns
     pub artist_name: Option<String>,
     pub album_title: Option<String>,
     pub album_cover_path: Option<String>,
+    pub album_loudness_lufs: Option<f64>,
+    pub album_true_peak: Option<f64>,
 }
 
 // ── Extra Tag ──
 
-#[derive(Debug, Clone, Serialize, Deserialize, Type, FromRow)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct ExtraTag {
     pub frame_id: String,
@@ -192,6 +789,11 @@ pub struct AlbumRow {
     pub track_count: i64,
     pub total_duration_secs: f64,
     pub total_size_bytes: i64,
+    /// Distinct codecs of the album's tracks, comma-separated
+    pub codecs: Option<String>,
+    pub avg_bitrate_kbps: Option<i32>,
+    pub max_sample_rate_hz: Option<i32>,
+    pub max_bit_depth: Option<i32>,
 }
 
 // ── Cover Art ──
@@ -219,6 +821,10 @@ pub enum AppError {
     Io(String),
     #[error("Serialization error: {0}")]
     Serialization(String),
+    /// Writing tags produced a file that failed verification (or the writer itself
+    /// failed), so continuing could damage more files. The original is left intact.
+    #[error("Tag write failed: {0}")]
+    TagWrite(String),
 }
 
 impl From<sqlx::Error> for AppError {
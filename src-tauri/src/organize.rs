@@ -0,0 +1,434 @@
+use crate::commands::is_audio_path;
+use crate::db::DbPool;
+use crate::models::{AppError, OrganizeMove, TrackRow};
+use chrono::Utc;
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Image files next to the audio that travel with an album when its folder moves.
+const COVER_FILE_STEMS: &[&str] = &["cover", "folder", "front", "album", "albumart", "albumartsmall"];
+const COVER_FILE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp"];
+
+// ── Path Templates ──
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    Composer,
+    Year,
+    Track,
+    TrackTotal,
+    Disc,
+    DiscTotal,
+    Ext,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "title" => Self::Title,
+            "artist" => Self::Artist,
+            "album" => Self::Album,
+            "album_artist" => Self::AlbumArtist,
+            "genre" => Self::Genre,
+            "composer" => Self::Composer,
+            "year" => Self::Year,
+            "track" => Self::Track,
+            "track_total" => Self::TrackTotal,
+            "disc" => Self::Disc,
+            "disc_total" => Self::DiscTotal,
+            "ext" => Self::Ext,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    /// A track field, zero-padded to `width` digits when numeric
+    Field { field: Field, width: usize },
+}
+
+/// A relative path pattern such as `{album_artist}/{year} - {album}/{track:02} {title}.{ext}`.
+/// `/` separates folders; each `{field}` is replaced by the track's value with
+/// characters that are illegal in file names swapped for `_`.
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, AppError> {
+        let invalid = |msg: String| AppError::InvalidInput(format!("Invalid template: {}", msg));
+        let template = template.trim();
+        if template.starts_with('/') || template.starts_with('\\') {
+            return Err(invalid("must be relative to the collection folder".into()));
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(i) if rest.as_bytes()[i] == b'}' => {
+                    return Err(invalid(format!("unmatched '}}' at {:?}", &rest[i..])));
+                }
+                Some(i) => {
+                    if i > 0 {
+                        parts.push(Part::Literal(rest[..i].to_string()));
+                    }
+                    let end = rest[i..]
+                        .find('}')
+                        .ok_or_else(|| invalid(format!("unclosed '{{' at {:?}", &rest[i..])))?;
+                    let spec = &rest[i + 1..i + end];
+                    let (name, width) = match spec.split_once(':') {
+                        Some((name, width)) => (
+                            name,
+                            width
+                                .parse::<usize>()
+                                .map_err(|_| invalid(format!("bad width in {{{}}}", spec)))?,
+                        ),
+                        None => (spec, 0),
+                    };
+                    let field = Field::parse(name.trim())
+                        .ok_or_else(|| invalid(format!("unknown field {{{}}}", name)))?;
+                    parts.push(Part::Field { field, width });
+                    rest = &rest[i + end + 1..];
+                }
+                None => {
+                    parts.push(Part::Literal(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+
+        for part in &parts {
+            if let Part::Literal(text) = part {
+                if text.split(['/', '\\']).any(|c| c == "..") {
+                    return Err(invalid("'..' is not allowed".into()));
+                }
+            }
+        }
+        if !parts.iter().any(|p| matches!(p, Part::Field { field: Field::Ext, .. })) {
+            return Err(invalid("must include {ext}".into()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The track's path relative to its collection folder.
+    /// Missing text fields read "Unknown …"; missing numbers render as 0.
+    pub fn render(&self, track: &TrackRow) -> PathBuf {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(&text.replace('\\', "/")),
+                Part::Field { field, width } => {
+                    out.push_str(&sanitize_value(&field_value(track, *field, *width)))
+                }
+            }
+        }
+        out.split('/').filter(|c| !c.is_empty()).map(sanitize_component).collect()
+    }
+}
+
+fn field_value(track: &TrackRow, field: Field, width: usize) -> String {
+    let text = |value: Option<&str>, fallback: &str| {
+        value
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .unwrap_or(fallback)
+            .to_string()
+    };
+    let number = |value: Option<i32>| format!("{:0width$}", value.unwrap_or(0), width = width);
+    match field {
+        Field::Title => text(Some(&track.title), "Unknown Title"),
+        Field::Artist => text(track.artist_name.as_deref(), "Unknown Artist"),
+        Field::Album => text(track.album_title.as_deref(), "Unknown Album"),
+        Field::AlbumArtist => text(
+            track.album_artist.as_deref().or(track.artist_name.as_deref()),
+            "Unknown Artist",
+        ),
+        Field::Genre => text(track.genre.as_deref(), "Unknown Genre"),
+        Field::Composer => text(track.composer.as_deref(), "Unknown Composer"),
+        Field::Year => number(track.year),
+        Field::Track => number(track.track_number),
+        Field::TrackTotal => number(track.track_total),
+        Field::Disc => number(track.disc_number),
+        Field::DiscTotal => number(track.disc_total),
+        Field::Ext => Path::new(&track.file_path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default(),
+    }
+}
+
+/// Replace characters that are illegal in a file name on any supported OS
+/// (including path separators, so a value never adds a folder level).
+fn sanitize_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Make one path component safe: no surrounding spaces, no trailing dots (dropped
+/// by Windows), no `.`/`..`, and no reserved Windows device names.
+fn sanitize_component(component: &str) -> String {
+    let trimmed = component.trim().trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() {
+        return "_".to_string();
+    }
+    let stem = trimmed.split('.').next().unwrap_or("").to_ascii_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.as_bytes()[3].is_ascii_digit());
+    if reserved {
+        format!("_{}", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+// ── Planning ──
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Case-insensitive key, since two names differing only in case are the same
+/// file on macOS and Windows.
+fn collision_key(path: &Path) -> String {
+    path_string(path).to_lowercase()
+}
+
+fn is_cover_file(path: &Path) -> bool {
+    let lower = |s: Option<&std::ffi::OsStr>| s.map(|s| s.to_string_lossy().to_lowercase());
+    let (Some(stem), Some(ext)) = (lower(path.file_stem()), lower(path.extension())) else {
+        return false;
+    };
+    COVER_FILE_STEMS.contains(&stem.as_str()) && COVER_FILE_EXTENSIONS.contains(&ext.as_str())
+}
+
+/// Work out where each track (paired with its collection folder) would go under
+/// `template`. Tracks already in place are left out. Moves that cannot be made
+/// safely carry a `problem`:
+/// - two or more files map to the same target, or
+/// - a different file already exists at the target.
+///
+/// When every audio file in a folder moves to the same new folder, the cover
+/// images in it (`cover.jpg`, `folder.png`, …) are planned to move along.
+pub fn plan_moves(template: &Template, tracks: &[(TrackRow, String)]) -> Vec<OrganizeMove> {
+    let mut moves: Vec<OrganizeMove> = tracks
+        .iter()
+        .map(|(track, root)| {
+            let target = Path::new(root).join(template.render(track));
+            OrganizeMove {
+                track_id: Some(track.id),
+                from_path: track.file_path.clone(),
+                to_path: path_string(&target),
+                problem: None,
+            }
+        })
+        .filter(|m| m.from_path != m.to_path)
+        .collect();
+
+    let mut targets: HashMap<String, usize> = HashMap::new();
+    for m in &moves {
+        *targets.entry(collision_key(Path::new(&m.to_path))).or_default() += 1;
+    }
+    for m in &mut moves {
+        let count = targets[&collision_key(Path::new(&m.to_path))];
+        if count > 1 {
+            m.problem = Some(format!("{} files would be moved to this path", count));
+        } else if occupied_by_other(&m.from_path, &m.to_path) {
+            m.problem = Some("A different file already exists at this path".to_string());
+        }
+    }
+
+    let cover_moves = plan_cover_moves(&moves);
+    moves.extend(cover_moves);
+    moves
+}
+
+/// Whether `to` exists and is not just `from` under a different letter case.
+fn occupied_by_other(from: &str, to: &str) -> bool {
+    Path::new(to).exists() && from.to_lowercase() != to.to_lowercase()
+}
+
+fn plan_cover_moves(track_moves: &[OrganizeMove]) -> Vec<OrganizeMove> {
+    // Source folder -> (files moving out of it, folders they move to)
+    let mut by_dir: HashMap<PathBuf, (HashSet<PathBuf>, HashSet<PathBuf>)> = HashMap::new();
+    for m in track_moves.iter().filter(|m| m.problem.is_none()) {
+        let (from, to) = (Path::new(&m.from_path), Path::new(&m.to_path));
+        let (Some(from_dir), Some(to_dir)) = (from.parent(), to.parent()) else {
+            continue;
+        };
+        let entry = by_dir.entry(from_dir.to_path_buf()).or_default();
+        entry.0.insert(from.to_path_buf());
+        entry.1.insert(to_dir.to_path_buf());
+    }
+
+    let mut cover_moves = Vec::new();
+    for (dir, (moving, target_dirs)) in by_dir {
+        let Some(target_dir) = target_dirs.iter().next().filter(|_| target_dirs.len() == 1) else {
+            continue;
+        };
+        if collision_key(target_dir) == collision_key(&dir) {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+        // Another album (or an unscanned file) still lives here: leave the art alone
+        if files.iter().any(|p| is_audio_path(p) && !moving.contains(p)) {
+            continue;
+        }
+        for cover in files.iter().filter(|p| is_cover_file(p)) {
+            let Some(name) = cover.file_name() else { continue };
+            let from_path = path_string(cover);
+            let to_path = path_string(&target_dir.join(name));
+            let problem = occupied_by_other(&from_path, &to_path)
+                .then(|| "A different file already exists at this path".to_string());
+            cover_moves.push(OrganizeMove { track_id: None, from_path, to_path, problem });
+        }
+    }
+    cover_moves.sort_by(|a, b| a.from_path.cmp(&b.from_path));
+    cover_moves
+}
+
+// ── Execution ──
+
+/// Carry out every planned move without a `problem`, file first, then point the
+/// track (and any file backup) at the new path. A move that fails gets its error
+/// recorded in `problem` and the rest continue. Folders left empty are removed.
+pub async fn apply_moves(db: &DbPool, moves: &mut [OrganizeMove]) -> Result<(), AppError> {
+    let mut vacated = HashSet::new();
+    for m in moves.iter_mut().filter(|m| m.problem.is_none()) {
+        if let Err(e) = move_file(Path::new(&m.from_path), Path::new(&m.to_path)) {
+            warn!("Failed to move {:?} to {:?}: {}", m.from_path, m.to_path, e);
+            m.problem = Some(format!("Move failed: {}", e));
+            continue;
+        }
+        if let Some(track_id) = m.track_id {
+            let mut tx = db.begin().await?;
+            sqlx::query("UPDATE tracks SET file_path = ?, updated_at = ? WHERE id = ?")
+                .bind(&m.to_path)
+                .bind(Utc::now().to_rfc3339())
+                .bind(track_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("UPDATE file_backups SET file_path = ? WHERE file_path = ?")
+                .bind(&m.to_path)
+                .bind(&m.from_path)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        }
+        if let Some(dir) = Path::new(&m.from_path).parent() {
+            vacated.insert(dir.to_path_buf());
+        }
+    }
+
+    for dir in vacated {
+        remove_empty_dirs(&dir);
+    }
+    info!("Organized {} files", moves.iter().filter(|m| m.problem.is_none()).count());
+    Ok(())
+}
+
+/// Rename, falling back to copy-and-delete when the target is on another volume.
+pub(crate) fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to)?;
+    if let Err(e) = std::fs::remove_file(from) {
+        let _ = std::fs::remove_file(to);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Remove `dir` and then its parents for as long as they are empty.
+fn remove_empty_dirs(dir: &Path) {
+    let mut current = Some(dir);
+    // remove_dir fails on a non-empty folder, which ends the walk
+    while let Some(d) = current {
+        if std::fs::remove_dir(d).is_err() {
+            break;
+        }
+        current = d.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_helpers::track_row;
+
+    fn track(title: &str, file_path: &str) -> TrackRow {
+        TrackRow { file_path: file_path.to_string(), ..track_row(1, title) }
+    }
+
+    #[test]
+    fn test_render_pads_numbers_and_falls_back() {
+        let template =
+            Template::parse("{album_artist}/{year} - {album}/{disc}-{track:02} {title}.{ext}").unwrap();
+        let mut t = track("Intro", "/music/x/01.MP3");
+        t.artist_name = Some("Band".into());
+        t.album_title = Some("First".into());
+        t.year = Some(1999);
+        t.disc_number = Some(1);
+        t.track_number = Some(3);
+
+        assert_eq!(
+            template.render(&t),
+            PathBuf::from("Band").join("1999 - First").join("1-03 Intro.mp3")
+        );
+
+        t.album_title = None;
+        t.track_number = None;
+        assert_eq!(
+            template.render(&t),
+            PathBuf::from("Band").join("1999 - Unknown Album").join("1-00 Intro.mp3")
+        );
+    }
+
+    #[test]
+    fn test_render_sanitizes_values() {
+        let template = Template::parse("{artist}/{title}.{ext}").unwrap();
+        let mut t = track("What? Now: Yes/No", "/music/a.flac");
+        t.artist_name = Some("..".into());
+
+        assert_eq!(template.render(&t), PathBuf::from("_").join("What_ Now_ Yes_No.flac"));
+
+        t.artist_name = Some("Con".into());
+        t.title = "Trailing dots...".into();
+        assert_eq!(template.render(&t), PathBuf::from("_Con").join("Trailing dots....flac"));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_templates() {
+        for bad in ["{title}", "{nope}.{ext}", "{title.{ext}", "../{title}.{ext}", "/abs/{title}.{ext}", "{track:x}.{ext}", "a}.{ext}"] {
+            assert!(
+                matches!(Template::parse(bad), Err(AppError::InvalidInput(_))),
+                "accepted {:?}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::models::{AppError, PlaylistFormat};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+/// One track to write into a playlist file.
+pub struct ExportEntry {
+    /// As written to the file: relative to the export directory, or absolute
+    pub location: String,
+    pub title: String,
+    pub artist: Option<String>,
+    pub duration_secs: Option<f64>,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "m3u" | "m3u8" => Some(Self::M3u),
+            "pls" => Some(Self::Pls),
+            "xspf" => Some(Self::Xspf),
+            _ => None,
+        }
+    }
+}
+
+// ── Reading ──
+
+/// Decode playlist bytes: UTF-8 (with or without BOM), else Latin-1 as used by
+/// older `.m3u` files.
+pub fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// The entry locations in file order, exactly as written (paths or URLs).
+pub fn parse(format: PlaylistFormat, content: &str) -> Result<Vec<String>, AppError> {
+    match format {
+        PlaylistFormat::M3u => Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect()),
+        PlaylistFormat::Pls => {
+            let mut files: Vec<(u32, String)> = content
+                .lines()
+                .filter_map(|line| {
+                    let (key, value) = line.trim().split_once('=')?;
+                    let index = key.trim().to_ascii_lowercase().strip_prefix("file")?.parse().ok()?;
+                    Some((index, value.trim().to_string()))
+                })
+                .collect();
+            files.sort_by_key(|(index, _)| *index);
+            Ok(files.into_iter().map(|(_, location)| location).collect())
+        }
+        PlaylistFormat::Xspf => parse_xspf(content),
+    }
+}
+
+fn parse_xspf(content: &str) -> Result<Vec<String>, AppError> {
+    let invalid = |e: &dyn std::fmt::Display| AppError::InvalidInput(format!("Invalid XSPF playlist: {}", e));
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut locations = Vec::new();
+    let mut in_track = false;
+    let mut in_location = false;
+    loop {
+        match reader.read_event().map_err(|e| invalid(&e))? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"track" => in_track = true,
+                // Only the first location of a track is used
+                b"location" if in_track => in_location = true,
+                _ => {}
+            },
+            Event::Text(text) if in_location => {
+                locations.push(text.unescape().map_err(|e| invalid(&e))?.trim().to_string());
+                in_location = false;
+                in_track = false;
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"track" => in_track = false,
+                b"location" => in_location = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(locations)
+}
+
+/// Turn a playlist entry into an absolute path with `/` separators, the form
+/// `tracks.file_path` uses. Relative entries are taken from `base_dir` (the
+/// playlist's folder). Returns None for streams and other non-file URLs.
+pub fn resolve_location(location: &str, base_dir: &Path) -> Option<String> {
+    let path = if location.contains("://") || location.starts_with("file:") {
+        if !location.to_ascii_lowercase().starts_with("file:") {
+            return None;
+        }
+        Url::parse(location).ok()?.to_file_path().ok()?
+    } else if is_uri_reference(location) {
+        // XSPF allows relative, percent-encoded URI references, but "100%25.mp3"
+        // may also be a file's real name: keep it literal if only that exists
+        let literal = literal_path(location, base_dir);
+        match Url::from_directory_path(base_dir).ok().and_then(|dir| dir.join(location).ok()) {
+            Some(url) => match url.to_file_path() {
+                Ok(decoded) if decoded.exists() || !literal.exists() => decoded,
+                _ => literal,
+            },
+            None => literal,
+        }
+    } else {
+        literal_path(location, base_dir)
+    };
+    Some(normalize(&path).to_string_lossy().replace('\\', "/"))
+}
+
+fn literal_path(location: &str, base_dir: &Path) -> PathBuf {
+    let location = location.replace('\\', "/");
+    let path = PathBuf::from(&location);
+    if path.is_absolute() || has_drive_prefix(&location) {
+        path
+    } else {
+        base_dir.join(path)
+    }
+}
+
+/// A relative location holding at least one valid `%XX` escape.
+fn is_uri_reference(location: &str) -> bool {
+    let has_escape = location
+        .as_bytes()
+        .windows(3)
+        .any(|w| w[0] == b'%' && w[1].is_ascii_hexdigit() && w[2].is_ascii_hexdigit());
+    has_escape && !location.contains('\\') && !has_drive_prefix(location)
+}
+
+/// `C:/…` style paths written by Windows players.
+fn has_drive_prefix(location: &str) -> bool {
+    let bytes = location.as_bytes();
+    bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'/'
+}
+
+/// Resolve `.` and `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// ── Writing ──
+
+/// `path` relative to the directory `base` with `/` separators, or `path`
+/// itself when the two share no root (e.g. different Windows drives).
+pub fn relative_location(path: &Path, base: &Path) -> String {
+    let (path, base) = (normalize(path), normalize(base));
+    let path_parts: Vec<Component> = path.components().collect();
+    let base_parts: Vec<Component> = base.components().collect();
+    let common = path_parts.iter().zip(&base_parts).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return path.to_string_lossy().replace('\\', "/");
+    }
+    let mut parts: Vec<String> = vec!["..".to_string(); base_parts.len() - common];
+    parts.extend(path_parts[common..].iter().map(|c| c.as_os_str().to_string_lossy().into_owned()));
+    parts.join("/")
+}
+
+/// Serialize a playlist. `title` is the playlist name, stored where the format has room for it.
+pub fn write(format: PlaylistFormat, title: &str, entries: &[ExportEntry]) -> String {
+    let display = |e: &ExportEntry| match &e.artist {
+        Some(artist) => format!("{} - {}", artist, e.title),
+        None => e.title.clone(),
+    };
+    let seconds = |e: &ExportEntry| e.duration_secs.map(|d| d.round() as i64).unwrap_or(-1);
+
+    let mut out = String::new();
+    match format {
+        PlaylistFormat::M3u => {
+            out.push_str("#EXTM3U\n");
+            out.push_str(&format!("#PLAYLIST:{}\n", title));
+            for e in entries {
+                out.push_str(&format!("#EXTINF:{},{}\n{}\n", seconds(e), display(e), e.location));
+            }
+        }
+        PlaylistFormat::Pls => {
+            out.push_str("[playlist]\n");
+            for (i, e) in entries.iter().enumerate() {
+                let n = i + 1;
+                out.push_str(&format!("File{}={}\nTitle{}={}\nLength{}={}\n", n, e.location, n, display(e), n, seconds(e)));
+            }
+            out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+        }
+        PlaylistFormat::Xspf => {
+            out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+            out.push_str(&format!("  <title>{}</title>\n  <trackList>\n", xml_escape(title)));
+            for e in entries {
+                out.push_str("    <track>\n");
+                out.push_str(&format!("      <location>{}</location>\n", xml_escape(&location_uri(&e.location))));
+                out.push_str(&format!("      <title>{}</title>\n", xml_escape(&e.title)));
+                if let Some(artist) = &e.artist {
+                    out.push_str(&format!("      <creator>{}</creator>\n", xml_escape(artist)));
+                }
+                if let Some(d) = e.duration_secs {
+                    out.push_str(&format!("      <duration>{}</duration>\n", (d * 1000.0).round() as i64));
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+        }
+    }
+    out
+}
+
+/// XSPF locations are URIs: absolute paths become `file://` URLs, relative ones
+/// percent-encoded references.
+fn location_uri(location: &str) -> String {
+    let path = Path::new(location);
+    if path.is_absolute() || has_drive_prefix(location) {
+        if let Ok(url) = Url::from_file_path(path) {
+            return url.to_string();
+        }
+    }
+    location.split('/').map(percent_encode_segment).collect::<Vec<_>>().join("/")
+}
+
+fn percent_encode_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for b in segment.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(location: &str, title: &str) -> ExportEntry {
+        ExportEntry {
+            location: location.to_string(),
+            title: title.to_string(),
+            artist: Some("Band".to_string()),
+            duration_secs: Some(61.4),
+        }
+    }
+
+    #[test]
+    fn test_parse_m3u_and_pls() {
+        let m3u = "#EXTM3U\n#EXTINF:61,Band - One\nrip/one.mp3\r\n\n# comment\n/abs/two.flac\n";
+        assert_eq!(parse(PlaylistFormat::M3u, m3u).unwrap(), ["rip/one.mp3", "/abs/two.flac"]);
+
+        let pls = "[playlist]\nFile2=b.mp3\nTitle1=A\nfile1=a.mp3\nNumberOfEntries=2\n";
+        assert_eq!(parse(PlaylistFormat::Pls, pls).unwrap(), ["a.mp3", "b.mp3"]);
+    }
+
+    #[test]
+    fn test_xspf_round_trip() {
+        let entries = [entry("rip/Tom & Jerry #1.mp3", "Tom & Jerry")];
+        let xml = write(PlaylistFormat::Xspf, "Mix <1>", &entries);
+        assert!(xml.contains("<location>rip/Tom%20%26%20Jerry%20%231.mp3</location>"), "{}", xml);
+
+        let locations = parse(PlaylistFormat::Xspf, &xml).unwrap();
+        assert_eq!(locations, ["rip/Tom%20%26%20Jerry%20%231.mp3"]);
+        let base = if cfg!(windows) { Path::new("C:/music") } else { Path::new("/music") };
+        let resolved = resolve_location(&locations[0], base).unwrap();
+        assert!(resolved.ends_with("/music/rip/Tom & Jerry #1.mp3"), "{}", resolved);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_location_forms() {
+        let base = Path::new("/music/lists");
+        assert_eq!(resolve_location("../rip/a.mp3", base).unwrap(), "/music/rip/a.mp3");
+        assert_eq!(resolve_location("sub\\b.mp3", base).unwrap(), "/music/lists/sub/b.mp3");
+        assert_eq!(resolve_location("/other/c.mp3", base).unwrap(), "/other/c.mp3");
+        assert_eq!(resolve_location("file:///other/d%20e.mp3", base).unwrap(), "/other/d e.mp3");
+        assert_eq!(resolve_location("http://radio.example/stream", base), None);
+    }
+
+    #[test]
+    fn test_percent_in_file_name_stays_literal() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("100%25 pure.mp3"), b"").unwrap();
+        std::fs::write(tmp.path().join("50% off.mp3"), b"").unwrap();
+        let base = tmp.path().to_string_lossy().replace('\\', "/");
+
+        // The decoded "100% pure.mp3" doesn't exist, the literal name does
+        let resolved = resolve_location("100%25 pure.mp3", tmp.path()).unwrap();
+        assert_eq!(resolved, format!("{}/100%25 pure.mp3", base));
+        // No valid escape: never decoded
+        let resolved = resolve_location("50% off.mp3", tmp.path()).unwrap();
+        assert_eq!(resolved, format!("{}/50% off.mp3", base));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_location() {
+        let base = Path::new("/music/lists");
+        assert_eq!(relative_location(Path::new("/music/rip/a.mp3"), base), "../rip/a.mp3");
+        assert_eq!(relative_location(Path::new("/music/lists/b.mp3"), base), "b.mp3");
+    }
+}
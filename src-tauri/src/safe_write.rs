@@ -0,0 +1,263 @@
+use crate::models::AppError;
+use lofty::file::{FileType, TaggedFile};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use std::fs::{FileTimes, Permissions};
+use std::io::Write;
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Change an audio file without ever leaving it half-written.
+///
+/// `write` is applied to a copy of `path` in the same directory, or in the
+/// target's directory when `path` is a symlink, which is left in place. The copy must
+/// still read back with Lofty as the same kind of file before it is flushed and
+/// renamed over the original, so a crash leaves either the old or the new file.
+/// Permission bits and the access time are carried over; the modification time
+/// is the time of the write so change detection picks it up.
+///
+/// `write` should report its own failures as `AppError::TagWrite`. A copy that
+/// fails verification is reported the same way; the original is never touched.
+pub fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&Path) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let resolved = std::fs::canonicalize(path)
+        .map_err(|e| AppError::Io(format!("Cannot resolve {:?}: {}", path, e)))?;
+    let path = resolved.as_path();
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| AppError::Io(format!("Cannot read {:?}: {}", path, e)))?;
+    let original_type = probe_file_type(path)
+        .map_err(|e| AppError::Io(format!("Cannot open audio file: {e}")))?;
+
+    // Dropping `temp` on any early return deletes the copy
+    let temp = temp_file_beside(path, None)?;
+    // Also copies the permission bits
+    std::fs::copy(path, temp.path())
+        .map_err(|e| AppError::Io(format!("Cannot copy {:?}: {}", path, e)))?;
+
+    write(temp.path())?;
+
+    match read_back(temp.path()) {
+        Ok(file) if Some(file.file_type()) == original_type => {}
+        Ok(file) => {
+            return Err(AppError::TagWrite(format!(
+                "{:?} would change from {:?} to {:?}",
+                path,
+                original_type,
+                file.file_type()
+            )))
+        }
+        Err(e) => {
+            return Err(AppError::TagWrite(format!(
+                "{:?} is unreadable after writing: {}",
+                path, e
+            )))
+        }
+    }
+
+    if let Ok(accessed) = metadata.accessed() {
+        let _ = temp.as_file().set_times(FileTimes::new().set_accessed(accessed));
+    }
+    persist(temp, path)
+}
+
+/// Create or replace `path` with `contents` the same way, for files written
+/// whole such as exported playlists: a crash leaves the old file or the new
+/// one. A new file gets the usual read-write permissions.
+pub fn write_file_atomically(path: &Path, contents: &[u8]) -> Result<(), AppError> {
+    let resolved = std::fs::canonicalize(path).ok();
+    let path = resolved.as_deref().unwrap_or(path);
+    let permissions = match std::fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        #[cfg(unix)]
+        Err(_) => Some(std::os::unix::fs::PermissionsExt::from_mode(0o644)),
+        #[cfg(not(unix))]
+        Err(_) => None,
+    };
+    let temp = temp_file_beside(path, permissions)?;
+    temp.as_file()
+        .write_all(contents)
+        .map_err(|e| AppError::Io(format!("Cannot write {:?}: {}", temp.path(), e)))?;
+    persist(temp, path)
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    path.parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Name prefix of the temp files written beside audio files. One left by a
+/// crash keeps its audio extension, so scans skip names with this prefix.
+pub const TEMP_FILE_PREFIX: &str = ".chant-";
+
+/// A hidden temp file in `path`'s directory, with its extension.
+fn temp_file_beside(path: &Path, permissions: Option<Permissions>) -> Result<NamedTempFile, AppError> {
+    let suffix = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut builder = tempfile::Builder::new();
+    if let Some(permissions) = permissions {
+        builder.permissions(permissions);
+    }
+    builder
+        .prefix(TEMP_FILE_PREFIX)
+        .suffix(&suffix)
+        .tempfile_in(parent_dir(path))
+        .map_err(|e| AppError::Io(format!("Cannot create temp file next to {:?}: {}", path, e)))
+}
+
+/// Flush `temp` and rename it over `path`.
+fn persist(temp: NamedTempFile, path: &Path) -> Result<(), AppError> {
+    temp.as_file()
+        .sync_all()
+        .map_err(|e| AppError::Io(format!("Cannot flush {:?}: {}", temp.path(), e)))?;
+    temp.persist(path)
+        .map_err(|e| AppError::Io(format!("Cannot replace {:?}: {}", path, e.error)))?;
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Ok(d) = std::fs::File::open(parent_dir(path)) {
+        let _ = d.sync_all();
+    }
+
+    Ok(())
+}
+
+fn probe_file_type(path: &Path) -> lofty::error::Result<Option<FileType>> {
+    Ok(Probe::open(path)?.guess_file_type()?.file_type())
+}
+
+fn read_back(path: &Path) -> lofty::error::Result<TaggedFile> {
+    Probe::open(path)?.guess_file_type()?.read()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lofty::config::WriteOptions;
+    use lofty::tag::{Tag, TagType};
+
+    /// Three bare MPEG frames: the smallest file Lofty reads as an MP3.
+    fn make_mp3(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("song.mp3");
+        let mut file = std::fs::File::create(&path).unwrap();
+        let mut frame = [0u8; 417];
+        frame[0] = 0xFF; frame[1] = 0xFB; frame[2] = 0x90; frame[3] = 0x64;
+        for _ in 0..3 { file.write_all(&frame).unwrap(); }
+        path
+    }
+
+    fn leftover_temp_files(dir: &Path) -> usize {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(".chant-"))
+            .count()
+    }
+
+    #[test]
+    fn test_replaces_file_with_written_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mp3 = make_mp3(tmp.path());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&mp3, std::fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        write_atomically(&mp3, |copy| {
+            let mut tag = Tag::new(TagType::Id3v2);
+            tag.set_title("Atomic".into());
+            tag.save_to_path(copy, WriteOptions::default())
+                .map_err(|e| AppError::TagWrite(e.to_string()))
+        })
+        .unwrap();
+
+        let tagged = lofty::read_from_path(&mp3).unwrap();
+        assert_eq!(tagged.primary_tag().unwrap().title().as_deref(), Some("Atomic"));
+        assert_eq!(leftover_temp_files(tmp.path()), 0);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&mp3).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_is_kept_and_target_replaced() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = tmp.path().join("store");
+        let library = tmp.path().join("library");
+        std::fs::create_dir(&store).unwrap();
+        std::fs::create_dir(&library).unwrap();
+        let target = make_mp3(&store);
+        let link = library.join("song.mp3");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomically(&link, |copy| {
+            let mut tag = Tag::new(TagType::Id3v2);
+            tag.set_title("Linked".into());
+            tag.save_to_path(copy, WriteOptions::default())
+                .map_err(|e| AppError::TagWrite(e.to_string()))
+        })
+        .unwrap();
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        let tagged = lofty::read_from_path(&target).unwrap();
+        assert_eq!(tagged.primary_tag().unwrap().title().as_deref(), Some("Linked"));
+        assert_eq!(leftover_temp_files(&store), 0);
+        assert_eq!(leftover_temp_files(&library), 0);
+    }
+
+    #[test]
+    fn test_write_file_creates_and_replaces() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("mix.m3u8");
+
+        write_file_atomically(&path, b"#EXTM3U\na.mp3\n").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"#EXTM3U\na.mp3\n");
+        write_file_atomically(&path, b"#EXTM3U\nb.mp3\n").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"#EXTM3U\nb.mp3\n");
+        assert_eq!(leftover_temp_files(tmp.path()), 0);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o644);
+        }
+    }
+
+    #[test]
+    fn test_unreadable_result_keeps_original() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mp3 = make_mp3(tmp.path());
+        let original = std::fs::read(&mp3).unwrap();
+
+        // Simulate a writer that truncates the file mid-way
+        let result = write_atomically(&mp3, |copy| {
+            std::fs::write(copy, b"ID3").unwrap();
+            Ok(())
+        });
+
+        assert!(matches!(result, Err(AppError::TagWrite(_))), "got {:?}", result);
+        assert_eq!(std::fs::read(&mp3).unwrap(), original);
+        assert_eq!(leftover_temp_files(tmp.path()), 0);
+    }
+
+    #[test]
+    fn test_writer_error_keeps_original() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mp3 = make_mp3(tmp.path());
+        let original = std::fs::read(&mp3).unwrap();
+
+        let result = write_atomically(&mp3, |_| Err(AppError::TagWrite("boom".into())));
+
+        assert!(matches!(result, Err(AppError::TagWrite(msg)) if msg == "boom"));
+        assert_eq!(std::fs::read(&mp3).unwrap(), original);
+        assert_eq!(leftover_temp_files(tmp.path()), 0);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::commands::sync_changed_paths_inner;
+use crate::db::DbPool;
+use crate::jobs::ScanJobs;
+use crate::models::{AppError, Collection};
+use log::{error, info, warn};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+
+/// Quiet period before a burst of events (e.g. an album being copied in) is applied.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Tauri-managed set of filesystem watchers, one per collection in watch mode.
+/// Dropping a collection's debouncer stops its watcher and ends its sync task.
+#[derive(Default)]
+pub struct CollectionWatchers {
+    watchers: Mutex<HashMap<i64, Debouncer<RecommendedWatcher>>>,
+}
+
+impl CollectionWatchers {
+    /// Start watching `collection` (replacing any existing watcher for it).
+    pub fn watch(&self, app_handle: &tauri::AppHandle, collection: &Collection) -> Result<(), AppError> {
+        let root = PathBuf::from(&collection.path);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Vec<PathBuf>>();
+
+        let mut debouncer = new_debouncer(DEBOUNCE, move |res: DebounceEventResult| match res {
+            Ok(events) => {
+                let paths: Vec<PathBuf> = events.into_iter().map(|e| e.path).collect();
+                let _ = tx.send(paths);
+            }
+            Err(e) => warn!("Watcher error: {}", e),
+        })
+        .map_err(|e| AppError::Io(format!("Failed to create watcher: {}", e)))?;
+        debouncer
+            .watcher()
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| AppError::Io(format!("Failed to watch {:?}: {}", root, e)))?;
+
+        let handle = app_handle.clone();
+        let db = app_handle.state::<DbPool>().inner().clone();
+        let covers_dir = app_handle.path().app_data_dir().ok().map(|d| d.join("covers"));
+        let collection_id = collection.id;
+        tauri::async_runtime::spawn(async move {
+            while let Some(mut paths) = rx.recv().await {
+                paths.sort();
+                paths.dedup();
+                let jobs = handle.state::<ScanJobs>();
+                let _library = jobs.hold_library().await;
+                match sync_changed_paths_inner(&db, collection_id, &paths, covers_dir.as_deref()).await {
+                    Ok(changes) => {
+                        for change in changes {
+                            let _ = handle.emit("library:changed", &change);
+                        }
+                    }
+                    Err(e) => error!("Live sync of collection {} failed: {}", collection_id, e),
+                }
+            }
+        });
+
+        info!("Watching collection {} at {:?}", collection.id, root);
+        self.watchers.lock().unwrap().insert(collection.id, debouncer);
+        Ok(())
+    }
+
+    pub fn unwatch(&self, collection_id: i64) {
+        if self.watchers.lock().unwrap().remove(&collection_id).is_some() {
+            info!("Stopped watching collection {}", collection_id);
+        }
+    }
+
+    pub fn unwatch_all(&self) {
+        self.watchers.lock().unwrap().clear();
+    }
+}
+
+/// Start watchers for every collection that has watch mode enabled.
+pub async fn restore_watchers(app_handle: &tauri::AppHandle) {
+    let db = app_handle.state::<DbPool>().inner().clone();
+    let collections = match sqlx::query_as::<_, Collection>(
+        "SELECT id, path, label, created_at, watch FROM collections WHERE watch = 1",
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to load watched collections: {}", e);
+            return;
+        }
+    };
+
+    let watchers = app_handle.state::<CollectionWatchers>();
+    for collection in &collections {
+        if let Err(e) = watchers.watch(app_handle, collection) {
+            warn!("Could not watch collection {}: {}", collection.id, e);
+        }
+    }
+}
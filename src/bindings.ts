@@ -31,6 +31,14 @@ async deleteCollection(collectionId: number) : Promise<Result<null, AppError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async setCollectionWatch(collectionId: number, enabled: boolean) : Promise<Result<Collection, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_collection_watch", { collectionId, enabled }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async clearAllData() : Promise<Result<null, AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("clear_all_data") };
@@ -39,6 +47,14 @@ async clearAllData() : Promise<Result<null, AppError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async vacuumLibrary() : Promise<Result<VacuumSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("vacuum_library") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async getLibraryStats() : Promise<Result<LibraryStats, AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("get_library_stats") };
@@ -95,6 +111,14 @@ async listTracks() : Promise<Result<TrackRow[], AppError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async queryTracks(filter: LibraryFilter, sort: SortSpec | null, page: PageRequest) : Promise<Result<Page<TrackRow>, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("query_tracks", { filter, sort, page }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async getTrack(trackId: number) : Promise<Result<TrackRow, AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("get_track", { trackId }) };
@@ -119,6 +143,62 @@ async batchUpdateTracks(trackIds: number[], input: TrackUpdateInput) : Promise<R
     else return { status: "error", error: e  as any };
 }
 },
+async undoLastEdit() : Promise<Result<EditOperation, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("undo_last_edit") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async redoEdit() : Promise<Result<EditOperation, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("redo_edit") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listEditHistory() : Promise<Result<EditOperation[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_edit_history") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async restoreTrackFile(trackId: number) : Promise<Result<TrackRow, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("restore_track_file", { trackId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async organizeFiles(trackIds: number[], template: string, dryRun: boolean) : Promise<Result<OrganizeMove[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("organize_files", { trackIds, template, dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async findDuplicates(criteria: DuplicateCriteria, collectionId: number | null) : Promise<Result<DuplicateGroup[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("find_duplicates", { criteria, collectionId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async removeDuplicates(trackIds: number[], action: DuplicateAction) : Promise<Result<RemovedDuplicate[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("remove_duplicates", { trackIds, action }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async staleTrackIds() : Promise<Result<number[], AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("stale_track_ids") };
@@ -127,6 +207,22 @@ async staleTrackIds() : Promise<Result<number[], AppError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async verifyAudioIntegrity(collectionId: number | null) : Promise<Result<number[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("verify_audio_integrity", { collectionId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async searchLibrary(query: string, limit: number | null) : Promise<Result<SearchResults, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("search_library", { query, limit }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async getTrackExtraTags(trackId: number) : Promise<Result<ExtraTag[], AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("get_track_extra_tags", { trackId }) };
@@ -143,6 +239,126 @@ async setTrackExtraTags(trackId: number, tags: ExtraTag[]) : Promise<Result<null
     else return { status: "error", error: e  as any };
 }
 },
+async listSmartPlaylists() : Promise<Result<SmartPlaylist[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_smart_playlists") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async createSmartPlaylist(input: SmartPlaylistInput) : Promise<Result<SmartPlaylist, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("create_smart_playlist", { input }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async updateSmartPlaylist(playlistId: number, input: SmartPlaylistInput) : Promise<Result<SmartPlaylist, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("update_smart_playlist", { playlistId, input }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async deleteSmartPlaylist(playlistId: number) : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("delete_smart_playlist", { playlistId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listSmartPlaylistTracks(playlistId: number) : Promise<Result<TrackRow[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_smart_playlist_tracks", { playlistId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listPlaylists() : Promise<Result<Playlist[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_playlists") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async createPlaylist(name: string) : Promise<Result<Playlist, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("create_playlist", { name }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async renamePlaylist(playlistId: number, name: string) : Promise<Result<Playlist, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("rename_playlist", { playlistId, name }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async deletePlaylist(playlistId: number) : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("delete_playlist", { playlistId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listPlaylistEntries(playlistId: number) : Promise<Result<PlaylistEntry[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_playlist_entries", { playlistId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async addTracksToPlaylist(playlistId: number, trackIds: number[], position: number | null) : Promise<Result<PlaylistEntry[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("add_tracks_to_playlist", { playlistId, trackIds, position }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async removePlaylistEntries(playlistId: number, entryIds: number[]) : Promise<Result<PlaylistEntry[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("remove_playlist_entries", { playlistId, entryIds }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async reorderPlaylist(playlistId: number, entryIds: number[]) : Promise<Result<PlaylistEntry[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("reorder_playlist", { playlistId, entryIds }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async importPlaylist(path: string, name: string | null) : Promise<Result<PlaylistImport, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("import_playlist", { path, name }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async exportPlaylist(playlistId: number, path: string, format: PlaylistFormat | null, relativeTo: string | null) : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("export_playlist", { playlistId, path, format, relativeTo }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async listArtists() : Promise<Result<Artist[], AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("list_artists") };
@@ -159,6 +375,70 @@ async listArtistRows() : Promise<Result<ArtistRow[], AppError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async queryArtistRows(filter: LibraryFilter, sort: SortSpec | null, page: PageRequest) : Promise<Result<Page<ArtistRow>, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("query_artist_rows", { filter, sort, page }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getTrackCredits(trackId: number) : Promise<Result<TrackCredit[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_track_credits", { trackId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async renameArtist(artistId: number, name: string, dryRun: boolean) : Promise<Result<EntityEditSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("rename_artist", { artistId, name, dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async mergeArtists(artistIds: number[], targetId: number, dryRun: boolean) : Promise<Result<EntityEditSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("merge_artists", { artistIds, targetId, dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async setArtistSortName(artistId: number, sortName: string | null, dryRun: boolean) : Promise<Result<EntityEditSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_artist_sort_name", { artistId, sortName, dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async findNameDuplicates() : Promise<Result<NameDuplicates, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("find_name_duplicates") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async mergeAlbums(albumIds: number[], targetId: number, dryRun: boolean) : Promise<Result<EntityEditSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("merge_albums", { albumIds, targetId, dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async splitAlbum(albumId: number, trackIds: number[], title: string, albumArtist: string | null, dryRun: boolean) : Promise<Result<EntityEditSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("split_album", { albumId, trackIds, title, albumArtist, dryRun }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async listAlbums(artistId: number | null) : Promise<Result<Album[], AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("list_albums", { artistId }) };
@@ -175,6 +455,14 @@ async listAlbumRows() : Promise<Result<AlbumRow[], AppError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async queryAlbumRows(filter: LibraryFilter, sort: SortSpec | null, page: PageRequest) : Promise<Result<Page<AlbumRow>, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("query_album_rows", { filter, sort, page }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async listTracksByAlbum(albumId: number) : Promise<Result<TrackRow[], AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("list_tracks_by_album", { albumId }) };
@@ -183,7 +471,7 @@ async listTracksByAlbum(albumId: number) : Promise<Result<TrackRow[], AppError>>
     else return { status: "error", error: e  as any };
 }
 },
-async scanCollection(collectionId: number) : Promise<Result<number, AppError>> {
+async scanCollection(collectionId: number) : Promise<Result<ScanSummary, AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("scan_collection", { collectionId }) };
 } catch (e) {
@@ -191,6 +479,118 @@ async scanCollection(collectionId: number) : Promise<Result<number, AppError>> {
     else return { status: "error", error: e  as any };
 }
 },
+async cancelScan(jobId: number) : Promise<Result<ScanJob, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_scan", { jobId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async pauseScan(jobId: number) : Promise<Result<ScanJob, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("pause_scan", { jobId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async resumeScan(jobId: number) : Promise<Result<ScanJob, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("resume_scan", { jobId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listScanJobs() : Promise<Result<ScanJob[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("list_scan_jobs") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async analyzeLoudness(options: LoudnessOptions) : Promise<Result<LoudnessSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("analyze_loudness", { options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async cancelLoudnessAnalysis() : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_loudness_analysis") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async pauseLoudnessAnalysis() : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("pause_loudness_analysis") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async resumeLoudnessAnalysis() : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("resume_loudness_analysis") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async fingerprintTracks(options: FingerprintOptions) : Promise<Result<FingerprintSummary, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("fingerprint_tracks", { options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async cancelFingerprinting() : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_fingerprinting") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async pauseFingerprinting() : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("pause_fingerprinting") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async resumeFingerprinting() : Promise<Result<null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("resume_fingerprinting") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async findSimilarAudio(trackId: number | null, collectionId: number | null, minSimilarity: number | null) : Promise<Result<SimilarAudioGroup[], AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("find_similar_audio", { trackId, collectionId, minSimilarity }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getAcoustidFingerprint(trackId: number) : Promise<Result<AcoustIdFingerprint | null, AppError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_acoustid_fingerprint", { trackId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async getCoverArt(trackId: number) : Promise<Result<CoverArt | null, AppError>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("get_cover_art", { trackId }) };
@@ -227,12 +627,67 @@ async getArtistCoverArt(artistId: number) : Promise<Result<CoverArt | null, AppE
 
 /** user-defined types **/
 
-export type Album = { id: number; title: string; artistId: number | null; year: number | null; genre: string | null; coverPath: string | null; musicbrainzId: string | null; createdAt: string }
-export type AlbumRow = { id: number; title: string; artistName: string | null; year: number | null; genre: string | null; trackCount: number; totalDurationSecs: number; totalSizeBytes: number }
-export type AppError = { Database: string } | { NotFound: string } | { InvalidInput: string } | { Io: string } | { Serialization: string }
+/**
+ * What an AcoustID lookup needs for one track.
+ */
+export type AcoustIdFingerprint = { 
+/**
+ * Compressed and base64 encoded, as `fpcalc` prints it
+ */
+fingerprint: string; 
+/**
+ * Whole seconds, as the lookup expects
+ */
+durationSecs: number }
+export type Album = { id: number; title: string; artistId: number | null; year: number | null; genre: string | null; coverPath: string | null; musicbrainzId: string | null; createdAt: string; 
+/**
+ * Integrated loudness of the whole album (EBU R128)
+ */
+loudnessLufs: number | null; 
+/**
+ * Highest true peak of the album's tracks, linear (1.0 = full scale)
+ */
+truePeak: number | null; loudnessRangeLu: number | null }
+export type AlbumRow = { id: number; title: string; artistName: string | null; year: number | null; genre: string | null; trackCount: number; totalDurationSecs: number; totalSizeBytes: number; 
+/**
+ * Distinct codecs of the album's tracks, comma-separated
+ */
+codecs: string | null; avgBitrateKbps: number | null; maxSampleRateHz: number | null; maxBitDepth: number | null }
+export type AlbumSearchHit = { albumId: number; title: string; artistName: string | null; 
+/**
+ * The album title with the matched words marked
+ */
+highlighted: SnippetPart[] }
+export type AnalysisFailure = { trackId: number; filePath: string; error: string }
+/**
+ * Payload of the `loudness:progress` and `fingerprint:progress` events.
+ */
+export type AnalysisProgress = { processed: number; total: number }
+export type AppError = { Database: string } | { NotFound: string } | { InvalidInput: string } | { Io: string } | { Serialization: string } | { TagWrite: string }
 export type Artist = { id: number; name: string; sortName: string | null; musicbrainzId: string | null; createdAt: string }
 export type ArtistRow = { id: number; name: string; sortName: string | null; albumCount: number; trackCount: number; totalDurationSecs: number }
-export type Collection = { id: number; path: string; label: string | null; createdAt: string }
+export type ArtistSearchHit = { artistId: number; name: string; 
+/**
+ * The artist name with the matched words marked
+ */
+highlighted: SnippetPart[] }
+/**
+ * Two tracks whose fingerprints match.
+ */
+export type AudioMatch = { trackId: number; otherTrackId: number; 
+/**
+ * Share of the shorter fingerprint covered by matching audio, 0 to 1
+ */
+similarity: number; 
+/**
+ * Length of the matching audio
+ */
+matchedSecs: number }
+export type Collection = { id: number; path: string; label: string | null; createdAt: string; 
+/**
+ * Live-sync the collection from filesystem notifications
+ */
+watch: boolean }
 export type CollectionInput = { path: string; label: string | null }
 export type CoverArt = { 
 /**
@@ -243,10 +698,347 @@ data: string;
  * MIME type, e.g. "image/jpeg" or "image/png"
  */
 mimeType: string }
+/**
+ * What to do with the files of duplicates being removed from the library.
+ */
+export type DuplicateAction = { kind: "delete" } | 
+/**
+ * Move into `directory`, e.g. a quarantine folder outside the collections
+ */
+{ kind: "moveTo"; directory: string }
+/**
+ * What makes two tracks duplicates of each other.
+ */
+export type DuplicateCriteria = 
+/**
+ * Same title and artist ignoring case and punctuation, durations within `toleranceSecs`
+ */
+{ kind: "metadata"; toleranceSecs: number } | 
+/**
+ * Byte-identical files
+ */
+{ kind: "fileContent" } | 
+/**
+ * Identical audio data; tags and embedded pictures may differ
+ */
+{ kind: "audioContent" }
+export type DuplicateGroup = { 
+/**
+ * The copy to keep: lossless over lossy, then higher bitrate, sample rate and bit depth
+ */
+bestTrackId: number; 
+/**
+ * Best copy first
+ */
+tracks: TrackRow[] }
+/**
+ * One journaled tag edit (a single-track update or a batch update).
+ */
+export type EditOperation = { id: number; 
+/**
+ * Short human-readable description, e.g. "Edit 12 tracks"
+ */
+label: string; trackCount: number; createdAt: string; 
+/**
+ * True once undone; undone operations can be redone until the next new edit
+ */
+undone: boolean }
+/**
+ * What an artist or album edit changed, or would change when previewed.
+ */
+export type EntityEditSummary = { 
+/**
+ * Tracks re-linked to another artist or album
+ */
+trackCount: number; 
+/**
+ * Files whose tags are (or would be) rewritten
+ */
+fileCount: number; dryRun: boolean }
 export type ExtraTag = { frameId: string; value: string }
+/**
+ * One condition on a row field. `field` is the camelCase name used by the row
+ * type, e.g. `albumTitle` or `durationSecs`.
+ */
+export type FieldPredicate = 
+/**
+ * Case-insensitive for text fields
+ */
+{ op: "equals"; field: string; value: FilterValue } | 
+/**
+ * Also matches rows where the field is empty
+ */
+{ op: "notEquals"; field: string; value: FilterValue } | 
+/**
+ * Case-insensitive substring match (text fields only)
+ */
+{ op: "contains"; field: string; value: string } | 
+/**
+ * Case-insensitive prefix match (text fields only)
+ */
+{ op: "startsWith"; field: string; value: string } | 
+/**
+ * Inclusive range; either bound may be omitted
+ */
+{ op: "between"; field: string; min: FilterValue | null; max: FilterValue | null } | { op: "greaterThan"; field: string; value: FilterValue } | { op: "lessThan"; field: string; value: FilterValue } | 
+/**
+ * Timestamp fields (`createdAt`, `updatedAt`) within the last `days` days
+ */
+{ op: "inLastDays"; field: string; days: number } | { op: "isNull"; field: string } | { op: "isNotNull"; field: string }
+export type FilterValue = number | string
+/**
+ * Which tracks `fingerprint_tracks` fingerprints.
+ */
+export type FingerprintOptions = { 
+/**
+ * Limit to one collection
+ */
+collectionId: number | null; 
+/**
+ * Limit to these tracks
+ */
+trackIds: number[] | null; 
+/**
+ * Fingerprint tracks again even if their audio is unchanged since the last run
+ */
+recompute: boolean }
+export type FingerprintSummary = { fingerprinted: number; failed: AnalysisFailure[]; cancelled: boolean }
+/**
+ * Payload of the `library:changed` event emitted by collection watchers.
+ */
+export type LibraryChange = { collectionId: number; trackId: number; filePath: string; kind: LibraryChangeKind }
+export type LibraryChangeKind = 
+/**
+ * Track row was inserted or re-read from its file
+ */
+"upserted" | 
+/**
+ * Track row was deleted because its file is gone
+ */
+"removed"
+/**
+ * Narrows a track, album or artist query. All parts are combined with AND.
+ */
+export type LibraryFilter = { collectionId: number | null; artistId: number | null; albumId: number | null; 
+/**
+ * Case-insensitive substring match over the row's name fields
+ * (title, artist and album for tracks)
+ */
+text: string | null; predicates: FieldPredicate[] }
 export type LibraryStats = { totalCollections: number; totalArtists: number; totalAlbums: number; totalTracks: number; totalSizeBytes: number; totalDurationSecs: number }
+/**
+ * Which tracks `analyze_loudness` measures. Tracks are analysed together with
+ * the rest of their album, so album values always cover the whole album.
+ */
+export type LoudnessOptions = { /**
+ * Limit to one collection
+ */
+collectionId: number | null; 
+/**
+ * Limit to these tracks
+ */
+trackIds: number[] | null; 
+/**
+ * Measure tracks again even if their audio is unchanged since the last analysis
+ */
+reanalyze: boolean; 
+/**
+ * Also write ReplayGain tags to the files (R128 gain tags for Opus).
+ */
+writeTags: boolean }
+export type LoudnessSummary = { /**
+ * Tracks measured and stored
+ */
+analyzed: number; 
+/**
+ * Albums whose album values were stored
+ */
+albums: number; tagsWritten: number; failed: AnalysisFailure[]; 
+/**
+ * True when the job was cancelled; albums still in progress were not stored
+ */
+cancelled: boolean }
+/**
+ * Artists and albums whose names differ only in case, Unicode form, spacing,
+ * punctuation or a leading article ("AC/DC" and "Ac-Dc"), for merging.
+ */
+export type NameDuplicates = { 
+/**
+ * Groups of two or more artists
+ */
+artists: ArtistRow[][]; 
+/**
+ * Groups of two or more albums whose artists' names match as well
+ */
+albums: AlbumRow[][] }
+/**
+ * One file move computed from a path template.
+ */
+export type OrganizeMove = { 
+/**
+ * None for a cover image moving along with its album's tracks
+ */
+trackId: number | null; fromPath: string; toPath: string; 
+/**
+ * Why the move was (or would be) skipped: a collision or a failed move
+ */
+problem: string | null }
+export type Page<T> = { rows: T[]; 
+/**
+ * Rows matching the filter, across all pages
+ */
+total: number; 
+/**
+ * Pass back as `PageRequest.cursor` for the next page; None on the last page
+ */
+nextCursor: string | null }
+/**
+ * Either `offset` or `cursor` (from a previous page's `nextCursor`) may be set.
+ */
+export type PageRequest = { offset: number | null; 
+/**
+ * Defaults to 200; at most 5000
+ */
+limit: number | null; cursor: string | null }
+/**
+ * A hand-curated, ordered list of tracks.
+ */
+export type Playlist = { id: number; name: string; trackCount: number; createdAt: string; updatedAt: string }
+/**
+ * One slot in a playlist. The same track may fill several slots, so edits
+ * address entries by their own id.
+ */
+export type PlaylistEntry = { id: number; 
+/**
+ * 0-based sort key; may skip numbers where a deleted track's entry was
+ */
+position: number; track: TrackRow }
+/**
+ * Playlist file formats for import and export; `M3u` covers `.m3u` and `.m3u8`.
+ */
+export type PlaylistFormat = "m3u" | "pls" | "xspf"
+export type PlaylistImport = { playlist: Playlist; 
+/**
+ * Entries that matched no track in the library, as written in the file
+ */
+unresolved: string[] }
+export type RemovedDuplicate = { trackId: number; filePath: string; 
+/**
+ * Where the file went for `MoveTo`
+ */
+movedTo: string | null; 
+/**
+ * Why the track was kept: the file could not be deleted or moved
+ */
+problem: string | null }
+/**
+ * Predicates and nested groups combined with AND (`matchAll`) or OR.
+ * An empty group matches everything.
+ */
+export type RuleGroup = { matchAll: boolean; predicates: FieldPredicate[]; groups: RuleGroup[] }
+export type ScanJob = { id: number; collectionId: number; state: ScanJobState; 
+/**
+ * Files handled so far (written or skipped as unchanged)
+ */
+processed: number; startedAt: string; finishedAt: string | null; summary: ScanSummary | null; error: string | null }
+export type ScanJobState = "running" | "paused" | "cancelling" | "cancelled" | "completed" | "failed"
+/**
+ * Outcome of a (re)scan: how many files were new, re-read, skipped as unchanged
+ * or found under a new path, and how many rows were dropped because their file
+ * is gone from disk.
+ */
+export type ScanSummary = { added: number; updated: number; unchanged: number; removed: number; 
+/**
+ * Rows re-pointed to a file that was moved or renamed
+ */
+moved: number; 
+/**
+ * True when the scan was cancelled; counts cover only the work done so far
+ * and no rows were removed.
+ */
+cancelled: boolean }
+/**
+ * Search hits grouped by kind, each group ordered best match first.
+ */
+export type SearchResults = { tracks: TrackSearchHit[]; albums: AlbumSearchHit[]; artists: ArtistSearchHit[] }
 export type Setting = { key: string; value: string }
-export type TrackRow = { id: number; collectionId: number; albumId: number | null; artistId: number | null; title: string; trackNumber: number | null; discNumber: number | null; durationSecs: number | null; filePath: string; fileSizeBytes: number; fileFormat: string | null; bitrateKbps: number | null; sampleRateHz: number | null; lyrics: string | null; createdAt: string; updatedAt: string; genre: string | null; albumArtist: string | null; composer: string | null; bpm: number | null; comment: string | null; commentLang: string | null; year: number | null; lyricsLang: string | null; trackTotal: number | null; discTotal: number | null; fileMtime: number | null; artistName: string | null; albumTitle: string | null; albumCoverPath: string | null }
+/**
+ * Tracks that hold the same recording, linked by their pairwise matches.
+ */
+export type SimilarAudioGroup = { tracks: TrackRow[]; matches: AudioMatch[] }
+/**
+ * A saved rule set over track fields, evaluated whenever its tracks are listed.
+ */
+export type SmartPlaylist = { id: number; name: string; rules: RuleGroup; 
+/**
+ * Order of the tracks (and which ones are kept by `limit`); title when None
+ */
+sort: SortSpec | null; 
+/**
+ * Keep at most this many tracks
+ */
+limit: number | null; createdAt: string; updatedAt: string }
+export type SmartPlaylistInput = { name: string; rules: RuleGroup; sort: SortSpec | null; limit: number | null }
+/**
+ * A run of search result text; `highlight` marks the words that matched.
+ */
+export type SnippetPart = { text: string; highlight: boolean }
+export type SortSpec = { 
+/**
+ * camelCase row field, as in `FieldPredicate`
+ */
+field: string; descending: boolean }
+/**
+ * One artist credited on a track.
+ */
+export type TrackCredit = { artistId: number; artistName: string; 
+/**
+ * `artist`, `featured`, `composer`, `lyricist`, `remixer`, `conductor`,
+ * `performer` or `performer:<instrument>`
+ */
+role: string; 
+/**
+ * Order within the role, from 0
+ */
+position: number }
+export type TrackRow = { id: number; collectionId: number; albumId: number | null; artistId: number | null; title: string; trackNumber: number | null; discNumber: number | null; durationSecs: number | null; filePath: string; fileSizeBytes: number; fileFormat: string | null; 
+/**
+ * Bitrate of the audio stream alone
+ */
+bitrateKbps: number | null; 
+/**
+ * File size over duration, container and tags included
+ */
+overallBitrateKbps: number | null; sampleRateHz: number | null; channels: number | null; 
+/**
+ * Only set for PCM-based (lossless) formats
+ */
+bitDepth: number | null; 
+/**
+ * e.g. "MP3", "AAC", "ALAC", "FLAC", "Opus"
+ */
+codec: string | null; 
+/**
+ * e.g. "LC" or "HE-AAC" for AAC, "CBR", "VBR" or "ABR" for MP3
+ */
+codecProfile: string | null; lyrics: string | null; createdAt: string; updatedAt: string; genre: string | null; albumArtist: string | null; composer: string | null; bpm: number | null; comment: string | null; commentLang: string | null; year: number | null; lyricsLang: string | null; trackTotal: number | null; discTotal: number | null; fileMtime: number | null; 
+/**
+ * Hash of the audio data alone (see `audio_hash`); unchanged by tag edits
+ */
+audioHash: string | null; 
+/**
+ * Integrated loudness (EBU R128); None until analysed
+ */
+loudnessLufs: number | null; 
+/**
+ * Linear, 1.0 = digital full scale
+ */
+truePeak: number | null; loudnessRangeLu: number | null; artistName: string | null; albumTitle: string | null; albumCoverPath: string | null; albumLoudnessLufs: number | null; albumTruePeak: number | null }
+export type TrackSearchHit = { trackId: number; title: string; artistId: number | null; artistName: string | null; albumId: number | null; albumTitle: string | null; 
+/**
+ * Excerpt of the best-matching field (title, lyrics, comment, …)
+ */
+snippet: SnippetPart[] }
 export type TrackUpdateInput = { title: string | null; trackNumber: number | null; discNumber: number | null; lyrics: string | null; 
 /**
  * Set to Some("") to clear, Some("Name") to find-or-create, None to keep existing
@@ -256,6 +1048,26 @@ artistName: string | null;
  * Set to Some("") to clear, Some("Title") to find-or-create, None to keep existing
  */
 albumTitle: string | null; genre: string | null; albumArtist: string | null; composer: string | null; bpm: number | null; comment: string | null; commentLang: string | null; year: number | null; lyricsLang: string | null; trackTotal: number | null; discTotal: number | null }
+/**
+ * What `vacuum_library` removed.
+ */
+export type VacuumSummary = { 
+/**
+ * Artists no track, credit or album referred to
+ */
+removedArtists: number; 
+/**
+ * Albums without tracks
+ */
+removedAlbums: number; 
+/**
+ * Cover files no album used
+ */
+removedCovers: number; 
+/**
+ * How much smaller the database file got
+ */
+reclaimedBytes: number }
 
 /** tauri-specta globals **/
 
@@ -7,8 +7,11 @@ import {
   CommandList,
   CommandSeparator,
 } from "cmdk";
-import { useEffect } from "react";
+import { useEffect, useState } from "react";
 import { useNavigate } from "@tanstack/react-router";
+import { useQuery } from "@tanstack/react-query";
+import { commands, SnippetPart } from "../bindings";
+import { queryKeys } from "../lib/queryClient";
 import {
   LuHouse,
   LuMicVocal,
@@ -20,6 +23,27 @@ import {
   LuBot,
 } from "react-icons/lu";
 
+const itemClass =
+  "flex items-center gap-3 px-3 py-2 rounded-lg text-sm text-fg-secondary hover:bg-bg-overlay hover:text-fg-primary cursor-pointer transition-colors";
+const groupClass =
+  "px-2 py-1.5 text-[10px] font-bold uppercase tracking-wider text-fg-muted";
+
+function Highlighted({ parts }: { parts: SnippetPart[] }) {
+  return (
+    <>
+      {parts.map((part, i) =>
+        part.highlight ? (
+          <mark key={i} className="bg-accent-muted text-accent rounded-sm">
+            {part.text}
+          </mark>
+        ) : (
+          <span key={i}>{part.text}</span>
+        ),
+      )}
+    </>
+  );
+}
+
 export function CommandPalette({
   open,
   onOpenChange,
@@ -36,6 +60,31 @@ export function CommandPalette({
   onToggleRight: () => void;
 }) {
   const navigate = useNavigate();
+  const [search, setSearch] = useState("");
+  const [query, setQuery] = useState("");
+
+  // Debounce library search while typing
+  useEffect(() => {
+    const timer = setTimeout(() => setQuery(search.trim()), 150);
+    return () => clearTimeout(timer);
+  }, [search]);
+
+  useEffect(() => {
+    if (!open) setSearch("");
+  }, [open]);
+
+  const { data: results } = useQuery({
+    queryKey: [...queryKeys.tracks, "search", query],
+    queryFn: async () => {
+      const res = await commands.searchLibrary(query, 8);
+      if (res.status !== "ok") throw new Error("Search failed");
+      return res.data;
+    },
+    enabled: open && query.length >= 2,
+  });
+  const hasResults =
+    !!results &&
+    results.tracks.length + results.albums.length + results.artists.length > 0;
 
   useEffect(() => {
     const down = (e: KeyboardEvent) => {
@@ -64,7 +113,9 @@ export function CommandPalette({
       <div className="fixed inset-0 z-50 flex items-start justify-center pt-[20vh] bg-black/40 backdrop-blur-sm">
         <div className="w-full max-w-xl bg-bg-surface border border-border-strong rounded-xl shadow-2xl overflow-hidden animate-in fade-in zoom-in duration-200">
           <CommandInput
-            placeholder="Search commands..."
+            value={search}
+            onValueChange={setSearch}
+            placeholder="Search library and commands..."
             className="w-full bg-transparent border-none outline-none px-4 py-4 text-fg-primary placeholder:text-fg-muted text-sm border-b border-border"
           />
           <CommandList className="max-h-[300px] overflow-auto p-2 scrollbar-hide">
@@ -72,6 +123,94 @@ export function CommandPalette({
               No results found.
             </CommandEmpty>
 
+            {/* Library hits are ranked by the backend; the search text is part of each
+                value so cmdk's own filter keeps them */}
+            {hasResults && (
+              <>
+                {results.tracks.length > 0 && (
+                  <CommandGroup heading="Tracks" className={groupClass}>
+                    {results.tracks.map((hit) => (
+                      <CommandItem
+                        key={hit.trackId}
+                        value={`${search} track ${hit.trackId}`}
+                        onSelect={() =>
+                          run(() =>
+                            navigate({
+                              to: "/table",
+                              search:
+                                hit.albumId != null
+                                  ? { albumId: hit.albumId, albumName: hit.albumTitle ?? undefined }
+                                  : hit.artistId != null
+                                    ? { artistId: hit.artistId, artistName: hit.artistName ?? undefined }
+                                    : {},
+                            }),
+                          )}
+                        className={itemClass}
+                      >
+                        <LuMusic className="w-4 h-4 flex-shrink-0" />
+                        <div className="min-w-0">
+                          <div className="truncate text-fg-primary">{hit.title}</div>
+                          <div className="truncate text-xs text-fg-muted">
+                            <Highlighted parts={hit.snippet} />
+                          </div>
+                        </div>
+                      </CommandItem>
+                    ))}
+                  </CommandGroup>
+                )}
+                {results.albums.length > 0 && (
+                  <CommandGroup heading="Albums" className={groupClass}>
+                    {results.albums.map((hit) => (
+                      <CommandItem
+                        key={hit.albumId}
+                        value={`${search} album ${hit.albumId}`}
+                        onSelect={() =>
+                          run(() =>
+                            navigate({
+                              to: "/table",
+                              search: { albumId: hit.albumId, albumName: hit.title },
+                            }),
+                          )}
+                        className={itemClass}
+                      >
+                        <LuDisc3 className="w-4 h-4 flex-shrink-0" />
+                        <span className="truncate">
+                          <Highlighted parts={hit.highlighted} />
+                          {hit.artistName && (
+                            <span className="text-fg-muted"> · {hit.artistName}</span>
+                          )}
+                        </span>
+                      </CommandItem>
+                    ))}
+                  </CommandGroup>
+                )}
+                {results.artists.length > 0 && (
+                  <CommandGroup heading="Artists" className={groupClass}>
+                    {results.artists.map((hit) => (
+                      <CommandItem
+                        key={hit.artistId}
+                        value={`${search} artist ${hit.artistId}`}
+                        onSelect={() =>
+                          run(() =>
+                            navigate({
+                              to: "/table",
+                              search: { artistId: hit.artistId, artistName: hit.name },
+                            }),
+                          )}
+                        className={itemClass}
+                      >
+                        <LuMicVocal className="w-4 h-4 flex-shrink-0" />
+                        <span className="truncate">
+                          <Highlighted parts={hit.highlighted} />
+                        </span>
+                      </CommandItem>
+                    ))}
+                  </CommandGroup>
+                )}
+                <CommandSeparator className="my-1 border-t border-border" />
+              </>
+            )}
+
             <CommandGroup
               heading="Navigation"
               className="px-2 py-1.5 text-[10px] font-bold uppercase tracking-wider text-fg-muted"
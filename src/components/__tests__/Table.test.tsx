@@ -74,7 +74,7 @@ const tracks: TrackRow[] = [
 ];
 
 async function renderTable() {
-  mockInvoke({ list_tracks: tracks });
+  mockInvoke({ query_tracks: { rows: tracks, total: tracks.length, nextCursor: null } });
   // Dynamic imports so vi.mock() calls above are hoisted and modules share the same instance
   const [{ Table }, { QueryClient, QueryClientProvider }] = await Promise.all([
     import("../../routes/table"),
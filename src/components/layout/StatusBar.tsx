@@ -1,7 +1,7 @@
 import { useEffect, useState } from "react";
 import { useRouterState } from "@tanstack/react-router";
 import { listen } from "@tauri-apps/api/event";
-import { commands, LibraryStats } from "../../bindings";
+import { commands, LibraryStats, ScanSummary } from "../../bindings";
 
 const VIEW_NAMES: Record<string, string> = {
   "/": "Library",
@@ -34,12 +34,9 @@ export function StatusBar() {
 
     Promise.all([
       listen<number>("scan:progress", (e) => setScanCount(e.payload)),
-      listen<number>("scan:complete", (e) => {
+      listen<ScanSummary>("scan:complete", () => {
         setScanCount(null);
-        setStats((prev) =>
-          prev ? { ...prev, totalTracks: e.payload } : prev
-        );
-        // Full refresh to get accurate album/artist counts too
+        // Full refresh to get accurate track/album/artist counts
         commands.getLibraryStats().then((res) => {
           if (res.status === "ok") setStats(res.data);
         });
@@ -1,6 +1,6 @@
 import { createFileRoute } from "@tanstack/react-router";
 import { useEffect, useState } from "react";
-import { commands } from "../bindings";
+import { commands, ScanSummary } from "../bindings";
 import { open } from "@tauri-apps/plugin-dialog";
 import { listen } from "@tauri-apps/api/event";
 import { audioManager } from "@/lib/audio";
@@ -21,6 +21,18 @@ function playTestTone(volume: number) {
   } catch { /* ignore */ }
 }
 
+/** Mirrors `DEFAULT_SEPARATORS` in src-tauri/src/db/credits.rs */
+const DEFAULT_ARTIST_SEPARATORS = '["; ", " feat. ", " ft. ", " featuring "]';
+
+function isStringList(value: string) {
+  try {
+    const parsed = JSON.parse(value);
+    return Array.isArray(parsed) && parsed.every((s) => typeof s === "string");
+  } catch {
+    return false;
+  }
+}
+
 export const Route = createFileRoute("/settings")({
   component: Settings,
 });
@@ -31,7 +43,13 @@ function Settings() {
   const [scannedCount, setScannedCount] = useState(0);
   const [confirmClear, setConfirmClear] = useState(false);
   const [clearing, setClearing] = useState(false);
+  const [vacuuming, setVacuuming] = useState(false);
+  const [vacuumResult, setVacuumResult] = useState<string | null>(null);
   const [volume, setVolume] = useState(() => Math.round(audioManager.getVolume() * 100));
+  const [backupMode, setBackupMode] = useState("off");
+  const [retentionDays, setRetentionDays] = useState("30");
+  const [separators, setSeparators] = useState(DEFAULT_ARTIST_SEPARATORS);
+  const [folding, setFolding] = useState<string[]>([]);
 
   useEffect(() => {
     return audioManager.onStateChange((s) => setVolume(Math.round(s.volume * 100)));
@@ -43,6 +61,14 @@ function Settings() {
       if (res.status === "ok") {
         setMusicDir(res.data);
       }
+      const mode = await commands.getSetting("backup_mode");
+      if (mode.status === "ok" && mode.data) setBackupMode(mode.data);
+      const retention = await commands.getSetting("backup_retention_days");
+      if (retention.status === "ok" && retention.data) setRetentionDays(retention.data);
+      const seps = await commands.getSetting("artist_separators");
+      if (seps.status === "ok" && seps.data) setSeparators(seps.data);
+      const fold = await commands.getSetting("name_folding");
+      if (fold.status === "ok" && fold.data && isStringList(fold.data)) setFolding(JSON.parse(fold.data));
     }
     loadSettings();
   }, []);
@@ -63,13 +89,34 @@ function Settings() {
     }
   };
 
+  const handleBackupModeChange = async (mode: string) => {
+    const res = await commands.setSetting("backup_mode", mode);
+    if (res.status === "ok") setBackupMode(mode);
+  };
+
+  const handleRetentionChange = async (days: string) => {
+    setRetentionDays(days);
+    if (/^\d+$/.test(days)) await commands.setSetting("backup_retention_days", days);
+  };
+
+  const handleSeparatorsChange = async (value: string) => {
+    setSeparators(value);
+    if (isStringList(value)) await commands.setSetting("artist_separators", value);
+  };
+
+  const handleFoldingChange = async (step: string, enabled: boolean) => {
+    const next = enabled ? [...folding, step] : folding.filter((s) => s !== step);
+    const res = await commands.setSetting("name_folding", JSON.stringify(next));
+    if (res.status === "ok") setFolding(next);
+  };
+
   useEffect(() => {
     let unlistens: Array<() => void> = [];
     let mounted = true;
 
     Promise.all([
       listen<number>("scan:progress", (e) => setScannedCount(e.payload)),
-      listen<number>("scan:complete", () => setScannedCount(0)),
+      listen<ScanSummary>("scan:complete", () => setScannedCount(0)),
     ]).then((fns) => {
       if (mounted) {
         unlistens = fns;
@@ -94,6 +141,21 @@ function Settings() {
     setScanning(false);
   };
 
+  const handleVacuum = async () => {
+    setVacuuming(true);
+    const res = await commands.vacuumLibrary();
+    if (res.status === "ok") {
+      const { removedArtists, removedAlbums, removedCovers, reclaimedBytes } = res.data;
+      setVacuumResult(
+        `Removed ${removedArtists} artists, ${removedAlbums} albums and ${removedCovers} covers; ` +
+          `freed ${(reclaimedBytes / 1024).toFixed(0)} KB.`,
+      );
+    } else {
+      setVacuumResult(Object.values(res.error)[0]);
+    }
+    setVacuuming(false);
+  };
+
   const handleClearAllData = async () => {
     setClearing(true);
     const res = await commands.clearAllData();
@@ -154,6 +216,114 @@ function Settings() {
                 </button>
               </div>
             )}
+
+            <div className="mt-2 flex items-center justify-between p-4 bg-bg-overlay rounded-lg border border-border">
+              <div>
+                <h3 className="text-sm font-medium text-fg-secondary">
+                  Clean up
+                </h3>
+                <p className="text-xs text-fg-muted">
+                  {vacuumResult ??
+                    "Remove artists, albums and cover art no track uses, and compact the database."}
+                </p>
+              </div>
+              <button
+                type="button"
+                onClick={handleVacuum}
+                disabled={vacuuming || scanning}
+                className="px-6 py-2 bg-bg-surface hover:bg-bg-overlay disabled:opacity-50 rounded-lg text-xs font-bold uppercase tracking-widest transition-all text-fg-secondary"
+              >
+                {vacuuming ? "Cleaning…" : "Clean Up"}
+              </button>
+            </div>
+          </div>
+        </div>
+
+        <div className="bg-bg-overlay rounded-xl p-6 border border-border-strong shadow-xl">
+          <h2 className="text-xl font-semibold mb-4 text-fg-secondary">
+            File Safety
+          </h2>
+          <div className="flex flex-col gap-4">
+            <div>
+              <label className="text-[10px] uppercase tracking-wider text-fg-muted font-bold block mb-2">
+                Backup before first write
+              </label>
+              <select
+                value={backupMode}
+                onChange={(e) => handleBackupModeChange(e.target.value)}
+                className="bg-bg-surface border border-border-strong rounded-lg px-3 py-1.5 text-sm text-fg-secondary outline-none focus:border-accent"
+              >
+                <option value="off">Off</option>
+                <option value="tags">Tags only</option>
+                <option value="full">Whole file</option>
+              </select>
+              <p className="text-xs text-fg-muted mt-2">
+                Saves each file's original tags (or the whole file) before Chant first edits it,
+                so it can be restored later.
+              </p>
+            </div>
+            <div>
+              <label className="text-[10px] uppercase tracking-wider text-fg-muted font-bold block mb-2">
+                Keep backups for (days, 0 = forever)
+              </label>
+              <input
+                type="number"
+                min={0}
+                value={retentionDays}
+                onChange={(e) => handleRetentionChange(e.target.value)}
+                className="w-24 bg-bg-input rounded-lg px-3 py-1.5 border border-border text-sm text-fg-secondary outline-none focus:border-accent"
+              />
+            </div>
+          </div>
+        </div>
+
+        <div className="bg-bg-overlay rounded-xl p-6 border border-border-strong shadow-xl">
+          <h2 className="text-xl font-semibold mb-4 text-fg-secondary">
+            Artists
+          </h2>
+          <div>
+            <label className="text-[10px] uppercase tracking-wider text-fg-muted font-bold block mb-2">
+              Name separators
+            </label>
+            <input
+              type="text"
+              value={separators}
+              onChange={(e) => handleSeparatorsChange(e.target.value)}
+              spellCheck={false}
+              className={`w-full bg-bg-input rounded-lg px-3 py-1.5 border border-border text-sm font-mono text-fg-secondary outline-none focus:border-accent ${
+                isStringList(separators) ? "" : "ring-1 ring-red-500/60"
+              }`}
+            />
+            <p className="text-xs text-fg-muted mt-2">
+              A JSON list of the strings that split one artist, composer or performer tag into
+              several names. Names after "feat." or "ft." are credited as featured. Applies to
+              files scanned or edited from now on.
+            </p>
+          </div>
+          <div className="mt-4">
+            <label className="text-[10px] uppercase tracking-wider text-fg-muted font-bold block mb-2">
+              Name matching
+            </label>
+            <div className="flex flex-col gap-1.5">
+              {[
+                ["articles", 'Ignore a leading "The" ("The Beatles" = "Beatles, The")'],
+                ["punctuation", 'Ignore punctuation and spaces ("AC/DC" = "AC DC")'],
+              ].map(([step, label]) => (
+                <label key={step} className="flex items-center gap-2 text-sm text-fg-secondary">
+                  <input
+                    type="checkbox"
+                    checked={folding.includes(step)}
+                    onChange={(e) => handleFoldingChange(step, e.target.checked)}
+                    className="accent-amber-500"
+                  />
+                  {label}
+                </label>
+              ))}
+            </div>
+            <p className="text-xs text-fg-muted mt-2">
+              Artist and album names always match regardless of case and Unicode form. Existing
+              near-duplicates are not merged automatically.
+            </p>
           </div>
         </div>
 
@@ -1,15 +1,12 @@
 import { createFileRoute, useNavigate } from "@tanstack/react-router";
 import { useEffect, useMemo, useRef, useState } from "react";
-import { useQuery } from "@tanstack/react-query";
-import { commands, TrackRow } from "../bindings";
+import { useInfiniteQuery, useQuery } from "@tanstack/react-query";
+import { commands, LibraryFilter, SortSpec, TrackRow } from "../bindings";
 import { queryKeys } from "../lib/queryClient";
 import {
   createColumnHelper,
-  FilterFn,
   flexRender,
   getCoreRowModel,
-  getFilteredRowModel,
-  getSortedRowModel,
   SortingState,
   VisibilityState,
   useReactTable,
@@ -42,16 +39,21 @@ const defaultColumnVisibility: VisibilityState = {
   discNumber: false,
   bitrateKbps: false,
   sampleRateHz: false,
+  bitDepth: false,
+  channels: false,
+  codec: false,
   filePath: false,
 };
 
-const trackTextFilter: FilterFn<TrackRow> = (row, _columnId, filterValue) => {
-  const q = String(filterValue).toLowerCase();
-  return (
-    (row.original.title ?? "").toLowerCase().includes(q) ||
-    (row.original.artistName ?? "").toLowerCase().includes(q) ||
-    (row.original.albumTitle ?? "").toLowerCase().includes(q)
-  );
+/** Rows fetched per request; more are loaded as the table is scrolled. */
+const PAGE_SIZE = 500;
+
+const emptyFilter: LibraryFilter = {
+  collectionId: null,
+  artistId: null,
+  albumId: null,
+  text: null,
+  predicates: [],
 };
 
 function formatDuration(secs: number | null) {
@@ -66,21 +68,69 @@ export function Table() {
   const search = Route.useSearch();
   const navigate = useNavigate();
 
-  const { data: tracks = [] } = useQuery({
-    queryKey: queryKeys.tracks,
-    queryFn: async () => {
-      const res = await commands.listTracks();
-      if (res.status !== "ok") throw new Error("Failed to load tracks");
-      return res.data;
-    },
-  });
-
   const [sorting, setSorting] = useState<SortingState>([]);
   const [columnVisibility, setColumnVisibility] =
     useState<VisibilityState>(defaultColumnVisibility);
   const [showColumnMenu, setShowColumnMenu] = useState(false);
   const [selectedIds, setSelectedIds] = useState<Set<number>>(new Set());
   const [globalFilter, setGlobalFilter] = useState("");
+  const [textFilter, setTextFilter] = useState("");
+
+  // Debounce the text filter so typing doesn't query on every keystroke
+  useEffect(() => {
+    const timer = setTimeout(() => setTextFilter(globalFilter.trim()), 200);
+    return () => clearTimeout(timer);
+  }, [globalFilter]);
+
+  // Filtering, sorting and paging happen in the backend
+  const filter = useMemo<LibraryFilter>(
+    () => ({
+      ...emptyFilter,
+      albumId: search.albumId ?? null,
+      artistId: search.artistId ?? null,
+      text: textFilter || null,
+    }),
+    [search.albumId, search.artistId, textFilter],
+  );
+  const sort = useMemo<SortSpec | null>(
+    () => (sorting[0] ? { field: sorting[0].id, descending: sorting[0].desc } : null),
+    [sorting],
+  );
+
+  const {
+    data: pages,
+    fetchNextPage,
+    hasNextPage,
+    isFetchingNextPage,
+  } = useInfiniteQuery({
+    queryKey: [...queryKeys.tracks, "query", filter, sort],
+    queryFn: async ({ pageParam }) => {
+      const res = await commands.queryTracks(filter, sort, {
+        offset: null,
+        limit: PAGE_SIZE,
+        cursor: pageParam,
+      });
+      if (res.status !== "ok") throw new Error("Failed to load tracks");
+      return res.data;
+    },
+    initialPageParam: null as string | null,
+    getNextPageParam: (lastPage) => lastPage.nextCursor,
+  });
+  const tracks = useMemo(() => pages?.pages.flatMap((p) => p.rows) ?? [], [pages]);
+  const filteredCount = pages?.pages[0]?.total ?? 0;
+
+  const { data: libraryCount = 0 } = useQuery({
+    queryKey: [...queryKeys.tracks, "count"],
+    queryFn: async () => {
+      const res = await commands.queryTracks(emptyFilter, null, {
+        offset: null,
+        limit: 1,
+        cursor: null,
+      });
+      if (res.status !== "ok") throw new Error("Failed to count tracks");
+      return res.data.total;
+    },
+  });
   const columnMenuRef = useRef<HTMLDivElement>(null);
   const columnMenuBtnRef = useRef<HTMLButtonElement>(null);
   const lastClickedIndexRef = useRef<number | null>(null);
@@ -110,12 +160,11 @@ export function Table() {
     return () => document.removeEventListener("mousedown", handleClick);
   }, [showColumnMenu]);
 
-  // Pre-filter by albumId or artistId from URL params
-  const displayTracks = useMemo(() => {
-    if (search.albumId != null) return tracks.filter((t) => t.albumId === search.albumId);
-    if (search.artistId != null) return tracks.filter((t) => t.artistId === search.artistId);
-    return tracks;
-  }, [tracks, search.albumId, search.artistId]);
+  function handleTableScroll(e: React.UIEvent<HTMLDivElement>) {
+    const el = e.currentTarget;
+    const nearBottom = el.scrollHeight - el.scrollTop - el.clientHeight < 400;
+    if (nearBottom && hasNextPage && !isFetchingNextPage) fetchNextPage();
+  }
 
   const columns = useMemo(
     () => [
@@ -217,6 +266,37 @@ export function Table() {
           );
         },
       }),
+      columnHelper.accessor("bitDepth", {
+        id: "bitDepth",
+        header: "Bit Depth",
+        size: 80,
+        cell: (info) => {
+          const v = info.getValue();
+          return <span className="block truncate">{v != null ? `${v}-bit` : ""}</span>;
+        },
+      }),
+      columnHelper.accessor("channels", {
+        id: "channels",
+        header: "Channels",
+        size: 80,
+        cell: (info) => (
+          <span className="block truncate">{info.getValue() ?? ""}</span>
+        ),
+      }),
+      columnHelper.accessor("codec", {
+        id: "codec",
+        header: "Codec",
+        size: 110,
+        cell: (info) => {
+          const profile = info.row.original.codecProfile;
+          const codec = info.getValue();
+          return (
+            <span className="block truncate">
+              {codec != null ? (profile ? `${codec} ${profile}` : codec) : ""}
+            </span>
+          );
+        },
+      }),
       columnHelper.accessor("filePath", {
         id: "filePath",
         header: "Path",
@@ -235,26 +315,23 @@ export function Table() {
   );
 
   const table = useReactTable({
-    data: displayTracks,
+    data: tracks,
     columns,
     columnResizeMode: "onChange",
-    globalFilterFn: trackTextFilter,
+    manualSorting: true,
+    manualFiltering: true,
+    enableMultiSort: false,
     state: {
       sorting,
       columnVisibility,
-      globalFilter,
     },
     onSortingChange: setSorting,
     onColumnVisibilityChange: setColumnVisibility,
-    onGlobalFilterChange: setGlobalFilter,
     getCoreRowModel: getCoreRowModel(),
-    getFilteredRowModel: getFilteredRowModel(),
-    getSortedRowModel: getSortedRowModel(),
   });
 
   const visibleRows = table.getRowModel().rows;
-  const filteredCount = table.getFilteredRowModel().rows.length;
-  const isFiltered = search.albumId != null || search.artistId != null || globalFilter.length > 0;
+  const isFiltered = search.albumId != null || search.artistId != null || textFilter.length > 0;
 
   function handleRowClick(
     e: React.MouseEvent,
@@ -346,8 +423,8 @@ export function Table() {
           ) : (
             <div className="text-xs text-fg-muted">
               {isFiltered
-                ? `${filteredCount} of ${tracks.length} songs`
-                : `${tracks.length} songs`}
+                ? `${filteredCount} of ${libraryCount} songs`
+                : `${libraryCount} songs`}
             </div>
           )}
 
@@ -389,7 +466,10 @@ export function Table() {
       {/* Body: table + detail panel — equal 50/50 split */}
       <div className="flex flex-1 overflow-hidden min-h-0">
         {/* Track table — left half */}
-        <div className="flex-1 overflow-auto min-w-0 border-r border-border">
+        <div
+          className="flex-1 overflow-auto min-w-0 border-r border-border"
+          onScroll={handleTableScroll}
+        >
           <table
             className="text-left text-xs border-collapse"
             style={{ width: table.getCenterTotalSize() }}